@@ -0,0 +1,247 @@
+#[cfg(feature = "gzip")]
+use flate2::read::GzDecoder;
+use std::cmp;
+#[cfg(feature = "gzip")]
+use std::io::{self, Read};
+
+pub mod constants {
+    /* Ascii decimals */
+    pub const TAB: u8 = 9;
+    pub const NEWLINE: u8 = 10;
+    pub const CR: u8 = 13;
+    pub const SPACE: u8 = 32;
+    /* Content-Length: */
+    pub const CONTENT_LENGTH_FIELD: &[u8] = &[
+        67, 111, 110, 116, 101, 110, 116, 45, 76, 101, 110, 103, 116, 104, 58, 32,
+    ];
+    /* \r\n\r\n */
+    pub const HEADER_END: &[u8] = &[13, 10, 13, 10];
+    /* Content-Encoding: */
+    pub const CONTENT_ENCODING_FIELD: &[u8] = &[
+        67, 111, 110, 116, 101, 110, 116, 45, 69, 110, 99, 111, 100, 105, 110, 103, 58, 32,
+    ];
+    /* gzip */
+    pub const GZIP_ENCODING: &[u8] = &[103, 122, 105, 112];
+    /* x-gzip */
+    pub const X_GZIP_ENCODING: &[u8] = &[120, 45, 103, 122, 105, 112];
+    /* Get */
+    pub const GET_REQUEST: &[u8] = &[71, 69, 84];
+    /* Head */
+    pub const HEAD_REQUEST: &[u8] = &[72, 69, 65, 68];
+    /* Trace */
+    pub const TRACE_REQUEST: &[u8] = &[84, 82, 65, 67, 69];
+    /* Connect */
+    pub const CONNECT_REQUEST: &[u8] = &[67, 79, 78, 78, 69, 67, 84];
+    /* Post */
+    pub const POST_REQUEST: &[u8] = &[80, 79, 83, 84];
+}
+
+pub fn extract_number(buffer: &[u8]) -> i64 {
+    /*
+     *  Extract the number in the buffer. It tries to extract until it
+     *  hits the \r\n sequence. (EOL)
+     *
+     *  Arguments:
+     *      buffer: The buffer, which must be preprocessed.
+     *
+     *  Returns:
+     *      Returns either the number or 0 if failed. The number saturates
+     *      at i64::MAX, so the huge values can't overflow.
+     */
+
+    // TODO: Should do better handling the verification.
+    let mut num_bytes: Vec<i64> = Vec::new();
+
+    for el in buffer {
+        /* Flags to handle sequences */
+        let cr_flag: bool = *el == constants::CR;
+        let nl_flag: bool = *el == constants::NEWLINE;
+
+        /* If true, then we are EOL, so there is no more numbers */
+        if cr_flag || nl_flag {
+            break;
+        }
+        /* If the previous conditions is false, we add numbers to the buf */
+        let num_in_byte: i64 = (*el).into();
+        num_bytes.push(num_in_byte);
+    }
+
+    if num_bytes.iter().any(|x: &i64| !(48..=57).contains(x)) {
+        return 0;
+    }
+    num_bytes.iter().fold(0, |number: i64, &x: &i64| {
+        number.saturating_mul(10).saturating_add(x - 48)
+    })
+}
+pub fn find_in_buffer(buffer: &[u8], pattern: &[u8]) -> usize {
+    /*
+     *  Find index in a buffer with given pattern. It might be (is) used
+     *  for preprocessing. It is based on Rabin-Karp algorithm.
+     *
+     *  Arguments:
+     *      buffer: The buffer that you want to search.
+     *      pattern: The pattern that needs to be found.
+     *
+     *  Returns:
+     *      Returns the index on which the pattern starts. If the pattern
+     *      doesn't exist, maximum number is returned.
+     */
+
+    /* Declare helper variables */
+    let pattern_sz: usize = pattern.len();
+    let buffer_sz: usize = buffer.len();
+    if pattern_sz > buffer_sz {
+        return usize::MAX;
+    }
+    let prime: i64 = 31;
+    let large_prime: i64 = 1_000_000_009;
+
+    /* Must explicitly cast */
+    let capacity: i64 = cmp::max(pattern_sz as i64, buffer_sz as i64);
+
+    /* Ignore the value initialization up to capcity + 1, the one that
+     * matters is the first entry which must be 1, from the first index up to
+     * the end, it will be calculated and overriden.
+     */
+    let mut powers: Vec<i64> = Vec::from_iter(1..capacity + 1);
+    for idx in 1..capacity as usize {
+        powers[idx] = (powers[idx - 1] * prime) % large_prime;
+    }
+
+    /* Again ignore the initialization - it is the same as the vector powers,
+     * what matters is only first entry which must be initialized to the 0
+     * */
+    let mut buffer_hash_prefixes: Vec<i64> = Vec::from_iter(0..(buffer_sz + 1) as i64);
+    for idx in 0..buffer_sz {
+        buffer_hash_prefixes[idx + 1] =
+            (buffer_hash_prefixes[idx] + buffer[idx] as i64 * powers[idx]) % large_prime;
+    }
+
+    let mut hashed_pattern: i64 = 0;
+    for idx in 0..pattern_sz {
+        hashed_pattern += (pattern[idx] as i64 * powers[idx]) % large_prime;
+    }
+
+    for idx in 0..(buffer_sz - pattern_sz + 1) {
+        let current_hash: i64 = (buffer_hash_prefixes[idx + pattern_sz] + large_prime
+            - buffer_hash_prefixes[idx])
+            % large_prime;
+        if current_hash == hashed_pattern * powers[idx] % large_prime {
+            return idx;
+        }
+    }
+    /* If the above for loops fails, return this. */
+    usize::MAX
+}
+
+pub fn read_header_value<'a>(buffer: &'a [u8], field: &[u8]) -> Option<&'a [u8]> {
+    /*
+     *  Read the value of the header field, up to the EOL.
+     *
+     *  Arguments:
+     *      buffer: Bytes of the request.
+     *      field: Name of the field followed by ": ", e.g. Content-Length:
+     *
+     *  Returns:
+     *      The value without the EOL, None if the field is missing.
+     */
+    if buffer.len() < field.len() {
+        return None;
+    }
+    let field_idx: usize = find_in_buffer(buffer, field);
+    if field_idx == usize::MAX {
+        return None;
+    }
+    let value: &[u8] = &buffer[field_idx + field.len()..];
+    let value_end: usize = value
+        .iter()
+        .position(|byte| *byte == constants::CR || *byte == constants::NEWLINE)
+        .unwrap_or(value.len());
+    Some(&value[..value_end])
+}
+
+#[cfg(feature = "gzip")]
+pub fn inflate_gzip(buffer: &[u8], max_size: usize) -> Result<Vec<u8>, io::Error> {
+    /*
+     *  Decompress the gzip encoded buffer. The output is capped, so
+     *  a small compressed body can't exhaust the memory (a gzip bomb).
+     *
+     *  Arguments:
+     *      buffer: The gzip encoded bytes.
+     *      max_size: The maximum allowed size after decompression.
+     *
+     *  Returns:
+     *      Returns the decoded bytes, or an error if the buffer isn't
+     *      valid gzip or it inflates beyond the maximum size.
+     */
+    let mut decoded: Vec<u8> = Vec::new();
+    GzDecoder::new(buffer)
+        .take(max_size as u64 + 1)
+        .read_to_end(&mut decoded)?;
+    if decoded.len() > max_size {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Decompressed body exceeds {max_size} bytes"),
+        ));
+    }
+    Ok(decoded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::constants::{CONTENT_ENCODING_FIELD, CONTENT_LENGTH_FIELD};
+    use super::*;
+
+    #[test]
+    fn find_in_buffer_test() {
+        /* Test 1 */
+        let vec_content_buf: Vec<u8> = Vec::from(CONTENT_LENGTH_FIELD);
+        let content_pos = find_in_buffer(&vec_content_buf, CONTENT_LENGTH_FIELD);
+        assert_eq!(content_pos, 0);
+
+        /* Test 2 */
+        const POST_REQUEST: &[u8] = b"POST /api/data HTTP/1.1\r\n\
+            Host: example.com\r\n\
+            Content-Type: application/json\r\n\
+            Content-Length: 27\r\n\
+            \r\n\
+            {\"key\":\"value\",\"number\":42}";
+
+        let vec_post_buf: Vec<u8> = Vec::from(POST_REQUEST);
+        let post_pos = find_in_buffer(&vec_post_buf, CONTENT_LENGTH_FIELD);
+        assert_eq!(post_pos, 76);
+    }
+
+    #[test]
+    fn extract_number_test() {
+        assert_eq!(extract_number(b"27\r\n"), 27);
+        assert_eq!(extract_number(b"12a\r\n"), 0);
+        assert_eq!(extract_number(b"99999999999999999999999\r\n"), i64::MAX);
+    }
+
+    #[test]
+    fn read_header_value_test() {
+        let request: &[u8] = b"POST / HTTP/1.1\r\nContent-Encoding: gzip\r\n\r\n";
+        assert_eq!(
+            read_header_value(request, CONTENT_ENCODING_FIELD),
+            Some(&b"gzip"[..])
+        );
+        assert_eq!(read_header_value(request, CONTENT_LENGTH_FIELD), None);
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn inflate_gzip_test() {
+        use flate2::{Compression, write::GzEncoder};
+        use std::io::Write;
+
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&[b'a'; 4096]).unwrap();
+        let compressed: Vec<u8> = encoder.finish().unwrap();
+
+        assert_eq!(inflate_gzip(&compressed, 4096).unwrap(), vec![b'a'; 4096]);
+        /* Inflating past the limit is refused */
+        assert!(inflate_gzip(&compressed, 1024).is_err());
+        assert!(inflate_gzip(b"not gzip", 1024).is_err());
+    }
+}
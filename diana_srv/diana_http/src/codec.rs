@@ -0,0 +1,128 @@
+use crate::status::HttpResponseStatus;
+
+pub fn add_headers(headers: &[(String, String)]) -> String {
+    /*
+     *  Format the header lines of the HTTP message.
+     *
+     *  Arguments:
+     *      headers: Pairs of header names and their values.
+     *
+     *  Returns:
+     *      Header lines, each one terminated with \r\n.
+     */
+    headers
+        .iter()
+        .map(|(name, value)| format!("{name}: {value}\r\n"))
+        .collect()
+}
+
+pub fn request_head(method: &str, target: &str, headers: &[(String, String)]) -> Vec<u8> {
+    /*
+     *  Format the request line and the headers of the HTTP/1.1 request.
+     *
+     *  Returns:
+     *      The head in bytes, terminated with the empty line.
+     */
+    format!("{method} {target} HTTP/1.1\r\n{}\r\n", add_headers(headers)).into_bytes()
+}
+
+pub fn response_head(status: HttpResponseStatus, headers: &[(String, String)]) -> Vec<u8> {
+    /*
+     *  Format the status line and the headers of the HTTP/1.1 response,
+     *  the headers are written as they are.
+     *
+     *  Returns:
+     *      The head in bytes, terminated with the empty line.
+     */
+    format!(
+        "HTTP/1.1 {} {}\r\n{}\r\n",
+        status.value(),
+        status.reason(),
+        add_headers(headers)
+    )
+    .into_bytes()
+}
+
+pub fn parse_status_line(line: &str) -> Option<u16> {
+    /*
+     *  Arguments:
+     *      line: Status line of the response without the EOL.
+     *
+     *  Returns:
+     *      The status code, None if the line isn't HTTP/1.x
+     */
+    let mut parts = line.splitn(3, ' ');
+    if !parts
+        .next()
+        .is_some_and(|version| version.starts_with("HTTP/1."))
+    {
+        return None;
+    }
+    parts.next()?.parse().ok()
+}
+
+pub fn parse_header_line(line: &str) -> Option<(&str, &str)> {
+    /*
+     *  Returns:
+     *      The trimmed name and value, None if the colon is missing.
+     */
+    let (name, value): (&str, &str) = line.split_once(':')?;
+    Some((name.trim(), value.trim()))
+}
+
+pub fn parse_chunk_size(line: &str) -> Option<usize> {
+    /*
+     *  Arguments:
+     *      line: Size line of the chunk, the extensions are ignored.
+     *
+     *  Returns:
+     *      The size of the chunk, zero for the last one.
+     */
+    let size_hex: &str = line.split(';').next().unwrap_or("").trim();
+    usize::from_str_radix(size_hex, 16).ok()
+}
+
+pub fn has_body(status: u16) -> bool {
+    /*
+     *  Returns:
+     *      False for the statuses, whose responses never carry the body.
+     */
+    !(100..200).contains(&status) && status != 204 && status != 304
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn codec_test() {
+        let headers: Vec<(String, String)> = vec![
+            (String::from("Host"), String::from("example.com")),
+            (String::from("Content-Length"), String::from("0")),
+        ];
+        assert_eq!(
+            request_head("GET", "/a?b", &headers),
+            b"GET /a?b HTTP/1.1\r\nHost: example.com\r\nContent-Length: 0\r\n\r\n".to_vec()
+        );
+        assert_eq!(
+            response_head(HttpResponseStatus::NotFound, &headers[1..]),
+            b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n".to_vec()
+        );
+
+        assert_eq!(parse_status_line("HTTP/1.1 204 No Content"), Some(204));
+        assert_eq!(parse_status_line("HTTP/1.0 200"), Some(200));
+        assert_eq!(parse_status_line("HTTP/2 200 OK"), None);
+        assert_eq!(parse_status_line("HTTP/1.1 abc OK"), None);
+        assert_eq!(
+            parse_header_line("Content-Type :  text/html "),
+            Some(("Content-Type", "text/html"))
+        );
+        assert_eq!(parse_header_line("no colon"), None);
+        assert_eq!(parse_chunk_size("1a;name=value"), Some(26));
+        assert_eq!(parse_chunk_size("0"), Some(0));
+        assert_eq!(parse_chunk_size("zz"), None);
+        assert!(has_body(200));
+        assert!(!has_body(101));
+        assert!(!has_body(304));
+    }
+}
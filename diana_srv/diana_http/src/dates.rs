@@ -0,0 +1,109 @@
+/* 1970-01-01 was a Thursday */
+const WEEKDAYS: [&str; 7] = ["Thu", "Fri", "Sat", "Sun", "Mon", "Tue", "Wed"];
+const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+const SECS_PER_DAY: u64 = 86400;
+
+pub fn http_date(secs: u64) -> String {
+    /*
+     *  Format the time as the IMF-fixdate, e.g. Sun, 06 Nov 1994 08:49:37 GMT
+     *
+     *  Arguments:
+     *      secs: Seconds since the epoch.
+     */
+    let days: u64 = secs / SECS_PER_DAY;
+    let time: u64 = secs % SECS_PER_DAY;
+    let (year, month, day) = civil_from_days(days as i64);
+    format!(
+        "{}, {day:02} {} {year} {:02}:{:02}:{:02} GMT",
+        WEEKDAYS[(days % 7) as usize],
+        MONTHS[month as usize - 1],
+        time / 3600,
+        time % 3600 / 60,
+        time % 60
+    )
+}
+
+pub fn parse_http_date(value: &str) -> Option<u64> {
+    /*
+     *  Parse the IMF-fixdate, the obsolete formats aren't sent by
+     *  the clients, that resume the downloads.
+     *
+     *  Returns:
+     *      Seconds since the epoch, None if the date is malformed.
+     */
+    let (_, date) = value.trim().split_once(", ")?;
+    let mut fields = date.split(' ');
+    let day: u32 = fields.next()?.parse().ok()?;
+    let month_name: &str = fields.next()?;
+    let month: u32 = MONTHS.iter().position(|month| *month == month_name)? as u32 + 1;
+    let year: i64 = fields.next()?.parse().ok()?;
+    let mut clock = fields
+        .next()?
+        .split(':')
+        .map(|field| field.parse::<u64>().ok());
+    let (hours, minutes, seconds) = (clock.next()??, clock.next()??, clock.next()??);
+    if fields.next()? != "GMT" || fields.next().is_some() || !(1..=31).contains(&day) {
+        return None;
+    }
+    if hours > 23 || minutes > 59 || seconds > 60 {
+        return None;
+    }
+    let days: u64 = u64::try_from(days_from_civil(year, month, day)).ok()?;
+    Some(days * SECS_PER_DAY + hours * 3600 + minutes * 60 + seconds)
+}
+
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    /*
+     *  Convert the days since the epoch to the proleptic Gregorian date,
+     *  Howard Hinnant's algorithm.
+     *
+     *  Returns:
+     *      The year, the month and the day.
+     */
+    let days: i64 = days + 719468;
+    let era: i64 = days.div_euclid(146097);
+    let day_of_era: i64 = days.rem_euclid(146097);
+    let year_of_era: i64 =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year: i64 = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month: i64 = (5 * day_of_year + 2) / 153;
+    let day: u32 = (day_of_year - (153 * shifted_month + 2) / 5 + 1) as u32;
+    let month: u32 = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    } as u32;
+    let year: i64 = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year: i64 = year - i64::from(month <= 2);
+    let era: i64 = year.div_euclid(400);
+    let year_of_era: i64 = year.rem_euclid(400);
+    let shifted_month: i64 = (i64::from(month) + 9) % 12;
+    let day_of_year: i64 = (153 * shifted_month + 2) / 5 + i64::from(day) - 1;
+    let day_of_era: i64 = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dates_test() {
+        assert_eq!(http_date(0), "Thu, 01 Jan 1970 00:00:00 GMT");
+        assert_eq!(http_date(784111777), "Sun, 06 Nov 1994 08:49:37 GMT");
+        assert_eq!(http_date(951782400), "Tue, 29 Feb 2000 00:00:00 GMT");
+        for secs in [0, 784111777, 951782400, 1_700_000_000, 4_102_444_800] {
+            assert_eq!(parse_http_date(&http_date(secs)), Some(secs));
+        }
+        assert_eq!(parse_http_date("Sunday, 06-Nov-94 08:49:37 GMT"), None);
+        assert_eq!(parse_http_date("Sun, 06 Nov 1994 08:49:37 UTC"), None);
+        assert_eq!(parse_http_date("Sun, 06 Nov 1994 25:49:37 GMT"), None);
+        assert_eq!(parse_http_date("\"5f0e-12\""), None);
+    }
+}
@@ -0,0 +1,170 @@
+use crate::validation::{header_lines, strip_port};
+use std::borrow::Cow;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct HeaderMap {
+    /*
+     *  Headers of the request. The names are stored lowercase, so they
+     *  are looked up case-insensitively, and the repeated headers keep
+     *  every value in the order they were sent.
+     *
+     *  Attributes:
+     *      entries: Lowercase names with the trimmed values.
+     */
+    entries: Vec<(Vec<u8>, Vec<u8>)>,
+}
+
+impl HeaderMap {
+    pub fn parse(buffer: &[u8]) -> Self {
+        /*
+         *  Parse the header section of the request, the lines without
+         *  the colon are skipped.
+         *
+         *  Arguments:
+         *      buffer: Bytes of the request.
+         */
+        let mut headers: HeaderMap = HeaderMap::default();
+        for line in header_lines(buffer) {
+            if let Some(colon_idx) = line.iter().position(|byte| *byte == b':') {
+                headers.append(&line[..colon_idx], line[colon_idx + 1..].trim_ascii());
+            }
+        }
+        headers
+    }
+
+    pub fn append(&mut self, name: &[u8], value: &[u8]) {
+        /*
+         *  Add the value, the earlier values of the name are kept.
+         */
+        self.entries
+            .push((name.to_ascii_lowercase(), value.to_vec()));
+    }
+
+    pub fn get(&self, name: &str) -> Option<&[u8]> {
+        /*
+         *  Returns:
+         *      Value of the first header with the name.
+         */
+        self.get_all(name).next()
+    }
+
+    pub fn get_all<'a>(&'a self, name: &str) -> impl Iterator<Item = &'a [u8]> {
+        /*
+         *  Returns:
+         *      Values of every header with the name, e.g. of Set-Cookie,
+         *      which can't be joined.
+         */
+        let name: Vec<u8> = name.as_bytes().to_ascii_lowercase();
+        self.entries
+            .iter()
+            .filter(move |(entry_name, _)| *entry_name == name)
+            .map(|(_, value)| value.as_slice())
+    }
+
+    pub fn get_joined(&self, name: &str) -> Option<Cow<'_, [u8]>> {
+        /*
+         *  Join the repeated header into the single list, as RFC 9110
+         *  allows for the list-based fields, e.g. Accept.
+         *
+         *  Returns:
+         *      The values separated by ", ", None if the header is missing.
+         */
+        let mut values = self.get_all(name);
+        let first: &[u8] = values.next()?;
+        let Some(second) = values.next() else {
+            return Some(Cow::Borrowed(first));
+        };
+        let mut joined: Vec<u8> = first.to_vec();
+        for value in [second].into_iter().chain(values) {
+            joined.extend_from_slice(b", ");
+            joined.extend_from_slice(value);
+        }
+        Some(Cow::Owned(joined))
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.get(name).is_some()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&[u8], &[u8])> {
+        /*
+         *  Returns:
+         *      Lowercase names and the values, in the order they were sent.
+         */
+        self.entries
+            .iter()
+            .map(|(name, value)| (name.as_slice(), value.as_slice()))
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn content_length(&self) -> Option<u64> {
+        /*
+         *  Returns:
+         *      Length of the body, None if it is missing, invalid, or its
+         *      repeated values disagree.
+         */
+        let joined: Cow<'_, [u8]> = self.get_joined("content-length")?;
+        let mut lengths = joined.split(|byte| *byte == b',').map(|value| {
+            let value: &[u8] = value.trim_ascii();
+            if value.is_empty() || !value.iter().all(u8::is_ascii_digit) {
+                return None;
+            }
+            std::str::from_utf8(value).ok()?.parse::<u64>().ok()
+        });
+        let length: u64 = lengths.next()??;
+        lengths.all(|other| other == Some(length)).then_some(length)
+    }
+
+    pub fn host(&self) -> Option<&[u8]> {
+        /*
+         *  Returns:
+         *      Name of the host without the port, None if the Host header
+         *      is missing, repeated or invalid.
+         */
+        let mut hosts = self.get_all("host");
+        let host: &[u8] = hosts.next()?;
+        if hosts.next().is_some() {
+            return None;
+        }
+        strip_port(host)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn header_map_test() {
+        let headers: HeaderMap = HeaderMap::parse(
+            b"GET / HTTP/1.1\r\nHOST: Example.com:8080\r\nAccept-Encoding: br\r\n\
+              accept-encoding: gzip;q=0.5\r\nSet-Cookie: a=1\r\nset-cookie: b=2\r\n\
+              Content-Length: 42, 42\r\n\r\nbody: no",
+        );
+        assert_eq!(headers.len(), 6);
+        assert_eq!(headers.get("host"), Some(&b"Example.com:8080"[..]));
+        assert_eq!(headers.host(), Some(&b"Example.com"[..]));
+        assert_eq!(
+            headers.get_joined("Accept-Encoding").as_deref(),
+            Some(&b"br, gzip;q=0.5"[..])
+        );
+        assert_eq!(
+            headers.get_all("SET-COOKIE").collect::<Vec<&[u8]>>(),
+            vec![&b"a=1"[..], &b"b=2"[..]]
+        );
+        assert_eq!(headers.content_length(), Some(42));
+        assert!(!headers.contains("body"));
+
+        let conflicting: HeaderMap =
+            HeaderMap::parse(b"POST / HTTP/1.1\r\nContent-Length: 1\r\nContent-Length: 2\r\n\r\n");
+        assert_eq!(conflicting.content_length(), None);
+        assert_eq!(conflicting.host(), None);
+    }
+}
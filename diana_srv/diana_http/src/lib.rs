@@ -0,0 +1,16 @@
+/*
+ *  HTTP/1.1 primitives of the server: the request parser, the header
+ *  validation, the status codes, the codec of the message heads,
+ *  the dates, the byte ranges and the normal form of the paths.
+ *  Nothing here does I/O, so it is tested and fuzzed on plain bytes,
+ *  and shared by the server, its client and the proxy.
+ */
+pub mod buffers;
+pub mod codec;
+pub mod dates;
+pub mod headers;
+pub mod normalize;
+pub mod parser;
+pub mod ranges;
+pub mod status;
+pub mod validation;
@@ -0,0 +1,183 @@
+pub fn split_target(target: &[u8]) -> (&[u8], Option<&[u8]>) {
+    /*
+     *  Split the request target at the first question mark, the query
+     *  never names the resource.
+     *
+     *  Arguments:
+     *      target: Request target in the origin form, e.g. /index.html?v=1
+     *
+     *  Returns:
+     *      The resource path and the query without the question mark, e.g.
+     *      /index.html and v=1. None if the target has no query.
+     */
+    match target.iter().position(|byte| *byte == b'?') {
+        Some(query_idx) => (&target[..query_idx], Some(&target[query_idx + 1..])),
+        None => (target, None),
+    }
+}
+
+pub fn normalize_path(target: &[u8]) -> Vec<u8> {
+    /*
+     *  Bring the resource path to its normal form, so the equivalent paths
+     *  are routed, cached and checked by the access rules the same way.
+     *  The percent-encoded unreserved characters are decoded, the other
+     *  encodings get the uppercase hex digits, the repeated slashes are
+     *  collapsed and the dot-segments are resolved as in RFC 3986 section
+     *  5.2.4. The path never climbs above the root. The query is kept as
+     *  it is.
+     *
+     *  Arguments:
+     *      target: Resource path with the query, e.g. /docs//./a/../b?v=1
+     *
+     *  Returns:
+     *      The normal path, e.g. /docs/b?v=1. Targets, that don't start
+     *      with the slash, e.g. the asterisk, are returned untouched.
+     */
+    if !target.starts_with(b"/") {
+        return target.to_vec();
+    }
+    let (path, query): (&[u8], Option<&[u8]>) = split_target(target);
+    let path: Vec<u8> = normalize_percent_encoding(path);
+
+    let raw_segments: Vec<&[u8]> = path[1..].split(|byte| *byte == b'/').collect();
+    let mut segments: Vec<&[u8]> = Vec::with_capacity(raw_segments.len());
+    let mut trailing_slash: bool = false;
+    for (idx, segment) in raw_segments.iter().enumerate() {
+        let last: bool = idx + 1 == raw_segments.len();
+        match *segment {
+            b"" | b"." => {}
+            b".." => {
+                segments.pop();
+            }
+            segment => {
+                segments.push(segment);
+                continue;
+            }
+        }
+        /* /a/. and /a/b/.. name the directory, so they keep the slash */
+        trailing_slash |= last;
+    }
+
+    let mut normal: Vec<u8> = Vec::with_capacity(target.len());
+    for segment in &segments {
+        normal.push(b'/');
+        normal.extend_from_slice(segment);
+    }
+    if trailing_slash || segments.is_empty() {
+        normal.push(b'/');
+    }
+    if let Some(query) = query {
+        normal.push(b'?');
+        normal.extend_from_slice(query);
+    }
+    normal
+}
+
+fn normalize_percent_encoding(path: &[u8]) -> Vec<u8> {
+    /*
+     *  Decode the percent-encoded unreserved characters, e.g. %7E to ~,
+     *  and uppercase the hex digits of the rest, e.g. %2f to %2F. The
+     *  malformed encodings are kept as they are.
+     */
+    let mut normal: Vec<u8> = Vec::with_capacity(path.len());
+    let mut idx: usize = 0;
+    while idx < path.len() {
+        let decoded: Option<u8> = (path[idx] == b'%')
+            .then(|| path.get(idx + 1..idx + 3))
+            .flatten()
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match decoded {
+            Some(byte) if is_unreserved(byte) => normal.push(byte),
+            Some(byte) => normal.extend_from_slice(format!("%{byte:02X}").as_bytes()),
+            None => {
+                normal.push(path[idx]);
+                idx += 1;
+                continue;
+            }
+        }
+        idx += 3;
+    }
+    normal
+}
+
+fn is_unreserved(byte: u8) -> bool {
+    /*
+     *  Characters, that mean the same when they are percent-encoded,
+     *  RFC 3986 section 2.3
+     */
+    byte.is_ascii_alphanumeric() || b"-._~".contains(&byte)
+}
+
+pub fn lowercase_host(buffer: &mut [u8]) {
+    /*
+     *  Lowercase the value of the Host header in place, the host names
+     *  are case-insensitive, so the handlers see a single spelling.
+     *
+     *  Arguments:
+     *      buffer: Bytes of the request, only the head is changed.
+     */
+    let head_end: usize = buffer
+        .windows(4)
+        .position(|end| end == b"\r\n\r\n")
+        .unwrap_or(buffer.len());
+    let mut line_start: usize = 0;
+    while line_start < head_end {
+        let line_end: usize = buffer[line_start..head_end]
+            .windows(2)
+            .position(|end| end == b"\r\n")
+            .map_or(head_end, |idx| line_start + idx);
+        let line: &mut [u8] = &mut buffer[line_start..line_end];
+        if line.len() > 5 && line[..5].eq_ignore_ascii_case(b"host:") {
+            line[5..].make_ascii_lowercase();
+        }
+        line_start = line_end + 2;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalize_test() {
+        let normal =
+            |path: &str| -> String { String::from_utf8(normalize_path(path.as_bytes())).unwrap() };
+        assert_eq!(normal("/docs//./a/../b?v=1"), "/docs/b?v=1");
+        assert_eq!(normal("//index.html"), "/index.html");
+        assert_eq!(normal("/a/b/"), "/a/b/");
+        assert_eq!(normal("/a/b/.."), "/a/");
+        assert_eq!(normal("/a/."), "/a/");
+        assert_eq!(normal("/"), "/");
+        assert_eq!(normal("/a/../../../etc/passwd"), "/etc/passwd");
+        assert_eq!(normal("/.."), "/");
+        assert_eq!(normal("/..."), "/...");
+
+        /* The encoded dots are dot-segments, the encoded slashes aren't separators */
+        assert_eq!(normal("/a/%2e%2E/b"), "/b");
+        assert_eq!(normal("/%7Euser/%61.html"), "/~user/a.html");
+        assert_eq!(normal("/a%2fb/%c3%a9"), "/a%2Fb/%C3%A9");
+        assert_eq!(normal("/100%/%zz"), "/100%/%zz");
+        assert_eq!(normal("/q?next=/a/../b//c"), "/q?next=/a/../b//c");
+        assert_eq!(normal("*"), "*");
+
+        assert_eq!(
+            split_target(b"/index.html?v=1"),
+            (&b"/index.html"[..], Some(&b"v=1"[..]))
+        );
+        assert_eq!(
+            split_target(b"/q?next=/a?b"),
+            (&b"/q"[..], Some(&b"next=/a?b"[..]))
+        );
+        assert_eq!(split_target(b"/search?"), (&b"/search"[..], Some(&b""[..])));
+        assert_eq!(split_target(b"/index.html"), (&b"/index.html"[..], None));
+
+        let mut request: Vec<u8> =
+            b"GET /A HTTP/1.1\r\nHOST: Example.COM:8080\r\nX-Name: Diana\r\n\r\nBODY".to_vec();
+        lowercase_host(&mut request);
+        assert_eq!(
+            request,
+            b"GET /A HTTP/1.1\r\nHOST: example.com:8080\r\nX-Name: Diana\r\n\r\nBODY"
+        );
+    }
+}
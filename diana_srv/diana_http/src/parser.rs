@@ -0,0 +1,340 @@
+use crate::buffers::constants::{CR, HEADER_END, NEWLINE, SPACE};
+use crate::buffers::find_in_buffer;
+use crate::status::HttpResponseStatus;
+use crate::validation::{FramingError, check_message_framing, header_lines};
+use std::fmt;
+
+/* HTTP/1.0 */
+const HTTP_1_0: [u8; 8] = [72, 84, 84, 80, 47, 49, 46, 48];
+/* HTTP/1.1 */
+const HTTP_1_1: [u8; 8] = [72, 84, 84, 80, 47, 49, 46, 49];
+/* HTTP/ */
+const HTTP_PREFIX: [u8; 5] = [72, 84, 84, 80, 47];
+/* The maximum length of the request line with the headers */
+pub const MAX_HEAD_SIZE: usize = 8192;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HttpVersion {
+    Http10,
+    Http11,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ParseError {
+    /*
+     *  Reasons to refuse the request, before its head is complete.
+     */
+    HeadTooLarge,
+    InvalidRequestLine,
+    UnsupportedVersion,
+    InvalidContentLength,
+    UnsupportedTransferEncoding,
+    Framing(FramingError),
+}
+
+impl ParseError {
+    pub fn status(&self) -> HttpResponseStatus {
+        /*
+         *  Returns:
+         *      Status, that the refusal is answered with.
+         */
+        match self {
+            Self::HeadTooLarge => HttpResponseStatus::RequestHeaderFieldsTooLarge,
+            Self::UnsupportedVersion => HttpResponseStatus::HttpVersionNotSupported,
+            Self::UnsupportedTransferEncoding => HttpResponseStatus::NotImplemented,
+            Self::InvalidRequestLine | Self::InvalidContentLength | Self::Framing(_) => {
+                HttpResponseStatus::BadRequest
+            }
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let msg: &str = match self {
+            Self::Framing(e) => return write!(f, "{e}"),
+            Self::HeadTooLarge => "Request head is too large",
+            Self::InvalidRequestLine => "Request line is malformed",
+            Self::UnsupportedVersion => "HTTP version is not supported",
+            Self::InvalidContentLength => "Content-Length is not a number",
+            Self::UnsupportedTransferEncoding => "Transfer-Encoding is not supported",
+        };
+        write!(f, "{msg}")
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RequestHead {
+    /*
+     *  Framing of the request, known once its head is received.
+     *
+     *  Attributes:
+     *      version: HTTP version from the request line.
+     *      head_len: Length of the request line with the headers, including
+     *      the empty line.
+     *      body_len: Length of the body, that follows the head.
+     *      keep_alive: Whether the client wants to send more requests on
+     *      the connection.
+     */
+    pub version: HttpVersion,
+    pub head_len: usize,
+    pub body_len: usize,
+    pub keep_alive: bool,
+}
+
+impl RequestHead {
+    pub fn message_len(&self) -> usize {
+        self.head_len + self.body_len
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ParserState {
+    /*
+     *  Progress of the request, that is received in parts.
+     *
+     *  RequestLine: Waiting for the end of the request line.
+     *  Headers: Waiting for the empty line, that ends the headers.
+     *  Body: The head is received, the body is still incomplete.
+     *  Complete: The whole request is in the buffer.
+     */
+    RequestLine,
+    Headers(HttpVersion),
+    Body(RequestHead),
+    Complete(RequestHead),
+}
+
+#[derive(Debug)]
+pub struct RequestParser {
+    /*
+     *  Incremental parser of the HTTP/1.x requests. It is fed the growing
+     *  buffer after every read, and it resumes where the previous call
+     *  stopped, so the terminators split between the reads are found.
+     *
+     *  Attributes:
+     *      state: Progress of the current request.
+     *      scanned: Bytes of the buffer already searched for the terminator.
+     *      max_head_size: The maximum length of the head.
+     */
+    state: ParserState,
+    scanned: usize,
+    max_head_size: usize,
+}
+
+impl RequestParser {
+    pub fn new(max_head_size: usize) -> Self {
+        RequestParser {
+            state: ParserState::RequestLine,
+            scanned: 0,
+            max_head_size,
+        }
+    }
+
+    pub fn state(&self) -> ParserState {
+        self.state
+    }
+
+    pub fn reset(&mut self) {
+        /*
+         *  Prepare for the next request on the connection, the buffer must
+         *  start with it.
+         */
+        self.state = ParserState::RequestLine;
+        self.scanned = 0;
+    }
+
+    pub fn advance(&mut self, buffer: &[u8]) -> Result<ParserState, ParseError> {
+        /*
+         *  Continue parsing with the bytes received since the last call.
+         *
+         *  Arguments:
+         *      buffer: Bytes of the request received so far, it must start
+         *      with the request line and only grow between the calls.
+         *
+         *  Returns:
+         *      The state reached, or the reason to refuse the request.
+         */
+        loop {
+            match self.state {
+                ParserState::RequestLine => {
+                    let Some(eol_idx) = buffer[self.scanned..]
+                        .iter()
+                        .position(|byte| *byte == NEWLINE)
+                    else {
+                        return self.wait(buffer);
+                    };
+                    let eol_idx: usize = self.scanned + eol_idx;
+                    let version: HttpVersion = parse_request_line(&buffer[..eol_idx])?;
+                    self.state = ParserState::Headers(version);
+                    self.scanned = eol_idx + 1;
+                }
+                ParserState::Headers(version) => {
+                    /* The terminator might start in the bytes scanned before */
+                    let from: usize = self.scanned.saturating_sub(HEADER_END.len() - 1);
+                    let end_idx: usize = find_in_buffer(&buffer[from..], HEADER_END);
+                    if end_idx == usize::MAX {
+                        return self.wait(buffer);
+                    }
+                    let head_len: usize = from + end_idx + HEADER_END.len();
+                    if head_len > self.max_head_size {
+                        return Err(ParseError::HeadTooLarge);
+                    }
+                    self.state = ParserState::Body(parse_head(&buffer[..head_len], version)?);
+                    self.scanned = head_len;
+                }
+                ParserState::Body(head) => {
+                    if buffer.len() < head.message_len() {
+                        return Ok(self.state);
+                    }
+                    self.state = ParserState::Complete(head);
+                }
+                ParserState::Complete(_) => return Ok(self.state),
+            }
+        }
+    }
+
+    fn wait(&mut self, buffer: &[u8]) -> Result<ParserState, ParseError> {
+        if buffer.len() > self.max_head_size {
+            return Err(ParseError::HeadTooLarge);
+        }
+        self.scanned = buffer.len();
+        Ok(self.state)
+    }
+}
+
+fn parse_request_line(line: &[u8]) -> Result<HttpVersion, ParseError> {
+    /*
+     *  GET /index.html HTTP/1.1
+     */
+    let line: &[u8] = line.strip_suffix(&[CR]).unwrap_or(line);
+    let parts: Vec<&[u8]> = line.split(|byte| *byte == SPACE).collect();
+    let [method, target, version] = parts[..] else {
+        return Err(ParseError::InvalidRequestLine);
+    };
+    if method.is_empty() || target.is_empty() {
+        return Err(ParseError::InvalidRequestLine);
+    }
+    match version {
+        version if version == HTTP_1_1 => Ok(HttpVersion::Http11),
+        version if version == HTTP_1_0 => Ok(HttpVersion::Http10),
+        version if version.starts_with(&HTTP_PREFIX) => Err(ParseError::UnsupportedVersion),
+        _ => Err(ParseError::InvalidRequestLine),
+    }
+}
+
+fn parse_head(head: &[u8], version: HttpVersion) -> Result<RequestHead, ParseError> {
+    /*
+     *  Read the headers, that frame the body and decide the persistence
+     *  of the connection. HTTP/1.1 keeps the connection by default,
+     *  HTTP/1.0 only with Connection: keep-alive. The ambiguous framing
+     *  is refused first, so Transfer-Encoding with Content-Length is
+     *  answered as the smuggling attempt, not as the missing feature.
+     */
+    check_message_framing(head).map_err(ParseError::Framing)?;
+    let mut body_len: usize = 0;
+    let mut keep_alive: bool = version == HttpVersion::Http11;
+    for line in header_lines(head) {
+        let Some(colon_idx) = line.iter().position(|byte| *byte == b':') else {
+            continue;
+        };
+        let name: &[u8] = &line[..colon_idx];
+        let value: &[u8] = line[colon_idx + 1..].trim_ascii();
+        if name.eq_ignore_ascii_case(b"content-length") {
+            /* Conflicting values are refused by the framing check */
+            let first: &[u8] = value.split(|byte| *byte == b',').next().unwrap_or(value);
+            body_len = std::str::from_utf8(first.trim_ascii())
+                .ok()
+                .and_then(|length| length.parse().ok())
+                .ok_or(ParseError::InvalidContentLength)?;
+        } else if name.eq_ignore_ascii_case(b"transfer-encoding") {
+            return Err(ParseError::UnsupportedTransferEncoding);
+        } else if name.eq_ignore_ascii_case(b"connection") {
+            for token in value.split(|byte| *byte == b',') {
+                let token: &[u8] = token.trim_ascii();
+                if token.eq_ignore_ascii_case(b"close") {
+                    keep_alive = false;
+                } else if token.eq_ignore_ascii_case(b"keep-alive") {
+                    keep_alive = true;
+                }
+            }
+        }
+    }
+    Ok(RequestHead {
+        version,
+        head_len: head.len(),
+        body_len,
+        keep_alive,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn partial_reads_test() {
+        let request: &[u8] = b"POST /api/data HTTP/1.1\r\nHost: localhost\r\nContent-Length: 4\r\n\r\nbodyGET / HTTP/1.1\r\n";
+        let head_len: usize = 63;
+        let expected = RequestHead {
+            version: HttpVersion::Http11,
+            head_len,
+            body_len: 4,
+            keep_alive: true,
+        };
+        /* Every split point, including the ones inside the terminator */
+        for split in 1..head_len {
+            let mut parser: RequestParser = RequestParser::new(8192);
+            let first: ParserState = parser.advance(&request[..split]).unwrap();
+            assert!(matches!(
+                first,
+                ParserState::RequestLine | ParserState::Headers(_)
+            ));
+            assert_eq!(
+                parser.advance(&request[..head_len + 2]),
+                Ok(ParserState::Body(expected))
+            );
+            assert_eq!(parser.advance(request), Ok(ParserState::Complete(expected)));
+        }
+    }
+
+    #[test]
+    fn keep_alive_test() {
+        let parse = |request: &[u8]| -> Result<ParserState, ParseError> {
+            RequestParser::new(8192).advance(request)
+        };
+        let keep_alive = |request: &[u8]| -> bool {
+            match parse(request) {
+                Ok(ParserState::Complete(head)) => head.keep_alive,
+                state => panic!("{state:?}"),
+            }
+        };
+        assert!(!keep_alive(b"GET / HTTP/1.0\r\n\r\n"));
+        assert!(keep_alive(
+            b"GET / HTTP/1.0\r\nConnection: Keep-Alive\r\n\r\n"
+        ));
+        assert!(!keep_alive(b"GET / HTTP/1.1\r\nConnection: close\r\n\r\n"));
+        assert_eq!(
+            parse(b"GET / HTTP/2.0\r\n\r\n"),
+            Err(ParseError::UnsupportedVersion)
+        );
+        assert_eq!(parse(b"GET /\r\n"), Err(ParseError::InvalidRequestLine));
+        assert_eq!(
+            parse(b"POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n"),
+            Err(ParseError::UnsupportedTransferEncoding)
+        );
+        let both: Result<ParserState, ParseError> = parse(
+            b"POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\nContent-Length: 4\r\n\r\nbody",
+        );
+        assert_eq!(
+            both,
+            Err(ParseError::Framing(
+                FramingError::ContentLengthWithTransferEncoding
+            ))
+        );
+        assert_eq!(both.unwrap_err().status().value(), 400);
+        assert_eq!(
+            RequestParser::new(16).advance(b"GET /a-very-long-path HTTP/1.1\r\n"),
+            Err(ParseError::HeadTooLarge)
+        );
+    }
+}
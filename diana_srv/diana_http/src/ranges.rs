@@ -0,0 +1,132 @@
+use std::ops::Range;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RangeSelection {
+    /*
+     *  What the Range header asks for.
+     *
+     *  Full: The whole representation with 200, also when the header is
+     *  missing, malformed or asks for several ranges.
+     *  Partial: The bytes in the range with 206.
+     *  Unsatisfiable: No byte of the range exists, it is answered with 416.
+     */
+    Full,
+    Partial(Range<usize>),
+    Unsatisfiable,
+}
+
+pub fn parse_range(value: &[u8], len: usize) -> RangeSelection {
+    /*
+     *  Parse the single byte range, e.g. bytes=0-499, bytes=500- or
+     *  bytes=-500 for the last 500 bytes.
+     *
+     *  Arguments:
+     *      value: Value of the Range header.
+     *      len: Length of the representation.
+     */
+    let Some(spec) = std::str::from_utf8(value)
+        .ok()
+        .and_then(|value| value.trim().strip_prefix("bytes="))
+    else {
+        return RangeSelection::Full;
+    };
+    if spec.contains(',') {
+        return RangeSelection::Full;
+    }
+    let Some((first, last)) = spec.trim().split_once('-') else {
+        return RangeSelection::Full;
+    };
+    let number = |field: &str| -> Option<usize> {
+        let field: &str = field.trim();
+        field
+            .bytes()
+            .all(|byte| byte.is_ascii_digit())
+            .then(|| field.parse().ok())
+            .flatten()
+    };
+    match (first.trim().is_empty(), number(first), number(last)) {
+        /* The suffix of the representation */
+        (true, _, Some(suffix)) => match suffix.min(len) {
+            0 => RangeSelection::Unsatisfiable,
+            suffix => RangeSelection::Partial(len - suffix..len),
+        },
+        (false, Some(start), _) if last.trim().is_empty() => bounded(start, len, len),
+        (false, Some(start), Some(end)) if start <= end => bounded(start, end + 1, len),
+        _ => RangeSelection::Full,
+    }
+}
+
+fn bounded(start: usize, end: usize, len: usize) -> RangeSelection {
+    if start >= len {
+        return RangeSelection::Unsatisfiable;
+    }
+    RangeSelection::Partial(start..end.min(len))
+}
+
+pub fn content_range(selection: &RangeSelection, len: usize) -> Option<String> {
+    /*
+     *  Returns:
+     *      Value of the Content-Range header, None for the whole
+     *      representation.
+     */
+    match selection {
+        RangeSelection::Full => None,
+        RangeSelection::Partial(range) => {
+            Some(format!("bytes {}-{}/{len}", range.start, range.end - 1))
+        }
+        RangeSelection::Unsatisfiable => Some(format!("bytes */{len}")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ranges_test() {
+        assert_eq!(
+            parse_range(b"bytes=0-499", 1000),
+            RangeSelection::Partial(0..500)
+        );
+        assert_eq!(
+            parse_range(b"bytes=500-", 1000),
+            RangeSelection::Partial(500..1000)
+        );
+        assert_eq!(
+            parse_range(b"bytes=-200", 1000),
+            RangeSelection::Partial(800..1000)
+        );
+        assert_eq!(
+            parse_range(b"bytes=-2000", 1000),
+            RangeSelection::Partial(0..1000)
+        );
+        assert_eq!(
+            parse_range(b"bytes=900-5000", 1000),
+            RangeSelection::Partial(900..1000)
+        );
+        assert_eq!(
+            parse_range(b"bytes=1000-", 1000),
+            RangeSelection::Unsatisfiable
+        );
+        assert_eq!(
+            parse_range(b"bytes=-0", 1000),
+            RangeSelection::Unsatisfiable
+        );
+
+        /* The ranges, that can't be served in one piece, are ignored */
+        assert_eq!(parse_range(b"bytes=0-1,5-6", 1000), RangeSelection::Full);
+        assert_eq!(parse_range(b"bytes=5-1", 1000), RangeSelection::Full);
+        assert_eq!(parse_range(b"bytes=+1-2", 1000), RangeSelection::Full);
+        assert_eq!(parse_range(b"items=0-1", 1000), RangeSelection::Full);
+
+        assert_eq!(
+            content_range(&RangeSelection::Partial(0..500), 1000).as_deref(),
+            Some("bytes 0-499/1000")
+        );
+        assert_eq!(
+            content_range(&RangeSelection::Unsatisfiable, 1000).as_deref(),
+            Some("bytes */1000")
+        );
+        assert_eq!(content_range(&RangeSelection::Full, 1000), None);
+    }
+}
@@ -0,0 +1,210 @@
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HttpResponseStatus {
+    /*
+     * Defines all status codes
+     */
+    Ok = 200,
+    NoContent = 204,
+    PartialContent = 206,
+    MovedPermanently = 301,
+    Found = 302,
+    NotModified = 304,
+    TemporaryRedirect = 307,
+    PermanentRedirect = 308,
+    BadRequest = 400,
+    Unauthorized = 401,
+    Forbidden = 403,
+    NotFound = 404,
+    MethodNotAllowed = 405,
+    NotAcceptable = 406,
+    Conflict = 409,
+    PreconditionFailed = 412,
+    PayloadTooLarge = 413,
+    UnsupportedMediaType = 415,
+    RangeNotSatisfiable = 416,
+    IamATeapot = 418,
+    MisdirectedRequest = 421,
+    UnprocessableContent = 422,
+    Locked = 423,
+    TooManyRequests = 429,
+    RequestHeaderFieldsTooLarge = 431,
+    InternalServerError = 500,
+    NotImplemented = 501,
+    BadGateway = 502,
+    ServiceUnavailable = 503,
+    GatewayTimeout = 504,
+    HttpVersionNotSupported = 505,
+}
+
+impl HttpResponseStatus {
+    pub fn value(&self) -> usize {
+        /*
+         *  Accessor.
+         *
+         *  Returns:
+         *      Status code, that this enum owns.
+         */
+        match self {
+            Self::Ok => 200,
+            Self::NoContent => 204,
+            Self::PartialContent => 206,
+            Self::MovedPermanently => 301,
+            Self::Found => 302,
+            Self::NotModified => 304,
+            Self::TemporaryRedirect => 307,
+            Self::PermanentRedirect => 308,
+            Self::BadRequest => 400,
+            Self::Unauthorized => 401,
+            Self::Forbidden => 403,
+            Self::NotFound => 404,
+            Self::MethodNotAllowed => 405,
+            Self::NotAcceptable => 406,
+            Self::Conflict => 409,
+            Self::PreconditionFailed => 412,
+            Self::PayloadTooLarge => 413,
+            Self::UnsupportedMediaType => 415,
+            Self::RangeNotSatisfiable => 416,
+            Self::IamATeapot => 418,
+            Self::MisdirectedRequest => 421,
+            Self::UnprocessableContent => 422,
+            Self::Locked => 423,
+            Self::TooManyRequests => 429,
+            Self::RequestHeaderFieldsTooLarge => 431,
+            Self::InternalServerError => 500,
+            Self::NotImplemented => 501,
+            Self::BadGateway => 502,
+            Self::ServiceUnavailable => 503,
+            Self::GatewayTimeout => 504,
+            Self::HttpVersionNotSupported => 505,
+        }
+    }
+
+    pub fn from_value(value: usize) -> Option<Self> {
+        /*
+         *  Returns:
+         *      The status of the code, None if the server never sends it.
+         */
+        match value {
+            200 => Some(Self::Ok),
+            204 => Some(Self::NoContent),
+            206 => Some(Self::PartialContent),
+            301 => Some(Self::MovedPermanently),
+            302 => Some(Self::Found),
+            304 => Some(Self::NotModified),
+            307 => Some(Self::TemporaryRedirect),
+            308 => Some(Self::PermanentRedirect),
+            400 => Some(Self::BadRequest),
+            401 => Some(Self::Unauthorized),
+            403 => Some(Self::Forbidden),
+            404 => Some(Self::NotFound),
+            405 => Some(Self::MethodNotAllowed),
+            406 => Some(Self::NotAcceptable),
+            409 => Some(Self::Conflict),
+            412 => Some(Self::PreconditionFailed),
+            413 => Some(Self::PayloadTooLarge),
+            415 => Some(Self::UnsupportedMediaType),
+            416 => Some(Self::RangeNotSatisfiable),
+            418 => Some(Self::IamATeapot),
+            421 => Some(Self::MisdirectedRequest),
+            422 => Some(Self::UnprocessableContent),
+            423 => Some(Self::Locked),
+            429 => Some(Self::TooManyRequests),
+            431 => Some(Self::RequestHeaderFieldsTooLarge),
+            500 => Some(Self::InternalServerError),
+            501 => Some(Self::NotImplemented),
+            502 => Some(Self::BadGateway),
+            503 => Some(Self::ServiceUnavailable),
+            504 => Some(Self::GatewayTimeout),
+            505 => Some(Self::HttpVersionNotSupported),
+            _ => None,
+        }
+    }
+
+    pub fn reason(&self) -> &'static str {
+        /*
+         *  Accessor.
+         *
+         *  Returns:
+         *      Reason phrase, that is sent next to the status code.
+         */
+        match self {
+            Self::Ok => "OK",
+            Self::NoContent => "No Content",
+            Self::PartialContent => "Partial Content",
+            Self::MovedPermanently => "Moved Permanently",
+            Self::Found => "Found",
+            Self::NotModified => "Not Modified",
+            Self::TemporaryRedirect => "Temporary Redirect",
+            Self::PermanentRedirect => "Permanent Redirect",
+            Self::BadRequest => "Bad Request",
+            Self::Unauthorized => "Unauthorized",
+            Self::Forbidden => "Forbidden",
+            Self::NotFound => "Not Found",
+            Self::MethodNotAllowed => "Method Not Allowed",
+            Self::NotAcceptable => "Not Acceptable",
+            Self::Conflict => "Conflict",
+            Self::PreconditionFailed => "Precondition Failed",
+            Self::PayloadTooLarge => "Payload Too Large",
+            Self::UnsupportedMediaType => "Unsupported Media Type",
+            Self::RangeNotSatisfiable => "Range Not Satisfiable",
+            Self::IamATeapot => "I'm a teapot",
+            Self::MisdirectedRequest => "Misdirected Request",
+            Self::UnprocessableContent => "Unprocessable Content",
+            Self::Locked => "Locked",
+            Self::TooManyRequests => "Too Many Requests",
+            Self::RequestHeaderFieldsTooLarge => "Request Header Fields Too Large",
+            Self::InternalServerError => "Internal Server Error",
+            Self::NotImplemented => "Not Implemented",
+            Self::BadGateway => "Bad Gateway",
+            Self::ServiceUnavailable => "Service Unavailable",
+            Self::GatewayTimeout => "Gateway Timeout",
+            Self::HttpVersionNotSupported => "HTTP Version Not Supported",
+        }
+    }
+}
+
+#[derive(PartialEq, Eq, Hash, Debug, Clone, Copy)]
+pub enum RequestType {
+    /*
+     * Specify HTTP methods
+     */
+    Get = 0,
+    Post = 1,
+    Head = 2,
+    Trace = 3,
+    Invalid = -1,
+}
+
+impl RequestType {
+    pub fn value(&self) -> usize {
+        /*
+         *  Accessor. Returns offsets to parse the HTML request.
+         *
+         *  Returns:
+         *      HTTP request method, that this enum owns.
+         */
+        match self {
+            Self::Get => 3,
+            Self::Post => 4,
+            Self::Head => 4,
+            Self::Trace => 5,
+            Self::Invalid => usize::MAX,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        /*
+         *  Accessor.
+         *
+         *  Returns:
+         *      The method, as it is written in the request line.
+         */
+        match self {
+            Self::Get => "GET",
+            Self::Post => "POST",
+            Self::Head => "HEAD",
+            Self::Trace => "TRACE",
+            Self::Invalid => "",
+        }
+    }
+}
@@ -0,0 +1,516 @@
+use crate::buffers::constants::{CR, NEWLINE, SPACE, TAB};
+use std::fmt;
+
+/* Headers, that TRACE never echoes, so the page can't read the credentials */
+const TRACE_HIDDEN_HEADERS: [&[u8]; 3] = [b"authorization", b"cookie", b"proxy-authorization"];
+
+#[derive(Debug, PartialEq)]
+pub enum FramingError {
+    /*
+     *  Reasons to refuse the request, because its length is ambiguous.
+     *  Proxies in front of the server could frame it differently than we do
+     *  (request smuggling), so the connection must be closed afterwards.
+     */
+    ContentLengthWithTransferEncoding,
+    ConflictingContentLength,
+    InvalidContentLength,
+    ObsoleteLineFolding,
+}
+
+impl fmt::Display for FramingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let msg: &str = match self {
+            Self::ContentLengthWithTransferEncoding => {
+                "Both Content-Length and Transfer-Encoding are present"
+            }
+            Self::ConflictingContentLength => "Content-Length values differ",
+            Self::InvalidContentLength => "Content-Length is not a number",
+            Self::ObsoleteLineFolding => "Header is continued with obs-fold",
+        };
+        write!(f, "{msg}")
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum HostError {
+    /*
+     *  Reasons to refuse the request because of its Host header. All of them
+     *  are answered with 400, except Misdirected, which is answered with 421.
+     */
+    Missing,
+    Duplicate,
+    Invalid,
+    Misdirected,
+}
+
+impl fmt::Display for HostError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let msg: &str = match self {
+            Self::Missing => "Host header is missing",
+            Self::Duplicate => "Host header is repeated",
+            Self::Invalid => "Host header is malformed",
+            Self::Misdirected => "Host is not served by this server",
+        };
+        write!(f, "{msg}")
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum SyntaxError {
+    /*
+     *  Reasons to answer the request with 400, because its header section
+     *  or its path contains bytes, that aren't allowed there.
+     */
+    InvalidHeaderName,
+    InvalidHeaderValue,
+    InvalidPath,
+}
+
+impl fmt::Display for SyntaxError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let msg: &str = match self {
+            Self::InvalidHeaderName => "Header name is not a token",
+            Self::InvalidHeaderValue => "Header value contains control characters",
+            Self::InvalidPath => "Path is not valid UTF-8 or contains control characters",
+        };
+        write!(f, "{msg}")
+    }
+}
+
+pub fn header_lines(buffer: &[u8]) -> Vec<&[u8]> {
+    /*
+     *  Split the header section into lines, the request line is skipped.
+     *
+     *  Arguments:
+     *      buffer: Bytes of the request.
+     *
+     *  Returns:
+     *      Header lines without the EOL, up to the empty line or the end
+     *      of the buffer.
+     */
+    let mut lines: Vec<&[u8]> = Vec::new();
+    for line in buffer.split(|byte| *byte == NEWLINE).skip(1) {
+        let line: &[u8] = line.strip_suffix(&[CR]).unwrap_or(line);
+        if line.is_empty() {
+            break;
+        }
+        lines.push(line);
+    }
+    lines
+}
+
+pub fn trace_echo(buffer: &[u8]) -> Vec<u8> {
+    /*
+     *  Body of the TRACE response, the request line and the headers as
+     *  the server received them, except the credentials.
+     *
+     *  Arguments:
+     *      buffer: Bytes of the request.
+     *
+     *  Returns:
+     *      The message/http content, terminated with the empty line.
+     */
+    let request_line: &[u8] = buffer
+        .split(|byte| *byte == NEWLINE)
+        .next()
+        .unwrap_or_default();
+    let mut echo: Vec<u8> = request_line
+        .strip_suffix(&[CR])
+        .unwrap_or(request_line)
+        .to_vec();
+    echo.extend_from_slice(b"\r\n");
+    for line in header_lines(buffer) {
+        let name: &[u8] = line
+            .iter()
+            .position(|byte| *byte == b':')
+            .map_or(line, |colon_idx| &line[..colon_idx]);
+        if TRACE_HIDDEN_HEADERS
+            .iter()
+            .any(|hidden| name.trim_ascii().eq_ignore_ascii_case(hidden))
+        {
+            continue;
+        }
+        echo.extend_from_slice(line);
+        echo.extend_from_slice(b"\r\n");
+    }
+    echo.extend_from_slice(b"\r\n");
+    echo
+}
+
+pub fn header_value<'a>(buffer: &'a [u8], name: &str) -> Option<&'a [u8]> {
+    /*
+     *  Returns:
+     *      Trimmed value of the first header with the name, compared
+     *      case-insensitively.
+     */
+    header_lines(buffer).into_iter().find_map(|line| {
+        let colon_idx: usize = line.iter().position(|byte| *byte == b':')?;
+        line[..colon_idx]
+            .eq_ignore_ascii_case(name.as_bytes())
+            .then(|| line[colon_idx + 1..].trim_ascii())
+    })
+}
+
+pub fn check_message_framing(buffer: &[u8]) -> Result<(), FramingError> {
+    /*
+     *  Validate the headers, that decide where the request body ends.
+     *
+     *  Arguments:
+     *      buffer: Bytes of the request.
+     *
+     *  Returns:
+     *      Ok if the framing is unambiguous, the reason of the refusal
+     *      otherwise.
+     */
+    let mut content_length: Option<&[u8]> = None;
+    let mut has_transfer_encoding: bool = false;
+
+    for line in header_lines(buffer) {
+        /* Line starting with whitespace continues the previous header */
+        if line[0] == SPACE || line[0] == TAB {
+            return Err(FramingError::ObsoleteLineFolding);
+        }
+        let Some(colon_idx) = line.iter().position(|byte| *byte == b':') else {
+            continue;
+        };
+        let name: &[u8] = &line[..colon_idx];
+        let value: &[u8] = line[colon_idx + 1..].trim_ascii();
+
+        if name.eq_ignore_ascii_case(b"transfer-encoding") {
+            has_transfer_encoding = true;
+        } else if name.eq_ignore_ascii_case(b"content-length") {
+            /* The value might be a list, e.g. Content-Length: 42, 42 */
+            for length in value.split(|byte| *byte == b',') {
+                let length: &[u8] = length.trim_ascii();
+                if length.is_empty() || !length.iter().all(u8::is_ascii_digit) {
+                    return Err(FramingError::InvalidContentLength);
+                }
+                match content_length {
+                    Some(previous) if previous != length => {
+                        return Err(FramingError::ConflictingContentLength);
+                    }
+                    _ => content_length = Some(length),
+                }
+            }
+        }
+    }
+
+    if content_length.is_some() && has_transfer_encoding {
+        return Err(FramingError::ContentLengthWithTransferEncoding);
+    }
+    Ok(())
+}
+
+pub fn check_header_syntax(buffer: &[u8]) -> Result<(), SyntaxError> {
+    /*
+     *  Validate the header names and values. Names must be tokens, values
+     *  must be visible ASCII separated by spaces or tabs.
+     *
+     *  Arguments:
+     *      buffer: Bytes of the request.
+     */
+    for line in header_lines(buffer) {
+        let Some(colon_idx) = line.iter().position(|byte| *byte == b':') else {
+            return Err(SyntaxError::InvalidHeaderName);
+        };
+        let name: &[u8] = &line[..colon_idx];
+        if name.is_empty() || !name.iter().all(|byte| is_token_char(*byte)) {
+            return Err(SyntaxError::InvalidHeaderName);
+        }
+        if !line[colon_idx + 1..]
+            .iter()
+            .all(|byte| byte.is_ascii_graphic() || *byte == SPACE || *byte == TAB)
+        {
+            return Err(SyntaxError::InvalidHeaderValue);
+        }
+    }
+    Ok(())
+}
+
+pub fn check_path(path: &[u8]) -> Result<(), SyntaxError> {
+    /*
+     *  Validate the resource path, it is used to build the file paths,
+     *  so it must be valid UTF-8 without the control characters.
+     */
+    match std::str::from_utf8(path) {
+        Ok(path) if !path.chars().any(|c| c.is_control() || c == ' ') => Ok(()),
+        _ => Err(SyntaxError::InvalidPath),
+    }
+}
+
+fn is_token_char(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&byte)
+}
+
+pub fn split_request_target(target: &[u8]) -> (Vec<u8>, Option<Vec<u8>>) {
+    /*
+     *  Split the absolute-form target (GET http://host/path), that proxies
+     *  send, into the path and the authority.
+     *
+     *  Arguments:
+     *      target: Request target from the request line.
+     *
+     *  Returns:
+     *      The path, with the authority if the target is in absolute-form.
+     *      Targets in origin-form are returned untouched.
+     */
+    let scheme_len: usize = if starts_with_ignore_case(target, b"http://") {
+        7
+    } else if starts_with_ignore_case(target, b"https://") {
+        8
+    } else {
+        return (target.to_vec(), None);
+    };
+    let rest: &[u8] = &target[scheme_len..];
+    let authority_end: usize = rest
+        .iter()
+        .position(|byte| *byte == b'/' || *byte == b'?')
+        .unwrap_or(rest.len());
+    let mut path: Vec<u8> = rest[authority_end..].to_vec();
+    if !path.starts_with(b"/") {
+        path.insert(0, b'/');
+    }
+    (path, Some(rest[..authority_end].to_vec()))
+}
+
+pub fn check_host(
+    buffer: &[u8],
+    target_authority: Option<&[u8]>,
+    server_names: &[String],
+) -> Result<(), HostError> {
+    /*
+     *  Validate the Host header and match it against the served names.
+     *
+     *  Arguments:
+     *      buffer: Bytes of the request.
+     *      target_authority: Authority of the absolute-form target, it takes
+     *      precedence over the Host header.
+     *      server_names: Names served by this server, any host is accepted
+     *      if it is empty.
+     *
+     *  Returns:
+     *      Ok if the host is served, the reason of the refusal otherwise.
+     */
+    let hosts: Vec<&[u8]> = header_lines(buffer)
+        .into_iter()
+        .filter_map(|line| {
+            let colon_idx: usize = line.iter().position(|byte| *byte == b':')?;
+            line[..colon_idx]
+                .eq_ignore_ascii_case(b"host")
+                .then(|| line[colon_idx + 1..].trim_ascii())
+        })
+        .collect();
+    if hosts.len() > 1 {
+        return Err(HostError::Duplicate);
+    }
+
+    /* HTTP/1.0 clients aren't required to send the Host */
+    let request_line: &[u8] = buffer.split(|byte| *byte == NEWLINE).next().unwrap_or(&[]);
+    let is_http_10: bool = request_line.trim_ascii_end().ends_with(b"HTTP/1.0");
+
+    let host: &[u8] = match (target_authority, hosts.first()) {
+        (Some(authority), _) => authority,
+        (None, Some(host)) => host,
+        (None, None) if is_http_10 => return Ok(()),
+        (None, None) => return Err(HostError::Missing),
+    };
+    let host_name: &[u8] = strip_port(host).ok_or(HostError::Invalid)?;
+
+    if server_names.is_empty()
+        || server_names
+            .iter()
+            .any(|name| name.as_bytes().eq_ignore_ascii_case(host_name))
+    {
+        return Ok(());
+    }
+    Err(HostError::Misdirected)
+}
+
+pub fn request_host<'a>(buffer: &'a [u8], target_authority: Option<&'a [u8]>) -> Option<&'a [u8]> {
+    /*
+     *  Arguments:
+     *      buffer: Bytes of the request.
+     *      target_authority: Authority of the absolute-form target.
+     *
+     *  Returns:
+     *      Name of the requested host without the port, None if the request
+     *      doesn't name a valid one.
+     */
+    let host: &[u8] = match target_authority {
+        Some(authority) => authority,
+        None => header_lines(buffer).into_iter().find_map(|line| {
+            let colon_idx: usize = line.iter().position(|byte| *byte == b':')?;
+            line[..colon_idx]
+                .eq_ignore_ascii_case(b"host")
+                .then(|| line[colon_idx + 1..].trim_ascii())
+        })?,
+    };
+    strip_port(host)
+}
+
+pub fn strip_port(host: &[u8]) -> Option<&[u8]> {
+    /*
+     *  Validate the host and remove its port, IPv6 literals keep their
+     *  brackets, e.g. [::1]:8080 -> [::1]
+     */
+    let (name, port): (&[u8], &[u8]) = if host.starts_with(b"[") {
+        let bracket_idx: usize = host.iter().position(|byte| *byte == b']')?;
+        let (name, port) = host.split_at(bracket_idx + 1);
+        if !name[1..bracket_idx]
+            .iter()
+            .all(|byte| byte.is_ascii_hexdigit() || *byte == b':' || *byte == b'.')
+        {
+            return None;
+        }
+        (name, port)
+    } else {
+        let colon_idx: usize = host
+            .iter()
+            .position(|byte| *byte == b':')
+            .unwrap_or(host.len());
+        let (name, port) = host.split_at(colon_idx);
+        if !name
+            .iter()
+            .all(|byte| byte.is_ascii_alphanumeric() || *byte == b'-' || *byte == b'.')
+        {
+            return None;
+        }
+        (name, port)
+    };
+    if name.is_empty() {
+        return None;
+    }
+    match port.strip_prefix(b":") {
+        Some(digits) if !digits.is_empty() && digits.iter().all(u8::is_ascii_digit) => Some(name),
+        None if port.is_empty() => Some(name),
+        _ => None,
+    }
+}
+
+fn starts_with_ignore_case(buffer: &[u8], prefix: &[u8]) -> bool {
+    buffer.len() >= prefix.len() && buffer[..prefix.len()].eq_ignore_ascii_case(prefix)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn trace_echo_test() {
+        let request: &[u8] = b"TRACE /a HTTP/1.1\r\nHost: x\r\nCookie: id=1\r\nX-Trace: 1\r\n\r\n";
+        assert_eq!(
+            trace_echo(request),
+            b"TRACE /a HTTP/1.1\r\nHost: x\r\nX-Trace: 1\r\n\r\n".to_vec()
+        );
+    }
+
+    #[test]
+    fn check_message_framing_test() {
+        let valid: &[u8] = b"POST / HTTP/1.1\r\nContent-Length: 2\r\ncontent-length: 2\r\n\r\nab";
+        assert_eq!(check_message_framing(valid), Ok(()));
+
+        let both: &[u8] =
+            b"POST / HTTP/1.1\r\nContent-Length: 2\r\nTransfer-Encoding: chunked\r\n\r\nab";
+        assert_eq!(
+            check_message_framing(both),
+            Err(FramingError::ContentLengthWithTransferEncoding)
+        );
+
+        let conflicting: &[u8] =
+            b"POST / HTTP/1.1\r\nContent-Length: 2\r\nContent-Length: 3\r\n\r\n";
+        assert_eq!(
+            check_message_framing(conflicting),
+            Err(FramingError::ConflictingContentLength)
+        );
+
+        let listed: &[u8] = b"POST / HTTP/1.1\r\nContent-Length: 2, 4\r\n\r\n";
+        assert_eq!(
+            check_message_framing(listed),
+            Err(FramingError::ConflictingContentLength)
+        );
+
+        let folded: &[u8] = b"GET / HTTP/1.1\r\nX-Custom: a\r\n b\r\n\r\n";
+        assert_eq!(
+            check_message_framing(folded),
+            Err(FramingError::ObsoleteLineFolding)
+        );
+
+        let signed: &[u8] = b"POST / HTTP/1.1\r\nContent-Length: +2\r\n\r\nab";
+        assert_eq!(
+            check_message_framing(signed),
+            Err(FramingError::InvalidContentLength)
+        );
+    }
+
+    #[test]
+    fn check_syntax_test() {
+        let valid: &[u8] = b"GET / HTTP/1.1\r\nX-Token_1: a b\tc ~!\r\n\r\n";
+        assert_eq!(check_header_syntax(valid), Ok(()));
+        assert_eq!(
+            check_header_syntax(b"GET / HTTP/1.1\r\nX Bad: a\r\n\r\n"),
+            Err(SyntaxError::InvalidHeaderName)
+        );
+        assert_eq!(
+            check_header_syntax(b"GET / HTTP/1.1\r\nNo-Colon\r\n\r\n"),
+            Err(SyntaxError::InvalidHeaderName)
+        );
+        assert_eq!(
+            check_header_syntax(b"GET / HTTP/1.1\r\nX-Nul: a\x00b\r\n\r\n"),
+            Err(SyntaxError::InvalidHeaderValue)
+        );
+        assert_eq!(check_path("/zażółć.html".as_bytes()), Ok(()));
+        assert_eq!(check_path(b"/a\xff.html"), Err(SyntaxError::InvalidPath));
+        assert_eq!(check_path(b"/a\x1b.html"), Err(SyntaxError::InvalidPath));
+    }
+
+    #[test]
+    fn split_request_target_test() {
+        assert_eq!(
+            split_request_target(b"http://Example.com:8080/api/data"),
+            (b"/api/data".to_vec(), Some(b"Example.com:8080".to_vec()))
+        );
+        assert_eq!(
+            split_request_target(b"https://example.com"),
+            (b"/".to_vec(), Some(b"example.com".to_vec()))
+        );
+        assert_eq!(
+            split_request_target(b"/index.html"),
+            (b"/index.html".to_vec(), None)
+        );
+    }
+
+    #[test]
+    fn check_host_test() {
+        let names: Vec<String> = vec![String::from("example.com"), String::from("[::1]")];
+        let request = |host: &str| format!("GET / HTTP/1.1\r\n{host}\r\n\r\n").into_bytes();
+
+        assert_eq!(
+            check_host(&request("Host: EXAMPLE.com:8080"), None, &names),
+            Ok(())
+        );
+        assert_eq!(check_host(&request("Host: [::1]:80"), None, &names), Ok(()));
+        assert_eq!(
+            check_host(&request("Host: other.com"), None, &names),
+            Err(HostError::Misdirected)
+        );
+        assert_eq!(
+            check_host(&request("Host: example.com:80x"), None, &names),
+            Err(HostError::Invalid)
+        );
+        assert_eq!(
+            check_host(&request("Host: a\r\nHost: b"), None, &names),
+            Err(HostError::Duplicate)
+        );
+        assert_eq!(
+            check_host(&request("Accept: */*"), None, &names),
+            Err(HostError::Missing)
+        );
+        assert_eq!(check_host(b"GET / HTTP/1.0\r\n\r\n", None, &names), Ok(()));
+        /* The absolute-form target wins over the Host header */
+        assert_eq!(
+            check_host(&request("Host: other.com"), Some(b"example.com"), &names),
+            Ok(())
+        );
+        assert_eq!(check_host(&request("Host: other.com"), None, &[]), Ok(()));
+    }
+}
@@ -1 +1,57 @@
+pub mod access;
+pub mod acme;
+pub mod assets;
+pub mod cache;
+pub mod chaos;
+pub mod client;
+pub mod compression;
+pub mod connection;
+pub mod daemon;
+pub mod dev;
+pub mod disconnect;
+pub mod dns;
+pub mod dump;
+pub mod errors;
+pub mod extensions;
+pub mod extract;
+pub mod fds;
+pub mod forwarded;
+pub mod hooks;
+pub mod http;
+pub mod limits;
+pub mod listen;
+pub mod listing;
+pub mod logging;
+pub mod manifest;
+pub mod markdown;
+pub mod memory;
+pub mod metrics;
+pub mod mirror;
+pub mod negotiation;
+pub mod overrides;
+pub mod panics;
+pub mod pool;
+pub mod precompress;
+pub mod preconditions;
+pub mod privileges;
+pub mod proxy_protocol;
+pub mod quotas;
+pub mod record;
+pub mod report;
+pub mod response_headers;
+pub mod router;
+pub mod scheduler;
+pub mod security;
 pub mod server;
+pub mod service;
+pub mod shortener;
+pub mod signals;
+pub mod slowlog;
+pub mod spa;
+pub mod sse;
+pub mod status;
+pub mod storage;
+pub mod throttle;
+pub mod upgrade;
+pub mod userdir;
+pub mod webhooks;
@@ -0,0 +1,125 @@
+use crate::backend::forwarded::{Cidr, is_trusted};
+use crate::backend::limits::matches_prefix;
+use crate::backend::server::RequestType;
+use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct AccessRule {
+    /*
+     *  Entry of the [[access_rules]] table in the config.
+     *
+     *  Attributes:
+     *      path: Route prefix, that the rule applies to, e.g. /admin
+     *      allow_methods: Methods allowed on the route, any if it is empty.
+     *      allow_from: Address blocks of the allowed clients, any if it
+     *      is empty.
+     */
+    pub path: String,
+    #[serde(default)]
+    pub allow_methods: Vec<String>,
+    #[serde(default)]
+    pub allow_from: Vec<Cidr>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum AccessDecision {
+    /*
+     *  Outcome of the access rules. MethodNotAllowed carries the allowed
+     *  methods for the Allow header.
+     */
+    Allow,
+    MethodNotAllowed(String),
+    Forbidden,
+}
+
+pub fn check_access(
+    rules: &[AccessRule],
+    method: RequestType,
+    resource_path: &[u8],
+    client: IpAddr,
+) -> AccessDecision {
+    /*
+     *  Evaluate the rule with the longest path covering the request,
+     *  the rules of the shorter paths are overridden by it.
+     *
+     *  Arguments:
+     *      rules: The configured rules.
+     *      method: HTTP method of the request.
+     *      resource_path: Resource path from the request.
+     *      client: Effective address of the client.
+     */
+    let Some(rule) = rules
+        .iter()
+        .filter(|rule| matches_prefix(resource_path, rule.path.as_bytes()))
+        .max_by_key(|rule| rule.path.len())
+    else {
+        return AccessDecision::Allow;
+    };
+    if !rule.allow_from.is_empty() && !is_trusted(client, &rule.allow_from) {
+        return AccessDecision::Forbidden;
+    }
+    if !rule.allow_methods.is_empty()
+        && !rule.allow_methods.iter().any(|allowed| {
+            /* HEAD is allowed wherever GET is */
+            allowed.eq_ignore_ascii_case(method.name())
+                || (method == RequestType::Head && allowed.eq_ignore_ascii_case("GET"))
+        })
+    {
+        return AccessDecision::MethodNotAllowed(rule.allow_methods.join(", ").to_uppercase());
+    }
+    AccessDecision::Allow
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_access_test() {
+        let rules: Vec<AccessRule> = toml::from_str::<toml::Table>(
+            r#"
+            [[rules]]
+            path = "/admin"
+            allow_methods = ["GET"]
+            allow_from = ["10.0.0.0/8"]
+
+            [[rules]]
+            path = "/admin/cache"
+            allow_methods = ["get", "post"]
+            "#,
+        )
+        .unwrap()["rules"]
+            .clone()
+            .try_into()
+            .unwrap();
+        let inside: IpAddr = "10.1.2.3".parse().unwrap();
+        let outside: IpAddr = "192.0.2.1".parse().unwrap();
+
+        assert_eq!(
+            check_access(&rules, RequestType::Get, b"/admin/stats", inside),
+            AccessDecision::Allow
+        );
+        assert_eq!(
+            check_access(&rules, RequestType::Head, b"/admin/stats", inside),
+            AccessDecision::Allow
+        );
+        assert_eq!(
+            check_access(&rules, RequestType::Get, b"/admin", outside),
+            AccessDecision::Forbidden
+        );
+        assert_eq!(
+            check_access(&rules, RequestType::Post, b"/admin", inside),
+            AccessDecision::MethodNotAllowed(String::from("GET"))
+        );
+        /* The longer path has no network restriction of its own */
+        assert_eq!(
+            check_access(&rules, RequestType::Post, b"/admin/cache/flush", outside),
+            AccessDecision::Allow
+        );
+        assert_eq!(
+            check_access(&rules, RequestType::Post, b"/index.html", outside),
+            AccessDecision::Allow
+        );
+    }
+}
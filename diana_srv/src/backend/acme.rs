@@ -0,0 +1,67 @@
+use crate::backend::server::HttpResponseStatus;
+use std::fs;
+use std::path::Path;
+
+/* /.well-known/acme-challenge/ */
+const ACME_CHALLENGE_PREFIX: &[u8] = b"/.well-known/acme-challenge/";
+
+pub fn challenge_response(
+    challenge_dir: &Path,
+    resource_path: &[u8],
+) -> Option<(HttpResponseStatus, Vec<u8>)> {
+    /*
+     *  Answer the HTTP-01 challenge of the ACME client (e.g. certbot in
+     *  the webroot mode), that writes the key authorizations into
+     *  the challenge directory, one file per token.
+     *
+     *  Arguments:
+     *      challenge_dir: Directory with the key authorizations.
+     *      resource_path: Resource path from the request.
+     *
+     *  Returns:
+     *      The status with the key authorization, or None if the path isn't
+     *      a challenge.
+     */
+    let token: &[u8] = resource_path.strip_prefix(ACME_CHALLENGE_PREFIX)?;
+    /* Tokens are base64url, so they can't escape the directory */
+    if token.is_empty()
+        || !token
+            .iter()
+            .all(|byte| byte.is_ascii_alphanumeric() || *byte == b'-' || *byte == b'_')
+    {
+        return Some((HttpResponseStatus::NotFound, Vec::new()));
+    }
+    let token: &str = std::str::from_utf8(token).ok()?;
+    match fs::read(challenge_dir.join(token)) {
+        Ok(key_authorization) => Some((HttpResponseStatus::Ok, key_authorization)),
+        Err(_) => Some((HttpResponseStatus::NotFound, Vec::new())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn challenge_response_test() {
+        let dir = env::temp_dir().join(format!("diana_acme_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("tok-EN_1"), b"tok-EN_1.thumbprint").unwrap();
+
+        assert_eq!(
+            challenge_response(&dir, b"/.well-known/acme-challenge/tok-EN_1"),
+            Some((HttpResponseStatus::Ok, b"tok-EN_1.thumbprint".to_vec()))
+        );
+        assert_eq!(
+            challenge_response(&dir, b"/.well-known/acme-challenge/../secret"),
+            Some((HttpResponseStatus::NotFound, Vec::new()))
+        );
+        assert_eq!(
+            challenge_response(&dir, b"/.well-known/acme-challenge/missing"),
+            Some((HttpResponseStatus::NotFound, Vec::new()))
+        );
+        assert_eq!(challenge_response(&dir, b"/index.html"), None);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
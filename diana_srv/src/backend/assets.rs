@@ -0,0 +1,183 @@
+use crate::utils::patterns::glob_match;
+use crate::utils::readers::files::list_files;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/* Hashed names never change their content, so they are cached for a year */
+pub const IMMUTABLE_CACHE_CONTROL: &str = "public, max-age=31536000, immutable";
+/* Hex digits of the content hash in the file name */
+const HASH_LEN: usize = 8;
+
+#[derive(Debug, Default)]
+pub struct HashedAssets {
+    /*
+     *  Assets served under the names with their content hash, e.g.
+     *  app.1a2b3c4d.js, so they can be cached forever. The hashed names
+     *  aren't written to the disk, they are resolved to the originals.
+     *
+     *  Attributes:
+     *      originals: Resource paths of the originals by the hashed ones.
+     *      hashed: Hashed names by the original names, relative to
+     *      the resource directory, as the manifest lists them.
+     */
+    originals: HashMap<Vec<u8>, Vec<u8>>,
+    hashed: BTreeMap<String, String>,
+}
+
+impl HashedAssets {
+    pub fn build(html_dir: &Path, globs: &[String], manifest_name: &str) -> Self {
+        /*
+         *  Hash the assets in the resource directory.
+         *
+         *  Arguments:
+         *      html_dir: The resource directory.
+         *      globs: Patterns of the assets relative to the directory,
+         *      e.g. **.js
+         *      manifest_name: Name of the manifest, that is never hashed.
+         */
+        let mut assets: HashedAssets = HashedAssets::default();
+        for file in list_files(html_dir) {
+            let Some(relative) = file.strip_prefix(html_dir).ok().and_then(|p| p.to_str()) else {
+                continue;
+            };
+            if relative == manifest_name || !is_hashed_asset(globs, relative) {
+                continue;
+            }
+            let Ok(content) = fs::read(&file) else {
+                continue;
+            };
+            let hashed: String = hashed_name(relative, &content_hash(&content));
+            assets.originals.insert(
+                format!("/{hashed}").into_bytes(),
+                format!("/{relative}").into_bytes(),
+            );
+            assets.hashed.insert(String::from(relative), hashed);
+        }
+        assets
+    }
+
+    pub fn original(&self, resource_path: &[u8]) -> Option<&[u8]> {
+        /*
+         *  Returns:
+         *      Resource path of the original, if the path is the hashed one.
+         */
+        self.originals.get(resource_path).map(Vec::as_slice)
+    }
+
+    pub fn manifest_json(&self) -> String {
+        /*
+         *  Returns:
+         *      The manifest, the hashed names by the original ones.
+         */
+        serde_json::to_string_pretty(&self.hashed).unwrap_or_default()
+    }
+
+    pub fn write_manifest(&self, path: &Path) -> io::Result<bool> {
+        /*
+         *  Write the manifest, unless it is the same on the disk, so
+         *  the watcher of the directory isn't woken up for nothing.
+         *
+         *  Returns:
+         *      Whether the manifest was written.
+         */
+        let manifest: String = self.manifest_json();
+        if fs::read_to_string(path).is_ok_and(|written| written == manifest) {
+            return Ok(false);
+        }
+        fs::write(path, manifest)?;
+        Ok(true)
+    }
+
+    pub fn len(&self) -> usize {
+        self.hashed.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.hashed.is_empty()
+    }
+}
+
+pub fn is_hashed_asset(globs: &[String], relative: &str) -> bool {
+    globs.iter().any(|glob| {
+        let glob: &str = glob.strip_prefix('/').unwrap_or(glob);
+        glob_match(glob.as_bytes(), relative.as_bytes())
+    })
+}
+
+pub fn content_hash(content: &[u8]) -> String {
+    /*
+     *  Hash the content with 64-bit FNV-1a, it is stable across the builds,
+     *  so the names survive the restarts and the upgrades.
+     *
+     *  Returns:
+     *      The leading hex digits of the hash.
+     */
+    let hash: u64 = content.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3)
+    });
+    format!("{hash:016x}")[..HASH_LEN].to_string()
+}
+
+pub fn hashed_name(relative: &str, hash: &str) -> String {
+    /*
+     *  Put the hash before the extension of the file name, e.g.
+     *  static/app.js becomes static/app.1a2b3c4d.js
+     */
+    let name_start: usize = relative.rfind('/').map_or(0, |idx| idx + 1);
+    match relative[name_start..].rfind('.') {
+        Some(dot) if dot > 0 => {
+            let (stem, extension) = relative.split_at(name_start + dot);
+            format!("{stem}.{hash}{extension}")
+        }
+        _ => format!("{relative}.{hash}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hashed_assets_test() {
+        assert_eq!(
+            hashed_name("static/app.js", "1a2b3c4d"),
+            "static/app.1a2b3c4d.js"
+        );
+        assert_eq!(
+            hashed_name("app.min.css", "1a2b3c4d"),
+            "app.min.1a2b3c4d.css"
+        );
+        assert_eq!(hashed_name("v1.2/.env", "1a2b3c4d"), "v1.2/.env.1a2b3c4d");
+        assert_eq!(content_hash(b""), "cbf29ce4");
+        assert_ne!(content_hash(b"a"), content_hash(b"b"));
+
+        let html_dir = std::env::temp_dir().join(format!("diana_assets_{}", std::process::id()));
+        fs::create_dir_all(html_dir.join("static")).unwrap();
+        fs::write(html_dir.join("static/app.js"), b"console.log(1)").unwrap();
+        fs::write(html_dir.join("index.html"), b"<p>diana</p>").unwrap();
+        let globs: Vec<String> = vec![String::from("/**.js"), String::from("*.json")];
+        let manifest: &Path = &html_dir.join("asset-manifest.json");
+
+        let assets: HashedAssets = HashedAssets::build(&html_dir, &globs, "asset-manifest.json");
+        let hashed: String = format!("/static/app.{}.js", content_hash(b"console.log(1)"));
+        assert_eq!(assets.len(), 1);
+        assert_eq!(
+            assets.original(hashed.as_bytes()),
+            Some(&b"/static/app.js"[..])
+        );
+        assert_eq!(assets.original(b"/static/app.js"), None);
+        assert!(assets.write_manifest(manifest).unwrap());
+        assert!(!assets.write_manifest(manifest).unwrap());
+        assert_eq!(
+            fs::read_to_string(manifest).unwrap(),
+            format!("{{\n  \"static/app.js\": \"{}\"\n}}", &hashed[1..])
+        );
+
+        /* The manifest itself isn't hashed, though the globs match it */
+        let rebuilt: HashedAssets = HashedAssets::build(&html_dir, &globs, "asset-manifest.json");
+        assert_eq!(rebuilt.len(), 1);
+        fs::remove_dir_all(&html_dir).unwrap();
+    }
+}
@@ -0,0 +1,575 @@
+pub mod disk;
+
+use crate::backend::compression::ContentCoding;
+use async_trait::async_trait;
+use diana_http::validation::header_lines;
+#[cfg(feature = "mmap")]
+use memmap2::Mmap;
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::fs::File;
+use std::io::{self, Read};
+use std::ops::Deref;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CacheStatus {
+    /*
+     *  How the site was fetched, sent back in the X-Diana-Cache header.
+     */
+    Hit,
+    Miss,
+    Bypass,
+    Revalidated,
+    Stale,
+}
+
+impl CacheStatus {
+    pub fn value(&self) -> &'static str {
+        /*
+         *  Accessor.
+         *
+         *  Returns:
+         *      Value of the X-Diana-Cache header.
+         */
+        match self {
+            Self::Hit => "HIT",
+            Self::Miss => "MISS",
+            Self::Bypass => "BYPASS",
+            Self::Revalidated => "REVALIDATED",
+            Self::Stale => "STALE",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum SiteContent {
+    /*
+     *  Contents of the cached site. Large files are memory-mapped, so they
+     *  share the OS page cache instead of being copied to the heap. Mapped
+     *  files must not be truncated while they are cached. Both are shared,
+     *  so the connections take the site out of the cache without copying it.
+     */
+    Heap(Arc<[u8]>),
+    #[cfg(feature = "mmap")]
+    Mapped(Arc<Mmap>),
+}
+
+impl SiteContent {
+    pub fn load(path: &Path, mmap_threshold: Option<u64>) -> Result<Self, io::Error> {
+        /*
+         *  Read the site from the disk.
+         *
+         *  Arguments:
+         *      path: Path of the file.
+         *      mmap_threshold: Files of at least this size are memory-mapped,
+         *      requires the mmap feature.
+         *
+         *  Returns:
+         *      The contents, or the error if the file can't be read.
+         */
+        let mut file: File = File::open(path)?;
+        let size: u64 = file.metadata()?.len();
+        #[cfg(feature = "mmap")]
+        if mmap_threshold.is_some_and(|threshold| size >= threshold) && size > 0 {
+            /* The file is only read, the mapping lives as long as the entry */
+            let mapped: Mmap = unsafe { Mmap::map(&file)? };
+            return Ok(SiteContent::Mapped(Arc::new(mapped)));
+        }
+        #[cfg(not(feature = "mmap"))]
+        let _ = mmap_threshold;
+        let mut content: Vec<u8> = Vec::with_capacity(size as usize);
+        file.read_to_end(&mut content)?;
+        Ok(SiteContent::from(content))
+    }
+
+    pub fn is_mapped(&self) -> bool {
+        !matches!(self, SiteContent::Heap(_))
+    }
+
+    pub fn heap_len(&self) -> usize {
+        /*
+         *  Returns:
+         *      Bytes on the heap, the mapped sites are in the page cache.
+         */
+        if self.is_mapped() { 0 } else { self.len() }
+    }
+}
+
+impl Deref for SiteContent {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            SiteContent::Heap(content) => content,
+            #[cfg(feature = "mmap")]
+            SiteContent::Mapped(content) => content,
+        }
+    }
+}
+
+impl From<Vec<u8>> for SiteContent {
+    fn from(content: Vec<u8>) -> Self {
+        SiteContent::Heap(Arc::from(content))
+    }
+}
+
+#[async_trait]
+pub trait SiteCache: Debug + Send + Sync {
+    /*
+     *  Backend of the site cache. The backends shared by several instances,
+     *  e.g. the disk one, let them share the cached sites too.
+     *
+     *  The pinned sites, e.g. the error pages, are never evicted nor
+     *  flushed. The encoded sites are kept next to their content and are
+     *  dropped together with it. heap_bytes and shrink concern only what
+     *  the cache holds on the heap of this instance, for the memory budget.
+     */
+
+    async fn get(&self, resource_path: &[u8]) -> Option<SiteContent>;
+
+    async fn put(&self, resource_path: &[u8], content: SiteContent) -> usize;
+
+    async fn pin(&self, resource_path: &[u8], content: SiteContent);
+
+    async fn get_encoded(&self, resource_path: &[u8], coding: ContentCoding)
+    -> Option<SiteContent>;
+
+    async fn put_encoded(
+        &self,
+        resource_path: &[u8],
+        coding: ContentCoding,
+        content: SiteContent,
+    ) -> bool;
+
+    async fn invalidate(&self, resource_path: &[u8]) -> bool;
+
+    async fn clear(&self) -> usize;
+
+    async fn shrink(&self, max_bytes: usize) -> usize;
+
+    fn heap_bytes(&self) -> usize;
+
+    fn len(&self) -> usize;
+
+    fn capacity(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[derive(Debug)]
+pub struct MemoryCache {
+    /*
+     *  Cache of the sites in the memory of this instance, the least
+     *  recently used site is evicted when the cache is full.
+     *
+     *  Attributes:
+     *      sites: The cached sites, the lock is never held across an await.
+     */
+    sites: Mutex<LruSites>,
+}
+
+impl MemoryCache {
+    pub fn new(capacity: usize) -> Self {
+        MemoryCache {
+            sites: Mutex::new(LruSites::new(capacity)),
+        }
+    }
+}
+
+#[async_trait]
+impl SiteCache for MemoryCache {
+    async fn get(&self, resource_path: &[u8]) -> Option<SiteContent> {
+        self.sites.lock().unwrap().get(resource_path)
+    }
+
+    async fn put(&self, resource_path: &[u8], content: SiteContent) -> usize {
+        /*
+         *  Returns:
+         *      The number of evicted sites.
+         */
+        self.sites.lock().unwrap().insert(resource_path, content)
+    }
+
+    async fn pin(&self, resource_path: &[u8], content: SiteContent) {
+        self.sites.lock().unwrap().pin(resource_path, content);
+    }
+
+    async fn get_encoded(
+        &self,
+        resource_path: &[u8],
+        coding: ContentCoding,
+    ) -> Option<SiteContent> {
+        self.sites
+            .lock()
+            .unwrap()
+            .get_encoded(resource_path, coding)
+    }
+
+    async fn put_encoded(
+        &self,
+        resource_path: &[u8],
+        coding: ContentCoding,
+        content: SiteContent,
+    ) -> bool {
+        self.sites
+            .lock()
+            .unwrap()
+            .insert_encoded(resource_path, coding, content)
+    }
+
+    async fn invalidate(&self, resource_path: &[u8]) -> bool {
+        self.sites.lock().unwrap().invalidate(resource_path)
+    }
+
+    async fn clear(&self) -> usize {
+        self.sites.lock().unwrap().clear()
+    }
+
+    async fn shrink(&self, max_bytes: usize) -> usize {
+        self.sites.lock().unwrap().shrink(max_bytes)
+    }
+
+    fn heap_bytes(&self) -> usize {
+        self.sites.lock().unwrap().heap_bytes()
+    }
+
+    fn len(&self) -> usize {
+        self.sites.lock().unwrap().len()
+    }
+
+    fn capacity(&self) -> usize {
+        self.sites.lock().unwrap().capacity()
+    }
+}
+
+#[derive(Debug, Clone)]
+struct CachedSite {
+    /*
+     *  Attributes:
+     *      content: The contents of the site.
+     *      encoded: The site compressed with the encodings, that the clients
+     *      asked for. They are dropped together with the content.
+     *      last_used: Tick of the cache clock, when the site was last used.
+     *      pinned: Pinned sites are never evicted nor flushed.
+     */
+    content: SiteContent,
+    encoded: HashMap<ContentCoding, SiteContent>,
+    last_used: u64,
+    pinned: bool,
+}
+
+impl CachedSite {
+    fn heap_bytes(&self) -> usize {
+        self.content.heap_len()
+            + self
+                .encoded
+                .values()
+                .map(SiteContent::heap_len)
+                .sum::<usize>()
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct LruSites {
+    /*
+     *  Sites of the memory cache, the least recently used site is evicted
+     *  when the cache is full.
+     *
+     *  Attributes:
+     *      entries: Cached sites by their resource path.
+     *      capacity: The maximum number of the unpinned sites.
+     *      clock: Incremented on every access, orders the sites by use.
+     */
+    entries: HashMap<Vec<u8>, CachedSite>,
+    capacity: usize,
+    clock: u64,
+}
+
+impl LruSites {
+    fn new(capacity: usize) -> Self {
+        LruSites {
+            entries: HashMap::new(),
+            capacity,
+            clock: 0,
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    #[cfg(test)]
+    fn contains_key(&self, resource_path: &[u8]) -> bool {
+        self.entries.contains_key(resource_path)
+    }
+
+    fn get(&mut self, resource_path: &[u8]) -> Option<SiteContent> {
+        /*
+         *  Get the site and mark it as the most recently used.
+         */
+        self.clock += 1;
+        let site: &mut CachedSite = self.entries.get_mut(resource_path)?;
+        site.last_used = self.clock;
+        Some(site.content.clone())
+    }
+
+    fn pin(&mut self, resource_path: &[u8], content: impl Into<SiteContent>) {
+        /*
+         *  Insert the site, that must stay in the cache, e.g. the error pages.
+         */
+        self.clock += 1;
+        self.entries.insert(
+            resource_path.to_vec(),
+            CachedSite {
+                content: content.into(),
+                encoded: HashMap::new(),
+                last_used: self.clock,
+                pinned: true,
+            },
+        );
+    }
+
+    fn insert(&mut self, resource_path: &[u8], content: impl Into<SiteContent>) -> usize {
+        /*
+         *  Insert the site, evicting the least recently used ones if
+         *  the cache is full.
+         *
+         *  Arguments:
+         *      resource_path: Resource path of the site.
+         *      content: The contents of the site.
+         *
+         *  Returns:
+         *      The number of evicted sites.
+         */
+        self.clock += 1;
+        let pinned: bool = self
+            .entries
+            .get(resource_path)
+            .is_some_and(|site| site.pinned);
+        self.entries.insert(
+            resource_path.to_vec(),
+            CachedSite {
+                content: content.into(),
+                encoded: HashMap::new(),
+                last_used: self.clock,
+                pinned,
+            },
+        );
+
+        let mut evicted: usize = 0;
+        while self.entries.values().filter(|site| !site.pinned).count() > self.capacity {
+            let lru_key: Vec<u8> = match self
+                .entries
+                .iter()
+                .filter(|(_, site)| !site.pinned)
+                .min_by_key(|(_, site)| site.last_used)
+            {
+                Some((key, _)) => key.clone(),
+                None => break,
+            };
+            self.entries.remove(&lru_key);
+            evicted += 1;
+        }
+        evicted
+    }
+
+    fn get_encoded(&mut self, resource_path: &[u8], coding: ContentCoding) -> Option<SiteContent> {
+        /*
+         *  Get the encoded site and mark the site as the most recently used.
+         */
+        self.clock += 1;
+        let site: &mut CachedSite = self.entries.get_mut(resource_path)?;
+        site.last_used = self.clock;
+        site.encoded.get(&coding).cloned()
+    }
+
+    fn insert_encoded(
+        &mut self,
+        resource_path: &[u8],
+        coding: ContentCoding,
+        content: impl Into<SiteContent>,
+    ) -> bool {
+        /*
+         *  Keep the encoded site next to its content.
+         *
+         *  Returns:
+         *      False if the site isn't cached, so the encoding isn't kept.
+         */
+        match self.entries.get_mut(resource_path) {
+            Some(site) => {
+                site.encoded.insert(coding, content.into());
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn invalidate(&mut self, resource_path: &[u8]) -> bool {
+        /*
+         *  Drop the site, that changed on the disk. The pinned sites stay,
+         *  only their encodings are dropped.
+         *
+         *  Returns:
+         *      True if the site was cached.
+         */
+        match self.entries.get_mut(resource_path) {
+            Some(site) if site.pinned => {
+                site.encoded.clear();
+                true
+            }
+            Some(_) => self.entries.remove(resource_path).is_some(),
+            None => false,
+        }
+    }
+
+    fn clear(&mut self) -> usize {
+        /*
+         *  Remove every site, except the pinned ones.
+         *
+         *  Returns:
+         *      The number of removed sites.
+         */
+        let before: usize = self.entries.len();
+        self.entries.retain(|_, site| site.pinned);
+        before - self.entries.len()
+    }
+
+    fn heap_bytes(&self) -> usize {
+        self.entries.values().map(CachedSite::heap_bytes).sum()
+    }
+
+    fn shrink(&mut self, max_bytes: usize) -> usize {
+        /*
+         *  Evict the least recently used sites, until the sites on the heap
+         *  fit into the bytes. The pinned sites stay, even if they don't fit.
+         *
+         *  Returns:
+         *      The number of evicted sites.
+         */
+        let mut heap_bytes: usize = self.heap_bytes();
+        let mut evicted: usize = 0;
+        while heap_bytes > max_bytes {
+            let lru_key: Vec<u8> = match self
+                .entries
+                .iter()
+                .filter(|(_, site)| !site.pinned)
+                .min_by_key(|(_, site)| site.last_used)
+            {
+                Some((key, _)) => key.clone(),
+                None => break,
+            };
+            if let Some(site) = self.entries.remove(&lru_key) {
+                heap_bytes -= site.heap_bytes();
+                evicted += 1;
+            }
+        }
+        evicted
+    }
+}
+
+pub fn requests_revalidation(buffer: &[u8]) -> bool {
+    /*
+     *  Check if the client asks to skip the cached copy, either with
+     *  Cache-Control: no-cache or with the legacy Pragma: no-cache.
+     *
+     *  Arguments:
+     *      buffer: Bytes of the request.
+     */
+    header_lines(buffer).into_iter().any(|line| {
+        let Some(colon_idx) = line.iter().position(|byte| *byte == b':') else {
+            return false;
+        };
+        let name: &[u8] = &line[..colon_idx];
+        if !name.eq_ignore_ascii_case(b"cache-control") && !name.eq_ignore_ascii_case(b"pragma") {
+            return false;
+        }
+        line[colon_idx + 1..]
+            .split(|byte| *byte == b',')
+            .any(|directive| directive.trim_ascii().eq_ignore_ascii_case(b"no-cache"))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn site_cache_eviction_test() {
+        let mut cache = LruSites::new(2);
+        cache.pin(b"site_not_found.html", b"404".to_vec());
+        assert_eq!(cache.insert(b"/a", b"a".to_vec()), 0);
+        assert_eq!(cache.insert(b"/b", b"b".to_vec()), 0);
+
+        /* /a becomes the most recently used, so /b is evicted */
+        assert!(cache.get(b"/a").is_some());
+        assert_eq!(cache.insert(b"/c", b"c".to_vec()), 1);
+        assert!(!cache.contains_key(b"/b"));
+        assert!(cache.contains_key(b"site_not_found.html"));
+
+        assert_eq!(cache.clear(), 2);
+        assert_eq!(cache.len(), 1);
+
+        /* The shrinking evicts by use too, the pinned 404 page always stays */
+        cache.insert(b"/big", vec![0; 100]);
+        cache.insert(b"/small", vec![0; 10]);
+        assert!(cache.insert_encoded(b"/small", ContentCoding::Gzip, vec![0; 5]));
+        assert_eq!(cache.heap_bytes(), 118);
+        assert!(cache.get(b"/big").is_some());
+        assert_eq!(cache.shrink(110), 1);
+        assert!(!cache.contains_key(b"/small"));
+        assert_eq!(cache.shrink(0), 1);
+        assert_eq!(cache.heap_bytes(), 3);
+    }
+
+    #[test]
+    fn site_cache_encoded_test() {
+        let mut cache = LruSites::new(2);
+        assert!(!cache.insert_encoded(b"/a.css", ContentCoding::Gzip, b"gz".to_vec()));
+        cache.insert(b"/a.css", b"a".to_vec());
+        assert!(cache.insert_encoded(b"/a.css", ContentCoding::Gzip, b"gz".to_vec()));
+        assert_eq!(
+            cache.get_encoded(b"/a.css", ContentCoding::Gzip).as_deref(),
+            Some(&b"gz"[..])
+        );
+
+        /* The new content drops the stale encodings */
+        cache.insert(b"/a.css", b"b".to_vec());
+        assert!(cache.get_encoded(b"/a.css", ContentCoding::Gzip).is_none());
+
+        assert!(cache.invalidate(b"/a.css"));
+        assert!(!cache.contains_key(b"/a.css"));
+        assert!(!cache.invalidate(b"/a.css"));
+    }
+
+    #[test]
+    fn site_content_load_test() {
+        let path: &Path = Path::new("resource/html/index.html");
+        let heap: SiteContent = SiteContent::load(path, None).unwrap();
+        assert!(!heap.is_mapped());
+        let loaded: SiteContent = SiteContent::load(path, Some(1)).unwrap();
+        assert_eq!(cfg!(feature = "mmap"), loaded.is_mapped());
+        assert_eq!(&*heap, &*loaded);
+        assert!(SiteContent::load(Path::new("resource/html/missing"), None).is_err());
+    }
+
+    #[test]
+    fn requests_revalidation_test() {
+        assert!(requests_revalidation(
+            b"GET / HTTP/1.1\r\nCache-Control: max-age=0, No-Cache\r\n\r\n"
+        ));
+        assert!(requests_revalidation(
+            b"GET / HTTP/1.1\r\nPragma: no-cache\r\n\r\n"
+        ));
+        assert!(!requests_revalidation(
+            b"GET / HTTP/1.1\r\nCache-Control: max-age=0\r\n\r\n"
+        ));
+    }
+}
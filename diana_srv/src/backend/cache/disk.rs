@@ -0,0 +1,381 @@
+use crate::backend::cache::{SiteCache, SiteContent};
+use crate::backend::compression::{ContentCoding, SUPPORTED_CODINGS};
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::SystemTime;
+use tokio::fs;
+
+/* Extension of the site files, the encoded sites append their encoding to it */
+const SITE_EXTENSION: &str = "site";
+
+#[derive(Debug)]
+pub struct DiskCache {
+    /*
+     *  Cache of the sites in the directory, that several instances can
+     *  share. Every site is a file named by the hash of its resource path,
+     *  the file starts with the path, so the colliding sites are told apart.
+     *  The oldest written site is evicted, when the cache is full.
+     *
+     *  Attributes:
+     *      dir: Directory of the site files.
+     *      capacity: The maximum number of the unpinned sites.
+     *      sites: Number of the sites in the directory, recounted on eviction,
+     *      since the other instances write there too.
+     *      pinned: Pinned sites, they are kept in the memory, so the flushes
+     *      of the other instances don't remove them.
+     */
+    dir: PathBuf,
+    capacity: usize,
+    sites: AtomicUsize,
+    pinned: Mutex<HashMap<Vec<u8>, SiteContent>>,
+}
+
+impl DiskCache {
+    pub fn open(dir: &Path, capacity: usize) -> Result<Self, io::Error> {
+        /*
+         *  Constructor of the disk cache.
+         *
+         *  Arguments:
+         *      dir: Directory of the site files, it is created if it doesn't
+         *      exist. The sites already there are kept.
+         *      capacity: The maximum number of the unpinned sites.
+         */
+        std::fs::create_dir_all(dir)?;
+        let sites: usize = std::fs::read_dir(dir)?
+            .filter_map(Result::ok)
+            .filter(|entry| is_site_file(&entry.path()))
+            .count();
+        Ok(DiskCache {
+            dir: dir.to_path_buf(),
+            capacity,
+            sites: AtomicUsize::new(sites),
+            pinned: Mutex::new(HashMap::new()),
+        })
+    }
+
+    fn site_file(&self, resource_path: &[u8], coding: Option<ContentCoding>) -> PathBuf {
+        /*
+         *  Map the site onto its file, e.g. 00ab...ef.site or 00ab...ef.site.gzip
+         */
+        let mut file_name: String = format!("{:016x}.{SITE_EXTENSION}", fnv1a(resource_path));
+        if let Some(coding) = coding {
+            file_name.push('.');
+            file_name.push_str(coding.token());
+        }
+        self.dir.join(file_name)
+    }
+
+    async fn remove_site(&self, resource_path: &[u8]) -> bool {
+        /*
+         *  Remove the site file with its encodings.
+         *
+         *  Returns:
+         *      True if the site was cached.
+         */
+        for coding in SUPPORTED_CODINGS {
+            let _ = fs::remove_file(self.site_file(resource_path, Some(*coding))).await;
+        }
+        fs::remove_file(self.site_file(resource_path, None))
+            .await
+            .is_ok()
+    }
+
+    async fn evict(&self) -> usize {
+        /*
+         *  Remove the oldest written sites, until the cache fits its capacity.
+         *
+         *  Returns:
+         *      The number of evicted sites.
+         */
+        let mut sites: Vec<(SystemTime, PathBuf)> = Vec::new();
+        if let Ok(mut entries) = fs::read_dir(&self.dir).await {
+            while let Ok(Some(entry)) = entries.next_entry().await {
+                let path: PathBuf = entry.path();
+                if !is_site_file(&path) {
+                    continue;
+                }
+                let modified: SystemTime = match entry.metadata().await {
+                    Ok(metadata) => metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH),
+                    Err(_) => continue,
+                };
+                sites.push((modified, path));
+            }
+        }
+        sites.sort();
+        let mut evicted: usize = 0;
+        let excess: usize = sites.len().saturating_sub(self.capacity);
+        for (_, path) in sites.iter().take(excess) {
+            for coding in SUPPORTED_CODINGS {
+                let mut encoded = path.clone().into_os_string();
+                encoded.push(format!(".{}", coding.token()));
+                let _ = fs::remove_file(encoded).await;
+            }
+            if fs::remove_file(path).await.is_ok() {
+                evicted += 1;
+            }
+        }
+        self.sites
+            .store(sites.len().saturating_sub(evicted), Ordering::Relaxed);
+        evicted
+    }
+}
+
+#[async_trait]
+impl SiteCache for DiskCache {
+    async fn get(&self, resource_path: &[u8]) -> Option<SiteContent> {
+        if let Some(site) = self.pinned.lock().unwrap().get(resource_path) {
+            return Some(site.clone());
+        }
+        read_entry(&self.site_file(resource_path, None), resource_path).await
+    }
+
+    async fn put(&self, resource_path: &[u8], content: SiteContent) -> usize {
+        /*
+         *  Returns:
+         *      The number of evicted sites.
+         */
+        let existed: bool = self.remove_site(resource_path).await;
+        if let Err(e) = write_entry(
+            &self.site_file(resource_path, None),
+            resource_path,
+            &content,
+        )
+        .await
+        {
+            println!("[ERROR] Failed to write the site to the disk cache: {e}");
+            if existed {
+                self.sites.fetch_sub(1, Ordering::Relaxed);
+            }
+            return 0;
+        }
+        if !existed && self.sites.fetch_add(1, Ordering::Relaxed) + 1 > self.capacity {
+            return self.evict().await;
+        }
+        0
+    }
+
+    async fn pin(&self, resource_path: &[u8], content: SiteContent) {
+        self.pinned
+            .lock()
+            .unwrap()
+            .insert(resource_path.to_vec(), content);
+    }
+
+    async fn get_encoded(
+        &self,
+        resource_path: &[u8],
+        coding: ContentCoding,
+    ) -> Option<SiteContent> {
+        read_entry(&self.site_file(resource_path, Some(coding)), resource_path).await
+    }
+
+    async fn put_encoded(
+        &self,
+        resource_path: &[u8],
+        coding: ContentCoding,
+        content: SiteContent,
+    ) -> bool {
+        /*
+         *  The pinned sites are never encoded, they aren't on the disk.
+         */
+        if !fs::try_exists(self.site_file(resource_path, None))
+            .await
+            .unwrap_or(false)
+        {
+            return false;
+        }
+        let file: PathBuf = self.site_file(resource_path, Some(coding));
+        match write_entry(&file, resource_path, &content).await {
+            Ok(()) => true,
+            Err(e) => {
+                println!("[ERROR] Failed to write the encoded site to the disk cache: {e}");
+                false
+            }
+        }
+    }
+
+    async fn invalidate(&self, resource_path: &[u8]) -> bool {
+        if self.pinned.lock().unwrap().contains_key(resource_path) {
+            return true;
+        }
+        let removed: bool = self.remove_site(resource_path).await;
+        if removed {
+            let _ = self
+                .sites
+                .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |sites| {
+                    sites.checked_sub(1)
+                });
+        }
+        removed
+    }
+
+    async fn clear(&self) -> usize {
+        /*
+         *  Remove every site file, the pinned sites stay.
+         *
+         *  Returns:
+         *      The number of removed sites.
+         */
+        let mut cleared: usize = 0;
+        if let Ok(mut entries) = fs::read_dir(&self.dir).await {
+            while let Ok(Some(entry)) = entries.next_entry().await {
+                let path: PathBuf = entry.path();
+                let is_site: bool = is_site_file(&path);
+                let is_encoded: bool = path
+                    .file_name()
+                    .and_then(|name| name.to_str())
+                    .is_some_and(|name| name.contains(&format!(".{SITE_EXTENSION}.")));
+                if !is_site && !is_encoded {
+                    continue;
+                }
+                if fs::remove_file(&path).await.is_ok() && is_site {
+                    cleared += 1;
+                }
+            }
+        }
+        self.sites.store(0, Ordering::Relaxed);
+        cleared
+    }
+
+    async fn shrink(&self, _max_bytes: usize) -> usize {
+        /*
+         *  The sites are on the disk, only the pinned ones are on the heap.
+         */
+        0
+    }
+
+    fn heap_bytes(&self) -> usize {
+        self.pinned
+            .lock()
+            .unwrap()
+            .values()
+            .map(SiteContent::heap_len)
+            .sum()
+    }
+
+    fn len(&self) -> usize {
+        self.sites.load(Ordering::Relaxed) + self.pinned.lock().unwrap().len()
+    }
+
+    fn capacity(&self) -> usize {
+        self.capacity
+    }
+}
+
+async fn read_entry(file: &Path, resource_path: &[u8]) -> Option<SiteContent> {
+    /*
+     *  Read the site file, it is the length of the resource path
+     *  (4 bytes, big endian), the path and the content.
+     *
+     *  Returns:
+     *      The content, None if the file is missing or belongs to
+     *      the other path with the same hash.
+     */
+    let entry: Vec<u8> = fs::read(file).await.ok()?;
+    let path_len: usize = u32::from_be_bytes(entry.get(..4)?.try_into().ok()?) as usize;
+    if entry.get(4..4 + path_len)? != resource_path {
+        return None;
+    }
+    Some(SiteContent::from(entry[4 + path_len..].to_vec()))
+}
+
+async fn write_entry(file: &Path, resource_path: &[u8], content: &[u8]) -> Result<(), io::Error> {
+    /*
+     *  Write the site file through the temporary one, so the other
+     *  instances never read it half written.
+     */
+    let path_len: u32 = resource_path
+        .len()
+        .try_into()
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "Resource path is too long"))?;
+    let mut entry: Vec<u8> = Vec::with_capacity(4 + resource_path.len() + content.len());
+    entry.extend_from_slice(&path_len.to_be_bytes());
+    entry.extend_from_slice(resource_path);
+    entry.extend_from_slice(content);
+    let mut temporary = file.as_os_str().to_owned();
+    temporary.push(format!(".{}.tmp", std::process::id()));
+    fs::write(&temporary, &entry).await?;
+    fs::rename(&temporary, file).await
+}
+
+fn is_site_file(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|extension| extension == SITE_EXTENSION)
+}
+
+pub fn fnv1a(bytes: &[u8]) -> u64 {
+    /*
+     *  64-bit FNV-1a, the file names must stay the same across the builds,
+     *  so the instances of the different versions share the cache.
+     */
+    bytes.iter().fold(0xcbf29ce484222325, |hash: u64, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn disk_cache_test() {
+        let dir: PathBuf =
+            std::env::temp_dir().join(format!("diana_disk_cache_{}", std::process::id()));
+        let cache: DiskCache = DiskCache::open(&dir, 2).unwrap();
+        cache
+            .pin(b"site_not_found.html", SiteContent::from(b"404".to_vec()))
+            .await;
+        assert_eq!(cache.put(b"/a", SiteContent::from(b"a".to_vec())).await, 0);
+        assert!(
+            cache
+                .put_encoded(
+                    b"/a",
+                    ContentCoding::Gzip,
+                    SiteContent::from(b"gz".to_vec())
+                )
+                .await
+        );
+        /* The eviction orders the sites by mtime, so they must differ */
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert_eq!(cache.put(b"/b", SiteContent::from(b"b".to_vec())).await, 0);
+        assert_eq!(cache.get(b"/a").await.as_deref(), Some(&b"a"[..]));
+        assert_eq!(
+            cache
+                .get_encoded(b"/a", ContentCoding::Gzip)
+                .await
+                .as_deref(),
+            Some(&b"gz"[..])
+        );
+        assert_eq!(cache.len(), 3);
+
+        /* The second instance sees the sites of the first one */
+        let shared: DiskCache = DiskCache::open(&dir, 2).unwrap();
+        assert_eq!(shared.get(b"/b").await.as_deref(), Some(&b"b"[..]));
+
+        /* /a was written first, so it is evicted with its encoding */
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert_eq!(cache.put(b"/c", SiteContent::from(b"c".to_vec())).await, 1);
+        assert!(cache.get(b"/a").await.is_none());
+        assert!(
+            cache
+                .get_encoded(b"/a", ContentCoding::Gzip)
+                .await
+                .is_none()
+        );
+
+        assert!(cache.invalidate(b"/b").await);
+        assert!(!cache.invalidate(b"/b").await);
+        assert_eq!(cache.clear().await, 1);
+        assert_eq!(
+            cache.get(b"site_not_found.html").await.as_deref(),
+            Some(&b"404"[..])
+        );
+        assert_eq!(cache.len(), 1);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,175 @@
+use crate::utils::patterns::glob_match;
+use serde::{Deserialize, Serialize};
+use std::hash::{BuildHasher, RandomState};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ChaosRule {
+    /*
+     *  Entry of the [[chaos]] table in the config. The matching requests
+     *  are delayed or fail on purpose, so the retries and the timeouts of
+     *  the clients can be tested against the server. Never enable it in
+     *  the production.
+     *
+     *  Attributes:
+     *      path: Glob of the paths, that the faults are injected into.
+     *      latency_ms: Delay added before the request is answered.
+     *      latency_jitter_ms: Random delay added to the latency, up to this.
+     *      error_percent: Percentage of the requests answered with 500.
+     *      drop_percent: Percentage of the requests, which connection is
+     *      closed without any response.
+     */
+    pub path: String,
+    pub latency_ms: u64,
+    pub latency_jitter_ms: u64,
+    pub error_percent: u8,
+    pub drop_percent: u8,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Fault {
+    Error,
+    Drop,
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct ChaosPlan {
+    /*
+     *  What happens to the request.
+     *
+     *  Attributes:
+     *      delay: How long the request waits, before it is handled.
+     *      fault: The failure, that replaces the response, if any.
+     */
+    pub delay: Duration,
+    pub fault: Option<Fault>,
+}
+
+#[derive(Debug)]
+pub struct Chaos {
+    /*
+     *  Injector of the faults of the configured rules.
+     *
+     *  Attributes:
+     *      rules: The configured rules, the first matching one is applied.
+     *      state: State of the xorshift generator, that draws the faults.
+     */
+    rules: Vec<ChaosRule>,
+    state: AtomicU64,
+}
+
+impl Chaos {
+    pub fn new(rules: Vec<ChaosRule>) -> Self {
+        /* The seed differs per process, so the runs don't fail the same requests */
+        Chaos::with_seed(rules, RandomState::new().hash_one(0u8))
+    }
+
+    pub fn with_seed(rules: Vec<ChaosRule>, seed: u64) -> Self {
+        Chaos {
+            rules,
+            /* Zero is the only state, that xorshift never leaves */
+            state: AtomicU64::new(seed.max(1)),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !self.rules.is_empty()
+    }
+
+    pub fn plan(&self, resource_path: &[u8]) -> ChaosPlan {
+        /*
+         *  Draw the fate of the request.
+         *
+         *  Arguments:
+         *      resource_path: Resource path from the request.
+         *
+         *  Returns:
+         *      The delay with the fault, the default plan if no rule matches.
+         */
+        let Some(rule) = self
+            .rules
+            .iter()
+            .find(|rule| glob_match(rule.path.as_bytes(), resource_path))
+        else {
+            return ChaosPlan::default();
+        };
+        let mut delay: Duration = Duration::from_millis(rule.latency_ms);
+        if rule.latency_jitter_ms > 0 {
+            delay += Duration::from_millis(self.next() % (rule.latency_jitter_ms + 1));
+        }
+        /* A single draw, so the percentages of the both faults add up */
+        let roll: u64 = self.next() % 100;
+        let fault: Option<Fault> = if roll < rule.drop_percent.into() {
+            Some(Fault::Drop)
+        } else if roll < u64::from(rule.drop_percent) + u64::from(rule.error_percent) {
+            Some(Fault::Error)
+        } else {
+            None
+        };
+        ChaosPlan { delay, fault }
+    }
+
+    fn next(&self) -> u64 {
+        let step = |mut x: u64| -> u64 {
+            x ^= x << 13;
+            x ^= x >> 7;
+            x ^= x << 17;
+            x
+        };
+        let previous: u64 = self
+            .state
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |x| Some(step(x)))
+            .unwrap_or_default();
+        step(previous)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chaos_test() {
+        let rules: Vec<ChaosRule> = vec![
+            ChaosRule {
+                path: String::from("/api/**"),
+                latency_ms: 100,
+                latency_jitter_ms: 50,
+                error_percent: 30,
+                drop_percent: 20,
+            },
+            ChaosRule {
+                path: String::from("/slow"),
+                latency_ms: 250,
+                ..ChaosRule::default()
+            },
+        ];
+        let chaos: Chaos = Chaos::with_seed(rules, 42);
+        assert!(chaos.is_enabled());
+        assert!(!Chaos::new(Vec::new()).is_enabled());
+        assert_eq!(chaos.plan(b"/index.html"), ChaosPlan::default());
+        assert_eq!(
+            chaos.plan(b"/slow"),
+            ChaosPlan {
+                delay: Duration::from_millis(250),
+                fault: None,
+            }
+        );
+
+        let (mut errors, mut drops): (u32, u32) = (0, 0);
+        for _ in 0..1000 {
+            let plan: ChaosPlan = chaos.plan(b"/api/users");
+            assert!(plan.delay >= Duration::from_millis(100));
+            assert!(plan.delay <= Duration::from_millis(150));
+            match plan.fault {
+                Some(Fault::Error) => errors += 1,
+                Some(Fault::Drop) => drops += 1,
+                None => {}
+            }
+        }
+        assert!((200..400).contains(&errors), "{errors} errors");
+        assert!((100..300).contains(&drops), "{drops} drops");
+    }
+}
@@ -0,0 +1,514 @@
+use crate::backend::dns::Resolver;
+use crate::backend::metrics::Metrics;
+use diana_http::codec::{
+    has_body, parse_chunk_size, parse_header_line, parse_status_line, request_head,
+};
+use std::collections::HashMap;
+use std::fmt;
+use std::io;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+use tokio::sync::Mutex;
+use tokio::time::{Instant, timeout};
+
+/* How long the resolved upstream addresses are reused */
+const DEFAULT_DNS_TTL: Duration = Duration::from_secs(60);
+/* How long the idle connection waits in the pool for the next request */
+const DEFAULT_IDLE_TIMEOUT: Duration = Duration::from_secs(30);
+/* Connections are reconnected after this, even when they are busy */
+const DEFAULT_MAX_LIFETIME: Duration = Duration::from_secs(300);
+
+#[derive(Debug)]
+pub enum ClientError {
+    /*
+     *  Reasons, why the outbound request failed.
+     */
+    InvalidUrl,
+    InvalidResponse,
+    Timeout,
+    Io(io::Error),
+}
+
+impl fmt::Display for ClientError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidUrl => write!(f, "URL is not in the http://host[:port]/path form"),
+            Self::InvalidResponse => write!(f, "Response is malformed"),
+            Self::Timeout => write!(f, "Upstream didn't answer in time"),
+            Self::Io(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl From<io::Error> for ClientError {
+    fn from(e: io::Error) -> Self {
+        ClientError::Io(e)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClientResponse {
+    /*
+     *  Response of the upstream.
+     *
+     *  Attributes:
+     *      status: Status code.
+     *      headers: Headers as (name, value) pairs, in the received order.
+     *      body: The response body, already de-chunked.
+     */
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl ClientResponse {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        /*
+         *  Returns:
+         *      Value of the first header matching the name, case-insensitive.
+         */
+        self.headers
+            .iter()
+            .find(|(header, _)| header.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+#[derive(Debug)]
+struct UpstreamConn {
+    /*
+     *  Connection to the upstream, it is counted as closed once dropped.
+     *
+     *  Attributes:
+     *      reader: The buffered stream.
+     *      opened: When the connection was opened.
+     *      idle_since: When the connection was returned to the pool.
+     *      metrics: Counters of the connections.
+     */
+    reader: BufReader<TcpStream>,
+    opened: Instant,
+    idle_since: Instant,
+    metrics: Arc<Metrics>,
+}
+
+impl Drop for UpstreamConn {
+    fn drop(&mut self) {
+        Metrics::increment(&self.metrics.upstream_connections_closed);
+    }
+}
+
+#[derive(Debug)]
+pub struct HttpClient {
+    /*
+     *  Small HTTP/1.1 client for the outbound requests, e.g. the reverse
+     *  proxy and the health checks. Connections are kept alive and reused
+     *  per host.
+     *
+     *  Attributes:
+     *      idle: Idle connections by their host:port, the most recent last.
+     *      max_idle_per_host: The maximum number of idle connections kept
+     *      for each host.
+     *      idle_timeout: How long the idle connection is kept.
+     *      max_lifetime: Connections older than this aren't reused.
+     *      request_timeout: Time given to the upstream to send the whole
+     *      response.
+     *      resolver: Cache of the upstream addresses.
+     *      metrics: Counters of the pool hits and the connections.
+     */
+    idle: Mutex<HashMap<String, Vec<UpstreamConn>>>,
+    max_idle_per_host: usize,
+    idle_timeout: Duration,
+    max_lifetime: Duration,
+    request_timeout: Duration,
+    resolver: Arc<Resolver>,
+    metrics: Arc<Metrics>,
+}
+
+impl HttpClient {
+    pub fn new(max_idle_per_host: usize, request_timeout: Duration) -> Self {
+        HttpClient {
+            idle: Mutex::new(HashMap::new()),
+            max_idle_per_host,
+            idle_timeout: DEFAULT_IDLE_TIMEOUT,
+            max_lifetime: DEFAULT_MAX_LIFETIME,
+            request_timeout,
+            resolver: Arc::new(Resolver::new(DEFAULT_DNS_TTL)),
+            metrics: Arc::new(Metrics::default()),
+        }
+    }
+
+    pub fn with_pool_limits(mut self, idle_timeout: Duration, max_lifetime: Duration) -> Self {
+        /*
+         *  Arguments:
+         *      idle_timeout: How long the idle connection is kept.
+         *      max_lifetime: Connections older than this aren't reused.
+         */
+        self.idle_timeout = idle_timeout;
+        self.max_lifetime = max_lifetime;
+        self
+    }
+
+    pub fn with_metrics(mut self, metrics: Arc<Metrics>) -> Self {
+        /*
+         *  Count the pool hits and the connections in the server's metrics.
+         */
+        self.metrics = metrics;
+        self
+    }
+
+    pub fn with_resolver(mut self, resolver: Arc<Resolver>) -> Self {
+        /*
+         *  Share the resolver, e.g. between the proxy and the health checks.
+         */
+        self.resolver = resolver;
+        self
+    }
+
+    pub async fn get(&self, url: &str) -> Result<ClientResponse, ClientError> {
+        self.request("GET", url, &[], &[]).await
+    }
+
+    pub async fn request(
+        &self,
+        method: &str,
+        url: &str,
+        headers: &[(String, String)],
+        body: &[u8],
+    ) -> Result<ClientResponse, ClientError> {
+        /*
+         *  Send the request and wait for the whole response.
+         *
+         *  Arguments:
+         *      method: HTTP method, e.g. GET
+         *      url: Absolute URL of the resource, only http is supported.
+         *      headers: Headers sent after Host and Content-Length.
+         *      body: The request body, may be empty.
+         *
+         *  Returns:
+         *      The response, or the reason of the failure.
+         */
+        let (host, path): (String, String) = split_url(url).ok_or(ClientError::InvalidUrl)?;
+        let mut head: Vec<(String, String)> = vec![
+            (String::from("Host"), host.clone()),
+            (String::from("Content-Length"), body.len().to_string()),
+        ];
+        head.extend_from_slice(headers);
+        let mut request: Vec<u8> = request_head(method, &path, &head);
+        request.extend_from_slice(body);
+        let is_head: bool = method.eq_ignore_ascii_case("HEAD");
+
+        match timeout(self.request_timeout, async {
+            /* The pooled connection might have been closed by the upstream meanwhile */
+            if let Some(mut conn) = self.take_idle(&host).await
+                && let Ok(response) = exchange(&mut conn.reader, &request, is_head).await
+            {
+                self.release(&host, conn, &response).await;
+                return Ok(response);
+            }
+            let mut conn: UpstreamConn = self.connect(&host).await?;
+            let response: ClientResponse = exchange(&mut conn.reader, &request, is_head).await?;
+            self.release(&host, conn, &response).await;
+            Ok(response)
+        })
+        .await
+        {
+            Ok(result) => result,
+            Err(_) => Err(ClientError::Timeout),
+        }
+    }
+
+    async fn connect(&self, host: &str) -> Result<UpstreamConn, io::Error> {
+        /*
+         *  Connect to the first address of the host, that accepts. If none
+         *  does, the addresses might be stale, so the host is resolved again.
+         */
+        let mut last_err: io::Error = io::Error::from(io::ErrorKind::NotFound);
+        for attempt in 0..2 {
+            if attempt > 0 {
+                self.resolver.invalidate(host).await;
+            }
+            let addrs: Vec<SocketAddr> = self.resolver.resolve(host).await?;
+            for addr in addrs {
+                match TcpStream::connect(addr).await {
+                    Ok(stream) => {
+                        Metrics::increment(&self.metrics.upstream_connections_opened);
+                        let now: Instant = Instant::now();
+                        return Ok(UpstreamConn {
+                            reader: BufReader::new(stream),
+                            opened: now,
+                            idle_since: now,
+                            metrics: Arc::clone(&self.metrics),
+                        });
+                    }
+                    Err(e) => last_err = e,
+                }
+            }
+        }
+        Err(last_err)
+    }
+
+    pub async fn idle_connections(&self, host: &str) -> usize {
+        self.idle.lock().await.get(host).map_or(0, Vec::len)
+    }
+
+    fn is_reusable(&self, conn: &UpstreamConn, now: Instant) -> bool {
+        now - conn.idle_since < self.idle_timeout && now - conn.opened < self.max_lifetime
+    }
+
+    async fn take_idle(&self, host: &str) -> Option<UpstreamConn> {
+        /*
+         *  Take the most recent idle connection of the host, the expired
+         *  ones are closed on the way.
+         */
+        let now: Instant = Instant::now();
+        let mut idle = self.idle.lock().await;
+        let conns: &mut Vec<UpstreamConn> = idle.get_mut(host)?;
+        conns.retain(|conn| self.is_reusable(conn, now));
+        let conn: UpstreamConn = conns.pop()?;
+        Metrics::increment(&self.metrics.upstream_pool_hits);
+        Some(conn)
+    }
+
+    async fn release(&self, host: &str, mut conn: UpstreamConn, response: &ClientResponse) {
+        /*
+         *  Give the connection back to the pool, unless the upstream closes
+         *  it, it outlived the maximum lifetime or the pool is full.
+         */
+        let closes: bool = response
+            .header("Connection")
+            .is_some_and(|value| value.eq_ignore_ascii_case("close"))
+            || (response.header("Content-Length").is_none()
+                && response.header("Transfer-Encoding").is_none()
+                && has_body(response.status));
+        conn.idle_since = Instant::now();
+        if closes || !self.is_reusable(&conn, conn.idle_since) {
+            return;
+        }
+        let mut idle = self.idle.lock().await;
+        let conns: &mut Vec<UpstreamConn> = idle.entry(host.to_string()).or_default();
+        conns.retain(|pooled| self.is_reusable(pooled, conn.idle_since));
+        if conns.len() < self.max_idle_per_host {
+            conns.push(conn);
+        }
+    }
+}
+
+fn split_url(url: &str) -> Option<(String, String)> {
+    /*
+     *  Split the URL into the host:port to connect to and the path.
+     *
+     *  Returns:
+     *      Host with the port (80 if missing) and the path, None if the URL
+     *      isn't a plain http one.
+     */
+    let rest: &str = url.strip_prefix("http://")?;
+    let path_idx: usize = rest.find('/').unwrap_or(rest.len());
+    let (authority, path): (&str, &str) = rest.split_at(path_idx);
+    if authority.is_empty() {
+        return None;
+    }
+    let host: String = if authority
+        .rsplit_once(':')
+        .is_some_and(|(_, port)| !port.is_empty() && port.bytes().all(|byte| byte.is_ascii_digit()))
+    {
+        authority.to_string()
+    } else {
+        format!("{authority}:80")
+    };
+    let path: String = if path.is_empty() {
+        String::from("/")
+    } else {
+        path.to_string()
+    };
+    Some((host, path))
+}
+
+async fn exchange(
+    conn: &mut BufReader<TcpStream>,
+    request: &[u8],
+    is_head: bool,
+) -> Result<ClientResponse, ClientError> {
+    /*
+     *  Write the request on the connection and read the response.
+     *
+     *  Arguments:
+     *      conn: Connection to the upstream.
+     *      request: The formatted request.
+     *      is_head: Responses to HEAD never carry the body.
+     */
+    conn.get_mut().write_all(request).await?;
+
+    let status_line: String = read_line(conn).await?;
+    let status: u16 = parse_status_line(&status_line).ok_or(ClientError::InvalidResponse)?;
+
+    let mut headers: Vec<(String, String)> = Vec::new();
+    loop {
+        let line: String = read_line(conn).await?;
+        if line.is_empty() {
+            break;
+        }
+        let (name, value): (&str, &str) =
+            parse_header_line(&line).ok_or(ClientError::InvalidResponse)?;
+        headers.push((name.to_string(), value.to_string()));
+    }
+    let mut response = ClientResponse {
+        status,
+        headers,
+        body: Vec::new(),
+    };
+    if is_head || !has_body(status) {
+        return Ok(response);
+    }
+
+    if response
+        .header("Transfer-Encoding")
+        .is_some_and(|value| value.to_ascii_lowercase().contains("chunked"))
+    {
+        loop {
+            let size_line: String = read_line(conn).await?;
+            let size: usize = parse_chunk_size(&size_line).ok_or(ClientError::InvalidResponse)?;
+            if size == 0 {
+                /* Skip the trailers */
+                while !read_line(conn).await?.is_empty() {}
+                break;
+            }
+            let start: usize = response.body.len();
+            response.body.resize(start + size, 0);
+            conn.read_exact(&mut response.body[start..]).await?;
+            if !read_line(conn).await?.is_empty() {
+                return Err(ClientError::InvalidResponse);
+            }
+        }
+    } else if let Some(length) = response.header("Content-Length") {
+        let length: usize = length.parse().map_err(|_| ClientError::InvalidResponse)?;
+        response.body = vec![0; length];
+        conn.read_exact(&mut response.body).await?;
+    } else {
+        conn.read_to_end(&mut response.body).await?;
+    }
+    Ok(response)
+}
+
+async fn read_line(conn: &mut BufReader<TcpStream>) -> Result<String, ClientError> {
+    /*
+     *  Returns:
+     *      The line without the EOL, error if the connection was closed.
+     */
+    let mut line: Vec<u8> = Vec::new();
+    if conn.read_until(b'\n', &mut line).await? == 0 {
+        return Err(ClientError::Io(io::Error::from(
+            io::ErrorKind::UnexpectedEof,
+        )));
+    }
+    while line
+        .last()
+        .is_some_and(|byte| *byte == b'\n' || *byte == b'\r')
+    {
+        line.pop();
+    }
+    String::from_utf8(line).map_err(|_| ClientError::InvalidResponse)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use tokio::net::TcpListener;
+
+    #[test]
+    fn split_url_test() {
+        assert_eq!(
+            split_url("http://example.com"),
+            Some((String::from("example.com:80"), String::from("/")))
+        );
+        assert_eq!(
+            split_url("http://127.0.0.1:8080/api?x=1"),
+            Some((String::from("127.0.0.1:8080"), String::from("/api?x=1")))
+        );
+        assert_eq!(split_url("https://example.com/"), None);
+    }
+
+    #[tokio::test]
+    async fn client_pooling_test() {
+        let listener: TcpListener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr: String = listener.local_addr().unwrap().to_string();
+        /* Only one connection is accepted, so the second request must reuse it */
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut conn = BufReader::new(stream);
+            for response in [
+                &b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nhello"[..],
+                &b"HTTP/1.1 201 Created\r\nTransfer-Encoding: chunked\r\n\r\n3\r\nabc\r\n2\r\nde\r\n0\r\n\r\n"[..],
+            ] {
+                let mut line: String = String::new();
+                while line != "\r\n" {
+                    line.clear();
+                    conn.read_line(&mut line).await.unwrap();
+                }
+                conn.get_mut().write_all(response).await.unwrap();
+            }
+        });
+
+        let client = HttpClient::new(4, Duration::from_secs(5));
+        let url: String = format!("http://{addr}/health");
+        let first: ClientResponse = client.get(&url).await.unwrap();
+        assert_eq!((first.status, first.body), (200, b"hello".to_vec()));
+        assert_eq!(client.idle_connections(&addr).await, 1);
+
+        let second: ClientResponse = client.get(&url).await.unwrap();
+        assert_eq!((second.status, second.body), (201, b"abcde".to_vec()));
+        assert_eq!(client.metrics.upstream_pool_hits.load(Ordering::Relaxed), 1);
+        assert_eq!(
+            client
+                .metrics
+                .upstream_connections_opened
+                .load(Ordering::Relaxed),
+            1
+        );
+    }
+
+    #[tokio::test]
+    async fn client_pool_expiry_test() {
+        let listener: TcpListener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr: String = listener.local_addr().unwrap().to_string();
+        tokio::spawn(async move {
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                tokio::spawn(async move {
+                    let mut conn = BufReader::new(stream);
+                    let mut line: String = String::new();
+                    while conn.read_line(&mut line).await.unwrap_or(0) > 0 {
+                        if line == "\r\n" {
+                            let response: &[u8] = b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok";
+                            conn.get_mut().write_all(response).await.unwrap();
+                        }
+                        line.clear();
+                    }
+                });
+            }
+        });
+        let url: String = format!("http://{addr}/");
+        let load = |counter: &AtomicU64| counter.load(Ordering::Relaxed);
+
+        /* The idle connection expires before the next request */
+        let client = HttpClient::new(4, Duration::from_secs(5))
+            .with_pool_limits(Duration::from_millis(50), Duration::from_secs(60));
+        client.get(&url).await.unwrap();
+        assert_eq!(client.idle_connections(&addr).await, 1);
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        client.get(&url).await.unwrap();
+        assert_eq!(load(&client.metrics.upstream_pool_hits), 0);
+        assert_eq!(load(&client.metrics.upstream_connections_opened), 2);
+        assert_eq!(load(&client.metrics.upstream_connections_closed), 1);
+
+        /* The connection past its lifetime isn't pooled at all */
+        let client = HttpClient::new(4, Duration::from_secs(5))
+            .with_pool_limits(Duration::from_secs(60), Duration::ZERO);
+        client.get(&url).await.unwrap();
+        assert_eq!(client.idle_connections(&addr).await, 0);
+        assert_eq!(load(&client.metrics.upstream_connections_closed), 1);
+    }
+}
@@ -0,0 +1,251 @@
+use flate2::{Compression, write::GzEncoder};
+use std::io::{self, Write};
+
+/* Extensions of the sites, that shrink when they are compressed */
+const COMPRESSIBLE_EXTENSIONS: [&[u8]; 12] = [
+    b"html", b"htm", b"css", b"js", b"mjs", b"json", b"map", b"md", b"svg", b"txt", b"xml", b"wasm",
+];
+/* Window of the Brotli encoder, 2^22 bytes covers the typical assets whole */
+#[cfg(feature = "brotli")]
+const BROTLI_WINDOW_BITS: u32 = 22;
+#[cfg(feature = "brotli")]
+const BROTLI_QUALITY: u32 = 9;
+/* Quality of the files encoded ahead of time, the time doesn't matter there */
+#[cfg(feature = "brotli")]
+const BROTLI_MAX_QUALITY: u32 = 11;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ContentCoding {
+    /*
+     *  Encodings of the responses, in the order the server prefers them.
+     */
+    #[cfg(feature = "brotli")]
+    Brotli,
+    Gzip,
+}
+
+/* The supported encodings, the preferred one first */
+pub const SUPPORTED_CODINGS: &[ContentCoding] = &[
+    #[cfg(feature = "brotli")]
+    ContentCoding::Brotli,
+    ContentCoding::Gzip,
+];
+
+impl ContentCoding {
+    pub fn token(&self) -> &'static str {
+        /*
+         *  Accessor.
+         *
+         *  Returns:
+         *      Value of the Content-Encoding header.
+         */
+        match self {
+            #[cfg(feature = "brotli")]
+            Self::Brotli => "br",
+            Self::Gzip => "gzip",
+        }
+    }
+
+    pub fn file_extension(&self) -> &'static str {
+        /*
+         *  Accessor.
+         *
+         *  Returns:
+         *      Extension of the encoded file next to the site, e.g. app.js.gz
+         */
+        match self {
+            #[cfg(feature = "brotli")]
+            Self::Brotli => "br",
+            Self::Gzip => "gz",
+        }
+    }
+
+    fn matches(&self, token: &str) -> bool {
+        token.eq_ignore_ascii_case(self.token())
+            || (*self == Self::Gzip && token.eq_ignore_ascii_case("x-gzip"))
+    }
+
+    pub fn encode(&self, content: &[u8]) -> Result<Vec<u8>, io::Error> {
+        /*
+         *  Compress the whole content.
+         *
+         *  Returns:
+         *      The encoded bytes.
+         */
+        self.encode_at(content, false)
+    }
+
+    pub fn encode_max(&self, content: &[u8]) -> Result<Vec<u8>, io::Error> {
+        /*
+         *  Compress the whole content at the highest level, it is slow, so
+         *  only the files encoded ahead of time use it.
+         */
+        self.encode_at(content, true)
+    }
+
+    fn encode_at(&self, content: &[u8], max: bool) -> Result<Vec<u8>, io::Error> {
+        match self {
+            #[cfg(feature = "brotli")]
+            Self::Brotli => {
+                let mut encoded: Vec<u8> = Vec::new();
+                {
+                    let mut encoder = brotli::CompressorWriter::new(
+                        &mut encoded,
+                        4096,
+                        if max {
+                            BROTLI_MAX_QUALITY
+                        } else {
+                            BROTLI_QUALITY
+                        },
+                        BROTLI_WINDOW_BITS,
+                    );
+                    encoder.write_all(content)?;
+                }
+                Ok(encoded)
+            }
+            Self::Gzip => {
+                let level: Compression = if max {
+                    Compression::best()
+                } else {
+                    Compression::default()
+                };
+                let mut encoder = GzEncoder::new(Vec::new(), level);
+                encoder.write_all(content)?;
+                encoder.finish()
+            }
+        }
+    }
+}
+
+pub fn negotiate_coding(accept_encoding: Option<&[u8]>) -> Option<ContentCoding> {
+    /*
+     *  Pick the encoding from the Accept-Encoding header. The weight of
+     *  an encoding comes from its own element or from *, the ties are won
+     *  by the encoding, that the server prefers.
+     *
+     *  Arguments:
+     *      accept_encoding: Value of the header, None if it is missing.
+     *
+     *  Returns:
+     *      The encoding, None if the response must not be encoded.
+     */
+    let value: &str = std::str::from_utf8(accept_encoding?).ok()?;
+    let weights: Vec<(&str, f32)> = coding_weights(value);
+    let mut best: Option<(ContentCoding, f32)> = None;
+    for coding in SUPPORTED_CODINGS {
+        let q: f32 = weight_of(&weights, coding);
+        if q > 0.0 && best.is_none_or(|(_, best_q)| q > best_q) {
+            best = Some((*coding, q));
+        }
+    }
+    best.map(|(coding, _)| coding)
+}
+
+pub fn accepts_coding(accept_encoding: Option<&[u8]>, coding: ContentCoding) -> bool {
+    /*
+     *  Check if the client accepts the encoding. The missing header is
+     *  taken as no encoding, the same as in negotiate_coding.
+     *
+     *  Arguments:
+     *      accept_encoding: Value of the header, None if it is missing.
+     *      coding: The encoding to check.
+     */
+    let Some(value) = accept_encoding.and_then(|value| std::str::from_utf8(value).ok()) else {
+        return false;
+    };
+    weight_of(&coding_weights(value), &coding) > 0.0
+}
+
+fn coding_weights(value: &str) -> Vec<(&str, f32)> {
+    /*
+     *  Returns:
+     *      Tokens of the Accept-Encoding value with their q-values.
+     */
+    let mut weights: Vec<(&str, f32)> = Vec::new();
+    for element in value.split(',') {
+        let mut params = element.split(';');
+        let token: &str = params.next().unwrap_or_default().trim();
+        if token.is_empty() {
+            continue;
+        }
+        let mut q: f32 = 1.0;
+        for param in params {
+            if let Some((name, value)) = param.trim().split_once('=')
+                && name.trim().eq_ignore_ascii_case("q")
+            {
+                q = value.trim().parse().unwrap_or(0.0);
+            }
+        }
+        weights.push((token, q));
+    }
+    weights
+}
+
+fn weight_of(weights: &[(&str, f32)], coding: &ContentCoding) -> f32 {
+    /*
+     *  The weight of the encoding comes from its own element or from *
+     */
+    weights
+        .iter()
+        .find(|(token, _)| coding.matches(token))
+        .or_else(|| weights.iter().find(|(token, _)| *token == "*"))
+        .map_or(0.0, |(_, q)| *q)
+}
+
+pub fn is_compressible(resource_path: &[u8]) -> bool {
+    /*
+     *  Check if the site is text, the images and the archives are
+     *  compressed already.
+     */
+    let Some(dot_idx) = resource_path.iter().rposition(|byte| *byte == b'.') else {
+        return false;
+    };
+    let extension: &[u8] = &resource_path[dot_idx + 1..];
+    COMPRESSIBLE_EXTENSIONS
+        .iter()
+        .any(|compressible| compressible.eq_ignore_ascii_case(extension))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::read::GzDecoder;
+    use std::io::Read;
+
+    #[test]
+    fn negotiate_coding_test() {
+        assert_eq!(negotiate_coding(None), None);
+        assert_eq!(
+            negotiate_coding(Some(b"gzip;q=0.5, identity")),
+            Some(ContentCoding::Gzip)
+        );
+        assert_eq!(negotiate_coding(Some(b"X-GZIP")), Some(ContentCoding::Gzip));
+        assert_eq!(negotiate_coding(Some(b"gzip;q=0, deflate")), None);
+        assert_eq!(negotiate_coding(Some(b"*;q=0")), None);
+        assert!(accepts_coding(Some(b"br, gzip;q=0.5"), ContentCoding::Gzip));
+        assert!(!accepts_coding(None, ContentCoding::Gzip));
+        #[cfg(feature = "brotli")]
+        assert_eq!(
+            negotiate_coding(Some(b"gzip, br")),
+            Some(ContentCoding::Brotli)
+        );
+        #[cfg(not(feature = "brotli"))]
+        assert_eq!(negotiate_coding(Some(b"*")), Some(ContentCoding::Gzip));
+
+        assert!(is_compressible(b"/static/app.JS"));
+        assert!(!is_compressible(b"/images/logo.png"));
+        assert!(!is_compressible(b"/README"));
+    }
+
+    #[test]
+    fn encode_test() {
+        let content: Vec<u8> = b"<p>diana</p>".repeat(100);
+        let encoded: Vec<u8> = ContentCoding::Gzip.encode(&content).unwrap();
+        assert!(encoded.len() < content.len());
+        let mut decoded: Vec<u8> = Vec::new();
+        GzDecoder::new(encoded.as_slice())
+            .read_to_end(&mut decoded)
+            .unwrap();
+        assert_eq!(decoded, content);
+    }
+}
@@ -0,0 +1,62 @@
+use crate::backend::disconnect::peer_closed;
+use std::future::Future;
+use tokio::io::{AsyncRead, AsyncWrite, DuplexStream, ReadHalf, WriteHalf};
+use tokio::net::TcpStream;
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+
+pub trait Connection: AsyncRead + AsyncWrite + Unpin + Send + Sized + 'static {
+    /*
+     *  Stream of the client's connection, that the requests are read from
+     *  and answered on. The server accepts TcpStream, the tests drive
+     *  the handler with the in-memory DuplexStream and check the exact
+     *  bytes of the responses.
+     */
+    type ReadHalf: AsyncRead + Unpin + Send + 'static;
+    type WriteHalf: AsyncWrite + Unpin + Send + 'static;
+
+    fn split(self) -> (Self::ReadHalf, Self::WriteHalf);
+
+    fn reunite(read_half: Self::ReadHalf, write_half: Self::WriteHalf) -> Option<Self>;
+
+    fn peer_closed(read_half: &mut Self::ReadHalf) -> impl Future<Output = ()> + Send;
+}
+
+impl Connection for TcpStream {
+    type ReadHalf = OwnedReadHalf;
+    type WriteHalf = OwnedWriteHalf;
+
+    fn split(self) -> (OwnedReadHalf, OwnedWriteHalf) {
+        self.into_split()
+    }
+
+    fn reunite(read_half: OwnedReadHalf, write_half: OwnedWriteHalf) -> Option<Self> {
+        read_half.reunite(write_half).ok()
+    }
+
+    fn peer_closed(read_half: &mut OwnedReadHalf) -> impl Future<Output = ()> + Send {
+        peer_closed(read_half)
+    }
+}
+
+impl Connection for DuplexStream {
+    type ReadHalf = ReadHalf<DuplexStream>;
+    type WriteHalf = WriteHalf<DuplexStream>;
+
+    fn split(self) -> (ReadHalf<DuplexStream>, WriteHalf<DuplexStream>) {
+        tokio::io::split(self)
+    }
+
+    fn reunite(
+        read_half: ReadHalf<DuplexStream>,
+        write_half: WriteHalf<DuplexStream>,
+    ) -> Option<Self> {
+        read_half
+            .is_pair_of(&write_half)
+            .then(|| read_half.unsplit(write_half))
+    }
+
+    fn peer_closed(_read_half: &mut ReadHalf<DuplexStream>) -> impl Future<Output = ()> + Send {
+        /* The memory stream can't be peeked, its client is always there */
+        std::future::pending()
+    }
+}
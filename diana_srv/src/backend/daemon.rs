@@ -0,0 +1,151 @@
+use std::fs::{self, File, OpenOptions};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+#[cfg(unix)]
+use std::os::unix::process::CommandExt;
+
+/* Flag, that makes the binary detach, it isn't passed to the detached server */
+pub const DAEMON_FLAG: &str = "--daemon";
+
+#[derive(Debug)]
+pub struct PidFile {
+    /*
+     *  File holding the PID of the running server, so the operators and
+     *  the init scripts can find it. It is removed when it is dropped,
+     *  unless the server switched the user or chrooted since, then it is
+     *  replaced on the next start.
+     *
+     *  Attributes:
+     *      path: Location of the file.
+     */
+    path: PathBuf,
+}
+
+impl PidFile {
+    pub fn create(path: &Path) -> io::Result<Self> {
+        /*
+         *  Write the PID of this process to the file. A file left by
+         *  the process, that isn't running anymore, is replaced.
+         *
+         *  Arguments:
+         *      path: Location of the file.
+         *
+         *  Returns:
+         *      The file, or the error if another server still runs.
+         */
+        check_pid_file(path)?;
+        fs::write(path, format!("{}\n", std::process::id()))?;
+        Ok(PidFile {
+            path: path.to_path_buf(),
+        })
+    }
+}
+
+impl Drop for PidFile {
+    fn drop(&mut self) {
+        /* The file might be taken over by a newer server, that must keep it */
+        if read_pid(&self.path) == Some(std::process::id())
+            && let Err(e) = fs::remove_file(&self.path)
+        {
+            println!(
+                "[WARNING] Failed to remove the PID file {}: {e}",
+                self.path.display()
+            );
+        }
+    }
+}
+
+pub fn check_pid_file(path: &Path) -> io::Result<()> {
+    /*
+     *  Refuse to start, if the PID file belongs to a live process.
+     *
+     *  Returns:
+     *      Error of the kind AlreadyExists, if the server is running.
+     */
+    let Some(pid) = read_pid(path) else {
+        return Ok(());
+    };
+    if pid != std::process::id() && is_alive(pid) {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("{} belongs to the running process {pid}", path.display()),
+        ));
+    }
+    println!("[WARNING] Replacing the stale PID file {}.", path.display());
+    Ok(())
+}
+
+fn read_pid(path: &Path) -> Option<u32> {
+    fs::read_to_string(path).ok()?.trim().parse().ok()
+}
+
+fn is_alive(pid: u32) -> bool {
+    /*
+     *  Check the process through procfs, or with kill -0 where it is
+     *  missing. Without either the process is assumed to be alive.
+     */
+    if Path::new("/proc/self").exists() {
+        return Path::new(&format!("/proc/{pid}")).exists();
+    }
+    Command::new("kill")
+        .args(["-0", &pid.to_string()])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .map_or(true, |status| status.success())
+}
+
+pub fn daemonize(args: &[String], log_path: &Path) -> io::Result<u32> {
+    /*
+     *  Start the server again in the background, detached from the terminal.
+     *  The output goes to the log, since there is no terminal to print to.
+     *
+     *  Arguments:
+     *      args: Command line of this process, the first one is the binary.
+     *      log_path: File, that the output of the server is appended to.
+     *
+     *  Returns:
+     *      PID of the detached server.
+     */
+    let log: File = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(log_path)?;
+    let mut command: Command = Command::new(std::env::current_exe()?);
+    command
+        .args(args.iter().skip(1).filter(|arg| *arg != DAEMON_FLAG))
+        .stdin(Stdio::null())
+        .stdout(log.try_clone()?)
+        .stderr(log);
+    /* Own process group, so the signals sent to the terminal don't reach it */
+    #[cfg(unix)]
+    command.process_group(0);
+    Ok(command.spawn()?.id())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pid_file_test() {
+        let path: PathBuf = std::env::temp_dir().join(format!("diana_{}.pid", std::process::id()));
+        {
+            let _pid_file: PidFile = PidFile::create(&path).unwrap();
+            assert_eq!(read_pid(&path), Some(std::process::id()));
+        }
+        assert!(!path.exists());
+
+        /* PID 1 is always alive, the init process */
+        fs::write(&path, "1\n").unwrap();
+        assert_eq!(
+            PidFile::create(&path).unwrap_err().kind(),
+            io::ErrorKind::AlreadyExists
+        );
+        fs::write(&path, "not a pid\n").unwrap();
+        drop(PidFile::create(&path).unwrap());
+        assert!(!path.exists());
+    }
+}
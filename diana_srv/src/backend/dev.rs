@@ -0,0 +1,208 @@
+use crate::backend::sse::{EVENT_STREAM_HEAD, KEEPALIVE_EVENT, format_event, send_event};
+use crate::utils::readers::files::list_files;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use tokio::io::AsyncWrite;
+use tokio::sync::watch;
+
+/* Route of the event stream, that tells the browsers to reload */
+pub const RELOAD_PATH: &str = "/__diana/reload";
+/* How often the resource directory is checked for the changes */
+const WATCH_INTERVAL: Duration = Duration::from_millis(500);
+/* How often the idle stream sends the keepalive comment */
+const KEEPALIVE_INTERVAL: Duration = Duration::from_secs(15);
+/* Script injected into the served HTML, the browser reconnects by itself */
+const RELOAD_SCRIPT: &str = "<script>new EventSource(\"/__diana/reload\").onmessage=function(){location.reload()};</script>";
+
+/* Path, modification time and size of every watched file */
+type Snapshot = Vec<(PathBuf, Option<SystemTime>, u64)>;
+
+#[derive(Debug, Clone)]
+pub struct LiveReload {
+    /*
+     *  Watcher of the resource directory. It reloads the browsers in
+     *  the development mode and tells the server, which files changed.
+     *
+     *  Attributes:
+     *      generation: Incremented on every change of the directory,
+     *      the event streams wait for it.
+     */
+    generation: watch::Receiver<u64>,
+}
+
+impl LiveReload {
+    pub fn spawn(dir: &Path, on_change: impl Fn(Vec<PathBuf>) + Send + 'static) -> Self {
+        /*
+         *  Start polling the directory, it must be called inside
+         *  the runtime.
+         *
+         *  Arguments:
+         *      dir: The resource directory.
+         *      on_change: Called with the files, that were added, modified
+         *      or removed since the last poll.
+         */
+        let (tx, generation) = watch::channel(0);
+        let dir: PathBuf = dir.to_path_buf();
+        tokio::spawn(async move {
+            let mut last: Snapshot = snapshot(&dir);
+            let mut interval = tokio::time::interval(WATCH_INTERVAL);
+            loop {
+                interval.tick().await;
+                let current: Snapshot = snapshot(&dir);
+                if current != last {
+                    println!("[INFO] {} changed.", dir.display());
+                    on_change(changed_files(&last, &current));
+                    last = current;
+                    tx.send_modify(|generation| *generation += 1);
+                }
+                if tx.is_closed() {
+                    return;
+                }
+            }
+        });
+        LiveReload { generation }
+    }
+
+    pub async fn serve_events(&self, mut stream: impl AsyncWrite + Unpin) {
+        /*
+         *  Keep the event stream open, it sends an event after every change.
+         *
+         *  Arguments:
+         *      stream: Connection of the browser, that asked for the stream.
+         */
+        let mut generation: watch::Receiver<u64> = self.generation.clone();
+        generation.mark_unchanged();
+        if !send_event(&mut stream, EVENT_STREAM_HEAD).await {
+            return;
+        }
+        loop {
+            let event: Vec<u8> = tokio::select! {
+                changed = generation.changed() => {
+                    if changed.is_err() {
+                        return;
+                    }
+                    format_event("reload")
+                }
+                _ = tokio::time::sleep(KEEPALIVE_INTERVAL) => KEEPALIVE_EVENT.to_vec(),
+            };
+            /* The browser closed the tab or navigated away */
+            if !send_event(&mut stream, &event).await {
+                return;
+            }
+        }
+    }
+}
+
+fn snapshot(dir: &Path) -> Snapshot {
+    list_files(dir)
+        .into_iter()
+        .map(|file| {
+            let metadata = file.metadata().ok();
+            let modified: Option<SystemTime> = metadata
+                .as_ref()
+                .and_then(|metadata| metadata.modified().ok());
+            let len: u64 = metadata.map_or(0, |metadata| metadata.len());
+            (file, modified, len)
+        })
+        .collect()
+}
+
+fn changed_files(last: &Snapshot, current: &Snapshot) -> Vec<PathBuf> {
+    /*
+     *  Returns:
+     *      Files, that differ between the snapshots.
+     */
+    let mut changed: Vec<PathBuf> = current
+        .iter()
+        .filter(|file| !last.contains(file))
+        .map(|(path, _, _)| path.clone())
+        .collect();
+    for (path, _, _) in last {
+        if !current
+            .iter()
+            .any(|(current_path, _, _)| current_path == path)
+        {
+            changed.push(path.clone());
+        }
+    }
+    changed
+}
+
+pub fn is_html(resource_path: &[u8]) -> bool {
+    resource_path.ends_with(b"/")
+        || resource_path.ends_with(b".html")
+        || resource_path.ends_with(b".htm")
+}
+
+pub fn inject_reload_script(html: &[u8]) -> Vec<u8> {
+    /*
+     *  Insert the live reload script before the closing body tag, or at
+     *  the end if the page has none.
+     */
+    let lowercase: Vec<u8> = html.to_ascii_lowercase();
+    let insert_idx: usize = lowercase
+        .windows(7)
+        .rposition(|window| window == b"</body>")
+        .unwrap_or(html.len());
+    let mut injected: Vec<u8> = Vec::with_capacity(html.len() + RELOAD_SCRIPT.len());
+    injected.extend_from_slice(&html[..insert_idx]);
+    injected.extend_from_slice(RELOAD_SCRIPT.as_bytes());
+    injected.extend_from_slice(&html[insert_idx..]);
+    injected
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::AsyncReadExt;
+    use tokio::net::{TcpListener, TcpStream};
+
+    #[test]
+    fn inject_reload_script_test() {
+        let injected: Vec<u8> = inject_reload_script(b"<html><BODY><p>hi</p></BODY></html>");
+        let expected: String = format!("<html><BODY><p>hi</p>{RELOAD_SCRIPT}</BODY></html>");
+        assert_eq!(injected, expected.into_bytes());
+        assert!(inject_reload_script(b"<p>hi</p>").ends_with(RELOAD_SCRIPT.as_bytes()));
+        assert!(is_html(b"/index.html"));
+        assert!(is_html(b"/docs/"));
+        assert!(!is_html(b"/style.css"));
+    }
+
+    #[tokio::test]
+    async fn live_reload_test() {
+        let dir: PathBuf = std::env::temp_dir().join(format!("diana_dev_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let (changed_tx, mut changed_rx) = tokio::sync::mpsc::unbounded_channel();
+        let reload: LiveReload = LiveReload::spawn(&dir, move |changed| {
+            let _ = changed_tx.send(changed);
+        });
+
+        let listener: TcpListener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            reload.serve_events(stream).await;
+        });
+        let mut browser: TcpStream = TcpStream::connect(addr).await.unwrap();
+        let mut received: Vec<u8> = Vec::new();
+        while !received.ends_with(b"\r\n\r\n") {
+            browser.read_buf(&mut received).await.unwrap();
+        }
+
+        std::fs::write(dir.join("index.html"), "<p>changed</p>").unwrap();
+        received.clear();
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while !received.ends_with(b"\n\n") {
+                browser.read_buf(&mut received).await.unwrap();
+            }
+        })
+        .await
+        .unwrap();
+        assert_eq!(received, b"data: reload\n\n".to_vec());
+        assert_eq!(
+            changed_rx.recv().await.unwrap(),
+            vec![dir.join("index.html")]
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
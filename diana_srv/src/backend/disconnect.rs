@@ -0,0 +1,113 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use tokio::net::tcp::OwnedReadHalf;
+use tokio::sync::Notify;
+
+#[derive(Debug, Default)]
+struct DisconnectState {
+    closed: AtomicBool,
+    notify: Notify,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Disconnect {
+    /*
+     *  Signal of the client, that closed the connection before it was
+     *  answered. The handler is cancelled then, the work it spawned
+     *  of its own, e.g. the fetches from the upstreams, should watch it.
+     */
+    state: Arc<DisconnectState>,
+}
+
+impl Disconnect {
+    pub fn is_closed(&self) -> bool {
+        self.state.closed.load(Ordering::Acquire)
+    }
+
+    pub async fn closed(&self) {
+        /*
+         *  Wait until the client is gone, e.g. in select! next to the work.
+         */
+        loop {
+            /* Registered before the check, so the close in between isn't missed */
+            let notified = self.state.notify.notified();
+            if self.is_closed() {
+                return;
+            }
+            notified.await;
+        }
+    }
+
+    pub fn close(&self) {
+        self.state.closed.store(true, Ordering::Release);
+        self.state.notify.notify_waiters();
+    }
+}
+
+pub async fn peer_closed(read_half: &mut OwnedReadHalf) {
+    /*
+     *  Wait until the client closes the connection, while its request is
+     *  handled. The bytes sent meanwhile are the next pipelined request,
+     *  they are left unread and the client is known to be there.
+     */
+    let mut byte: [u8; 1] = [0];
+    match read_half.peek(&mut byte).await {
+        Ok(0) | Err(_) => {}
+        Ok(_) => std::future::pending::<()>().await,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use tokio::io::AsyncWriteExt;
+    use tokio::net::{TcpListener, TcpStream};
+    use tokio::time::timeout;
+
+    #[tokio::test]
+    async fn disconnect_test() {
+        let disconnect: Disconnect = Disconnect::default();
+        let watched: Disconnect = disconnect.clone();
+        let watcher = tokio::spawn(async move { watched.closed().await });
+        tokio::task::yield_now().await;
+        assert!(!disconnect.is_closed());
+        disconnect.close();
+        assert!(disconnect.is_closed());
+        timeout(Duration::from_secs(1), watcher)
+            .await
+            .unwrap()
+            .unwrap();
+        /* The late watchers return at once */
+        timeout(Duration::from_secs(1), disconnect.closed())
+            .await
+            .unwrap();
+
+        let listener: TcpListener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let mut client: TcpStream = TcpStream::connect(listener.local_addr().unwrap())
+            .await
+            .unwrap();
+        let (inc_stream, _) = listener.accept().await.unwrap();
+        let (mut read_half, _write_half) = inc_stream.into_split();
+
+        /* The pipelined request means, that the client is still there */
+        client.write_all(b"GET / HTTP/1.1\r\n\r\n").await.unwrap();
+        assert!(
+            timeout(Duration::from_millis(100), peer_closed(&mut read_half))
+                .await
+                .is_err()
+        );
+        drop(client);
+        let (mut read_half, _write_half) = {
+            let mut client: TcpStream = TcpStream::connect(listener.local_addr().unwrap())
+                .await
+                .unwrap();
+            let (inc_stream, _) = listener.accept().await.unwrap();
+            client.shutdown().await.unwrap();
+            inc_stream.into_split()
+        };
+        timeout(Duration::from_secs(1), peer_closed(&mut read_half))
+            .await
+            .unwrap();
+    }
+}
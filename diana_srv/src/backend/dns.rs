@@ -0,0 +1,104 @@
+use std::collections::HashMap;
+use std::io;
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio::net::lookup_host;
+use tokio::sync::Mutex;
+use tokio::time::Instant;
+
+#[derive(Debug, Clone)]
+struct ResolvedHost {
+    /*
+     *  Attributes:
+     *      addrs: Addresses, that the host resolved to.
+     *      expires_at: When the addresses have to be resolved again.
+     */
+    addrs: Vec<SocketAddr>,
+    expires_at: Instant,
+}
+
+#[derive(Debug)]
+pub struct Resolver {
+    /*
+     *  Resolves the upstream hosts without blocking the runtime and caches
+     *  the addresses for the TTL. The system resolver doesn't expose the TTL
+     *  of the records, so the configured one is used for every host.
+     *
+     *  Attributes:
+     *      entries: Resolved addresses by their host:port.
+     *      ttl: How long the addresses are reused.
+     */
+    entries: Mutex<HashMap<String, ResolvedHost>>,
+    ttl: Duration,
+}
+
+impl Resolver {
+    pub fn new(ttl: Duration) -> Self {
+        Resolver {
+            entries: Mutex::new(HashMap::new()),
+            ttl,
+        }
+    }
+
+    pub async fn resolve(&self, host: &str) -> Result<Vec<SocketAddr>, io::Error> {
+        /*
+         *  Resolve the host, the cached addresses are used until they expire.
+         *
+         *  Arguments:
+         *      host: Host with the port, e.g. example.com:80
+         *
+         *  Returns:
+         *      Addresses of the host, error if the host doesn't resolve.
+         */
+        if let Some(resolved) = self.entries.lock().await.get(host)
+            && resolved.expires_at > Instant::now()
+        {
+            return Ok(resolved.addrs.clone());
+        }
+        let addrs: Vec<SocketAddr> = lookup_host(host).await?.collect();
+        if addrs.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("{host} has no addresses"),
+            ));
+        }
+        self.entries.lock().await.insert(
+            host.to_string(),
+            ResolvedHost {
+                addrs: addrs.clone(),
+                expires_at: Instant::now() + self.ttl,
+            },
+        );
+        Ok(addrs)
+    }
+
+    pub async fn invalidate(&self, host: &str) -> bool {
+        /*
+         *  Forget the addresses, e.g. when none of them accepts the connection
+         *  anymore, so the next call resolves the host again.
+         *
+         *  Returns:
+         *      True if the host was cached.
+         */
+        self.entries.lock().await.remove(host).is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn resolver_cache_test() {
+        let resolver = Resolver::new(Duration::from_secs(60));
+        let addrs: Vec<SocketAddr> = resolver.resolve("127.0.0.1:8080").await.unwrap();
+        assert_eq!(addrs, vec!["127.0.0.1:8080".parse().unwrap()]);
+        assert!(resolver.invalidate("127.0.0.1:8080").await);
+        assert!(!resolver.invalidate("127.0.0.1:8080").await);
+
+        /* Expired addresses are resolved again */
+        let expiring = Resolver::new(Duration::ZERO);
+        expiring.resolve("127.0.0.1:80").await.unwrap();
+        assert!(expiring.resolve("127.0.0.1:80").await.is_ok());
+    }
+}
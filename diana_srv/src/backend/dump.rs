@@ -0,0 +1,193 @@
+use crate::backend::logging::format_clf_time;
+use std::fmt::Write as _;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/* Bytes on a single line of the hex dump */
+const BYTES_PER_LINE: usize = 16;
+const DUMP_EXTENSION: &str = "dump";
+
+#[derive(Debug)]
+pub struct ParseDumps {
+    /*
+     *  Dumps of the requests, that failed to parse, so the parser bugs
+     *  can be reported with the exact bytes. Every request gets its own
+     *  file in the directory.
+     *
+     *  Attributes:
+     *      dir: Directory of the dumps.
+     *      max_files: Dumps kept in the directory, the next ones are
+     *      dropped until the old ones are removed.
+     *      max_bytes: Bytes of the request written to the dump, the rest
+     *      is cut off.
+     *      written: Dumps in the directory.
+     */
+    dir: PathBuf,
+    max_files: usize,
+    max_bytes: usize,
+    written: Mutex<usize>,
+}
+
+impl ParseDumps {
+    pub fn open(dir: &Path, max_files: usize, max_bytes: usize) -> Result<Self, io::Error> {
+        /*
+         *  Create the directory, the dumps of the previous runs count
+         *  toward the limit.
+         */
+        fs::create_dir_all(dir)?;
+        let written: usize = fs::read_dir(dir)?
+            .filter_map(Result::ok)
+            .filter(|entry| {
+                entry
+                    .path()
+                    .extension()
+                    .is_some_and(|ext| ext == DUMP_EXTENSION)
+            })
+            .count();
+        Ok(ParseDumps {
+            dir: dir.to_path_buf(),
+            max_files,
+            max_bytes,
+            written: Mutex::new(written),
+        })
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn dump(&self, peer: SocketAddr, bytes: &[u8], reason: &str) -> Option<PathBuf> {
+        /*
+         *  Write the dump of the rejected request.
+         *
+         *  Arguments:
+         *      peer: Address of the client.
+         *      bytes: Raw bytes of the request.
+         *      reason: Error of the parser.
+         *
+         *  Returns:
+         *      Path of the dump, None if the limit is reached or it failed.
+         */
+        let mut written = self.written.lock().unwrap();
+        if *written >= self.max_files {
+            return None;
+        }
+        let elapsed = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        let path: PathBuf = self.dir.join(format!(
+            "request-{}-{}.{DUMP_EXTENSION}",
+            elapsed.as_millis(),
+            *written
+        ));
+        let content: String = format_dump(
+            peer,
+            &format_clf_time(elapsed.as_secs()),
+            bytes,
+            self.max_bytes,
+            reason,
+        );
+        /* The existing dump is never overwritten */
+        let result: Result<(), io::Error> = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&path)
+            .and_then(|mut file| file.write_all(content.as_bytes()));
+        match result {
+            Ok(()) => {
+                *written += 1;
+                Some(path)
+            }
+            Err(e) => {
+                println!(
+                    "[WARNING] Failed to dump the request to {}: {e}",
+                    path.display()
+                );
+                None
+            }
+        }
+    }
+}
+
+pub fn format_dump(
+    peer: SocketAddr,
+    time: &str,
+    bytes: &[u8],
+    max_bytes: usize,
+    reason: &str,
+) -> String {
+    /*
+     *  Format the dump, the header lines are followed by the hex dump,
+     *  e.g. 00000000  47 45 54 20 2f 0d 0a  |GET /..|
+     *
+     *  Returns:
+     *      Content of the dump file.
+     */
+    let shown: &[u8] = &bytes[..bytes.len().min(max_bytes)];
+    let mut dump: String = format!(
+        "peer: {peer}\ntime: {time}\nerror: {reason}\nlength: {}\n",
+        bytes.len()
+    );
+    if shown.len() < bytes.len() {
+        let _ = writeln!(dump, "truncated: first {} bytes shown", shown.len());
+    }
+    dump.push('\n');
+    for (line_idx, line) in shown.chunks(BYTES_PER_LINE).enumerate() {
+        let _ = write!(dump, "{:08x} ", line_idx * BYTES_PER_LINE);
+        for idx in 0..BYTES_PER_LINE {
+            match line.get(idx) {
+                Some(byte) => {
+                    let _ = write!(dump, " {byte:02x}");
+                }
+                None => dump.push_str("   "),
+            }
+        }
+        dump.push_str("  |");
+        dump.extend(line.iter().map(|byte| match byte {
+            0x20..=0x7e => *byte as char,
+            _ => '.',
+        }));
+        dump.push_str("|\n");
+    }
+    dump
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_dump_test() {
+        let peer: SocketAddr = "127.0.0.1:5000".parse().unwrap();
+        let request: &[u8] = b"GET /\x00 HTTP/1.1\r\nHost: a\r\n\r\n";
+        let dump: String = format_dump(peer, "t", request, 20, "Invalid target");
+        assert!(dump.starts_with("peer: 127.0.0.1:5000\ntime: t\nerror: Invalid target\n"));
+        assert!(dump.contains("length: 28\ntruncated: first 20 bytes shown\n"));
+        assert!(dump.contains(
+            "00000000  47 45 54 20 2f 00 20 48 54 54 50 2f 31 2e 31 0d  |GET /. HTTP/1.1.|\n"
+        ));
+        assert!(
+            dump.ends_with("00000010  0a 48 6f 73                                      |.Hos|\n")
+        );
+
+        let dir: PathBuf = std::env::temp_dir().join(format!("diana_dumps_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let dumps: ParseDumps = ParseDumps::open(&dir, 2, 1024).unwrap();
+        let first: PathBuf = dumps.dump(peer, request, "Invalid target").unwrap();
+        assert!(
+            fs::read_to_string(first)
+                .unwrap()
+                .contains("|GET /. HTTP/1.1.|")
+        );
+        assert!(dumps.dump(peer, request, "Invalid target").is_some());
+        assert!(dumps.dump(peer, request, "Invalid target").is_none());
+        /* The earlier dumps count after the restart */
+        let reopened: ParseDumps = ParseDumps::open(&dir, 2, 1024).unwrap();
+        assert!(reopened.dump(peer, request, "Invalid target").is_none());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,143 @@
+use crate::backend::server::HttpResponseStatus;
+use diana_http::parser::ParseError;
+use diana_http::validation::{FramingError, HostError, SyntaxError};
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+#[derive(Debug)]
+pub enum RequestError {
+    /*
+     *  Reasons, why the request can't be answered as asked. Every one of
+     *  them maps to the status, that the client is answered with.
+     */
+    Parse(ParseError),
+    Framing(FramingError),
+    Syntax(SyntaxError),
+    Host(HostError),
+    UnsupportedMethod,
+    MethodNotAllowed,
+    MissingTarget,
+    BodyTooLarge,
+    IncompleteBody,
+    InvalidEncoding(io::Error),
+    Io(io::Error),
+}
+
+impl RequestError {
+    pub fn status(&self) -> HttpResponseStatus {
+        /*
+         *  Returns:
+         *      Status, that the request is answered with.
+         */
+        match self {
+            Self::Parse(e) => e.status(),
+            Self::Host(HostError::Misdirected) => HttpResponseStatus::MisdirectedRequest,
+            Self::UnsupportedMethod => HttpResponseStatus::NotImplemented,
+            Self::MethodNotAllowed => HttpResponseStatus::MethodNotAllowed,
+            Self::BodyTooLarge => HttpResponseStatus::PayloadTooLarge,
+            Self::Io(_) => HttpResponseStatus::InternalServerError,
+            Self::Framing(_)
+            | Self::Syntax(_)
+            | Self::Host(_)
+            | Self::MissingTarget
+            | Self::IncompleteBody
+            | Self::InvalidEncoding(_) => HttpResponseStatus::BadRequest,
+        }
+    }
+
+    pub fn closes_connection(&self) -> bool {
+        /*
+         *  Returns:
+         *      True if the rest of the connection can't be trusted, e.g.
+         *      the framing is ambiguous, so it must be closed.
+         */
+        !matches!(
+            self,
+            Self::Host(_) | Self::BodyTooLarge | Self::MethodNotAllowed
+        )
+    }
+
+    pub fn is_malformed(&self) -> bool {
+        /*
+         *  Returns:
+         *      True if the bytes of the request failed to parse, not if
+         *      the parsed request was refused.
+         */
+        matches!(self, Self::Parse(_) | Self::Framing(_) | Self::Syntax(_))
+    }
+}
+
+impl fmt::Display for RequestError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Parse(e) => write!(f, "{e}"),
+            Self::Framing(e) => write!(f, "{e}"),
+            Self::Syntax(e) => write!(f, "{e}"),
+            Self::Host(e) => write!(f, "{e}"),
+            Self::UnsupportedMethod => write!(f, "Request method is not supported"),
+            Self::MethodNotAllowed => write!(f, "Request method is not allowed"),
+            Self::MissingTarget => write!(f, "Request target is missing"),
+            Self::BodyTooLarge => write!(f, "Request body is too large"),
+            Self::IncompleteBody => write!(f, "Request body is shorter than Content-Length"),
+            Self::InvalidEncoding(e) => write!(f, "Failed to decode the body: {e}"),
+            Self::Io(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl Error for RequestError {}
+
+impl From<ParseError> for RequestError {
+    fn from(e: ParseError) -> Self {
+        Self::Parse(e)
+    }
+}
+
+impl From<FramingError> for RequestError {
+    fn from(e: FramingError) -> Self {
+        Self::Framing(e)
+    }
+}
+
+impl From<SyntaxError> for RequestError {
+    fn from(e: SyntaxError) -> Self {
+        Self::Syntax(e)
+    }
+}
+
+impl From<HostError> for RequestError {
+    fn from(e: HostError) -> Self {
+        Self::Host(e)
+    }
+}
+
+impl From<io::Error> for RequestError {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn request_error_status_test() {
+        let misdirected: RequestError = HostError::Misdirected.into();
+        assert_eq!(misdirected.status().value(), 421);
+        assert!(!misdirected.closes_connection());
+        let framing: RequestError = FramingError::ConflictingContentLength.into();
+        assert_eq!(framing.status().value(), 400);
+        assert!(framing.closes_connection());
+        assert_eq!(RequestError::BodyTooLarge.status().value(), 413);
+        assert_eq!(RequestError::MethodNotAllowed.status().value(), 405);
+        assert!(!RequestError::MethodNotAllowed.closes_connection());
+        assert_eq!(
+            RequestError::from(ParseError::HeadTooLarge)
+                .status()
+                .value(),
+            431
+        );
+    }
+}
@@ -0,0 +1,98 @@
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::fmt;
+
+#[derive(Default)]
+pub struct Extensions {
+    /*
+     *  Values attached to the request by the hooks, one per type, e.g.
+     *  the authenticated user or the request id, that the handlers
+     *  read later. Wrap the plain types into a struct of their own, so
+     *  the hooks don't replace each other's values.
+     *
+     *  Attributes:
+     *      values: The values by their type.
+     */
+    values: HashMap<TypeId, Box<dyn Any + Send + Sync>>,
+}
+
+impl Extensions {
+    pub fn insert<T: Send + Sync + 'static>(&mut self, value: T) -> Option<T> {
+        /*
+         *  Returns:
+         *      The value of the same type, that was replaced.
+         */
+        self.values
+            .insert(TypeId::of::<T>(), Box::new(value))
+            .and_then(|replaced| replaced.downcast().ok())
+            .map(|replaced| *replaced)
+    }
+
+    pub fn get<T: Send + Sync + 'static>(&self) -> Option<&T> {
+        self.values
+            .get(&TypeId::of::<T>())
+            .and_then(|value| value.downcast_ref())
+    }
+
+    pub fn get_mut<T: Send + Sync + 'static>(&mut self) -> Option<&mut T> {
+        self.values
+            .get_mut(&TypeId::of::<T>())
+            .and_then(|value| value.downcast_mut())
+    }
+
+    pub fn remove<T: Send + Sync + 'static>(&mut self) -> Option<T> {
+        self.values
+            .remove(&TypeId::of::<T>())
+            .and_then(|value| value.downcast().ok())
+            .map(|value| *value)
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+}
+
+impl fmt::Debug for Extensions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Extensions")
+            .field("values", &self.values.len())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq)]
+    struct User(String);
+
+    #[derive(Debug, PartialEq)]
+    struct RequestId(u64);
+
+    #[test]
+    fn extensions_test() {
+        let mut extensions: Extensions = Extensions::default();
+        assert!(extensions.is_empty());
+        assert_eq!(extensions.insert(User(String::from("alice"))), None);
+        assert_eq!(extensions.insert(RequestId(7)), None);
+        assert_eq!(extensions.len(), 2);
+        assert_eq!(extensions.get::<User>(), Some(&User(String::from("alice"))));
+
+        /* The value of the same type is replaced, the others are kept */
+        assert_eq!(
+            extensions.insert(User(String::from("bob"))),
+            Some(User(String::from("alice")))
+        );
+        extensions.get_mut::<RequestId>().unwrap().0 += 1;
+        assert_eq!(extensions.get::<RequestId>(), Some(&RequestId(8)));
+        assert_eq!(extensions.remove::<User>(), Some(User(String::from("bob"))));
+        assert_eq!(extensions.get::<User>(), None);
+        assert_eq!(extensions.get::<String>(), None);
+        assert_eq!(extensions.len(), 1);
+    }
+}
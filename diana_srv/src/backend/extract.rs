@@ -0,0 +1,513 @@
+use crate::backend::http::{Problem, Request, Response, body_error_status};
+use crate::backend::listing::percent_decode;
+use crate::backend::router::Handler;
+use crate::backend::server::HttpResponseStatus;
+use async_trait::async_trait;
+use diana_http::headers::HeaderMap;
+use serde::de::value::{Error as ValueError, MapDeserializer, SeqDeserializer};
+use serde::de::{self, DeserializeOwned, Deserializer, IntoDeserializer, Visitor};
+use std::future::Future;
+use std::marker::PhantomData;
+
+#[async_trait]
+pub trait FromRequest: Sized + Send {
+    /*
+     *  Part of the request, that the handler declares in its signature
+     *  instead of parsing the Request itself.
+     *
+     *  Returns:
+     *      The extracted value, or the problem answered in place of
+     *      the handler, e.g. 400 for the malformed query.
+     */
+    async fn from_request(req: &mut Request) -> Result<Self, Problem>;
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Path<T>(pub T);
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Query<T>(pub T);
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Json<T>(pub T);
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Headers(pub HeaderMap);
+
+#[async_trait]
+impl<T: DeserializeOwned + Send> FromRequest for Path<T> {
+    async fn from_request(req: &mut Request) -> Result<Self, Problem> {
+        /*
+         *  The route parameters, e.g. Path<u32> for /users/:id, or a struct
+         *  with a field per parameter. The unparsable ones are 400.
+         */
+        T::deserialize(Params(&req.params))
+            .map(Path)
+            .map_err(|e| invalid(HttpResponseStatus::BadRequest, "path", &e))
+    }
+}
+
+#[async_trait]
+impl<T: DeserializeOwned + Send> FromRequest for Query<T> {
+    async fn from_request(req: &mut Request) -> Result<Self, Problem> {
+        /*
+         *  The query parameters, usually a struct with a field per
+         *  parameter, Option for the optional ones. The missing and
+         *  unparsable ones are 400.
+         */
+        let pairs: Vec<(String, String)> = query_pairs(req.query.as_deref()).ok_or_else(|| {
+            Problem::new(HttpResponseStatus::BadRequest).with_detail("Invalid query encoding")
+        })?;
+        T::deserialize(Params(&pairs))
+            .map(Query)
+            .map_err(|e| invalid(HttpResponseStatus::BadRequest, "query", &e))
+    }
+}
+
+#[async_trait]
+impl<T: DeserializeOwned + Send> FromRequest for Json<T> {
+    async fn from_request(req: &mut Request) -> Result<Self, Problem> {
+        /*
+         *  The JSON body. Another media type is 415, the malformed JSON
+         *  is 400 and the JSON, that doesn't fit the type, is 422.
+         */
+        let is_json: bool = req.header("Content-Type").is_some_and(is_json_type);
+        if !is_json {
+            return Err(Problem::new(HttpResponseStatus::UnsupportedMediaType)
+                .with_detail("Expected the application/json body"));
+        }
+        let body: Vec<u8> = req
+            .body()
+            .await
+            .map_err(|e| Problem::from_error(body_error_status(&e), &e))?;
+        serde_json::from_slice(&body).map(Json).map_err(|e| {
+            let status: HttpResponseStatus = if e.is_data() {
+                HttpResponseStatus::UnprocessableContent
+            } else {
+                HttpResponseStatus::BadRequest
+            };
+            invalid(status, "body", &e)
+        })
+    }
+}
+
+#[async_trait]
+impl FromRequest for Headers {
+    async fn from_request(req: &mut Request) -> Result<Self, Problem> {
+        Ok(Headers(req.headers.clone()))
+    }
+}
+
+fn invalid(status: HttpResponseStatus, part: &str, e: &dyn std::error::Error) -> Problem {
+    Problem::new(status).with_detail(&format!("Invalid {part}: {e}"))
+}
+
+fn is_json_type(content_type: &[u8]) -> bool {
+    /*
+     *  Returns:
+     *      Whether the media type is JSON, e.g. application/json or
+     *      application/problem+json, the parameters are ignored.
+     */
+    let content_type: String = String::from_utf8_lossy(content_type).to_ascii_lowercase();
+    let mime: &str = content_type.split(';').next().unwrap_or_default().trim();
+    mime == "application/json" || (mime.starts_with("application/") && mime.ends_with("+json"))
+}
+
+fn query_pairs(query: Option<&[u8]>) -> Option<Vec<(String, String)>> {
+    /*
+     *  Returns:
+     *      The decoded query parameters in their order, None if one of
+     *      them isn't valid percent-encoded UTF-8.
+     */
+    let Some(query) = query else {
+        return Some(Vec::new());
+    };
+    query
+        .split(|byte| *byte == b'&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (key, value) = match pair.iter().position(|byte| *byte == b'=') {
+                Some(eq_idx) => (&pair[..eq_idx], &pair[eq_idx + 1..]),
+                None => (pair, &pair[pair.len()..]),
+            };
+            Some((percent_decode(key)?, percent_decode(value)?))
+        })
+        .collect()
+}
+
+struct Params<'de>(&'de [(String, String)]);
+
+struct Value<'de>(&'de str);
+
+macro_rules! parse_value {
+    ($($method:ident => $visit:ident),+ $(,)?) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, ValueError> {
+                match self.0.parse() {
+                    Ok(parsed) => visitor.$visit(parsed),
+                    Err(e) => Err(de::Error::custom(format!("{:?}: {e}", self.0))),
+                }
+            }
+        )+
+    };
+}
+
+impl<'de> Deserializer<'de> for Value<'de> {
+    /*
+     *  One textual value, that is parsed into the type asked for.
+     */
+    type Error = ValueError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, ValueError> {
+        visitor.visit_borrowed_str(self.0)
+    }
+
+    parse_value! {
+        deserialize_bool => visit_bool,
+        deserialize_i8 => visit_i8,
+        deserialize_i16 => visit_i16,
+        deserialize_i32 => visit_i32,
+        deserialize_i64 => visit_i64,
+        deserialize_u8 => visit_u8,
+        deserialize_u16 => visit_u16,
+        deserialize_u32 => visit_u32,
+        deserialize_u64 => visit_u64,
+        deserialize_f32 => visit_f32,
+        deserialize_f64 => visit_f64,
+        deserialize_char => visit_char,
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, ValueError> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, ValueError> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, ValueError> {
+        visitor.visit_enum(self.0.into_deserializer())
+    }
+
+    serde::forward_to_deserialize_any! {
+        i128 u128 str string bytes byte_buf unit unit_struct seq tuple
+        tuple_struct map struct identifier ignored_any
+    }
+}
+
+impl<'de> IntoDeserializer<'de, ValueError> for Value<'de> {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+macro_rules! single_value {
+    ($($method:ident),+ $(,)?) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, ValueError> {
+                self.single()?.$method(visitor)
+            }
+        )+
+    };
+}
+
+impl<'de> Params<'de> {
+    fn single(&self) -> Result<Value<'de>, ValueError> {
+        match self.0 {
+            [(_, value)] => Ok(Value(value)),
+            _ => Err(de::Error::custom(format!(
+                "expected 1 value, got {}",
+                self.0.len()
+            ))),
+        }
+    }
+
+    fn values(self) -> SeqDeserializer<impl Iterator<Item = Value<'de>>, ValueError> {
+        SeqDeserializer::new(self.0.iter().map(|(_, value)| Value(value)))
+    }
+}
+
+impl<'de> Deserializer<'de> for Params<'de> {
+    /*
+     *  The named values, that are a struct or a map, a tuple of
+     *  the values in their order, or the only value itself.
+     */
+    type Error = ValueError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, ValueError> {
+        visitor.visit_map(MapDeserializer::new(
+            self.0
+                .iter()
+                .map(|(name, value)| (name.as_str(), Value(value))),
+        ))
+    }
+
+    single_value! {
+        deserialize_bool, deserialize_i8, deserialize_i16, deserialize_i32, deserialize_i64,
+        deserialize_u8, deserialize_u16, deserialize_u32, deserialize_u64, deserialize_f32,
+        deserialize_f64, deserialize_char, deserialize_str, deserialize_string,
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, ValueError> {
+        self.single()?.deserialize_newtype_struct(name, visitor)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, ValueError> {
+        self.single()?.deserialize_enum(name, variants, visitor)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, ValueError> {
+        visitor.visit_seq(self.values())
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(
+        self,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, ValueError> {
+        visitor.visit_seq(self.values())
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, ValueError> {
+        visitor.visit_seq(self.values())
+    }
+
+    serde::forward_to_deserialize_any! {
+        i128 u128 bytes byte_buf option unit unit_struct map struct identifier ignored_any
+    }
+}
+
+#[async_trait]
+pub trait ExtractorFn<Args>: Send + Sync {
+    /*
+     *  Function, that takes the extractors, e.g.
+     *  |Path(id): Path<u32>, Json(user): Json<User>| async move { .. }
+     */
+    async fn call(&self, req: Request) -> Response;
+}
+
+macro_rules! extractor_fn {
+    ($($arg:ident),+) => {
+        #[async_trait]
+        impl<F, Fut, $($arg),+> ExtractorFn<($($arg,)+)> for F
+        where
+            F: Fn($($arg),+) -> Fut + Send + Sync,
+            Fut: Future<Output = Response> + Send,
+            $($arg: FromRequest + 'static),+
+        {
+            #[allow(non_snake_case)]
+            async fn call(&self, mut req: Request) -> Response {
+                $(
+                    let $arg: $arg = match $arg::from_request(&mut req).await {
+                        Ok(extracted) => extracted,
+                        Err(problem) => {
+                            let instance: String = String::from_utf8_lossy(&req.path).into_owned();
+                            return problem.with_instance(&instance).into();
+                        }
+                    };
+                )+
+                self($($arg),+).await
+            }
+        }
+    };
+}
+
+extractor_fn!(A);
+extractor_fn!(A, B);
+extractor_fn!(A, B, C);
+extractor_fn!(A, B, C, D);
+extractor_fn!(A, B, C, D, E);
+extractor_fn!(A, B, C, D, E, G);
+
+pub struct Extract<F, Args> {
+    /*
+     *  Adapter registering the functions with the extractors on
+     *  the router, see extract()
+     */
+    function: F,
+    args: PhantomData<fn() -> Args>,
+}
+
+pub fn extract<F: ExtractorFn<Args>, Args>(function: F) -> Extract<F, Args> {
+    /*
+     *  Make the handler of the function, that takes up to six extractors
+     *  instead of the Request. The failed extraction is answered with
+     *  the problem and the function isn't called.
+     *
+     *  Arguments:
+     *      function: Async function or closure taking the extractors.
+     */
+    Extract {
+        function,
+        args: PhantomData,
+    }
+}
+
+#[async_trait]
+impl<F: ExtractorFn<Args>, Args> Handler for Extract<F, Args> {
+    async fn handle(&self, req: Request) -> Response {
+        self.function.call(req).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::http::RequestBody;
+    use crate::backend::server::RequestType;
+    use diana_http::normalize::split_target;
+    use serde::Deserialize;
+    use std::time::Duration;
+    use tokio::time::Instant;
+
+    #[derive(Debug, Deserialize)]
+    struct Search {
+        q: String,
+        page: Option<u32>,
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct NewUser {
+        name: String,
+        age: u8,
+    }
+
+    fn request(target: &[u8], content_type: &str, body: &[u8]) -> Request {
+        let head: String = format!("POST / HTTP/1.1\r\nContent-Type: {content_type}\r\n\r\n");
+        let (path, query): (&[u8], Option<&[u8]>) = split_target(target);
+        Request::new(
+            RequestType::Post,
+            path.to_vec(),
+            head.as_bytes(),
+            RequestBody::buffered(body.to_vec()),
+            "127.0.0.1:4000".parse().unwrap(),
+            Instant::now() + Duration::from_secs(5),
+        )
+        .with_query(query.map(<[u8]>::to_vec))
+        .with_params(vec![(String::from("id"), String::from("42"))])
+    }
+
+    #[tokio::test]
+    async fn extract_test() {
+        let handler = extract(
+            |Path(id): Path<u32>, Query(search): Query<Search>, Json(user): Json<NewUser>| async move {
+                let body: String = format!(
+                    "{id} {} {:?} {} {}",
+                    search.q, search.page, user.name, user.age
+                );
+                Response::new(HttpResponseStatus::Ok).with_body(body.into_bytes())
+            },
+        );
+        let user: &[u8] = br#"{"name":"ann","age":30}"#;
+
+        let response: Response = handler
+            .handle(request(
+                b"/users/42?q=a%20b&page=2",
+                "application/json",
+                user,
+            ))
+            .await;
+        assert_eq!(response.status, HttpResponseStatus::Ok);
+        assert_eq!(response.body, b"42 a b Some(2) ann 30".to_vec());
+
+        let response: Response = handler
+            .handle(request(
+                b"/users/42?q=x",
+                "application/json; charset=utf-8",
+                user,
+            ))
+            .await;
+        assert_eq!(response.body, b"42 x None ann 30".to_vec());
+
+        let status = |response: Response| response.status;
+        assert_eq!(
+            status(
+                handler
+                    .handle(request(b"/users/42?page=2", "application/json", user))
+                    .await
+            ),
+            HttpResponseStatus::BadRequest
+        );
+        assert_eq!(
+            status(
+                handler
+                    .handle(request(b"/users/42?q=x&page=two", "application/json", user))
+                    .await
+            ),
+            HttpResponseStatus::BadRequest
+        );
+        assert_eq!(
+            status(
+                handler
+                    .handle(request(b"/users/42?q=x", "text/plain", user))
+                    .await
+            ),
+            HttpResponseStatus::UnsupportedMediaType
+        );
+        assert_eq!(
+            status(
+                handler
+                    .handle(request(b"/users/42?q=x", "application/json", b"{\"name\""))
+                    .await
+            ),
+            HttpResponseStatus::BadRequest
+        );
+        let rejected: Response = handler
+            .handle(request(
+                b"/users/42?q=x",
+                "application/json",
+                br#"{"name":"ann","age":300}"#,
+            ))
+            .await;
+        assert_eq!(rejected.status, HttpResponseStatus::UnprocessableContent);
+        let problem: serde_json::Value = serde_json::from_slice(&rejected.body).unwrap();
+        assert_eq!(problem["status"], 422);
+        assert_eq!(problem["instance"], "/users/42");
+
+        let headers = extract(
+            |Headers(headers): Headers, Path((id,)): Path<(String,)>| async move {
+                let content_type: Vec<u8> =
+                    headers.get("content-type").unwrap_or_default().to_vec();
+                Response::new(HttpResponseStatus::Ok)
+                    .with_body([id.into_bytes(), content_type].concat())
+            },
+        );
+        let response: Response = headers.handle(request(b"/", "text/csv", b"")).await;
+        assert_eq!(response.body, b"42text/csv".to_vec());
+
+        let missing = extract(|Path(id): Path<u32>| async move {
+            Response::new(HttpResponseStatus::Ok).with_body(id.to_string().into_bytes())
+        });
+        let mut no_params: Request = request(b"/", "text/plain", b"");
+        no_params.params.clear();
+        assert_eq!(
+            missing.handle(no_params).await.status,
+            HttpResponseStatus::BadRequest
+        );
+    }
+}
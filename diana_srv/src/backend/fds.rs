@@ -0,0 +1,98 @@
+use std::fs;
+use std::io;
+use std::time::Duration;
+
+/* Descriptors kept for the listener, the log, the cache and the storage */
+pub const FD_RESERVE: usize = 32;
+/* Bounds of the backoff between the failed accepts */
+const MIN_ACCEPT_BACKOFF: Duration = Duration::from_millis(5);
+const MAX_ACCEPT_BACKOFF: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FdUsage {
+    /*
+     *  Attributes:
+     *      open: Descriptors opened by the process.
+     *      limit: The soft limit of the open descriptors.
+     */
+    pub open: usize,
+    pub limit: usize,
+}
+
+impl FdUsage {
+    pub fn headroom(&self) -> usize {
+        self.limit.saturating_sub(self.open)
+    }
+}
+
+pub fn fd_usage() -> Option<FdUsage> {
+    /*
+     *  Read the usage of the descriptors from procfs.
+     *
+     *  Returns:
+     *      The usage, None if procfs isn't available or the limit is
+     *      unlimited.
+     */
+    let open: usize = fs::read_dir("/proc/self/fd").ok()?.count();
+    let limits: String = fs::read_to_string("/proc/self/limits").ok()?;
+    let limit: usize = parse_open_files_limit(&limits)?;
+    Some(FdUsage { open, limit })
+}
+
+fn parse_open_files_limit(limits: &str) -> Option<usize> {
+    /*
+     *  Max open files            1024                 4096                 files
+     */
+    let line: &str = limits
+        .lines()
+        .find(|line| line.starts_with("Max open files"))?;
+    line["Max open files".len()..]
+        .split_whitespace()
+        .next()?
+        .parse()
+        .ok()
+}
+
+pub fn is_fd_exhaustion(e: &io::Error) -> bool {
+    /*
+     *  Check if the error is EMFILE or ENFILE, the process or the system
+     *  ran out of the descriptors.
+     */
+    matches!(e.raw_os_error(), Some(23) | Some(24))
+}
+
+pub fn next_accept_backoff(current: Option<Duration>) -> Duration {
+    /*
+     *  Returns:
+     *      Time to wait before the next accept, doubled after every failure.
+     */
+    match current {
+        Some(backoff) => (backoff * 2).min(MAX_ACCEPT_BACKOFF),
+        None => MIN_ACCEPT_BACKOFF,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fd_limits_test() {
+        let limits: &str = "Limit                     Soft Limit           Hard Limit           Units\n\
+                            Max open files            1024                 4096                 files\n";
+        assert_eq!(parse_open_files_limit(limits), Some(1024));
+        assert_eq!(
+            parse_open_files_limit("Max open files unlimited unlimited files"),
+            None
+        );
+        assert!(is_fd_exhaustion(&io::Error::from_raw_os_error(24)));
+        assert!(!is_fd_exhaustion(&io::Error::from_raw_os_error(104)));
+
+        let mut backoff: Duration = next_accept_backoff(None);
+        assert_eq!(backoff, MIN_ACCEPT_BACKOFF);
+        for _ in 0..20 {
+            backoff = next_accept_backoff(Some(backoff));
+        }
+        assert_eq!(backoff, MAX_ACCEPT_BACKOFF);
+    }
+}
@@ -0,0 +1,220 @@
+use diana_http::validation::header_lines;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::net::IpAddr;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Cidr {
+    /*
+     *  Block of the addresses, e.g. 10.0.0.0/8 or fd00::/8. A bare address
+     *  is the block of its own.
+     *
+     *  Attributes:
+     *      addr: The first address of the block.
+     *      prefix_len: Number of the leading bits, that must match.
+     */
+    addr: IpAddr,
+    prefix_len: u8,
+}
+
+impl Cidr {
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        let ip: IpAddr = ip.to_canonical();
+        match (self.addr, ip) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => prefix_matches(
+                u32::from(net).into(),
+                u32::from(ip).into(),
+                self.prefix_len,
+                32,
+            ),
+            (IpAddr::V6(net), IpAddr::V6(ip)) => {
+                prefix_matches(u128::from(net), u128::from(ip), self.prefix_len, 128)
+            }
+            _ => false,
+        }
+    }
+}
+
+impl FromStr for Cidr {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let (addr, prefix_len): (&str, Option<&str>) = match value.split_once('/') {
+            Some((addr, prefix_len)) => (addr, Some(prefix_len)),
+            None => (value, None),
+        };
+        let addr: IpAddr = addr
+            .parse()
+            .map_err(|_| format!("{value} is not an address block"))?;
+        let max_len: u8 = if addr.is_ipv4() { 32 } else { 128 };
+        let prefix_len: u8 = match prefix_len {
+            Some(prefix_len) => prefix_len
+                .parse()
+                .ok()
+                .filter(|prefix_len| *prefix_len <= max_len)
+                .ok_or(format!("{value} has invalid prefix length"))?,
+            None => max_len,
+        };
+        Ok(Cidr { addr, prefix_len })
+    }
+}
+
+impl TryFrom<String> for Cidr {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<Cidr> for String {
+    fn from(cidr: Cidr) -> Self {
+        cidr.to_string()
+    }
+}
+
+impl fmt::Display for Cidr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.addr, self.prefix_len)
+    }
+}
+
+fn prefix_matches(net: u128, ip: u128, prefix_len: u8, bits: u32) -> bool {
+    let shift: u32 = bits - u32::from(prefix_len);
+    shift >= bits || net >> shift == ip >> shift
+}
+
+pub fn is_trusted(ip: IpAddr, trusted_proxies: &[Cidr]) -> bool {
+    trusted_proxies.iter().any(|cidr| cidr.contains(ip))
+}
+
+pub fn client_ip(buffer: &[u8], peer: IpAddr, trusted_proxies: &[Cidr]) -> IpAddr {
+    /*
+     *  Find the address of the client, that sent the request through
+     *  the trusted proxies. The chain of the forwarding headers is walked
+     *  from the nearest hop, the first untrusted address is the client.
+     *  Forwarded takes precedence over X-Forwarded-For.
+     *
+     *  Arguments:
+     *      buffer: Bytes of the request.
+     *      peer: Address of the connected peer.
+     *      trusted_proxies: Blocks of the proxies, which headers are believed.
+     *
+     *  Returns:
+     *      The effective client address, the peer if it isn't trusted.
+     */
+    if !is_trusted(peer, trusted_proxies) {
+        return peer;
+    }
+    let mut forwarded: Vec<&[u8]> = Vec::new();
+    let mut x_forwarded_for: Vec<&[u8]> = Vec::new();
+    for line in header_lines(buffer) {
+        let Some(colon_idx) = line.iter().position(|byte| *byte == b':') else {
+            continue;
+        };
+        let name: &[u8] = &line[..colon_idx];
+        let value: &[u8] = &line[colon_idx + 1..];
+        if name.eq_ignore_ascii_case(b"forwarded") {
+            for element in value.split(|byte| *byte == b',') {
+                /* Only the for= parameter tells the client */
+                if let Some(node) = element.split(|byte| *byte == b';').find_map(|pair| {
+                    let pair: &[u8] = pair.trim_ascii();
+                    (pair.len() > 4 && pair[..4].eq_ignore_ascii_case(b"for=")).then(|| &pair[4..])
+                }) {
+                    forwarded.push(node);
+                }
+            }
+        } else if name.eq_ignore_ascii_case(b"x-forwarded-for") {
+            x_forwarded_for.extend(value.split(|byte| *byte == b','));
+        }
+    }
+    let chain: Vec<&[u8]> = if forwarded.is_empty() {
+        x_forwarded_for
+    } else {
+        forwarded
+    };
+
+    let mut client: IpAddr = peer;
+    for node in chain.into_iter().rev() {
+        /* Obfuscated or unknown nodes can't be followed any further */
+        let Some(ip) = parse_node(node) else {
+            break;
+        };
+        client = ip;
+        if !is_trusted(ip, trusted_proxies) {
+            break;
+        }
+    }
+    client
+}
+
+fn parse_node(node: &[u8]) -> Option<IpAddr> {
+    /*
+     *  Parse the node of the chain, e.g. 192.0.2.60, "192.0.2.60:4711"
+     *  or "[2001:db8::1]:4711"
+     */
+    let node: &str = std::str::from_utf8(node.trim_ascii()).ok()?;
+    let node: &str = node.trim_matches('"');
+    if let Some(rest) = node.strip_prefix('[') {
+        return rest.split(']').next()?.parse().ok();
+    }
+    if let Ok(ip) = node.parse() {
+        return Some(ip);
+    }
+    node.rsplit_once(':')?
+        .0
+        .parse::<std::net::Ipv4Addr>()
+        .ok()
+        .map(IpAddr::V4)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cidr_test() {
+        let cidr: Cidr = "10.0.0.0/8".parse().unwrap();
+        assert!(cidr.contains("10.1.2.3".parse().unwrap()));
+        assert!(cidr.contains("::ffff:10.1.2.3".parse().unwrap()));
+        assert!(!cidr.contains("11.0.0.1".parse().unwrap()));
+        assert!(
+            "fd00::/8"
+                .parse::<Cidr>()
+                .unwrap()
+                .contains("fd12::1".parse().unwrap())
+        );
+        assert!(
+            "0.0.0.0/0"
+                .parse::<Cidr>()
+                .unwrap()
+                .contains("1.2.3.4".parse().unwrap())
+        );
+        assert!("10.0.0.0/33".parse::<Cidr>().is_err());
+    }
+
+    #[test]
+    fn client_ip_test() {
+        let trusted: Vec<Cidr> = vec!["10.0.0.0/8".parse().unwrap()];
+        let proxy: IpAddr = "10.0.0.1".parse().unwrap();
+        let request: &[u8] =
+            b"GET / HTTP/1.1\r\nX-Forwarded-For: 1.1.1.1, 2.2.2.2, 10.0.0.5\r\n\r\n";
+        /* The spoofed 1.1.1.1 is behind the first untrusted hop */
+        assert_eq!(
+            client_ip(request, proxy, &trusted),
+            "2.2.2.2".parse::<IpAddr>().unwrap()
+        );
+        let untrusted: IpAddr = "3.3.3.3".parse().unwrap();
+        assert_eq!(client_ip(request, untrusted, &trusted), untrusted);
+
+        let request: &[u8] = b"GET / HTTP/1.1\r\nForwarded: for=\"[2001:db8::1]:4711\";proto=http\r\nX-Forwarded-For: 4.4.4.4\r\n\r\n";
+        assert_eq!(
+            client_ip(request, proxy, &trusted),
+            "2001:db8::1".parse::<IpAddr>().unwrap()
+        );
+        let request: &[u8] = b"GET / HTTP/1.1\r\nForwarded: for=unknown\r\n\r\n";
+        assert_eq!(client_ip(request, proxy, &trusted), proxy);
+    }
+}
@@ -0,0 +1,212 @@
+use crate::backend::http::{Request, Response};
+use std::fmt;
+use std::sync::Arc;
+
+pub trait PostProcess: Send + Sync {
+    /*
+     *  Hook, that modifies the final response before it is written,
+     *  e.g. rewrites the HTML or injects a banner or an analytics snippet.
+     */
+    fn process(&self, response: &mut Response);
+}
+
+impl<F> PostProcess for F
+where
+    F: Fn(&mut Response) + Send + Sync,
+{
+    fn process(&self, response: &mut Response) {
+        self(response)
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct PostProcessors {
+    /*
+     *  Hooks of the responses, run in the order they were registered.
+     *
+     *  Attributes:
+     *      hooks: The hooks with the media types of the responses, that
+     *      they are applied to, e.g. text/html. Either part of the type
+     *      may be the wildcard.
+     */
+    hooks: Vec<(String, Arc<dyn PostProcess>)>,
+}
+
+impl PostProcessors {
+    pub fn add(&mut self, content_type: &str, hook: impl PostProcess + 'static) {
+        self.hooks
+            .push((content_type.trim().to_ascii_lowercase(), Arc::new(hook)));
+    }
+
+    pub fn applies_to(&self, content_type: Option<&str>) -> bool {
+        /*
+         *  Check, that any hook would process the response, so the response
+         *  isn't built for nothing.
+         */
+        content_type.is_some_and(|content_type| {
+            self.hooks
+                .iter()
+                .any(|(pattern, _)| media_type_matches(pattern, content_type))
+        })
+    }
+
+    pub fn apply(&self, response: &mut Response) {
+        /*
+         *  Run the hooks matching the Content-Type of the response. The hook
+         *  may change the type, the next hooks see the changed one. Streamed
+         *  bodies aren't buffered for the hooks, they are left alone.
+         */
+        if response.streamed_body.is_some() {
+            return;
+        }
+        for (pattern, hook) in &self.hooks {
+            if content_type(&response.headers)
+                .is_some_and(|content_type| media_type_matches(pattern, content_type))
+            {
+                hook.process(response);
+            }
+        }
+    }
+}
+
+impl fmt::Debug for PostProcessors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let patterns: Vec<&str> = self
+            .hooks
+            .iter()
+            .map(|(pattern, _)| pattern.as_str())
+            .collect();
+        f.debug_struct("PostProcessors")
+            .field("hooks", &patterns)
+            .finish()
+    }
+}
+
+pub trait PreProcess: Send + Sync {
+    /*
+     *  Hook, that sees the request before the handler of its route, e.g.
+     *  the authentication attaching the user to the request extensions.
+     *
+     *  Returns:
+     *      The response, that answers the request instead of the handler,
+     *      e.g. 401, None to pass the request on.
+     */
+    fn process(&self, request: &mut Request) -> Option<Response>;
+}
+
+impl<F> PreProcess for F
+where
+    F: Fn(&mut Request) -> Option<Response> + Send + Sync,
+{
+    fn process(&self, request: &mut Request) -> Option<Response> {
+        self(request)
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct PreProcessors {
+    /*
+     *  Hooks of the requests, run in the order they were registered.
+     */
+    hooks: Vec<Arc<dyn PreProcess>>,
+}
+
+impl PreProcessors {
+    pub fn add(&mut self, hook: impl PreProcess + 'static) {
+        self.hooks.push(Arc::new(hook));
+    }
+
+    pub fn apply(&self, request: &mut Request) -> Option<Response> {
+        /*
+         *  Run the hooks, until one of them answers the request.
+         */
+        self.hooks.iter().find_map(|hook| hook.process(request))
+    }
+}
+
+impl fmt::Debug for PreProcessors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PreProcessors")
+            .field("hooks", &self.hooks.len())
+            .finish()
+    }
+}
+
+pub fn content_type(headers: &[(String, String)]) -> Option<&str> {
+    /*
+     *  Returns:
+     *      Value of the Content-Type header, the last one wins as it does
+     *      when the headers are written.
+     */
+    headers
+        .iter()
+        .rev()
+        .find(|(name, _)| name.eq_ignore_ascii_case("content-type"))
+        .map(|(_, value)| value.as_str())
+}
+
+fn media_type_matches(pattern: &str, content_type: &str) -> bool {
+    /*
+     *  Match the media type without its parameters, e.g. text/html matches
+     *  text/html; charset=utf-8, and the wildcard subtype matches any text.
+     */
+    let media_type: &str = content_type.split(';').next().unwrap_or_default().trim();
+    let Some((main, sub)) = media_type.split_once('/') else {
+        return false;
+    };
+    let Some((pattern_main, pattern_sub)) = pattern.split_once('/') else {
+        return false;
+    };
+    (pattern_main == "*" || pattern_main.eq_ignore_ascii_case(main))
+        && (pattern_sub == "*" || pattern_sub.eq_ignore_ascii_case(sub))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::server::HttpResponseStatus;
+
+    #[test]
+    fn post_processors_test() {
+        let mut hooks: PostProcessors = PostProcessors::default();
+        hooks.add("text/html", |response: &mut Response| {
+            let banner: &[u8] = b"<div>Maintenance tonight</div>";
+            if let Some(idx) = response.body.windows(6).position(|tag| tag == b"<body>") {
+                response
+                    .body
+                    .splice(idx + 6..idx + 6, banner.iter().copied());
+            }
+        });
+        hooks.add("TEXT/*", |response: &mut Response| {
+            response
+                .headers
+                .push((String::from("X-Processed"), String::from("1")));
+        });
+        assert!(hooks.applies_to(Some("text/html; charset=utf-8")));
+        assert!(hooks.applies_to(Some("text/css")));
+        assert!(!hooks.applies_to(Some("application/json")));
+        assert!(!hooks.applies_to(None));
+
+        let mut page: Response = Response::new(HttpResponseStatus::Ok)
+            .with_header("Content-Type", "text/html; charset=utf-8")
+            .with_body(b"<html><body><p>diana</p></body></html>".to_vec());
+        hooks.apply(&mut page);
+        assert_eq!(
+            page.body,
+            b"<html><body><div>Maintenance tonight</div><p>diana</p></body></html>"
+        );
+        assert_eq!(
+            page.headers.last(),
+            Some(&(String::from("X-Processed"), String::from("1")))
+        );
+
+        /* Only the matching types are processed */
+        let mut json: Response = Response::json(&vec![1, 2]);
+        let body: Vec<u8> = json.body.clone();
+        hooks.apply(&mut json);
+        assert_eq!(json.body, body);
+        assert!(
+            content_type(&json.headers).is_some_and(|value| value.starts_with("application/json"))
+        );
+    }
+}
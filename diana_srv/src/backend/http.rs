@@ -0,0 +1,703 @@
+use crate::backend::disconnect::Disconnect;
+use crate::backend::extensions::Extensions;
+use crate::backend::server::{HttpResponseStatus, RequestType, format_head, format_response};
+use bytes::Bytes;
+use diana_http::headers::HeaderMap;
+use futures_core::Stream;
+use serde::{Serialize, Serializer};
+use std::error::Error;
+use std::fmt;
+use std::future::poll_fn;
+use std::io;
+use std::mem;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::time::Instant;
+
+/* Size of the chunks read from the streamed bodies */
+const STREAM_CHUNK_SIZE: usize = 8192;
+/* Query parameter, that asks for the indented JSON in the debug builds */
+const PRETTY_PARAM: &str = "pretty";
+
+pub struct RequestBody {
+    /*
+     *  Body of the request, that is read from the client while the handler
+     *  consumes it, e.g. for the uploads.
+     *
+     *  Attributes:
+     *      received: Bytes of the body, that were read with the headers.
+     *      rest: Reader of the bytes, that are still on the way.
+     *      remaining: Bytes, that the reader still owes to Content-Length.
+     *      limit: The maximum size of the buffered body.
+     */
+    received: Vec<u8>,
+    rest: Option<Pin<Box<dyn AsyncRead + Send>>>,
+    remaining: usize,
+    limit: usize,
+}
+
+impl RequestBody {
+    pub fn buffered(body: Vec<u8>) -> Self {
+        /*
+         *  Body, that was already read as a whole.
+         */
+        RequestBody {
+            limit: body.len(),
+            received: body,
+            rest: None,
+            remaining: 0,
+        }
+    }
+
+    pub fn streamed(
+        received: Vec<u8>,
+        rest: impl AsyncRead + Send + 'static,
+        remaining: usize,
+        limit: usize,
+    ) -> Self {
+        /*
+         *  Arguments:
+         *      received: Bytes of the body, that were read with the headers.
+         *      rest: Reader of the remaining bytes, it must end with the body.
+         *      remaining: Number of the bytes, that the reader must yield.
+         *      limit: The maximum size of the body, when it is buffered.
+         */
+        RequestBody {
+            received,
+            rest: Some(Box::pin(rest)),
+            remaining,
+            limit,
+        }
+    }
+
+    pub async fn chunk(&mut self) -> Result<Option<Bytes>, io::Error> {
+        /*
+         *  Returns:
+         *      The next chunk of the body, None once the body ends.
+         *      UnexpectedEof if the client stops short of Content-Length.
+         */
+        if !self.received.is_empty() {
+            return Ok(Some(Bytes::from(mem::take(&mut self.received))));
+        }
+        let Some(rest) = self.rest.as_mut() else {
+            return Ok(None);
+        };
+        let mut chunk: Vec<u8> = vec![0; STREAM_CHUNK_SIZE];
+        let sz: usize = rest.read(&mut chunk).await?;
+        if sz == 0 {
+            self.rest = None;
+            if self.remaining > 0 {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    format!("Request body ended {} bytes short", self.remaining),
+                ));
+            }
+            return Ok(None);
+        }
+        self.remaining = self.remaining.saturating_sub(sz);
+        chunk.truncate(sz);
+        Ok(Some(Bytes::from(chunk)))
+    }
+
+    pub async fn to_bytes(&mut self) -> Result<Vec<u8>, io::Error> {
+        /*
+         *  Read the rest of the body into the memory.
+         *
+         *  Returns:
+         *      The whole body, error if it exceeds the limit or it is
+         *      truncated, see body_error_status.
+         */
+        let mut body: Vec<u8> = Vec::new();
+        while let Some(chunk) = self.chunk().await? {
+            if body.len() + chunk.len() > self.limit {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Request body exceeds {} bytes", self.limit),
+                ));
+            }
+            body.extend_from_slice(&chunk);
+        }
+        Ok(body)
+    }
+}
+
+pub fn body_error_status(e: &io::Error) -> HttpResponseStatus {
+    /*
+     *  Returns:
+     *      400 for the truncated body, 413 for the one over the limit.
+     */
+    match e.kind() {
+        io::ErrorKind::UnexpectedEof => HttpResponseStatus::BadRequest,
+        _ => HttpResponseStatus::PayloadTooLarge,
+    }
+}
+
+impl fmt::Debug for RequestBody {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RequestBody")
+            .field("received", &self.received.len())
+            .field("streamed", &self.rest.is_some())
+            .field("remaining", &self.remaining)
+            .field("limit", &self.limit)
+            .finish()
+    }
+}
+
+#[derive(Debug)]
+pub struct Request {
+    /*
+     *  Parsed request, that is passed to the handlers.
+     *
+     *  Attributes:
+     *      method: HTTP method of the request.
+     *      path: Resource path without the query, e.g. /api/data
+     *      query: Query of the request target without the question mark,
+     *      e.g. page=2 of /api/data?page=2
+     *      headers: Headers of the request, looked up case-insensitively.
+     *      peer: Address of the client.
+     *      params: Names and decoded values of the :name segments of
+     *      the matched route, e.g. id=7 for /users/:id
+     *      body: The decoded request body, see body() and body_stream()
+     *      deadline: Instant, when the handler is cancelled.
+     *      disconnect: Signal of the client, that closed the connection.
+     *      extensions: Typed values attached by the hooks, e.g. the user.
+     */
+    pub method: RequestType,
+    pub path: Vec<u8>,
+    pub query: Option<Vec<u8>>,
+    pub headers: HeaderMap,
+    pub peer: SocketAddr,
+    pub params: Vec<(String, String)>,
+    body: RequestBody,
+    deadline: Instant,
+    disconnect: Disconnect,
+    extensions: Extensions,
+}
+
+impl Request {
+    pub fn new(
+        method: RequestType,
+        path: Vec<u8>,
+        buffer: &[u8],
+        body: RequestBody,
+        peer: SocketAddr,
+        deadline: Instant,
+    ) -> Self {
+        /*
+         *  Constructor of the request.
+         *
+         *  Arguments:
+         *      method: HTTP method of the request.
+         *      path: Resource path of the request.
+         *      buffer: Bytes of the request, the headers are parsed from it.
+         *      body: The decoded request body.
+         *      peer: Address of the client.
+         *      deadline: Instant, when the handler is cancelled.
+         */
+        Request {
+            method,
+            path,
+            query: None,
+            headers: HeaderMap::parse(buffer),
+            body,
+            peer,
+            params: Vec::new(),
+            deadline,
+            disconnect: Disconnect::default(),
+            extensions: Extensions::default(),
+        }
+    }
+
+    pub fn with_query(mut self, query: Option<Vec<u8>>) -> Self {
+        self.query = query;
+        self
+    }
+
+    pub fn with_params(mut self, params: Vec<(String, String)>) -> Self {
+        self.params = params;
+        self
+    }
+
+    pub fn with_disconnect(mut self, disconnect: Disconnect) -> Self {
+        self.disconnect = disconnect;
+        self
+    }
+
+    pub fn param(&self, name: &str) -> Option<&str> {
+        /*
+         *  Returns:
+         *      Value of the route segment with the name, e.g. param("id")
+         *      for /users/:id
+         */
+        self.params
+            .iter()
+            .find(|(param, _)| param == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub async fn body(&mut self) -> Result<Vec<u8>, io::Error> {
+        /*
+         *  Buffer the whole body, it can be called repeatedly.
+         *
+         *  Returns:
+         *      The body, error if it exceeds the limit, or UnexpectedEof if
+         *      the client closes the connection before all of the body was
+         *      sent.
+         */
+        let body: Vec<u8> = self.body.to_bytes().await?;
+        self.body = RequestBody::buffered(body.clone());
+        Ok(body)
+    }
+
+    pub fn body_stream(&mut self) -> &mut RequestBody {
+        /*
+         *  Accessor. Consume the body chunk by chunk, without buffering it.
+         */
+        &mut self.body
+    }
+
+    pub fn deadline(&self) -> Instant {
+        /*
+         *  Accessor. Handlers should bound their downstream calls with it,
+         *  the handler is cancelled once it passes.
+         */
+        self.deadline
+    }
+
+    pub fn disconnected(&self) -> &Disconnect {
+        /*
+         *  Accessor. The handler is cancelled, when the client closes
+         *  the connection, the work it spawned should stop on the signal.
+         */
+        &self.disconnect
+    }
+
+    pub fn extensions(&self) -> &Extensions {
+        /*
+         *  Accessor. Values, that the hooks attached to the request, e.g.
+         *  req.extensions().get::<User>()
+         */
+        &self.extensions
+    }
+
+    pub fn extensions_mut(&mut self) -> &mut Extensions {
+        &mut self.extensions
+    }
+
+    pub fn remaining(&self) -> Duration {
+        /*
+         *  Returns:
+         *      Time left until the deadline, zero if it already passed.
+         */
+        self.deadline.saturating_duration_since(Instant::now())
+    }
+
+    pub fn header(&self, name: &str) -> Option<&[u8]> {
+        /*
+         *  Returns:
+         *      Value of the first header with the name, compared
+         *      case-insensitively.
+         */
+        self.headers.get(name)
+    }
+
+    pub fn query(&self, name: &str) -> Option<&[u8]> {
+        /*
+         *  Returns:
+         *      Value of the first query parameter with the name, empty if
+         *      the parameter has no value, e.g. /api/data?pretty
+         */
+        self.query
+            .as_deref()?
+            .split(|byte| *byte == b'&')
+            .find_map(|pair| {
+                let (key, value) = match pair.iter().position(|byte| *byte == b'=') {
+                    Some(eq_idx) => (&pair[..eq_idx], &pair[eq_idx + 1..]),
+                    None => (pair, &pair[pair.len()..]),
+                };
+                (key == name.as_bytes()).then_some(value)
+            })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Problem {
+    /*
+     *  Error of the API in the problem details format (RFC 9457), sent as
+     *  application/problem+json.
+     *
+     *  Attributes:
+     *      problem_type: URI of the problem type, about:blank if it has
+     *      no more meaning than the status.
+     *      title: Summary of the problem type.
+     *      status: The status code of the response.
+     *      detail: Explanation of this occurrence of the problem.
+     *      instance: URI of this occurrence, e.g. the requested path.
+     */
+    #[serde(rename = "type")]
+    pub problem_type: String,
+    pub title: String,
+    #[serde(serialize_with = "serialize_status")]
+    pub status: HttpResponseStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub instance: Option<String>,
+}
+
+impl Problem {
+    pub fn new(status: HttpResponseStatus) -> Self {
+        Problem {
+            problem_type: String::from("about:blank"),
+            title: String::from(status.reason()),
+            status,
+            detail: None,
+            instance: None,
+        }
+    }
+
+    pub fn from_error(status: HttpResponseStatus, error: &dyn Error) -> Self {
+        /*
+         *  Describe the error, that made the handler fail.
+         */
+        Self::new(status).with_detail(&error.to_string())
+    }
+
+    pub fn with_detail(mut self, detail: &str) -> Self {
+        self.detail = Some(String::from(detail));
+        self
+    }
+
+    pub fn with_instance(mut self, instance: &str) -> Self {
+        self.instance = Some(String::from(instance));
+        self
+    }
+}
+
+pub enum StreamedBody {
+    /*
+     *  Body, that is produced while it is sent. It is written with
+     *  the chunked transfer coding, so its length doesn't need to be known.
+     */
+    Reader(Pin<Box<dyn AsyncRead + Send>>),
+    Stream(Pin<Box<dyn Stream<Item = Bytes> + Send>>),
+}
+
+impl StreamedBody {
+    async fn next_chunk(&mut self) -> Result<Option<Bytes>, io::Error> {
+        /*
+         *  Returns:
+         *      The next chunk of the body, None once the body ends.
+         */
+        match self {
+            Self::Reader(reader) => {
+                let mut chunk: Vec<u8> = vec![0; STREAM_CHUNK_SIZE];
+                let sz: usize = reader.read(&mut chunk).await?;
+                chunk.truncate(sz);
+                Ok((sz > 0).then(|| Bytes::from(chunk)))
+            }
+            Self::Stream(stream) => Ok(poll_fn(|cx| stream.as_mut().poll_next(cx)).await),
+        }
+    }
+}
+
+impl fmt::Debug for StreamedBody {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Reader(_) => write!(f, "StreamedBody::Reader"),
+            Self::Stream(_) => write!(f, "StreamedBody::Stream"),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Response {
+    /*
+     *  Response returned by the handlers.
+     *
+     *  Attributes:
+     *      status: Status of the response.
+     *      headers: Headers sent after the default ones.
+     *      body: The response body.
+     *      streamed_body: Body streamed to the client, it replaces the body.
+     */
+    pub status: HttpResponseStatus,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+    pub streamed_body: Option<StreamedBody>,
+}
+
+impl Response {
+    pub fn new(status: HttpResponseStatus) -> Self {
+        Response {
+            status,
+            headers: Vec::new(),
+            body: Vec::new(),
+            streamed_body: None,
+        }
+    }
+
+    pub fn json<T: Serialize + ?Sized>(value: &T) -> Self {
+        /*
+         *  Serialize the value as the body with 200.
+         *
+         *  Returns:
+         *      The response, 500 with the problem if the value can't be
+         *      serialized, e.g. a map with the non-string keys.
+         */
+        Self::encode_json(HttpResponseStatus::Ok, value, false)
+    }
+
+    pub fn json_for<T: Serialize + ?Sized>(req: &Request, value: &T) -> Self {
+        /*
+         *  Serialize the value like json(), the debug builds indent it
+         *  if the request has the pretty query parameter.
+         */
+        let pretty: bool = cfg!(debug_assertions) && req.query(PRETTY_PARAM).is_some();
+        Self::encode_json(HttpResponseStatus::Ok, value, pretty)
+    }
+
+    pub fn with_status(mut self, status: HttpResponseStatus) -> Self {
+        self.status = status;
+        self
+    }
+
+    fn encode_json<T: Serialize + ?Sized>(
+        status: HttpResponseStatus,
+        value: &T,
+        pretty: bool,
+    ) -> Self {
+        let encoded: Result<Vec<u8>, serde_json::Error> = if pretty {
+            serde_json::to_vec_pretty(value)
+        } else {
+            serde_json::to_vec(value)
+        };
+        match encoded {
+            Ok(body) => Response::new(status)
+                .with_header("Content-Type", "application/json")
+                .with_body(body),
+            Err(e) => {
+                println!("[ERROR] Failed to serialize the response: {e}");
+                Problem::from_error(HttpResponseStatus::InternalServerError, &e).into()
+            }
+        }
+    }
+
+    pub fn with_header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((String::from(name), String::from(value)));
+        self
+    }
+
+    pub fn with_body(mut self, body: Vec<u8>) -> Self {
+        self.body = body;
+        self
+    }
+
+    pub fn with_reader(mut self, reader: impl AsyncRead + Send + 'static) -> Self {
+        /*
+         *  Stream the body from the reader, e.g. a file or a pipe.
+         */
+        self.streamed_body = Some(StreamedBody::Reader(Box::pin(reader)));
+        self
+    }
+
+    pub fn with_stream(mut self, stream: impl Stream<Item = Bytes> + Send + 'static) -> Self {
+        /*
+         *  Stream the body from the chunks, that the stream yields.
+         */
+        self.streamed_body = Some(StreamedBody::Stream(Box::pin(stream)));
+        self
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        /*
+         *  Returns:
+         *      The formatted response, ready to be written to the stream.
+         *      The streamed body isn't included.
+         */
+        format_response(self.status, &self.headers, &self.body)
+    }
+
+    pub async fn write_to<W: AsyncWrite + Unpin>(self, writer: &mut W) -> Result<(), io::Error> {
+        /*
+         *  Write the response, the streamed body is sent in chunks as soon
+         *  as they are produced.
+         *
+         *  Arguments:
+         *      writer: Stream of the client.
+         */
+        let Some(mut streamed_body) = self.streamed_body else {
+            return writer.write_all(&self.to_bytes()).await;
+        };
+        let mut headers: Vec<(String, String)> = self.headers;
+        headers.push((String::from("Transfer-Encoding"), String::from("chunked")));
+        writer
+            .write_all(&format_head(self.status, &headers, None))
+            .await?;
+        while let Some(chunk) = streamed_body.next_chunk().await? {
+            if chunk.is_empty() {
+                continue;
+            }
+            let mut framed: Vec<u8> = format!("{:x}\r\n", chunk.len()).into_bytes();
+            framed.extend_from_slice(&chunk);
+            framed.extend_from_slice(b"\r\n");
+            writer.write_all(&framed).await?;
+            writer.flush().await?;
+        }
+        writer.write_all(b"0\r\n\r\n").await
+    }
+}
+
+fn serialize_status<S: Serializer>(
+    status: &HttpResponseStatus,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.serialize_u64(status.value() as u64)
+}
+
+impl From<Problem> for Response {
+    fn from(problem: Problem) -> Self {
+        /* The problem has only strings and a number, it always serializes */
+        Response::new(problem.status)
+            .with_header("Content-Type", "application/problem+json")
+            .with_body(serde_json::to_vec(&problem).unwrap_or_default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::VecDeque;
+    use std::task::{Context, Poll};
+
+    struct ChunkStream {
+        chunks: VecDeque<Bytes>,
+    }
+
+    impl Stream for ChunkStream {
+        type Item = Bytes;
+
+        fn poll_next(mut self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Option<Bytes>> {
+            Poll::Ready(self.chunks.pop_front())
+        }
+    }
+
+    fn split_head(written: &[u8]) -> (String, Vec<u8>) {
+        let head_end: usize = written.windows(4).position(|w| w == b"\r\n\r\n").unwrap() + 4;
+        (
+            String::from_utf8(written[..head_end].to_vec()).unwrap(),
+            written[head_end..].to_vec(),
+        )
+    }
+
+    #[tokio::test]
+    async fn request_body_test() {
+        let mut body = RequestBody::streamed(b"he".to_vec(), &b"llo"[..], 3, 5);
+        assert_eq!(body.chunk().await.unwrap(), Some(Bytes::from("he")));
+        assert_eq!(body.chunk().await.unwrap(), Some(Bytes::from("llo")));
+        assert_eq!(body.chunk().await.unwrap(), None);
+
+        let mut request = Request::new(
+            RequestType::Post,
+            b"/upload".to_vec(),
+            b"POST /upload HTTP/1.1\r\n\r\n",
+            RequestBody::streamed(b"he".to_vec(), &b"llo"[..], 3, 5),
+            "127.0.0.1:4000".parse().unwrap(),
+            Instant::now(),
+        );
+        assert_eq!(request.body().await.unwrap(), b"hello".to_vec());
+        assert_eq!(request.body().await.unwrap(), b"hello".to_vec());
+
+        let mut too_big = RequestBody::streamed(b"he".to_vec(), &b"llo"[..], 3, 4);
+        let e: io::Error = too_big.to_bytes().await.unwrap_err();
+        assert_eq!(body_error_status(&e), HttpResponseStatus::PayloadTooLarge);
+
+        /* Content-Length: 5, but the client sent hel and closed */
+        let mut truncated = RequestBody::streamed(b"he".to_vec(), &b"l"[..], 3, 5);
+        let e: io::Error = truncated.to_bytes().await.unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::UnexpectedEof);
+        assert_eq!(body_error_status(&e), HttpResponseStatus::BadRequest);
+    }
+
+    #[tokio::test]
+    async fn streamed_response_test() {
+        let stream = ChunkStream {
+            chunks: VecDeque::from(vec![
+                Bytes::from("hello "),
+                Bytes::new(),
+                Bytes::from("world"),
+            ]),
+        };
+        let mut written: Vec<u8> = Vec::new();
+        Response::new(HttpResponseStatus::Ok)
+            .with_stream(stream)
+            .write_to(&mut written)
+            .await
+            .unwrap();
+        let (head, body) = split_head(&written);
+        assert!(head.contains("Transfer-Encoding: chunked\r\n"));
+        assert!(!head.contains("Content-Length"));
+        assert_eq!(body, b"6\r\nhello \r\n5\r\nworld\r\n0\r\n\r\n".to_vec());
+
+        let mut written: Vec<u8> = Vec::new();
+        Response::new(HttpResponseStatus::Ok)
+            .with_reader(&b"from a reader"[..])
+            .write_to(&mut written)
+            .await
+            .unwrap();
+        let (_, body) = split_head(&written);
+        assert_eq!(body, b"d\r\nfrom a reader\r\n0\r\n\r\n".to_vec());
+    }
+
+    #[test]
+    fn json_response_test() {
+        #[derive(Serialize)]
+        struct Item {
+            id: u32,
+            name: &'static str,
+        }
+        let response: Response = Response::json(&Item {
+            id: 7,
+            name: "diana",
+        });
+        assert_eq!(response.status, HttpResponseStatus::Ok);
+        assert_eq!(response.body, br#"{"id":7,"name":"diana"}"#.to_vec());
+        assert!(response.headers.contains(&(
+            String::from("Content-Type"),
+            String::from("application/json")
+        )));
+
+        let req: Request = Request::new(
+            RequestType::Get,
+            b"/api/items".to_vec(),
+            b"GET /api/items?page=2&pretty HTTP/1.1\r\n\r\n",
+            RequestBody::buffered(Vec::new()),
+            "127.0.0.1:4000".parse().unwrap(),
+            Instant::now() + Duration::from_secs(5),
+        )
+        .with_query(Some(b"page=2&pretty".to_vec()));
+        assert_eq!(req.query("page"), Some(&b"2"[..]));
+        assert_eq!(req.query("pretty"), Some(&b""[..]));
+        assert_eq!(req.query("missing"), None);
+        let pretty: Response = Response::json_for(
+            &req,
+            &Item {
+                id: 7,
+                name: "diana",
+            },
+        );
+        assert_eq!(pretty.body.contains(&b'\n'), cfg!(debug_assertions));
+
+        let error = io::Error::new(io::ErrorKind::NotFound, "no item 8");
+        let problem: Response = Problem::from_error(HttpResponseStatus::NotFound, &error)
+            .with_instance("/api/items/8")
+            .into();
+        assert_eq!(problem.status, HttpResponseStatus::NotFound);
+        assert_eq!(
+            problem.body,
+            br#"{"type":"about:blank","title":"Not Found","status":404,"detail":"no item 8","instance":"/api/items/8"}"#.to_vec()
+        );
+    }
+}
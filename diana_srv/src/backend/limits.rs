@@ -0,0 +1,365 @@
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::fmt;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tokio::time::timeout;
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ConcurrencyLimit {
+    /*
+     *  Entry of the [[concurrency_limits]] table in the config.
+     *
+     *  Attributes:
+     *      prefix: Route prefix, that the limit applies to, e.g. /upload
+     *      max_concurrent: The maximum number of requests handled at once.
+     */
+    pub prefix: String,
+    pub max_concurrent: usize,
+}
+
+#[derive(Debug)]
+pub struct ConcurrencyPermit {
+    /*
+     *  Proof, that the request fits in the limits. The slots are given back
+     *  when the permit is dropped.
+     */
+    _global: Option<OwnedSemaphorePermit>,
+    _route: Option<OwnedSemaphorePermit>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Shed {
+    /*
+     *  Reasons to refuse the request over the limits.
+     */
+    Saturated,
+    Expired,
+}
+
+impl fmt::Display for Shed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Saturated => write!(f, "Concurrency limit reached"),
+            Self::Expired => write!(f, "Request waited too long in the queue"),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct RequestQueue {
+    /*
+     *  Requests waiting for a free slot, in the order they came.
+     *
+     *  Attributes:
+     *      depth: The maximum number of the waiting requests.
+     *      max_wait: The longest wait for the slot.
+     *      waiting: The number of the requests waiting right now.
+     */
+    depth: usize,
+    max_wait: Duration,
+    waiting: AtomicUsize,
+}
+
+struct QueuePlace<'a> {
+    /*
+     *  Place of the request in the queue, left once it is dropped.
+     */
+    waiting: &'a AtomicUsize,
+}
+
+impl Drop for QueuePlace<'_> {
+    fn drop(&mut self) {
+        self.waiting.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+impl RequestQueue {
+    fn enter(&self) -> Option<QueuePlace<'_>> {
+        /*
+         *  Returns:
+         *      The place in the queue, None if the queue is full.
+         */
+        self.waiting
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |waiting| {
+                (waiting < self.depth).then_some(waiting + 1)
+            })
+            .ok()?;
+        Some(QueuePlace {
+            waiting: &self.waiting,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ConcurrencyLimiter {
+    /*
+     *  Tracks the requests in flight, globally and per route prefix.
+     *
+     *  Attributes:
+     *      global: Semaphore shared by all the requests, if the global
+     *      limit is configured.
+     *      routes: Semaphores of the route prefixes, the longest prefix
+     *      comes first.
+     *      reserved: Slots of the global limit, that only the priority routes
+     *      can take, so they respond even if the bulk traffic is shed.
+     *      priority_prefixes: Route prefixes allowed to take the reserved slots.
+     *      queue: Requests waiting for a slot, none wait if it is missing.
+     */
+    global: Option<Arc<Semaphore>>,
+    routes: Vec<(Vec<u8>, Arc<Semaphore>)>,
+    reserved: Option<Arc<Semaphore>>,
+    priority_prefixes: Vec<Vec<u8>>,
+    queue: Option<Arc<RequestQueue>>,
+}
+
+impl ConcurrencyLimiter {
+    pub fn new(global_max: Option<usize>, limits: &[ConcurrencyLimit]) -> Self {
+        /*
+         *  Constructor of the limiter.
+         *
+         *  Arguments:
+         *      global_max: Limit of all the requests, None means unlimited.
+         *      limits: Limits of the route prefixes.
+         */
+        let mut routes: Vec<(Vec<u8>, Arc<Semaphore>)> = limits
+            .iter()
+            .map(|limit| {
+                (
+                    Vec::from(limit.prefix.as_bytes()),
+                    Arc::new(Semaphore::new(limit.max_concurrent)),
+                )
+            })
+            .collect();
+        routes.sort_by_key(|route| Reverse(route.0.len()));
+        ConcurrencyLimiter {
+            global: global_max.map(|max| Arc::new(Semaphore::new(max))),
+            routes,
+            reserved: None,
+            priority_prefixes: Vec::new(),
+            queue: None,
+        }
+    }
+
+    pub fn with_queue(mut self, depth: usize, max_wait: Duration) -> Self {
+        /*
+         *  Let the requests over the limits wait for a slot, instead of
+         *  refusing them at once.
+         *
+         *  Arguments:
+         *      depth: The maximum number of the waiting requests, none wait
+         *      if it is 0.
+         *      max_wait: The longest wait for the slot.
+         */
+        self.queue = (depth > 0).then(|| {
+            Arc::new(RequestQueue {
+                depth,
+                max_wait,
+                waiting: AtomicUsize::new(0),
+            })
+        });
+        self
+    }
+
+    pub fn queued(&self) -> usize {
+        self.queue
+            .as_ref()
+            .map_or(0, |queue| queue.waiting.load(Ordering::Relaxed))
+    }
+
+    pub fn with_priority(mut self, reserved_slots: usize, priority_routes: &[String]) -> Self {
+        /*
+         *  Carve the reserved slots out of the global limit. The other
+         *  requests get only the rest of the global limit.
+         *
+         *  Arguments:
+         *      reserved_slots: Number of the slots kept for the priority routes.
+         *      priority_routes: Route prefixes, e.g. /health
+         */
+        let Some(global) = &self.global else {
+            return self;
+        };
+        let reserved_slots: usize = global.forget_permits(reserved_slots);
+        if reserved_slots > 0 {
+            self.reserved = Some(Arc::new(Semaphore::new(reserved_slots)));
+        }
+        self.priority_prefixes = priority_routes
+            .iter()
+            .map(|route| Vec::from(route.as_bytes()))
+            .collect();
+        self
+    }
+
+    pub fn is_priority(&self, resource_path: &[u8]) -> bool {
+        self.priority_prefixes
+            .iter()
+            .any(|prefix| matches_prefix(resource_path, prefix))
+    }
+
+    fn route_semaphore(&self, resource_path: &[u8]) -> Option<&Arc<Semaphore>> {
+        self.routes
+            .iter()
+            .find(|(prefix, _)| matches_prefix(resource_path, prefix))
+            .map(|(_, semaphore)| semaphore)
+    }
+
+    pub fn try_acquire(&self, resource_path: &[u8]) -> Option<ConcurrencyPermit> {
+        /*
+         *  Take a slot of the global limit and of the route's limit.
+         *
+         *  Arguments:
+         *      resource_path: Resource path from the request.
+         *
+         *  Returns:
+         *      The permit, or None if any of the limits is saturated.
+         */
+        let route: Option<&Arc<Semaphore>> = self.route_semaphore(resource_path);
+
+        let route_permit: Option<OwnedSemaphorePermit> = match route {
+            Some(semaphore) => Some(Arc::clone(semaphore).try_acquire_owned().ok()?),
+            None => None,
+        };
+        let global_permit: Option<OwnedSemaphorePermit> = match &self.global {
+            Some(semaphore) => match Arc::clone(semaphore).try_acquire_owned() {
+                Ok(permit) => Some(permit),
+                /* The bulk traffic is shed first, the priority routes fall back to the reserve */
+                Err(_) => match &self.reserved {
+                    Some(reserved) if self.is_priority(resource_path) => {
+                        Some(Arc::clone(reserved).try_acquire_owned().ok()?)
+                    }
+                    _ => return None,
+                },
+            },
+            None => None,
+        };
+        Some(ConcurrencyPermit {
+            _global: global_permit,
+            _route: route_permit,
+        })
+    }
+
+    pub async fn wait(&self, resource_path: &[u8]) -> Result<ConcurrencyPermit, Shed> {
+        /*
+         *  Wait in the queue for the slots, that try_acquire didn't get.
+         *  The slots are handed out in the order the requests came.
+         *
+         *  Arguments:
+         *      resource_path: Resource path from the request.
+         *
+         *  Returns:
+         *      The permit, Saturated if the queue is full or missing, Expired
+         *      if the slot wasn't free in time.
+         */
+        let queue: &RequestQueue = self.queue.as_deref().ok_or(Shed::Saturated)?;
+        let _place: QueuePlace = queue.enter().ok_or(Shed::Saturated)?;
+        let acquire = async {
+            let route_permit: Option<OwnedSemaphorePermit> =
+                match self.route_semaphore(resource_path) {
+                    Some(semaphore) => Some(Arc::clone(semaphore).acquire_owned().await.ok()?),
+                    None => None,
+                };
+            let global_permit: Option<OwnedSemaphorePermit> = match &self.global {
+                Some(semaphore) => Some(Arc::clone(semaphore).acquire_owned().await.ok()?),
+                None => None,
+            };
+            Some(ConcurrencyPermit {
+                _global: global_permit,
+                _route: route_permit,
+            })
+        };
+        match timeout(queue.max_wait, acquire).await {
+            Ok(Some(permit)) => Ok(permit),
+            Ok(None) => Err(Shed::Saturated),
+            Err(_) => Err(Shed::Expired),
+        }
+    }
+}
+
+pub fn matches_prefix(resource_path: &[u8], prefix: &[u8]) -> bool {
+    /*
+     *  Check if the path lies under the prefix. The prefix has to end on
+     *  the segment boundary, so /upload doesn't cover /uploads.
+     */
+    if !resource_path.starts_with(prefix) {
+        return false;
+    }
+    resource_path.len() == prefix.len()
+        || prefix.ends_with(b"/")
+        || resource_path[prefix.len()] == b'/'
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn concurrency_limiter_test() {
+        let limits: Vec<ConcurrencyLimit> = vec![
+            ConcurrencyLimit {
+                prefix: String::from("/upload"),
+                max_concurrent: 1,
+            },
+            ConcurrencyLimit {
+                prefix: String::from("/upload/big"),
+                max_concurrent: 2,
+            },
+        ];
+        let limiter = ConcurrencyLimiter::new(Some(3), &limits);
+
+        let upload = limiter.try_acquire(b"/upload/file").unwrap();
+        assert!(limiter.try_acquire(b"/upload").is_none());
+        /* The longest prefix wins and /uploads isn't covered by /upload */
+        let _big = limiter.try_acquire(b"/upload/big/file").unwrap();
+        let _other = limiter.try_acquire(b"/uploads").unwrap();
+        /* The global limit is saturated now */
+        assert!(limiter.try_acquire(b"/index.html").is_none());
+
+        drop(upload);
+        assert!(limiter.try_acquire(b"/upload").is_some());
+    }
+
+    #[test]
+    fn priority_reservation_test() {
+        let limiter = ConcurrencyLimiter::new(Some(3), &[])
+            .with_priority(1, &[String::from("/health"), String::from("/admin")]);
+
+        let _first = limiter.try_acquire(b"/index.html").unwrap();
+        let _second = limiter.try_acquire(b"/health").unwrap();
+        /* Only the reserved slot is left */
+        assert!(limiter.try_acquire(b"/index.html").is_none());
+        let health = limiter.try_acquire(b"/health").unwrap();
+        assert!(limiter.try_acquire(b"/admin/cache/flush").is_none());
+
+        drop(health);
+        assert!(limiter.try_acquire(b"/admin/cache/flush").is_some());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn request_queue_test() {
+        let limiter = ConcurrencyLimiter::new(Some(1), &[]).with_queue(1, Duration::from_secs(1));
+        let busy: ConcurrencyPermit = limiter.try_acquire(b"/").unwrap();
+
+        /* The first request waits, the second one doesn't fit into the queue */
+        let waiting = tokio::spawn({
+            let limiter: ConcurrencyLimiter = limiter.clone();
+            async move { limiter.wait(b"/").await.map(drop) }
+        });
+        tokio::task::yield_now().await;
+        assert_eq!(limiter.queued(), 1);
+        assert_eq!(limiter.wait(b"/").await.err(), Some(Shed::Saturated));
+
+        drop(busy);
+        assert_eq!(waiting.await.unwrap(), Ok(()));
+        assert_eq!(limiter.queued(), 0);
+
+        /* The slot isn't freed in time */
+        let _busy: ConcurrencyPermit = limiter.try_acquire(b"/").unwrap();
+        assert_eq!(limiter.wait(b"/").await.err(), Some(Shed::Expired));
+
+        let unqueued = ConcurrencyLimiter::new(Some(1), &[]).with_queue(0, Duration::from_secs(1));
+        let _busy: ConcurrencyPermit = unqueued.try_acquire(b"/").unwrap();
+        assert_eq!(unqueued.wait(b"/").await.err(), Some(Shed::Saturated));
+    }
+}
@@ -0,0 +1,189 @@
+use serde::{Deserialize, Serialize};
+use std::future::poll_fn;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::task::Poll;
+use tokio::net::{TcpListener, TcpSocket, TcpStream};
+
+/* Connections waiting to be accepted, the same as the default of std */
+const LISTEN_BACKLOG: u32 = 1024;
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ListenerConfig {
+    /*
+     *  Entry of the [[listeners]] table in the config, the listener bound
+     *  next to the one of ip and port, e.g. the port of the load balancer.
+     *
+     *  Attributes:
+     *      ip: Address to listen on, IPv6 literals might be in the brackets.
+     *      port: Port to listen on.
+     *      proxy_protocol: Expect the PROXY protocol header (v1 or v2) on every
+     *      connection accepted by this listener, the address in it replaces
+     *      the peer's one. Enable it only for the port of the load balancer,
+     *      that always sends it.
+     */
+    pub ip: String,
+    pub port: u16,
+    #[serde(default)]
+    pub proxy_protocol: bool,
+}
+
+pub fn parse_listen_ip(ip: &str) -> Result<IpAddr, io::Error> {
+    /*
+     *  Parse the ip from the config, IPv6 literals might be in the brackets,
+     *  e.g. [::1]
+     *
+     *  Returns:
+     *      The address, or the error if it isn't the IP literal.
+     */
+    let literal: &str = ip
+        .trim()
+        .strip_prefix('[')
+        .and_then(|inner| inner.strip_suffix(']'))
+        .unwrap_or(ip.trim());
+    literal.parse().map_err(|_| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{ip} is not an IPv4 or IPv6 address"),
+        )
+    })
+}
+
+pub fn listen_addrs(ip: IpAddr, port: u16, dual_stack: bool) -> Result<Vec<SocketAddr>, io::Error> {
+    /*
+     *  Collect the addresses to bind. The dual stack binds the counterpart
+     *  of the ip in the other family too, so only the wildcard and
+     *  the loopback have one.
+     *
+     *  Returns:
+     *      The addresses, the configured one first.
+     */
+    let mut addrs: Vec<SocketAddr> = vec![SocketAddr::new(ip, port)];
+    if dual_stack {
+        let counterpart: IpAddr = match ip {
+            IpAddr::V4(v4) if v4.is_unspecified() => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+            IpAddr::V4(v4) if v4.is_loopback() => IpAddr::V6(Ipv6Addr::LOCALHOST),
+            IpAddr::V6(v6) if v6.is_unspecified() => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            IpAddr::V6(v6) if v6.is_loopback() => IpAddr::V4(Ipv4Addr::LOCALHOST),
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("dual_stack needs the wildcard or the loopback ip, not {ip}"),
+                ));
+            }
+        };
+        addrs.push(SocketAddr::new(counterpart, port));
+    }
+    Ok(addrs)
+}
+
+pub fn bind(addr: SocketAddr) -> Result<TcpListener, io::Error> {
+    /*
+     *  Bind the listener. IPv6 sockets accept only IPv6, so they don't
+     *  take the port of the IPv4 listener, even if the system maps IPv4
+     *  into IPv6 by default.
+     */
+    let socket: TcpSocket = match addr {
+        SocketAddr::V4(_) => TcpSocket::new_v4()?,
+        SocketAddr::V6(_) => {
+            let socket: TcpSocket = TcpSocket::new_v6()?;
+            #[cfg(unix)]
+            set_only_v6(&socket)?;
+            socket
+        }
+    };
+    #[cfg(unix)]
+    socket.set_reuseaddr(true)?;
+    socket.bind(addr)?;
+    socket.listen(LISTEN_BACKLOG)
+}
+
+#[cfg(unix)]
+fn set_only_v6(socket: &TcpSocket) -> Result<(), io::Error> {
+    use std::os::fd::AsRawFd;
+
+    let enabled: libc::c_int = 1;
+    let ret: libc::c_int = unsafe {
+        libc::setsockopt(
+            socket.as_raw_fd(),
+            libc::IPPROTO_IPV6,
+            libc::IPV6_V6ONLY,
+            &enabled as *const libc::c_int as *const libc::c_void,
+            size_of::<libc::c_int>() as libc::socklen_t,
+        )
+    };
+    if ret != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+pub async fn accept_any(
+    listeners: &[TcpListener],
+) -> Result<(usize, TcpStream, SocketAddr), io::Error> {
+    /*
+     *  Accept the connection from whichever listener has one first.
+     *
+     *  Returns:
+     *      Index of the listener, that accepted it, with the connection.
+     */
+    poll_fn(|cx| {
+        for (idx, listener) in listeners.iter().enumerate() {
+            if let Poll::Ready(accepted) = listener.poll_accept(cx) {
+                return Poll::Ready(accepted.map(|(stream, addr)| (idx, stream, addr)));
+            }
+        }
+        Poll::Pending
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn listen_addrs_test() {
+        assert_eq!(
+            parse_listen_ip("[::1]").unwrap(),
+            IpAddr::V6(Ipv6Addr::LOCALHOST)
+        );
+        assert_eq!(
+            parse_listen_ip(" 0.0.0.0 ").unwrap(),
+            IpAddr::V4(Ipv4Addr::UNSPECIFIED)
+        );
+        assert!(parse_listen_ip("localhost").is_err());
+        assert!(parse_listen_ip("[127.0.0.1").is_err());
+
+        let addrs: Vec<SocketAddr> =
+            listen_addrs(IpAddr::V6(Ipv6Addr::UNSPECIFIED), 8080, true).unwrap();
+        assert_eq!(addrs[0].to_string(), "[::]:8080");
+        assert_eq!(addrs[1].to_string(), "0.0.0.0:8080");
+        assert_eq!(
+            listen_addrs(IpAddr::V6(Ipv6Addr::LOCALHOST), 80, false)
+                .unwrap()
+                .len(),
+            1
+        );
+        assert!(listen_addrs("192.0.2.1".parse().unwrap(), 80, true).is_err());
+    }
+
+    #[tokio::test]
+    async fn dual_stack_bind_test() {
+        /* Both families share the port, the IPv6 loopback might be missing */
+        let v4: TcpListener = bind("127.0.0.1:0".parse().unwrap()).unwrap();
+        let port: u16 = v4.local_addr().unwrap().port();
+        let Ok(v6) = bind(SocketAddr::new(IpAddr::V6(Ipv6Addr::LOCALHOST), port)) else {
+            return;
+        };
+        let listeners: [TcpListener; 2] = [v4, v6];
+        let client = tokio::spawn(TcpStream::connect(SocketAddr::new(
+            IpAddr::V6(Ipv6Addr::LOCALHOST),
+            port,
+        )));
+        let (idx, _, peer) = accept_any(&listeners).await.unwrap();
+        assert_eq!(idx, 1);
+        assert!(peer.is_ipv6());
+        client.await.unwrap().unwrap();
+    }
+}
@@ -0,0 +1,305 @@
+use crate::backend::http::{Problem, Request, Response};
+use crate::backend::overrides::{Directive, OVERRIDE_FILE, Overrides};
+use crate::backend::precompress::is_encoded_sibling;
+use crate::backend::router::Handler;
+use crate::backend::server::HttpResponseStatus;
+use async_trait::async_trait;
+use serde::Serialize;
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::UNIX_EPOCH;
+use tokio::fs;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ListingEntry {
+    /*
+     *  Attributes:
+     *      name: File name of the entry.
+     *      size: Bytes of the file, 0 for the directories.
+     *      mtime: Seconds since the epoch, when the entry was modified.
+     *      entry_type: file or dir.
+     */
+    pub name: String,
+    pub size: u64,
+    pub mtime: u64,
+    #[serde(rename = "type")]
+    pub entry_type: &'static str,
+}
+
+#[derive(Debug, Serialize)]
+struct Listing {
+    path: String,
+    entries: Vec<ListingEntry>,
+}
+
+#[derive(Debug, Clone)]
+pub struct FileListing {
+    /*
+     *  Handler of GET <file_listing_path>?path=/docs, that lists
+     *  the directory under the resource directory as JSON.
+     *
+     *  Attributes:
+     *      root: The resource directory, nothing outside of it is listed.
+     *      overrides: Overrides of the resource directory, their auth
+     *      guards the listing of the directories, like it guards the sites.
+     */
+    root: PathBuf,
+    overrides: Arc<RwLock<Overrides>>,
+}
+
+impl FileListing {
+    pub fn new(root: &Path, overrides: Arc<RwLock<Overrides>>) -> Self {
+        FileListing {
+            root: root.to_path_buf(),
+            overrides,
+        }
+    }
+
+    pub async fn list(&self, path: &str) -> Option<Vec<ListingEntry>> {
+        /*
+         *  List the directory, the override files are hidden, the same as
+         *  when they are requested.
+         *
+         *  Arguments:
+         *      path: Path of the directory relative to the root, e.g. /docs
+         *
+         *  Returns:
+         *      The entries sorted by the name, None if the path isn't
+         *      a directory under the root.
+         */
+        let dir: PathBuf = resolve_under(&self.root, path)?;
+        let root: PathBuf = fs::canonicalize(&self.root).await.ok()?;
+        /* Symlinks must not lead out of the root either */
+        if !fs::canonicalize(&dir).await.ok()?.starts_with(&root) {
+            return None;
+        }
+        let mut read_dir = fs::read_dir(&dir).await.ok()?;
+        let mut entries: Vec<ListingEntry> = Vec::new();
+        while let Ok(Some(entry)) = read_dir.next_entry().await {
+            let Ok(name) = entry.file_name().into_string() else {
+                continue;
+            };
+            if name == OVERRIDE_FILE || is_encoded_sibling(&entry.path()) {
+                continue;
+            }
+            let Ok(metadata) = entry.metadata().await else {
+                continue;
+            };
+            let mtime: u64 = metadata
+                .modified()
+                .ok()
+                .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+                .map(|elapsed| elapsed.as_secs())
+                .unwrap_or_default();
+            entries.push(ListingEntry {
+                name,
+                size: if metadata.is_dir() { 0 } else { metadata.len() },
+                mtime,
+                entry_type: if metadata.is_dir() { "dir" } else { "file" },
+            });
+        }
+        entries.sort_by(|a, b| a.name.cmp(&b.name));
+        Some(entries)
+    }
+}
+
+#[async_trait]
+impl Handler for FileListing {
+    async fn handle(&self, req: Request) -> Response {
+        let path: String = match req.query("path").map(percent_decode) {
+            Some(Some(path)) => path,
+            Some(None) => {
+                return Problem::new(HttpResponseStatus::BadRequest)
+                    .with_detail("The path is not valid UTF-8")
+                    .into();
+            }
+            None => String::from("/"),
+        };
+        /* The directory is refused the same as its sites would be */
+        let directive: Directive = match directory_path(&path) {
+            Some(dir_path) => self
+                .overrides
+                .read()
+                .unwrap()
+                .resolve(dir_path.as_bytes(), req.header("authorization")),
+            None => Directive::Hidden,
+        };
+        let headers: Vec<(String, String)> = match directive {
+            Directive::Serve(headers) => headers,
+            Directive::Redirect(status, location) => {
+                return Response::new(status).with_header("Location", &location);
+            }
+            Directive::Unauthorized(realm) => {
+                return Response::new(HttpResponseStatus::Unauthorized)
+                    .with_header("WWW-Authenticate", &format!("Basic realm=\"{realm}\""));
+            }
+            Directive::Hidden => Vec::new(),
+        };
+        match self.list(&path).await {
+            Some(entries) => {
+                let mut response: Response = Response::json_for(&req, &Listing { path, entries });
+                response.headers.extend(headers);
+                response
+            }
+            None => Problem::new(HttpResponseStatus::NotFound)
+                .with_detail("No such directory")
+                .with_instance(&path)
+                .into(),
+        }
+    }
+}
+
+fn directory_path(path: &str) -> Option<String> {
+    /*
+     *  Normalize the listed path to the resource path of the directory,
+     *  which the overrides are matched against, e.g. docs/./private -> /docs/private/
+     *
+     *  Returns:
+     *      The path with both slashes, None if it would leave the root.
+     */
+    let mut dir_path: String = String::from("/");
+    for component in Path::new(path.trim_start_matches('/')).components() {
+        match component {
+            Component::Normal(name) => {
+                dir_path.push_str(name.to_str()?);
+                dir_path.push('/');
+            }
+            Component::CurDir => {}
+            Component::ParentDir | Component::RootDir | Component::Prefix(_) => return None,
+        }
+    }
+    Some(dir_path)
+}
+
+pub fn resolve_under(root: &Path, path: &str) -> Option<PathBuf> {
+    /*
+     *  Join the path to the root, it may only descend, so .. and
+     *  the absolute paths of the other drives are refused.
+     *
+     *  Returns:
+     *      The joined path, None if it would leave the root.
+     */
+    let mut resolved: PathBuf = root.to_path_buf();
+    for component in Path::new(path.trim_start_matches('/')).components() {
+        match component {
+            Component::Normal(name) => resolved.push(name),
+            Component::CurDir => {}
+            Component::ParentDir | Component::RootDir | Component::Prefix(_) => return None,
+        }
+    }
+    Some(resolved)
+}
+
+pub fn percent_decode(value: &[u8]) -> Option<String> {
+    /*
+     *  Decode the query value, e.g. %2Fdocs+old -> /docs old
+     */
+    let mut decoded: Vec<u8> = Vec::with_capacity(value.len());
+    let mut idx: usize = 0;
+    while idx < value.len() {
+        match value[idx] {
+            b'%' => {
+                let hex: &str = std::str::from_utf8(value.get(idx + 1..idx + 3)?).ok()?;
+                decoded.push(u8::from_str_radix(hex, 16).ok()?);
+                idx += 3;
+            }
+            b'+' => {
+                decoded.push(b' ');
+                idx += 1;
+            }
+            byte => {
+                decoded.push(byte);
+                idx += 1;
+            }
+        }
+    }
+    String::from_utf8(decoded).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::http::RequestBody;
+    use crate::backend::server::RequestType;
+    use std::env;
+    use std::time::Duration;
+    use tokio::time::Instant;
+
+    #[tokio::test]
+    async fn file_listing_test() {
+        let root: PathBuf = env::temp_dir().join(format!("diana_listing_{}", std::process::id()));
+        std::fs::create_dir_all(root.join("docs/sub")).unwrap();
+        std::fs::write(root.join("docs/a b.txt"), b"hello").unwrap();
+        std::fs::write(root.join("docs").join(OVERRIDE_FILE), b"").unwrap();
+        let listing: FileListing = FileListing::new(&root, Arc::default());
+
+        let entries: Vec<ListingEntry> = listing.list("/docs").await.unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!((entries[0].name.as_str(), entries[0].size), ("a b.txt", 5));
+        assert_eq!(
+            (entries[1].name.as_str(), entries[1].entry_type),
+            ("sub", "dir")
+        );
+        assert!(listing.list("/docs/../..").await.is_none());
+        assert!(listing.list("/docs/a b.txt").await.is_none());
+        assert!(listing.list("/missing").await.is_none());
+        assert_eq!(
+            percent_decode(b"%2Fdocs%2Fa+b.txt").as_deref(),
+            Some("/docs/a b.txt")
+        );
+        assert!(percent_decode(b"%zz").is_none());
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn file_listing_auth_test() {
+        let root: PathBuf =
+            env::temp_dir().join(format!("diana_listing_auth_{}", std::process::id()));
+        std::fs::create_dir_all(root.join("docs/private")).unwrap();
+        std::fs::write(root.join("docs/private/plan.txt"), b"secret").unwrap();
+        std::fs::write(
+            root.join("docs/private").join(OVERRIDE_FILE),
+            "[auth]\nrealm = \"Staff\"\nusers = { admin = \"secret\" }\n",
+        )
+        .unwrap();
+        let listing: FileListing =
+            FileListing::new(&root, Arc::new(RwLock::new(Overrides::load(&root))));
+        let request = |path: &str, head: &[u8]| {
+            Request::new(
+                RequestType::Get,
+                b"/api/files".to_vec(),
+                head,
+                RequestBody::buffered(Vec::new()),
+                "127.0.0.1:4000".parse().unwrap(),
+                Instant::now() + Duration::from_secs(5),
+            )
+            .with_query(Some(format!("path={path}").into_bytes()))
+        };
+
+        let refused: Response = listing
+            .handle(request("/docs/./private", b"GET / HTTP/1.1\r\n\r\n"))
+            .await;
+        assert_eq!(refused.status, HttpResponseStatus::Unauthorized);
+        assert!(refused.headers.contains(&(
+            String::from("WWW-Authenticate"),
+            String::from("Basic realm=\"Staff\"")
+        )));
+        assert!(refused.body.is_empty());
+
+        let listed: Response = listing
+            .handle(request(
+                "docs/private",
+                b"GET / HTTP/1.1\r\nAuthorization: Basic YWRtaW46c2VjcmV0\r\n\r\n",
+            ))
+            .await;
+        assert_eq!(listed.status, HttpResponseStatus::Ok);
+        assert!(listed.body.windows(8).any(|name| name == b"plan.txt"));
+
+        /* The parent isn't guarded */
+        let parent: Response = listing
+            .handle(request("/docs", b"GET / HTTP/1.1\r\n\r\n"))
+            .await;
+        assert_eq!(parent.status, HttpResponseStatus::Ok);
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,380 @@
+use crate::backend::status::RecentRequest;
+use flate2::{Compression, write::GzEncoder};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const SECS_PER_DAY: u64 = 86400;
+const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct LoggingConfig {
+    /*
+     *  The [logging] table of the config.
+     *
+     *  Attributes:
+     *      access_log: File of the answered requests in the Common Log Format.
+     *      error_log: File, that the output of the server is redirected to,
+     *      i.e. the [INFO], [WARNING] and [ERROR] lines. Unix only.
+     *      max_size_bytes: Rotate the log, once it grows over the size.
+     *      daily: Rotate the log, once the UTC day changes.
+     *      keep: Archives kept next to the log, e.g. access.log.1, the older
+     *      ones are removed.
+     *      gzip: Compress the archives, e.g. access.log.1.gz
+     *      check_interval_secs: How often the logs are checked for
+     *      the rotation.
+     */
+    pub access_log: Option<String>,
+    pub error_log: Option<String>,
+    pub max_size_bytes: Option<u64>,
+    pub daily: bool,
+    pub keep: usize,
+    pub gzip: bool,
+    pub check_interval_secs: u64,
+}
+
+impl Default for LoggingConfig {
+    fn default() -> Self {
+        LoggingConfig {
+            access_log: None,
+            error_log: None,
+            max_size_bytes: None,
+            daily: false,
+            keep: 7,
+            gzip: false,
+            check_interval_secs: 60,
+        }
+    }
+}
+
+impl LoggingConfig {
+    pub fn rotates(&self) -> bool {
+        self.max_size_bytes.is_some() || self.daily
+    }
+}
+
+#[derive(Debug)]
+struct OpenLog {
+    file: File,
+    day: u64,
+}
+
+#[derive(Debug)]
+pub struct RotatingLog {
+    /*
+     *  Log file, that is moved to the archives once it is due.
+     *
+     *  Attributes:
+     *      path: Path of the current log.
+     *      redirect_output: The log receives the output of the process.
+     *      open: The opened file with the day, when it was opened.
+     */
+    path: PathBuf,
+    redirect_output: bool,
+    open: Mutex<OpenLog>,
+}
+
+impl RotatingLog {
+    pub fn open(path: &Path, redirect_output: bool) -> Result<Self, io::Error> {
+        /*
+         *  Open the log for appending, its directory is created if missing.
+         *
+         *  Arguments:
+         *      path: Path of the log.
+         *      redirect_output: Point the stdout and the stderr of the process
+         *      to the log.
+         */
+        if let Some(parent) = path
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+        {
+            fs::create_dir_all(parent)?;
+        }
+        let log: RotatingLog = RotatingLog {
+            path: path.to_path_buf(),
+            redirect_output,
+            open: Mutex::new(open_log(path, redirect_output)?),
+        };
+        Ok(log)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn write_line(&self, line: &str) {
+        let mut open = self.open.lock().unwrap();
+        if let Err(e) = writeln!(open.file, "{line}") {
+            println!("[ERROR] Failed to write {}: {e}", self.path.display());
+        }
+    }
+
+    pub fn reopen(&self) -> Result<(), io::Error> {
+        /*
+         *  Open the path again, e.g. after logrotate moved the log away.
+         */
+        let reopened: OpenLog = open_log(&self.path, self.redirect_output)?;
+        *self.open.lock().unwrap() = reopened;
+        Ok(())
+    }
+
+    pub fn rotate_if_due(&self, cfg: &LoggingConfig) -> Result<bool, io::Error> {
+        /*
+         *  Move the log to the archives, if it is too large or it was
+         *  opened on the previous day.
+         *
+         *  Returns:
+         *      True if the log was rotated.
+         */
+        let size: u64 = fs::metadata(&self.path).map(|meta| meta.len())?;
+        let day_changed: bool = cfg.daily && self.open.lock().unwrap().day != today();
+        let too_large: bool = cfg.max_size_bytes.is_some_and(|max| size >= max);
+        if size == 0 || !(day_changed || too_large) {
+            return Ok(false);
+        }
+        self.rotate(cfg)?;
+        Ok(true)
+    }
+
+    fn rotate(&self, cfg: &LoggingConfig) -> Result<(), io::Error> {
+        /*
+         *  Shift the archives by one, the oldest falls out, then the log
+         *  becomes the first archive. The writes wait for the new file.
+         */
+        let mut open = self.open.lock().unwrap();
+        for idx in (1..=cfg.keep.max(1)).rev() {
+            for archive in [self.archive(idx, false), self.archive(idx, true)] {
+                if !archive.exists() {
+                    continue;
+                }
+                if idx >= cfg.keep {
+                    fs::remove_file(&archive)?;
+                } else {
+                    let gzipped: bool = archive.extension().is_some_and(|ext| ext == "gz");
+                    fs::rename(&archive, self.archive(idx + 1, gzipped))?;
+                }
+            }
+        }
+        let first: PathBuf = self.archive(1, false);
+        if cfg.keep > 0 {
+            fs::rename(&self.path, &first)?;
+        } else {
+            fs::remove_file(&self.path)?;
+        }
+        *open = open_log(&self.path, self.redirect_output)?;
+        drop(open);
+
+        if cfg.keep > 0 && cfg.gzip {
+            gzip_file(&first, &self.archive(1, true))?;
+        }
+        Ok(())
+    }
+
+    fn archive(&self, idx: usize, gzipped: bool) -> PathBuf {
+        let mut archive = self.path.clone().into_os_string();
+        archive.push(format!(".{idx}"));
+        if gzipped {
+            archive.push(".gz");
+        }
+        PathBuf::from(archive)
+    }
+}
+
+#[derive(Debug)]
+pub struct Logs {
+    /*
+     *  The logs configured by the [logging] table.
+     */
+    pub config: LoggingConfig,
+    pub access: Option<RotatingLog>,
+    pub error: Option<RotatingLog>,
+}
+
+impl Logs {
+    pub fn open(cfg: &LoggingConfig) -> Result<Self, io::Error> {
+        let access: Option<RotatingLog> = match &cfg.access_log {
+            Some(path) => Some(RotatingLog::open(Path::new(path), false)?),
+            None => None,
+        };
+        let error: Option<RotatingLog> = match &cfg.error_log {
+            Some(path) if cfg!(unix) => Some(RotatingLog::open(Path::new(path), true)?),
+            Some(path) => {
+                println!("[WARNING] {path} is ignored, the output can't be redirected here.");
+                None
+            }
+            None => None,
+        };
+        Ok(Logs {
+            config: cfg.clone(),
+            access,
+            error,
+        })
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &RotatingLog> {
+        self.access.iter().chain(self.error.iter())
+    }
+
+    pub fn access(&self, request: &RecentRequest) {
+        if let Some(access) = &self.access {
+            access.write_line(&format_access(request));
+        }
+    }
+
+    pub fn reopen(&self) {
+        for log in self.iter() {
+            if let Err(e) = log.reopen() {
+                println!("[ERROR] Failed to reopen {}: {e}", log.path().display());
+            }
+        }
+    }
+
+    pub fn rotate_if_due(&self) {
+        for log in self.iter() {
+            match log.rotate_if_due(&self.config) {
+                Ok(true) => println!("[INFO] Rotated {}.", log.path().display()),
+                Ok(false) => {}
+                Err(e) => println!("[ERROR] Failed to rotate {}: {e}", log.path().display()),
+            }
+        }
+    }
+}
+
+fn open_log(path: &Path, redirect_output: bool) -> Result<OpenLog, io::Error> {
+    let file: File = OpenOptions::new().create(true).append(true).open(path)?;
+    #[cfg(unix)]
+    if redirect_output {
+        use std::os::fd::AsRawFd;
+
+        /* Flush what was printed to the previous file */
+        io::stdout().flush()?;
+        for target in [libc::STDOUT_FILENO, libc::STDERR_FILENO] {
+            if unsafe { libc::dup2(file.as_raw_fd(), target) } < 0 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = redirect_output;
+    Ok(OpenLog { file, day: today() })
+}
+
+fn gzip_file(source: &Path, target: &Path) -> Result<(), io::Error> {
+    let mut encoder: GzEncoder<File> =
+        GzEncoder::new(File::create(target)?, Compression::default());
+    io::copy(&mut File::open(source)?, &mut encoder)?;
+    encoder.finish()?;
+    fs::remove_file(source)
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default()
+}
+
+fn today() -> u64 {
+    now_secs() / SECS_PER_DAY
+}
+
+pub fn format_access(request: &RecentRequest) -> String {
+    /*
+     *  Format the request in the Common Log Format, the size isn't known,
+     *  e.g. 127.0.0.1 - - [10/Oct/2000:13:55:36 +0000] "GET / HTTP/1.1" 200 -
+     */
+    format!(
+        "{} - - [{}] \"{}\" {} -",
+        request.client,
+        format_clf_time(request.timestamp),
+        request.request.escape_default(),
+        request.status
+    )
+}
+
+pub fn format_clf_time(secs: u64) -> String {
+    /*
+     *  Format the UTC time, the date is computed from the days since
+     *  the epoch with the civil calendar algorithm of Howard Hinnant.
+     */
+    let days: i64 = (secs / SECS_PER_DAY) as i64 + 719468;
+    let era: i64 = days.div_euclid(146097);
+    let day_of_era: i64 = days.rem_euclid(146097);
+    let year_of_era: i64 =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year: i64 = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_idx: i64 = (5 * day_of_year + 2) / 153;
+    let day: i64 = day_of_year - (153 * month_idx + 2) / 5 + 1;
+    let month: i64 = if month_idx < 10 {
+        month_idx + 3
+    } else {
+        month_idx - 9
+    };
+    let year: i64 = year_of_era + era * 400 + i64::from(month <= 2);
+    let secs_of_day: u64 = secs % SECS_PER_DAY;
+    format!(
+        "{day:02}/{}/{year}:{:02}:{:02}:{:02} +0000",
+        MONTHS[(month - 1) as usize],
+        secs_of_day / 3600,
+        secs_of_day / 60 % 60,
+        secs_of_day % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::{IpAddr, Ipv4Addr};
+
+    #[test]
+    fn log_rotation_test() {
+        let dir: PathBuf = std::env::temp_dir().join(format!("diana_logs_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let cfg: LoggingConfig = LoggingConfig {
+            max_size_bytes: Some(10),
+            keep: 2,
+            gzip: true,
+            ..LoggingConfig::default()
+        };
+        let log: RotatingLog = RotatingLog::open(&dir.join("access.log"), false).unwrap();
+        assert!(!log.rotate_if_due(&cfg).unwrap());
+        for round in 0..3 {
+            log.write_line(&format!("request number {round}"));
+            assert!(log.rotate_if_due(&cfg).unwrap());
+        }
+        log.write_line("current");
+
+        assert_eq!(
+            fs::read_to_string(dir.join("access.log")).unwrap(),
+            "current\n"
+        );
+        assert!(dir.join("access.log.1.gz").exists());
+        assert!(dir.join("access.log.2.gz").exists());
+        assert!(!dir.join("access.log.3.gz").exists());
+        let mut newest: String = String::new();
+        io::Read::read_to_string(
+            &mut flate2::read::GzDecoder::new(File::open(dir.join("access.log.1.gz")).unwrap()),
+            &mut newest,
+        )
+        .unwrap();
+        assert_eq!(newest, "request number 2\n");
+        fs::remove_dir_all(&dir).unwrap();
+
+        let request: RecentRequest = RecentRequest {
+            timestamp: 971185536,
+            client: IpAddr::V4(Ipv4Addr::LOCALHOST),
+            request: String::from("GET /a\"b HTTP/1.1"),
+            status: 200,
+        };
+        assert_eq!(
+            format_access(&request),
+            "127.0.0.1 - - [10/Oct/2000:13:45:36 +0000] \"GET /a\\\"b HTTP/1.1\" 200 -"
+        );
+    }
+}
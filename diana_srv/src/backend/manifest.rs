@@ -0,0 +1,314 @@
+use crate::backend::precompress::is_encoded_sibling;
+use crate::utils::patterns::glob_match;
+use crate::utils::readers::files::list_files;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::sync::OnceLock;
+use std::time::UNIX_EPOCH;
+
+/* Media types of the extensions, the unknown ones are sent as bytes */
+const MIME_TYPES: [(&str, &str); 24] = [
+    ("html", "text/html; charset=utf-8"),
+    ("htm", "text/html; charset=utf-8"),
+    ("css", "text/css; charset=utf-8"),
+    ("js", "text/javascript; charset=utf-8"),
+    ("mjs", "text/javascript; charset=utf-8"),
+    ("json", "application/json"),
+    ("map", "application/json"),
+    ("txt", "text/plain; charset=utf-8"),
+    ("xml", "application/xml"),
+    ("svg", "image/svg+xml"),
+    ("png", "image/png"),
+    ("jpg", "image/jpeg"),
+    ("jpeg", "image/jpeg"),
+    ("gif", "image/gif"),
+    ("webp", "image/webp"),
+    ("avif", "image/avif"),
+    ("ico", "image/x-icon"),
+    ("woff", "font/woff"),
+    ("woff2", "font/woff2"),
+    ("ttf", "font/ttf"),
+    ("wasm", "application/wasm"),
+    ("pdf", "application/pdf"),
+    ("mp4", "video/mp4"),
+    ("webm", "video/webm"),
+];
+const DEFAULT_MIME_TYPE: &str = "application/octet-stream";
+
+/* The configured media types, they are set once when the server starts */
+static CONFIGURED_MIME_TYPES: OnceLock<MimeTypes> = OnceLock::new();
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct MimeTypes {
+    /*
+     *  The [mime] table of the config, merged with the built-in types.
+     *
+     *  Attributes:
+     *      extra: Media types of the extensions, e.g. "m3u8" =
+     *      "application/vnd.apple.mpegurl". They replace the built-in type
+     *      of the same extension.
+     *      overrides: Media types of the paths matching the globs, they win
+     *      over the extensions. The first matching override is used.
+     */
+    pub extra: HashMap<String, String>,
+    pub overrides: Vec<MimeOverride>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct MimeOverride {
+    /*
+     *  Entry of the [[mime.overrides]] table in the config.
+     *
+     *  Attributes:
+     *      path: Glob of the paths, the same as of [[response_headers]].
+     *      content_type: Media type of the matching sites.
+     */
+    pub path: String,
+    pub content_type: String,
+}
+
+impl MimeTypes {
+    fn lookup(&self, path: &[u8], extension: Option<&[u8]>) -> Option<&str> {
+        /*
+         *  Returns:
+         *      The configured media type of the path, None if the built-in
+         *      table decides.
+         */
+        if let Some(rule) = self
+            .overrides
+            .iter()
+            .find(|rule| glob_match(rule.path.as_bytes(), path))
+        {
+            return Some(&rule.content_type);
+        }
+        let extension: &[u8] = extension?;
+        self.extra
+            .iter()
+            .find(|(known, _)| {
+                known
+                    .trim_start_matches('.')
+                    .as_bytes()
+                    .eq_ignore_ascii_case(extension)
+            })
+            .map(|(_, mime)| mime.as_str())
+    }
+}
+
+pub fn set_mime_types(mime_types: MimeTypes) {
+    /*
+     *  Merge the configured media types with the built-in ones. Only
+     *  the first call takes effect, the process serves one config.
+     */
+    let _ = CONFIGURED_MIME_TYPES.set(mime_types);
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ManifestEntry {
+    /*
+     *  What is known about the site without reading it.
+     *
+     *  Attributes:
+     *      size: Size of the file in bytes.
+     *      etag: Validator of the file, made of its modification time and
+     *      its size, so it is computed without reading the content.
+     *      mime: Media type of the file, from its extension.
+     *      modified: Seconds since the epoch, when the file was modified.
+     */
+    pub size: u64,
+    pub etag: String,
+    pub mime: &'static str,
+    pub modified: u64,
+}
+
+impl ManifestEntry {
+    pub fn of(file: &Path, resource_path: &[u8]) -> Option<Self> {
+        /*
+         *  Describe the file from its metadata.
+         *
+         *  Returns:
+         *      The entry, None if the file is missing or empty, which is
+         *      how the sites are served.
+         */
+        let metadata: fs::Metadata = fs::metadata(file).ok()?;
+        if !metadata.is_file() || metadata.len() == 0 {
+            return None;
+        }
+        let modified: u64 = metadata
+            .modified()
+            .ok()
+            .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+            .map(|elapsed| elapsed.as_secs())
+            .unwrap_or_default();
+        Some(ManifestEntry {
+            size: metadata.len(),
+            etag: format!("\"{modified:x}-{:x}\"", metadata.len()),
+            mime: mime_type(resource_path),
+            modified,
+        })
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct SiteManifest {
+    /*
+     *  Sizes, validators and media types of the sites in the resource
+     *  directory, computed on startup, so the headers of GET and HEAD
+     *  don't need the file's metadata read on every request. The sites
+     *  added later are described on their first request.
+     *
+     *  Attributes:
+     *      entries: The entries under the resource paths, e.g. /index.html
+     */
+    entries: HashMap<Vec<u8>, ManifestEntry>,
+}
+
+impl SiteManifest {
+    pub fn build(html_dir: &Path) -> Self {
+        /*
+         *  Describe every file in the resource directory.
+         *
+         *  Arguments:
+         *      html_dir: The resource directory.
+         */
+        let mut manifest: SiteManifest = SiteManifest::default();
+        for file in list_files(html_dir) {
+            manifest.refresh(html_dir, &file);
+        }
+        manifest
+    }
+
+    pub fn get(&self, resource_path: &[u8]) -> Option<&ManifestEntry> {
+        self.entries.get(resource_path)
+    }
+
+    pub fn refresh(&mut self, html_dir: &Path, file: &Path) {
+        /*
+         *  Describe the changed file again, the removed one is dropped.
+         *
+         *  Arguments:
+         *      html_dir: The resource directory.
+         *      file: Path of the file in the resource directory.
+         */
+        let Some(relative) = file.strip_prefix(html_dir).ok().and_then(|p| p.to_str()) else {
+            return;
+        };
+        let resource_path: Vec<u8> = format!("/{relative}").into_bytes();
+        /* The encoded copies are served in place of their sites */
+        if is_encoded_sibling(file) {
+            self.entries.remove(&resource_path);
+            return;
+        }
+        match ManifestEntry::of(file, &resource_path) {
+            Some(entry) => self.entries.insert(resource_path, entry),
+            None => self.entries.remove(&resource_path),
+        };
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+pub fn mime_type(resource_path: &[u8]) -> &'static str {
+    /*
+     *  Returns:
+     *      Media type of the site, from the configured overrides of its path
+     *      or from the extension of its path.
+     */
+    mime_type_with(CONFIGURED_MIME_TYPES.get(), resource_path)
+}
+
+fn mime_type_with<'a>(configured: Option<&'a MimeTypes>, resource_path: &[u8]) -> &'a str {
+    let name: &[u8] = resource_path
+        .rsplit(|byte| *byte == b'/')
+        .next()
+        .unwrap_or(resource_path);
+    let extension: Option<&[u8]> = name
+        .iter()
+        .rposition(|byte| *byte == b'.')
+        .map(|dot_idx| &name[dot_idx + 1..]);
+    if let Some(mime) =
+        configured.and_then(|configured| configured.lookup(resource_path, extension))
+    {
+        return mime;
+    }
+    let Some(extension) = extension else {
+        return DEFAULT_MIME_TYPE;
+    };
+    MIME_TYPES
+        .iter()
+        .find(|(known, _)| known.as_bytes().eq_ignore_ascii_case(extension))
+        .map_or(DEFAULT_MIME_TYPE, |(_, mime)| mime)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    #[test]
+    fn site_manifest_test() {
+        assert_eq!(mime_type(b"/docs/Index.HTML"), "text/html; charset=utf-8");
+        assert_eq!(mime_type(b"/fonts/a.woff2"), "font/woff2");
+        assert_eq!(mime_type(b"/v1.2/README"), DEFAULT_MIME_TYPE);
+
+        let configured: MimeTypes = toml::from_str(
+            r#"
+            extra = { "m3u8" = "application/vnd.apple.mpegurl", ".js" = "application/javascript" }
+            [[overrides]]
+            path = "/downloads/**"
+            content_type = "application/octet-stream"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            mime_type_with(Some(&configured), b"/live/stream.M3U8"),
+            "application/vnd.apple.mpegurl"
+        );
+        assert_eq!(
+            mime_type_with(Some(&configured), b"/app.js"),
+            "application/javascript"
+        );
+        assert_eq!(
+            mime_type_with(Some(&configured), b"/downloads/notes/a.txt"),
+            "application/octet-stream"
+        );
+        /* The other types stay built-in */
+        assert_eq!(mime_type_with(Some(&configured), b"/a.png"), "image/png");
+        assert_eq!(
+            mime_type_with(Some(&configured), b"/README"),
+            DEFAULT_MIME_TYPE
+        );
+
+        let dir: PathBuf =
+            std::env::temp_dir().join(format!("diana_manifest_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("css")).unwrap();
+        fs::write(dir.join("index.html"), b"<html></html>").unwrap();
+        fs::write(dir.join("css/site.css"), b"body{}").unwrap();
+        fs::write(dir.join("empty.txt"), b"").unwrap();
+
+        let mut manifest: SiteManifest = SiteManifest::build(&dir);
+        assert_eq!(manifest.len(), 2);
+        let entry: &ManifestEntry = manifest.get(b"/css/site.css").unwrap();
+        assert_eq!(entry.size, 6);
+        assert_eq!(entry.mime, "text/css; charset=utf-8");
+        assert!(entry.etag.starts_with('"') && entry.etag.ends_with("-6\""));
+        assert!(manifest.get(b"/empty.txt").is_none());
+
+        fs::write(dir.join("index.html"), b"<html><body></body></html>").unwrap();
+        manifest.refresh(&dir, &dir.join("index.html"));
+        assert_eq!(manifest.get(b"/index.html").unwrap().size, 26);
+        fs::remove_file(dir.join("css/site.css")).unwrap();
+        manifest.refresh(&dir, &dir.join("css/site.css"));
+        assert!(manifest.get(b"/css/site.css").is_none());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
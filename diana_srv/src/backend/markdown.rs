@@ -0,0 +1,403 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/* Page, that the rendered documents are wrapped in, unless a template is configured */
+pub const DEFAULT_TEMPLATE: &str = "<!DOCTYPE html>
+<html>
+<head>
+<meta charset=\"utf-8\">
+<meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">
+<title>{{title}}</title>
+<style>
+body { max-width: 48rem; margin: 2rem auto; padding: 0 1rem; font-family: sans-serif; line-height: 1.5; }
+pre { padding: 0.75rem; overflow-x: auto; background: #f4f4f4; }
+code { font-family: monospace; }
+blockquote { margin-left: 0; padding-left: 1rem; border-left: 4px solid #ddd; color: #555; }
+</style>
+</head>
+<body>
+{{content}}
+</body>
+</html>
+";
+pub const RENDERED_CONTENT_TYPE: &str = "text/html; charset=utf-8";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ListKind {
+    Unordered,
+    Ordered,
+}
+
+impl ListKind {
+    fn tag(&self) -> &'static str {
+        match self {
+            ListKind::Unordered => "ul",
+            ListKind::Ordered => "ol",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MarkdownRenderer {
+    /*
+     *  Renderer of the .md sites to HTML pages.
+     *
+     *  Attributes:
+     *      template: Page with the {{title}} and the {{content}}
+     *      placeholders, that the rendered document is put into.
+     */
+    template: String,
+}
+
+impl Default for MarkdownRenderer {
+    fn default() -> Self {
+        MarkdownRenderer {
+            template: String::from(DEFAULT_TEMPLATE),
+        }
+    }
+}
+
+impl MarkdownRenderer {
+    pub fn load(template_path: &str) -> io::Result<Self> {
+        /*
+         *  Arguments:
+         *      template_path: File of the template, the default one is used
+         *      if the path is empty.
+         */
+        if template_path.is_empty() {
+            return Ok(MarkdownRenderer::default());
+        }
+        Ok(MarkdownRenderer {
+            template: fs::read_to_string(Path::new(template_path))?,
+        })
+    }
+
+    pub fn render_page(&self, markdown: &[u8]) -> Vec<u8> {
+        /*
+         *  Render the document and wrap it in the template, its first
+         *  heading is the title of the page.
+         *
+         *  Returns:
+         *      The page, UTF-8 encoded.
+         */
+        let markdown: String = String::from_utf8_lossy(markdown).into_owned();
+        let title: String = markdown
+            .lines()
+            .find_map(heading)
+            .map(|(_, text)| escape_html(text))
+            .unwrap_or_default();
+        /* The content is put in last, so the placeholders in the document stay as written */
+        let (before, after) = self
+            .template
+            .split_once("{{content}}")
+            .unwrap_or((&self.template, ""));
+        let mut page: String = before.replace("{{title}}", &title);
+        page.push_str(&to_html(&markdown));
+        page.push_str(&after.replace("{{title}}", &title));
+        page.into_bytes()
+    }
+}
+
+pub fn is_markdown(resource_path: &[u8]) -> bool {
+    let Some(dot_idx) = resource_path.iter().rposition(|byte| *byte == b'.') else {
+        return false;
+    };
+    let extension: &[u8] = &resource_path[dot_idx + 1..];
+    extension.eq_ignore_ascii_case(b"md") || extension.eq_ignore_ascii_case(b"markdown")
+}
+
+pub fn to_html(markdown: &str) -> String {
+    /*
+     *  Render the subset of Markdown, that the docs are written in:
+     *  the ATX headings, the paragraphs, the lists, the quotes, the fenced
+     *  code, the rules and the inline code, emphasis, links and images.
+     *  Raw HTML in the document is escaped.
+     */
+    let mut html: String = String::new();
+    let mut paragraph: Vec<&str> = Vec::new();
+    let mut quote: Vec<&str> = Vec::new();
+    let mut list: Option<(ListKind, Vec<String>)> = None;
+    let mut code: Option<String> = None;
+
+    for line in markdown.lines() {
+        if let Some(block) = &mut code {
+            if line.trim_start().starts_with("```") {
+                html.push_str(&format!("<pre><code>{block}</code></pre>\n"));
+                code = None;
+            } else {
+                block.push_str(&escape_html(line));
+                block.push('\n');
+            }
+            continue;
+        }
+        let trimmed: &str = line.trim();
+        if let Some(quoted) = trimmed.strip_prefix('>') {
+            flush_paragraph(&mut html, &mut paragraph);
+            flush_list(&mut html, &mut list);
+            quote.push(quoted.strip_prefix(' ').unwrap_or(quoted));
+            continue;
+        }
+        flush_quote(&mut html, &mut quote);
+        if trimmed.is_empty() {
+            flush_paragraph(&mut html, &mut paragraph);
+            flush_list(&mut html, &mut list);
+        } else if trimmed.starts_with("```") {
+            flush_paragraph(&mut html, &mut paragraph);
+            flush_list(&mut html, &mut list);
+            code = Some(String::new());
+        } else if let Some((level, text)) = heading(trimmed) {
+            flush_paragraph(&mut html, &mut paragraph);
+            flush_list(&mut html, &mut list);
+            html.push_str(&format!("<h{level}>{}</h{level}>\n", inline(text)));
+        } else if is_rule(trimmed) {
+            flush_paragraph(&mut html, &mut paragraph);
+            flush_list(&mut html, &mut list);
+            html.push_str("<hr>\n");
+        } else if let Some((kind, item)) = list_item(trimmed) {
+            flush_paragraph(&mut html, &mut paragraph);
+            if list.as_ref().is_some_and(|(open, _)| *open != kind) {
+                flush_list(&mut html, &mut list);
+            }
+            list.get_or_insert_with(|| (kind, Vec::new()))
+                .1
+                .push(String::from(item));
+        } else if let Some((_, items)) = &mut list
+            && let Some(last) = items.last_mut()
+        {
+            /* The lines following an item continue it */
+            last.push(' ');
+            last.push_str(trimmed);
+        } else {
+            paragraph.push(trimmed);
+        }
+    }
+    /* An unterminated fence runs to the end of the document */
+    if let Some(block) = code {
+        html.push_str(&format!("<pre><code>{block}</code></pre>\n"));
+    }
+    flush_quote(&mut html, &mut quote);
+    flush_paragraph(&mut html, &mut paragraph);
+    flush_list(&mut html, &mut list);
+    html
+}
+
+fn flush_paragraph(html: &mut String, paragraph: &mut Vec<&str>) {
+    if paragraph.is_empty() {
+        return;
+    }
+    html.push_str(&format!("<p>{}</p>\n", inline(&paragraph.join("\n"))));
+    paragraph.clear();
+}
+
+fn flush_list(html: &mut String, list: &mut Option<(ListKind, Vec<String>)>) {
+    let Some((kind, items)) = list.take() else {
+        return;
+    };
+    html.push_str(&format!("<{}>\n", kind.tag()));
+    for item in items {
+        html.push_str(&format!("<li>{}</li>\n", inline(&item)));
+    }
+    html.push_str(&format!("</{}>\n", kind.tag()));
+}
+
+fn flush_quote(html: &mut String, quote: &mut Vec<&str>) {
+    if quote.is_empty() {
+        return;
+    }
+    html.push_str(&format!(
+        "<blockquote>\n{}</blockquote>\n",
+        to_html(&quote.join("\n"))
+    ));
+    quote.clear();
+}
+
+fn heading(line: &str) -> Option<(usize, &str)> {
+    /*
+     *  Returns:
+     *      Level and text of the ATX heading, e.g. ## Install
+     */
+    let line: &str = line.trim();
+    let level: usize = line.bytes().take_while(|byte| *byte == b'#').count();
+    if level == 0 || level > 6 {
+        return None;
+    }
+    let rest: &str = &line[level..];
+    if !rest.is_empty() && !rest.starts_with(' ') {
+        return None;
+    }
+    Some((level, rest.trim().trim_end_matches('#').trim_end()))
+}
+
+fn is_rule(line: &str) -> bool {
+    let marks: String = line.chars().filter(|c| !c.is_whitespace()).collect();
+    marks.len() >= 3
+        && ["-", "*", "_"]
+            .iter()
+            .any(|mark| marks.chars().all(|c| c.to_string() == *mark))
+}
+
+fn list_item(line: &str) -> Option<(ListKind, &str)> {
+    if let Some(item) = ["- ", "* ", "+ "]
+        .iter()
+        .find_map(|marker| line.strip_prefix(marker))
+    {
+        return Some((ListKind::Unordered, item.trim()));
+    }
+    let digits: usize = line.bytes().take_while(u8::is_ascii_digit).count();
+    if digits == 0 {
+        return None;
+    }
+    line[digits..]
+        .strip_prefix(". ")
+        .or_else(|| line[digits..].strip_prefix(") "))
+        .map(|item| (ListKind::Ordered, item.trim()))
+}
+
+fn inline(text: &str) -> String {
+    /*
+     *  Render the inline code, the emphasis, the links and the images
+     *  of the text, the rest is escaped.
+     */
+    let mut html: String = String::new();
+    let mut rest: &str = text;
+    while let Some(c) = rest.chars().next() {
+        let after: &str = &rest[c.len_utf8()..];
+        match c {
+            '\\' if after.starts_with(|next: char| next.is_ascii_punctuation()) => {
+                let escaped: char = after.chars().next().unwrap_or_default();
+                html.push_str(&escape_html(&escaped.to_string()));
+                rest = &after[escaped.len_utf8()..];
+                continue;
+            }
+            '`' => {
+                if let Some(end) = after.find('`') {
+                    html.push_str(&format!("<code>{}</code>", escape_html(&after[..end])));
+                    rest = &after[end + 1..];
+                    continue;
+                }
+            }
+            '*' | '_' => {
+                let strong: String = format!("{c}{c}");
+                if let Some(inner) = rest.strip_prefix(&strong)
+                    && let Some(end) = inner.find(&strong)
+                    && end > 0
+                {
+                    html.push_str(&format!("<strong>{}</strong>", inline(&inner[..end])));
+                    rest = &inner[end + 2..];
+                    continue;
+                }
+                if let Some(end) = after.find(c)
+                    && end > 0
+                    && !after.starts_with(char::is_whitespace)
+                {
+                    html.push_str(&format!("<em>{}</em>", inline(&after[..end])));
+                    rest = &after[end + 1..];
+                    continue;
+                }
+            }
+            '!' if after.starts_with('[') => {
+                if let Some((alt, src, tail)) = link(after) {
+                    html.push_str(&format!(
+                        "<img src=\"{}\" alt=\"{}\">",
+                        escape_html(src),
+                        escape_html(alt)
+                    ));
+                    rest = tail;
+                    continue;
+                }
+            }
+            '[' => {
+                if let Some((label, href, tail)) = link(rest) {
+                    html.push_str(&format!(
+                        "<a href=\"{}\">{}</a>",
+                        escape_html(href),
+                        inline(label)
+                    ));
+                    rest = tail;
+                    continue;
+                }
+            }
+            _ => {}
+        }
+        html.push_str(&escape_html(&rest[..c.len_utf8()]));
+        rest = after;
+    }
+    html
+}
+
+fn link(text: &str) -> Option<(&str, &str, &str)> {
+    /*
+     *  Arguments:
+     *      text: Text starting with the link, e.g. [docs](/docs.md) and more
+     *
+     *  Returns:
+     *      The label, the target and the text after the link.
+     */
+    let label_end: usize = text.find("](")?;
+    let target_end: usize = text[label_end + 2..].find(')')? + label_end + 2;
+    Some((
+        &text[1..label_end],
+        text[label_end + 2..target_end].trim(),
+        &text[target_end + 1..],
+    ))
+}
+
+pub fn escape_html(text: &str) -> String {
+    let mut escaped: String = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn markdown_test() {
+        assert!(is_markdown(b"/docs/README.md"));
+        assert!(is_markdown(b"/guide.Markdown"));
+        assert!(!is_markdown(b"/index.html"));
+        assert!(!is_markdown(b"/md"));
+
+        assert_eq!(
+            to_html("# Diana\n\nA *fast* `server`,\nsee [docs](/docs.md).\n"),
+            "<h1>Diana</h1>\n<p>A <em>fast</em> <code>server</code>,\nsee <a href=\"/docs.md\">docs</a>.</p>\n"
+        );
+        assert_eq!(
+            to_html("- one\n- **two**\n  continued\n1. first\n\n---"),
+            "<ul>\n<li>one</li>\n<li><strong>two</strong> continued</li>\n</ul>\n<ol>\n<li>first</li>\n</ol>\n<hr>\n"
+        );
+        assert_eq!(
+            to_html("```rust\nlet a = 1 < 2;\n```\n> quoted <b>"),
+            "<pre><code>let a = 1 &lt; 2;\n</code></pre>\n<blockquote>\n<p>quoted &lt;b&gt;</p>\n</blockquote>\n"
+        );
+        assert_eq!(
+            to_html("![logo](/logo.png) 2 * 3 \\*x\\*"),
+            "<p><img src=\"/logo.png\" alt=\"logo\"> 2 * 3 *x*</p>\n"
+        );
+
+        let renderer: MarkdownRenderer = MarkdownRenderer {
+            template: String::from("<title>{{title}}</title>{{content}}"),
+        };
+        assert_eq!(
+            renderer.render_page(b"## A & B\ntext {{title}}"),
+            b"<title>A &amp; B</title><h2>A &amp; B</h2>\n<p>text {{title}}</p>\n"
+        );
+        assert!(
+            String::from_utf8(MarkdownRenderer::load("").unwrap().render_page(b"# Hi"))
+                .unwrap()
+                .contains("<title>Hi</title>")
+        );
+        assert!(MarkdownRenderer::load("/nonexistent/template.html").is_err());
+    }
+}
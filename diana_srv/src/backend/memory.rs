@@ -0,0 +1,124 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/* Over the budget the cache is shrunk below its share by this many percent, so it doesn't thrash */
+const EVICTION_HEADROOM_PERCENT: usize = 25;
+
+#[derive(Debug)]
+pub struct MemoryBudget {
+    /*
+     *  Global budget of the memory held by the cache, the request bodies
+     *  in flight and the response buffers. The cache is measured, when
+     *  the budget is checked, the buffers reserve their bytes.
+     *
+     *  Attributes:
+     *      limit: The budget in bytes, unlimited if it is missing.
+     *      in_flight: Bytes of the buffers reserved right now.
+     */
+    limit: Option<usize>,
+    in_flight: Arc<AtomicUsize>,
+}
+
+#[derive(Debug)]
+pub struct Reservation {
+    /*
+     *  Bytes of the buffer, that are counted against the budget, until
+     *  the reservation is dropped.
+     */
+    bytes: usize,
+    in_flight: Arc<AtomicUsize>,
+}
+
+impl Drop for Reservation {
+    fn drop(&mut self) {
+        self.in_flight.fetch_sub(self.bytes, Ordering::Relaxed);
+    }
+}
+
+impl MemoryBudget {
+    pub fn new(limit: Option<usize>) -> Self {
+        MemoryBudget {
+            limit,
+            in_flight: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    pub fn reserve(&self, bytes: usize) -> Reservation {
+        /*
+         *  Count the buffer against the budget, the budget isn't checked.
+         *
+         *  Arguments:
+         *      bytes: Size of the buffer.
+         */
+        self.in_flight.fetch_add(bytes, Ordering::Relaxed);
+        Reservation {
+            bytes,
+            in_flight: Arc::clone(&self.in_flight),
+        }
+    }
+
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.load(Ordering::Relaxed)
+    }
+
+    pub fn is_exceeded(&self, cache_bytes: usize) -> bool {
+        /*
+         *  Arguments:
+         *      cache_bytes: Bytes, that the cache holds on the heap.
+         */
+        self.limit
+            .is_some_and(|limit| cache_bytes + self.in_flight() > limit)
+    }
+
+    pub fn admits(&self, cache_bytes: usize, bytes: usize) -> bool {
+        /*
+         *  Check, that the buffer fits into the budget.
+         *
+         *  Arguments:
+         *      cache_bytes: Bytes, that the cache holds on the heap.
+         *      bytes: Size of the new buffer.
+         */
+        self.limit
+            .is_none_or(|limit| cache_bytes + self.in_flight() + bytes <= limit)
+    }
+
+    pub fn cache_target(&self) -> Option<usize> {
+        /*
+         *  Returns:
+         *      Bytes, that the cache is shrunk to when the budget is
+         *      exceeded, what the buffers leave of the budget less
+         *      the headroom. None if the budget is unlimited.
+         */
+        let share: usize = self.limit?.saturating_sub(self.in_flight());
+        Some(share - share * EVICTION_HEADROOM_PERCENT / 100)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn memory_budget_test() {
+        let budget: MemoryBudget = MemoryBudget::new(Some(1000));
+        let body: Reservation = budget.reserve(300);
+        {
+            let _response: Reservation = budget.reserve(200);
+            assert_eq!(budget.in_flight(), 500);
+            assert!(!budget.is_exceeded(500));
+            assert!(budget.is_exceeded(501));
+        }
+        assert_eq!(budget.in_flight(), 300);
+        assert!(budget.admits(600, 100));
+        assert!(!budget.admits(600, 101));
+        assert_eq!(budget.cache_target(), Some(525));
+        drop(body);
+        assert_eq!(budget.in_flight(), 0);
+
+        let unlimited: MemoryBudget = MemoryBudget::new(None);
+        let _body: Reservation = unlimited.reserve(usize::MAX / 2);
+        assert!(!unlimited.is_exceeded(usize::MAX / 2));
+        assert!(unlimited.admits(0, 1 << 40));
+        assert_eq!(unlimited.cache_target(), None);
+    }
+}
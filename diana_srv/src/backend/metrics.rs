@@ -0,0 +1,405 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::runtime::{Handle, RuntimeMetrics};
+
+#[derive(Debug, Default)]
+pub struct Metrics {
+    /*
+     *  Counters of the server, shared by all the connections.
+     *
+     *  Attributes:
+     *      cache_hits: Sites served from the cache.
+     *      cache_misses: Sites read from the disk and then cached.
+     *      cache_evictions: Sites evicted, because the cache was full.
+     *      cache_bypasses: Sites re-read on the client's request.
+     *      compressed_hits: Encoded sites served from the cache.
+     *      compressions: Sites encoded, because the cache had no encoding.
+     *      accept_errors: Connections, that the listener failed to accept.
+     *      handler_panics: Connection handlers, that panicked.
+     *      client_disconnects: Handlers cancelled, because the client closed
+     *      the connection.
+     *      requests: Requests answered by the server.
+     *      client_errors: Requests answered with 4xx.
+     *      server_errors: Requests answered with 5xx.
+     *      open_connections: Connections being handled right now, counted
+     *      from the accept until their handler finishes.
+     *      peak_connections: The most connections open at once.
+     *      refused_connections: Connections closed on the accept, because
+     *      max_connected_hosts were open.
+     *      slow_requests: Requests, that exceeded the slow request threshold.
+     *      memory_evictions: Sites evicted, because the memory budget was
+     *      exceeded.
+     *      shed_requests: Requests refused, because their body didn't fit
+     *      into the memory budget.
+     *      queued_requests: Requests, that waited in the queue for a free
+     *      slot of the concurrency limits.
+     *      queue_timeouts: Queued requests refused, because they waited
+     *      too long.
+     *      upstream_pool_hits: Upstream requests sent on a pooled connection.
+     *      upstream_connections_opened: Connections opened to the upstreams.
+     *      upstream_connections_closed: Connections to the upstreams closed,
+     *      e.g. expired in the pool or closed by the upstream.
+     *      mirror_hits: Files served by the mirror without the upstream,
+     *      including the stale ones.
+     *      mirror_misses: Files fetched by the mirror from the upstream.
+     *      mirror_revalidations: Expired files of the mirror, that the upstream
+     *      confirmed.
+     *      injected_faults: Requests failed on purpose by the chaos rules.
+     *      error_statuses: Requests answered with 4xx and 5xx by the status.
+     */
+    pub cache_hits: AtomicU64,
+    pub cache_misses: AtomicU64,
+    pub cache_evictions: AtomicU64,
+    pub cache_bypasses: AtomicU64,
+    pub compressed_hits: AtomicU64,
+    pub compressions: AtomicU64,
+    pub accept_errors: AtomicU64,
+    pub handler_panics: AtomicU64,
+    pub client_disconnects: AtomicU64,
+    pub requests: AtomicU64,
+    pub client_errors: AtomicU64,
+    pub server_errors: AtomicU64,
+    pub open_connections: AtomicU64,
+    pub peak_connections: AtomicU64,
+    pub refused_connections: AtomicU64,
+    pub slow_requests: AtomicU64,
+    pub memory_evictions: AtomicU64,
+    pub shed_requests: AtomicU64,
+    pub queued_requests: AtomicU64,
+    pub queue_timeouts: AtomicU64,
+    pub upstream_pool_hits: AtomicU64,
+    pub upstream_connections_opened: AtomicU64,
+    pub upstream_connections_closed: AtomicU64,
+    pub mirror_hits: AtomicU64,
+    pub mirror_misses: AtomicU64,
+    pub mirror_revalidations: AtomicU64,
+    pub injected_faults: AtomicU64,
+    error_statuses: Mutex<BTreeMap<usize, u64>>,
+}
+
+impl Metrics {
+    pub fn increment(counter: &AtomicU64) {
+        Self::add(counter, 1);
+    }
+
+    pub fn add(counter: &AtomicU64, value: u64) {
+        counter.fetch_add(value, Ordering::Relaxed);
+    }
+
+    pub fn count_response(&self, status: usize) {
+        /*
+         *  Count the answered request, with its error class.
+         */
+        Self::increment(&self.requests);
+        match status {
+            400..=499 => Self::increment(&self.client_errors),
+            500..=599 => Self::increment(&self.server_errors),
+            _ => return,
+        }
+        *self
+            .error_statuses
+            .lock()
+            .unwrap()
+            .entry(status)
+            .or_default() += 1;
+    }
+
+    pub fn error_statuses(&self) -> BTreeMap<usize, u64> {
+        /*
+         *  Returns:
+         *      The number of the errors by their status code, e.g. 404: 7
+         */
+        self.error_statuses.lock().unwrap().clone()
+    }
+
+    pub fn render(&self) -> String {
+        /*
+         *  Render the counters in the Prometheus text format, with
+         *  the metrics of the runtime if it is called inside one.
+         *
+         *  Returns:
+         *      Body of the metrics endpoint.
+         */
+        let counters: [(&str, &str, &AtomicU64); 25] = [
+            (
+                "diana_cache_hits_total",
+                "Sites served from the cache.",
+                &self.cache_hits,
+            ),
+            (
+                "diana_cache_misses_total",
+                "Sites read from the disk.",
+                &self.cache_misses,
+            ),
+            (
+                "diana_cache_evictions_total",
+                "Sites evicted from the full cache.",
+                &self.cache_evictions,
+            ),
+            (
+                "diana_cache_bypasses_total",
+                "Sites re-read on the client's request.",
+                &self.cache_bypasses,
+            ),
+            (
+                "diana_compressed_hits_total",
+                "Encoded sites served from the cache.",
+                &self.compressed_hits,
+            ),
+            (
+                "diana_compressions_total",
+                "Sites encoded for the response.",
+                &self.compressions,
+            ),
+            (
+                "diana_accept_errors_total",
+                "Connections the listener failed to accept.",
+                &self.accept_errors,
+            ),
+            (
+                "diana_handler_panics_total",
+                "Connection handlers, that panicked.",
+                &self.handler_panics,
+            ),
+            (
+                "diana_client_disconnects_total",
+                "Handlers cancelled, because the client left.",
+                &self.client_disconnects,
+            ),
+            (
+                "diana_requests_total",
+                "Requests answered by the server.",
+                &self.requests,
+            ),
+            (
+                "diana_client_errors_total",
+                "Requests answered with 4xx.",
+                &self.client_errors,
+            ),
+            (
+                "diana_server_errors_total",
+                "Requests answered with 5xx.",
+                &self.server_errors,
+            ),
+            (
+                "diana_slow_requests_total",
+                "Requests exceeding the slow request threshold.",
+                &self.slow_requests,
+            ),
+            (
+                "diana_memory_evictions_total",
+                "Sites evicted over the memory budget.",
+                &self.memory_evictions,
+            ),
+            (
+                "diana_shed_requests_total",
+                "Large bodies refused over the memory budget.",
+                &self.shed_requests,
+            ),
+            (
+                "diana_queued_requests_total",
+                "Requests, that waited for a free slot.",
+                &self.queued_requests,
+            ),
+            (
+                "diana_queue_timeouts_total",
+                "Queued requests refused after the longest wait.",
+                &self.queue_timeouts,
+            ),
+            (
+                "diana_upstream_pool_hits_total",
+                "Upstream requests sent on a pooled connection.",
+                &self.upstream_pool_hits,
+            ),
+            (
+                "diana_upstream_connections_opened_total",
+                "Connections opened to the upstreams.",
+                &self.upstream_connections_opened,
+            ),
+            (
+                "diana_upstream_connections_closed_total",
+                "Connections to the upstreams closed.",
+                &self.upstream_connections_closed,
+            ),
+            (
+                "diana_mirror_hits_total",
+                "Files served by the mirror without the upstream.",
+                &self.mirror_hits,
+            ),
+            (
+                "diana_mirror_misses_total",
+                "Files fetched by the mirror from the upstream.",
+                &self.mirror_misses,
+            ),
+            (
+                "diana_mirror_revalidations_total",
+                "Expired files of the mirror confirmed by the upstream.",
+                &self.mirror_revalidations,
+            ),
+            (
+                "diana_injected_faults_total",
+                "Requests failed on purpose by the chaos rules.",
+                &self.injected_faults,
+            ),
+            (
+                "diana_refused_connections_total",
+                "Connections refused, because too many were open.",
+                &self.refused_connections,
+            ),
+        ];
+        let mut rendered: String = String::new();
+        for (name, help, counter) in counters {
+            let value: u64 = counter.load(Ordering::Relaxed);
+            let _ = write!(
+                rendered,
+                "# HELP {name} {help}\n# TYPE {name} counter\n{name} {value}\n"
+            );
+        }
+        let name: &str = "diana_open_connections";
+        let _ = write!(
+            rendered,
+            "# HELP {name} Connections being handled.\n# TYPE {name} gauge\n{name} {}\n",
+            self.open_connections.load(Ordering::Relaxed)
+        );
+        let name: &str = "diana_peak_connections";
+        let _ = write!(
+            rendered,
+            "# HELP {name} The most connections open at once.\n# TYPE {name} gauge\n{name} {}\n",
+            self.peak_connections.load(Ordering::Relaxed)
+        );
+        if let Ok(handle) = Handle::try_current() {
+            render_runtime(&mut rendered, &handle.metrics());
+        }
+        rendered
+    }
+}
+
+pub struct OpenConnection {
+    /*
+     *  Counts the connection as open, until it is dropped.
+     */
+    metrics: Arc<Metrics>,
+}
+
+impl OpenConnection {
+    pub fn new(metrics: Arc<Metrics>) -> Self {
+        let open: u64 = metrics.open_connections.fetch_add(1, Ordering::Relaxed) + 1;
+        metrics.peak_connections.fetch_max(open, Ordering::Relaxed);
+        OpenConnection { metrics }
+    }
+}
+
+impl Drop for OpenConnection {
+    fn drop(&mut self) {
+        self.metrics
+            .open_connections
+            .fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+fn render_runtime(rendered: &mut String, runtime: &RuntimeMetrics) {
+    /*
+     *  Render the gauges of the tokio runtime. The blocking pool and
+     *  the poll times are only measured by the builds with
+     *  RUSTFLAGS="--cfg tokio_unstable".
+     */
+    let gauges: Vec<(&str, &str, usize)> = vec![
+        (
+            "diana_runtime_workers",
+            "Worker threads of the runtime.",
+            runtime.num_workers(),
+        ),
+        (
+            "diana_runtime_alive_tasks",
+            "Tasks, that haven't finished yet.",
+            runtime.num_alive_tasks(),
+        ),
+        (
+            "diana_runtime_global_queue_depth",
+            "Tasks waiting in the global queue.",
+            runtime.global_queue_depth(),
+        ),
+        #[cfg(tokio_unstable)]
+        (
+            "diana_runtime_blocking_threads",
+            "Threads of the blocking pool.",
+            runtime.num_blocking_threads(),
+        ),
+        #[cfg(tokio_unstable)]
+        (
+            "diana_runtime_idle_blocking_threads",
+            "Idle threads of the blocking pool.",
+            runtime.num_idle_blocking_threads(),
+        ),
+        #[cfg(tokio_unstable)]
+        (
+            "diana_runtime_blocking_queue_depth",
+            "Tasks waiting for a blocking thread.",
+            runtime.blocking_queue_depth(),
+        ),
+    ];
+    for (name, help, value) in gauges {
+        let _ = write!(
+            rendered,
+            "# HELP {name} {help}\n# TYPE {name} gauge\n{name} {value}\n"
+        );
+    }
+
+    #[cfg(tokio_unstable)]
+    {
+        let name: &str = "diana_runtime_worker_mean_poll_seconds";
+        let _ = write!(
+            rendered,
+            "# HELP {name} Mean time a worker spends polling a task.\n# TYPE {name} gauge\n"
+        );
+        for worker in 0..runtime.num_workers() {
+            let mean: f64 = runtime.worker_mean_poll_time(worker).as_secs_f64();
+            let _ = writeln!(rendered, "{name}{{worker=\"{worker}\"}} {mean}");
+        }
+        let name: &str = "diana_runtime_worker_busy_seconds_total";
+        let _ = write!(
+            rendered,
+            "# HELP {name} Time a worker spent running tasks.\n# TYPE {name} counter\n"
+        );
+        for worker in 0..runtime.num_workers() {
+            let busy: f64 = runtime.worker_total_busy_duration(worker).as_secs_f64();
+            let _ = writeln!(rendered, "{name}{{worker=\"{worker}\"}} {busy}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn metrics_render_test() {
+        let metrics = Metrics::default();
+        Metrics::increment(&metrics.cache_hits);
+        Metrics::add(&metrics.cache_evictions, 3);
+        let rendered: String = metrics.render();
+        assert!(rendered.contains("# TYPE diana_cache_hits_total counter\n"));
+        assert!(rendered.contains("diana_cache_hits_total 1\n"));
+        assert!(rendered.contains("diana_cache_evictions_total 3\n"));
+        assert!(rendered.contains("diana_cache_misses_total 0\n"));
+        metrics.count_response(404);
+        assert!(metrics.render().contains("diana_client_errors_total 1\n"));
+
+        let metrics: Arc<Metrics> = Arc::new(metrics);
+        let open: OpenConnection = OpenConnection::new(Arc::clone(&metrics));
+        assert_eq!(metrics.open_connections.load(Ordering::Relaxed), 1);
+        drop(open);
+        assert_eq!(metrics.open_connections.load(Ordering::Relaxed), 0);
+        assert!(!rendered.contains("diana_runtime_workers"));
+    }
+
+    #[tokio::test]
+    async fn runtime_metrics_test() {
+        let rendered: String = Metrics::default().render();
+        assert!(rendered.contains("# TYPE diana_runtime_workers gauge\n"));
+        assert!(rendered.contains("diana_runtime_workers 1\n"));
+        assert!(rendered.contains("diana_runtime_alive_tasks "));
+    }
+}
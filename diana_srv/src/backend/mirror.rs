@@ -0,0 +1,601 @@
+use crate::backend::cache::CacheStatus;
+use crate::backend::cache::disk::fnv1a;
+use crate::backend::client::{ClientError, ClientResponse, HttpClient};
+use crate::backend::metrics::Metrics;
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, SystemTime};
+use tokio::fs;
+
+/* Extension of the mirrored files */
+const MIRROR_EXTENSION: &str = "mirror";
+/* Headers of the upstream, that are stored and sent with the mirrored file */
+const KEPT_HEADERS: [&str; 4] = ["content-type", "etag", "last-modified", "cache-control"];
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct MirrorConfig {
+    /*
+     *  The [mirror] table of the config. The GET requests under the prefix
+     *  are fetched from the upstream and kept on the disk, e.g. the crates
+     *  or the apt packages for the internal network.
+     *
+     *  Attributes:
+     *      upstream: Base URL of the mirrored server, e.g.
+     *      http://deb.debian.org/debian, only http is supported. The mirror
+     *      is disabled if it is empty.
+     *      prefix: Route prefix of the mirror, /mirror/dists/stable/Release
+     *      is fetched as <upstream>/dists/stable/Release
+     *      cache_dir: Directory of the mirrored files, they are kept across
+     *      the restarts.
+     *      max_cache_size: Bytes of the mirrored files, the least recently
+     *      fetched ones are removed over it. Larger files aren't kept.
+     *      ttl_secs: How long the mirrored file is served without asking
+     *      the upstream. It is revalidated with its ETag or Last-Modified
+     *      afterwards.
+     *      timeout_secs: Time given to the upstream to send the whole file.
+     */
+    pub upstream: String,
+    pub prefix: String,
+    pub cache_dir: String,
+    pub max_cache_size: u64,
+    pub ttl_secs: u64,
+    pub timeout_secs: u64,
+}
+
+impl Default for MirrorConfig {
+    fn default() -> Self {
+        MirrorConfig {
+            upstream: String::new(),
+            prefix: String::from("/mirror"),
+            cache_dir: String::from("mirror_cache"),
+            max_cache_size: 1 << 30,
+            ttl_secs: 300,
+            timeout_secs: 60,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+struct EntryMeta {
+    /*
+     *  Head of the mirrored file, stored in front of its content.
+     *
+     *  Attributes:
+     *      path: Path on the upstream, it tells the colliding files apart.
+     *      headers: The kept headers of the upstream's response.
+     */
+    path: String,
+    headers: Vec<(String, String)>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MirrorResponse {
+    /*
+     *  Answer of the mirror.
+     *
+     *  Attributes:
+     *      status: Status code, the upstream's one if it isn't mirrored.
+     *      headers: Headers of the file, including X-Diana-Cache.
+     *      body: Content of the file.
+     */
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+#[derive(Debug)]
+pub struct Mirror {
+    /*
+     *  Caching proxy of the upstream. Every file is a file in the cache
+     *  directory named by the hash of its path, the time of its last
+     *  modification is when it was fetched or revalidated.
+     *
+     *  Attributes:
+     *      config: The [mirror] table of the config.
+     *      dir: Directory of the mirrored files.
+     *      client: Client of the upstream.
+     *      size: Bytes of the mirrored files, recounted on eviction.
+     *      metrics: Counters of the hits, the misses and the revalidations.
+     */
+    config: MirrorConfig,
+    dir: PathBuf,
+    client: HttpClient,
+    size: AtomicU64,
+    metrics: Arc<Metrics>,
+}
+
+impl Mirror {
+    pub fn open(
+        config: &MirrorConfig,
+        client: HttpClient,
+        metrics: Arc<Metrics>,
+    ) -> Result<Self, io::Error> {
+        /*
+         *  Constructor of the mirror.
+         *
+         *  Arguments:
+         *      config: The [mirror] table of the config.
+         *      client: Client of the upstream, e.g. with the pool limits
+         *      of the config.
+         *      metrics: Counters of the server.
+         *
+         *  Returns:
+         *      The mirror with the files already in the directory, or
+         *      the error if the directory can't be created.
+         */
+        let dir: PathBuf = PathBuf::from(&config.cache_dir);
+        std::fs::create_dir_all(&dir)?;
+        let size: u64 = std::fs::read_dir(&dir)?
+            .filter_map(Result::ok)
+            .filter(|entry| is_mirror_file(&entry.path()))
+            .filter_map(|entry| entry.metadata().ok())
+            .map(|metadata| metadata.len())
+            .sum();
+        Ok(Mirror {
+            config: config.clone(),
+            dir,
+            client,
+            size: AtomicU64::new(size),
+            metrics,
+        })
+    }
+
+    pub fn upstream_path<'a>(&self, resource_path: &'a [u8]) -> Option<&'a [u8]> {
+        /*
+         *  Returns:
+         *      Path of the file on the upstream, None if the resource path
+         *      isn't under the prefix.
+         */
+        let prefix: &[u8] = self.config.prefix.trim_end_matches('/').as_bytes();
+        let rest: &[u8] = resource_path.strip_prefix(prefix)?;
+        match rest.first() {
+            Some(b'/') => Some(rest),
+            None => Some(b"/"),
+            Some(_) => None,
+        }
+    }
+
+    pub fn size(&self) -> u64 {
+        self.size.load(Ordering::Relaxed)
+    }
+
+    pub async fn fetch(&self, path: &[u8]) -> Result<MirrorResponse, ClientError> {
+        /*
+         *  Serve the file from the disk while it is fresh, revalidate it
+         *  with the upstream once it isn't. The stale file is served, when
+         *  the upstream fails, so the mirror outlives the upstream's outages.
+         *  Only 200 is mirrored, the other answers are passed through.
+         *
+         *  Arguments:
+         *      path: Path of the file on the upstream, e.g. /dists/Release
+         *
+         *  Returns:
+         *      The answer, or the reason of the failure, if the upstream
+         *      failed and the file isn't mirrored.
+         */
+        let path: &str = std::str::from_utf8(path).map_err(|_| ClientError::InvalidUrl)?;
+        let url: String = format!("{}{path}", self.config.upstream.trim_end_matches('/'));
+        let file: PathBuf = self.entry_file(path);
+        let cached: Option<(EntryMeta, Vec<u8>, SystemTime)> = read_entry(&file, path).await;
+        if let Some((meta, body, fetched)) = &cached
+            && fetched.elapsed().unwrap_or_default() < Duration::from_secs(self.config.ttl_secs)
+        {
+            Metrics::increment(&self.metrics.mirror_hits);
+            return Ok(answer(meta, body.clone(), CacheStatus::Hit));
+        }
+
+        let mut conditions: Vec<(String, String)> = Vec::new();
+        if let Some((meta, _, _)) = &cached {
+            for (condition, validator) in [
+                ("If-None-Match", "etag"),
+                ("If-Modified-Since", "last-modified"),
+            ] {
+                conditions.extend(
+                    header(&meta.headers, validator)
+                        .map(|value| (String::from(condition), String::from(value))),
+                );
+            }
+        }
+        let fetched: Result<ClientResponse, ClientError> =
+            self.client.request("GET", &url, &conditions, &[]).await;
+        match (fetched, cached) {
+            (Ok(response), Some((meta, body, _))) if response.status == 304 => {
+                Metrics::increment(&self.metrics.mirror_revalidations);
+                if let Err(e) = touch(&file).await {
+                    println!("[ERROR] Failed to revalidate the mirrored {path}: {e}");
+                }
+                Ok(answer(&meta, body, CacheStatus::Revalidated))
+            }
+            (Ok(response), _) if response.status == 200 => {
+                Metrics::increment(&self.metrics.mirror_misses);
+                let meta: EntryMeta = EntryMeta {
+                    path: String::from(path),
+                    headers: response
+                        .headers
+                        .iter()
+                        .filter(|(name, _)| {
+                            KEPT_HEADERS.contains(&name.to_ascii_lowercase().as_str())
+                        })
+                        .cloned()
+                        .collect(),
+                };
+                self.store(&file, &meta, &response.body).await;
+                Ok(answer(&meta, response.body, CacheStatus::Miss))
+            }
+            /* The upstream's errors don't replace the mirrored file */
+            (Ok(response), Some((meta, body, _))) if response.status >= 500 => {
+                Metrics::increment(&self.metrics.mirror_hits);
+                Ok(answer(&meta, body, CacheStatus::Stale))
+            }
+            (Err(e), Some((meta, body, _))) => {
+                println!("[WARNING] Upstream failed, the stale {path} is served: {e}");
+                Metrics::increment(&self.metrics.mirror_hits);
+                Ok(answer(&meta, body, CacheStatus::Stale))
+            }
+            (Ok(response), _) => {
+                Metrics::increment(&self.metrics.mirror_misses);
+                let mut headers: Vec<(String, String)> = response
+                    .headers
+                    .iter()
+                    .filter(|(name, _)| KEPT_HEADERS.contains(&name.to_ascii_lowercase().as_str()))
+                    .cloned()
+                    .collect();
+                headers.push((
+                    String::from("X-Diana-Cache"),
+                    String::from(CacheStatus::Bypass.value()),
+                ));
+                Ok(MirrorResponse {
+                    status: response.status,
+                    headers,
+                    body: response.body,
+                })
+            }
+            (Err(e), None) => Err(e),
+        }
+    }
+
+    fn entry_file(&self, path: &str) -> PathBuf {
+        self.dir.join(format!(
+            "{:016x}.{MIRROR_EXTENSION}",
+            fnv1a(path.as_bytes())
+        ))
+    }
+
+    async fn store(&self, file: &Path, meta: &EntryMeta, body: &[u8]) {
+        /*
+         *  Keep the fetched file, the least recently fetched files are
+         *  removed, until the mirror fits its size.
+         */
+        if body.len() as u64 > self.config.max_cache_size {
+            return;
+        }
+        let replaced: u64 = fs::metadata(file)
+            .await
+            .map_or(0, |metadata| metadata.len());
+        let written: u64 = match write_entry(file, meta, body).await {
+            Ok(written) => written,
+            Err(e) => {
+                println!("[ERROR] Failed to write the mirrored {}: {e}", meta.path);
+                return;
+            }
+        };
+        self.size.fetch_sub(replaced, Ordering::Relaxed);
+        if self.size.fetch_add(written, Ordering::Relaxed) + written > self.config.max_cache_size {
+            self.evict().await;
+        }
+    }
+
+    async fn evict(&self) {
+        /*
+         *  Remove the least recently fetched files, until the mirror fits
+         *  its size. The size is recounted, since the files might have been
+         *  removed by hand.
+         */
+        let mut files: Vec<(SystemTime, u64, PathBuf)> = Vec::new();
+        if let Ok(mut entries) = fs::read_dir(&self.dir).await {
+            while let Ok(Some(entry)) = entries.next_entry().await {
+                let path: PathBuf = entry.path();
+                if !is_mirror_file(&path) {
+                    continue;
+                }
+                if let Ok(metadata) = entry.metadata().await {
+                    let fetched: SystemTime = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+                    files.push((fetched, metadata.len(), path));
+                }
+            }
+        }
+        files.sort();
+        let mut size: u64 = files.iter().map(|(_, len, _)| len).sum();
+        for (_, len, path) in &files {
+            if size <= self.config.max_cache_size {
+                break;
+            }
+            if fs::remove_file(path).await.is_ok() {
+                size -= len;
+            }
+        }
+        self.size.store(size, Ordering::Relaxed);
+    }
+}
+
+fn answer(meta: &EntryMeta, body: Vec<u8>, cache_status: CacheStatus) -> MirrorResponse {
+    let mut headers: Vec<(String, String)> = meta.headers.clone();
+    headers.push((
+        String::from("X-Diana-Cache"),
+        String::from(cache_status.value()),
+    ));
+    MirrorResponse {
+        status: 200,
+        headers,
+        body,
+    }
+}
+
+fn header<'a>(headers: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    headers
+        .iter()
+        .find(|(header, _)| header.eq_ignore_ascii_case(name))
+        .map(|(_, value)| value.as_str())
+}
+
+fn is_mirror_file(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|extension| extension == MIRROR_EXTENSION)
+}
+
+async fn read_entry(file: &Path, path: &str) -> Option<(EntryMeta, Vec<u8>, SystemTime)> {
+    /*
+     *  Read the mirrored file, it is the length of its head (4 bytes,
+     *  big endian), the head in JSON and the content.
+     *
+     *  Returns:
+     *      The head, the content and when it was fetched, None if the file
+     *      is missing or belongs to the other path with the same hash.
+     */
+    let fetched: SystemTime = fs::metadata(file).await.ok()?.modified().ok()?;
+    let entry: Vec<u8> = fs::read(file).await.ok()?;
+    let meta_len: usize = u32::from_be_bytes(entry.get(..4)?.try_into().ok()?) as usize;
+    let meta: EntryMeta = serde_json::from_slice(entry.get(4..4 + meta_len)?).ok()?;
+    if meta.path != path {
+        return None;
+    }
+    Some((meta, entry[4 + meta_len..].to_vec(), fetched))
+}
+
+async fn write_entry(file: &Path, meta: &EntryMeta, body: &[u8]) -> Result<u64, io::Error> {
+    /*
+     *  Write the mirrored file through the temporary one, so the concurrent
+     *  requests never read it half written.
+     *
+     *  Returns:
+     *      Bytes of the written file.
+     */
+    let head: Vec<u8> = serde_json::to_vec(meta)?;
+    let mut entry: Vec<u8> = Vec::with_capacity(4 + head.len() + body.len());
+    entry.extend_from_slice(&(head.len() as u32).to_be_bytes());
+    entry.extend_from_slice(&head);
+    entry.extend_from_slice(body);
+    let mut temporary = file.as_os_str().to_owned();
+    temporary.push(format!(".{}.tmp", std::process::id()));
+    fs::write(&temporary, &entry).await?;
+    fs::rename(&temporary, file).await?;
+    Ok(entry.len() as u64)
+}
+
+async fn touch(file: &Path) -> Result<(), io::Error> {
+    /*
+     *  Mark the file as fetched now, so it is fresh again.
+     */
+    let file: std::fs::File = fs::File::options()
+        .write(true)
+        .open(file)
+        .await?
+        .into_std()
+        .await;
+    file.set_modified(SystemTime::now())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU8, AtomicUsize};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    async fn upstream(listener: TcpListener, requests: Arc<AtomicUsize>) {
+        /* Answers the conditional requests with 304, the others with the file */
+        loop {
+            let Ok((mut stream, _)) = listener.accept().await else {
+                return;
+            };
+            let mut head: Vec<u8> = Vec::new();
+            let mut buf: [u8; 1024] = [0; 1024];
+            while !head.windows(4).any(|end| end == b"\r\n\r\n") {
+                match stream.read(&mut buf).await {
+                    Ok(0) | Err(_) => break,
+                    Ok(read) => head.extend_from_slice(&buf[..read]),
+                }
+            }
+            requests.fetch_add(1, Ordering::SeqCst);
+            let head: String = String::from_utf8_lossy(&head).to_ascii_lowercase();
+            let response: &[u8] = if head.starts_with("get /debian/missing") {
+                b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+            } else if head.contains("if-none-match: \"v1\"") {
+                b"HTTP/1.1 304 Not Modified\r\nConnection: close\r\n\r\n"
+            } else {
+                b"HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nETag: \"v1\"\r\nX-Secret: 1\r\n\
+                  Content-Length: 7\r\nConnection: close\r\n\r\nrelease"
+            };
+            let _ = stream.write_all(response).await;
+        }
+    }
+
+    #[tokio::test]
+    async fn mirror_test() {
+        let listener: TcpListener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let upstream_addr = listener.local_addr().unwrap();
+        let requests: Arc<AtomicUsize> = Arc::new(AtomicUsize::new(0));
+        let server = tokio::spawn(upstream(listener, Arc::clone(&requests)));
+
+        let dir: PathBuf =
+            std::env::temp_dir().join(format!("diana_mirror_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let mut config: MirrorConfig = MirrorConfig {
+            upstream: format!("http://{upstream_addr}/debian/"),
+            prefix: String::from("/mirror/"),
+            cache_dir: dir.to_string_lossy().into_owned(),
+            ..MirrorConfig::default()
+        };
+        let metrics: Arc<Metrics> = Arc::new(Metrics::default());
+        let client = || HttpClient::new(1, Duration::from_secs(5));
+        let mirror: Mirror = Mirror::open(&config, client(), Arc::clone(&metrics)).unwrap();
+        assert_eq!(
+            mirror.upstream_path(b"/mirror/dists/Release"),
+            Some(&b"/dists/Release"[..])
+        );
+        assert_eq!(mirror.upstream_path(b"/mirror"), Some(&b"/"[..]));
+        assert_eq!(mirror.upstream_path(b"/mirrors/a"), None);
+
+        /* The first request fetches the file, the next one is served from the disk */
+        let fetched: MirrorResponse = mirror.fetch(b"/dists/Release").await.unwrap();
+        assert_eq!(
+            (fetched.status, fetched.body.as_slice()),
+            (200, &b"release"[..])
+        );
+        assert_eq!(header(&fetched.headers, "x-diana-cache"), Some("MISS"));
+        assert_eq!(header(&fetched.headers, "etag"), Some("\"v1\""));
+        assert_eq!(header(&fetched.headers, "x-secret"), None);
+        let cached: MirrorResponse = mirror.fetch(b"/dists/Release").await.unwrap();
+        assert_eq!(header(&cached.headers, "x-diana-cache"), Some("HIT"));
+        assert_eq!(cached.body, b"release");
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+
+        let missing: MirrorResponse = mirror.fetch(b"/missing").await.unwrap();
+        assert_eq!(missing.status, 404);
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+
+        /* The expired file is revalidated, it stays on the disk across the restarts */
+        config.ttl_secs = 0;
+        let mirror: Mirror = Mirror::open(&config, client(), Arc::clone(&metrics)).unwrap();
+        assert!(mirror.size() > 7);
+        let revalidated: MirrorResponse = mirror.fetch(b"/dists/Release").await.unwrap();
+        assert_eq!(
+            header(&revalidated.headers, "x-diana-cache"),
+            Some("REVALIDATED")
+        );
+        assert_eq!(revalidated.body, b"release");
+        assert_eq!(requests.load(Ordering::SeqCst), 3);
+
+        /* The stale file outlives the upstream */
+        server.abort();
+        let _ = server.await;
+        let stale: MirrorResponse = mirror.fetch(b"/dists/Release").await.unwrap();
+        assert_eq!(header(&stale.headers, "x-diana-cache"), Some("STALE"));
+        assert!(mirror.fetch(b"/dists/InRelease").await.is_err());
+        assert_eq!(metrics.mirror_hits.load(Ordering::Relaxed), 2);
+        assert_eq!(metrics.mirror_misses.load(Ordering::Relaxed), 2);
+        assert_eq!(metrics.mirror_revalidations.load(Ordering::Relaxed), 1);
+
+        /* The files over the size are removed */
+        config.max_cache_size = 1;
+        let mirror: Mirror = Mirror::open(&config, client(), Arc::clone(&metrics)).unwrap();
+        mirror.evict().await;
+        assert_eq!(mirror.size(), 0);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    /* How the flaky upstream answers */
+    const ANSWERS: u8 = 0;
+    const FAILS: u8 = 1;
+    const TRUNCATES: u8 = 2;
+    const STALLS: u8 = 3;
+
+    async fn flaky_upstream(listener: TcpListener, mode: Arc<AtomicU8>) {
+        /* Answers every path with the file, unless the mode breaks it */
+        loop {
+            let Ok((mut stream, _)) = listener.accept().await else {
+                return;
+            };
+            let mode: u8 = mode.load(Ordering::SeqCst);
+            tokio::spawn(async move {
+                let mut buf: [u8; 1024] = [0; 1024];
+                let _ = stream.read(&mut buf).await;
+                let response: &[u8] = match mode {
+                    FAILS => {
+                        b"HTTP/1.1 503 Service Unavailable\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+                    }
+                    /* The connection drops in the middle of the body */
+                    TRUNCATES => b"HTTP/1.1 200 OK\r\nContent-Length: 7\r\nConnection: close\r\n\r\nrel",
+                    _ => {
+                        b"HTTP/1.1 200 OK\r\nETag: \"v1\"\r\nContent-Length: 7\r\nConnection: close\r\n\r\nrelease"
+                    }
+                };
+                if mode == STALLS {
+                    tokio::time::sleep(Duration::from_secs(2)).await;
+                }
+                let _ = stream.write_all(response).await;
+            });
+        }
+    }
+
+    #[tokio::test]
+    async fn mirror_upstream_failures_test() {
+        let listener: TcpListener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let upstream_addr = listener.local_addr().unwrap();
+        let mode: Arc<AtomicU8> = Arc::new(AtomicU8::new(ANSWERS));
+        let server = tokio::spawn(flaky_upstream(listener, Arc::clone(&mode)));
+
+        let dir: PathBuf =
+            std::env::temp_dir().join(format!("diana_mirror_flaky_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        /* Every request goes to the upstream, the file is always expired */
+        let config: MirrorConfig = MirrorConfig {
+            upstream: format!("http://{upstream_addr}/debian"),
+            cache_dir: dir.to_string_lossy().into_owned(),
+            ttl_secs: 0,
+            ..MirrorConfig::default()
+        };
+        let client: HttpClient = HttpClient::new(1, Duration::from_millis(300));
+        let mirror: Mirror = Mirror::open(&config, client, Arc::new(Metrics::default())).unwrap();
+        let fetched: MirrorResponse = mirror.fetch(b"/dists/Release").await.unwrap();
+        assert_eq!(header(&fetched.headers, "x-diana-cache"), Some("MISS"));
+        let size: u64 = mirror.size();
+        let entry = || std::fs::read(mirror.entry_file("/dists/Release")).unwrap();
+        let stored: Vec<u8> = entry();
+
+        for failure in [TRUNCATES, STALLS, FAILS] {
+            mode.store(failure, Ordering::SeqCst);
+
+            /* The mirrored file is served stale and stays as it was */
+            let stale: MirrorResponse = mirror.fetch(b"/dists/Release").await.unwrap();
+            assert_eq!(
+                (stale.status, stale.body.as_slice()),
+                (200, &b"release"[..]),
+                "{failure}"
+            );
+            assert_eq!(header(&stale.headers, "x-diana-cache"), Some("STALE"));
+            assert_eq!(entry(), stored);
+
+            /* Nothing is kept of the file, that isn't mirrored yet */
+            let fresh: Result<MirrorResponse, ClientError> =
+                mirror.fetch(b"/dists/InRelease").await;
+            match failure {
+                TRUNCATES => assert!(matches!(fresh, Err(ClientError::Io(_))), "{fresh:?}"),
+                STALLS => assert!(matches!(fresh, Err(ClientError::Timeout)), "{fresh:?}"),
+                _ => {
+                    let passed: MirrorResponse = fresh.unwrap();
+                    assert_eq!(passed.status, 503);
+                    assert_eq!(header(&passed.headers, "x-diana-cache"), Some("BYPASS"));
+                }
+            }
+            assert!(!mirror.entry_file("/dists/InRelease").exists());
+            assert_eq!(mirror.size(), size);
+        }
+        server.abort();
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,242 @@
+use crate::backend::http::{Request, Response};
+use crate::backend::router::Handler;
+use crate::backend::server::HttpResponseStatus;
+use async_trait::async_trait;
+use std::borrow::Cow;
+use std::sync::Arc;
+
+#[derive(Debug, PartialEq)]
+struct MediaRange<'a> {
+    /*
+     *  Element of the Accept header, e.g. text/html;q=0.8
+     *
+     *  Attributes:
+     *      main: Type, e.g. text, or * for any type.
+     *      sub: Subtype, e.g. html, or * for any subtype.
+     *      q: Weight of the range, 0 means not acceptable.
+     */
+    main: &'a str,
+    sub: &'a str,
+    q: f32,
+}
+
+impl MediaRange<'_> {
+    fn specificity(&self, media_type: &str) -> Option<u8> {
+        /*
+         *  Returns:
+         *      How closely the range matches the type, None if it doesn't.
+         */
+        let (main, sub) = media_type.split_once('/')?;
+        match (self.main, self.sub) {
+            ("*", "*") => Some(0),
+            (range_main, "*") if range_main.eq_ignore_ascii_case(main) => Some(1),
+            (range_main, range_sub)
+                if range_main.eq_ignore_ascii_case(main) && range_sub.eq_ignore_ascii_case(sub) =>
+            {
+                Some(2)
+            }
+            _ => None,
+        }
+    }
+}
+
+fn parse_accept(value: &str) -> Vec<MediaRange<'_>> {
+    /*
+     *  Parse the Accept header, the malformed elements are skipped.
+     */
+    let mut ranges: Vec<MediaRange> = Vec::new();
+    for element in value.split(',') {
+        let mut params = element.split(';');
+        let Some((main, sub)) = params.next().and_then(|range| range.trim().split_once('/')) else {
+            continue;
+        };
+        let mut q: Option<f32> = Some(1.0);
+        for param in params {
+            if let Some((name, value)) = param.trim().split_once('=')
+                && name.trim().eq_ignore_ascii_case("q")
+            {
+                q = value
+                    .trim()
+                    .parse()
+                    .ok()
+                    .filter(|q: &f32| (0.0..=1.0).contains(q));
+            }
+        }
+        if let Some(q) = q {
+            ranges.push(MediaRange {
+                main: main.trim(),
+                sub: sub.trim(),
+                q,
+            });
+        }
+    }
+    ranges
+}
+
+pub fn negotiate<'a>(accept: Option<&str>, offered: &[&'a str]) -> Option<&'a str> {
+    /*
+     *  Pick the media type, that the client prefers. The weight of a type
+     *  comes from the most specific range matching it, the ties are won by
+     *  the type offered first.
+     *
+     *  Arguments:
+     *      accept: Value of the Accept header, any type is accepted if missing.
+     *      offered: Media types, that the server can send, in its preference.
+     *
+     *  Returns:
+     *      The chosen type, None if the client accepts none of them.
+     */
+    let Some(accept) = accept else {
+        return offered.first().copied();
+    };
+    let ranges: Vec<MediaRange> = parse_accept(accept);
+    let mut chosen: Option<(&str, f32)> = None;
+    for media_type in offered {
+        let q: f32 = ranges
+            .iter()
+            .filter_map(|range| Some((range.specificity(media_type)?, range.q)))
+            .max_by_key(|(specificity, _)| *specificity)
+            .map_or(0.0, |(_, q)| q);
+        if q > 0.0 && chosen.is_none_or(|(_, best)| q > best) {
+            chosen = Some((media_type, q));
+        }
+    }
+    chosen.map(|(media_type, _)| media_type)
+}
+
+#[derive(Clone, Default)]
+pub struct Negotiated {
+    /*
+     *  Handler of the route, that has a representation per media type,
+     *  e.g. application/json for the API clients and text/html for
+     *  the browsers. The one chosen by the Accept header answers.
+     *
+     *  Attributes:
+     *      variants: Handlers by the media type, in the server's preference.
+     */
+    variants: Vec<(String, Arc<dyn Handler>)>,
+}
+
+impl Negotiated {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn variant(mut self, media_type: &str, handler: impl Handler + 'static) -> Self {
+        /*
+         *  Add the representation, the first added is sent to the clients
+         *  without the Accept header.
+         */
+        self.variants
+            .push((String::from(media_type), Arc::new(handler)));
+        self
+    }
+}
+
+#[async_trait]
+impl Handler for Negotiated {
+    async fn handle(&self, req: Request) -> Response {
+        let offered: Vec<&str> = self
+            .variants
+            .iter()
+            .map(|(media_type, _)| media_type.as_str())
+            .collect();
+        let accept_header: Option<Cow<'_, [u8]>> = req.headers.get_joined("accept");
+        let accept: Option<&str> = accept_header
+            .as_deref()
+            .and_then(|value| std::str::from_utf8(value).ok());
+        let Some(chosen) = negotiate(accept, &offered) else {
+            return Response::new(HttpResponseStatus::NotAcceptable)
+                .with_header("Vary", "Accept")
+                .with_body(offered.join(", ").into_bytes());
+        };
+        let handler: Arc<dyn Handler> = self
+            .variants
+            .iter()
+            .find(|(media_type, _)| media_type == chosen)
+            .map(|(_, handler)| Arc::clone(handler))
+            .unwrap();
+        let mut response: Response = handler.handle(req).await.with_header("Vary", "Accept");
+        if !response
+            .headers
+            .iter()
+            .any(|(name, _)| name.eq_ignore_ascii_case("content-type"))
+        {
+            response = response.with_header("Content-Type", chosen);
+        }
+        response
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::http::RequestBody;
+    use crate::backend::server::RequestType;
+    use std::time::Duration;
+    use tokio::time::Instant;
+
+    #[test]
+    fn negotiate_test() {
+        let offered: [&str; 2] = ["application/json", "text/html"];
+        assert_eq!(negotiate(None, &offered), Some("application/json"));
+        assert_eq!(
+            negotiate(Some("text/html,application/xhtml+xml,*/*;q=0.8"), &offered),
+            Some("text/html")
+        );
+        assert_eq!(
+            negotiate(Some("text/*;q=0.5, application/json;q=0.4"), &offered),
+            Some("text/html")
+        );
+        /* The specific range overrides the wildcard */
+        assert_eq!(
+            negotiate(Some("*/*, application/json;q=0"), &offered),
+            Some("text/html")
+        );
+        assert_eq!(negotiate(Some("image/png"), &offered), None);
+        assert_eq!(negotiate(Some("text/html;q=2"), &offered), None);
+    }
+
+    #[tokio::test]
+    async fn negotiated_handler_test() {
+        let handler: Negotiated = Negotiated::new()
+            .variant("application/json", |_req: Request| async {
+                Response::new(HttpResponseStatus::Ok).with_body(b"{}".to_vec())
+            })
+            .variant("text/html", |_req: Request| async {
+                Response::new(HttpResponseStatus::Ok).with_body(b"<p></p>".to_vec())
+            });
+        let request = |head: &[u8]| {
+            Request::new(
+                RequestType::Get,
+                b"/api/data".to_vec(),
+                head,
+                RequestBody::buffered(Vec::new()),
+                "127.0.0.1:4000".parse().unwrap(),
+                Instant::now() + Duration::from_secs(5),
+            )
+        };
+
+        let html: Response = handler
+            .handle(request(
+                b"GET /api/data HTTP/1.1\r\nAccept: text/html\r\n\r\n",
+            ))
+            .await;
+        assert_eq!(html.body, b"<p></p>".to_vec());
+        assert!(
+            html.headers
+                .contains(&(String::from("Vary"), String::from("Accept")))
+        );
+        assert!(
+            html.headers
+                .contains(&(String::from("Content-Type"), String::from("text/html")))
+        );
+
+        let refused: Response = handler
+            .handle(request(
+                b"GET /api/data HTTP/1.1\r\nAccept: image/png\r\n\r\n",
+            ))
+            .await;
+        assert_eq!(refused.status, HttpResponseStatus::NotAcceptable);
+    }
+}
@@ -0,0 +1,319 @@
+use crate::backend::server::HttpResponseStatus;
+use crate::utils::formatters::base64;
+use crate::utils::readers::files::{list_files, read_toml};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/* Name of the override files in the resource directory */
+pub const OVERRIDE_FILE: &str = ".diana";
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct DirectoryConfig {
+    /*
+     *  Contents of the .diana file, e.g.
+     *
+     *      [headers]
+     *      Cache-Control = "max-age=3600"
+     *
+     *      [auth]
+     *      realm = "Staff"
+     *      users = { admin = "secret" }
+     *
+     *      [[redirects]]
+     *      from = "old.html"
+     *      to = "/new.html"
+     *      status = 301
+     */
+    headers: HashMap<String, String>,
+    auth: Option<AuthConfig>,
+    redirects: Vec<RedirectConfig>,
+}
+
+#[derive(Debug, Deserialize)]
+struct AuthConfig {
+    realm: String,
+    users: HashMap<String, String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RedirectConfig {
+    from: String,
+    to: String,
+    #[serde(default = "default_redirect_status")]
+    status: u16,
+}
+
+#[derive(Debug, Clone)]
+pub struct BasicAuth {
+    /*
+     *  Attributes:
+     *      realm: Realm sent in WWW-Authenticate.
+     *      credentials: Expected user:password pairs, base64 encoded.
+     */
+    realm: String,
+    credentials: Vec<String>,
+}
+
+impl BasicAuth {
+    pub fn new(realm: String, users: &HashMap<String, String>) -> Self {
+        BasicAuth {
+            realm,
+            credentials: users
+                .iter()
+                .map(|(user, password)| base64::encode(format!("{user}:{password}").as_bytes()))
+                .collect(),
+        }
+    }
+
+    pub fn realm(&self) -> &str {
+        &self.realm
+    }
+
+    pub fn allows(&self, authorization: Option<&[u8]>) -> bool {
+        /*
+         *  Arguments:
+         *      authorization: Value of the Authorization header.
+         *
+         *  Returns:
+         *      True if the header carries one of the expected credentials.
+         */
+        authorization
+            .and_then(|value| value.strip_prefix(b"Basic "))
+            .map(<[u8]>::trim_ascii)
+            .is_some_and(|token| self.credentials.iter().any(|cred| cred.as_bytes() == token))
+    }
+}
+
+#[derive(Debug, Clone)]
+struct DirectoryOverride {
+    /*
+     *  Parsed .diana file.
+     *
+     *  Attributes:
+     *      prefix: Resource path of the directory, e.g. /docs/
+     *      headers: Headers added to the responses of the directory.
+     *      auth: Credentials required in the directory.
+     *      redirects: Resource paths redirected, with the target and status.
+     */
+    prefix: Vec<u8>,
+    headers: Vec<(String, String)>,
+    auth: Option<BasicAuth>,
+    redirects: Vec<(Vec<u8>, String, HttpResponseStatus)>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Directive {
+    /*
+     *  What the overrides decided about the request.
+     *
+     *  Serve: Serve the site, with the additional headers.
+     *  Redirect: Answer with the status and the Location.
+     *  Unauthorized: Ask for the credentials of the realm.
+     *  Hidden: The override file itself, it must never be served.
+     */
+    Serve(Vec<(String, String)>),
+    Redirect(HttpResponseStatus, String),
+    Unauthorized(String),
+    Hidden,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Overrides {
+    /*
+     *  Overrides of all the directories, the parents before the children.
+     */
+    dirs: Vec<DirectoryOverride>,
+}
+
+impl Overrides {
+    pub fn load(html_dir: &Path) -> Self {
+        /*
+         *  Find and parse the override files. Malformed files are reported
+         *  and skipped, so a typo doesn't take the server down.
+         *
+         *  Arguments:
+         *      html_dir: The resource directory.
+         */
+        let mut dirs: Vec<DirectoryOverride> = Vec::new();
+        for file in list_files(html_dir) {
+            if file.file_name().is_none_or(|name| name != OVERRIDE_FILE) {
+                continue;
+            }
+            let config: DirectoryConfig = match read_toml(&file) {
+                Ok(config) => config,
+                Err(e) => {
+                    println!("[ERROR] {}: {e}", file.display());
+                    continue;
+                }
+            };
+            let Some(prefix) = directory_prefix(html_dir, &file) else {
+                continue;
+            };
+            dirs.push(DirectoryOverride::new(prefix, config, &file));
+        }
+        dirs.sort_by_key(|dir| dir.prefix.len());
+        if !dirs.is_empty() {
+            println!("[INFO] Loaded {} directory overrides.", dirs.len());
+        }
+        Overrides { dirs }
+    }
+
+    pub fn len(&self) -> usize {
+        self.dirs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.dirs.is_empty()
+    }
+
+    pub fn resolve(&self, resource_path: &[u8], authorization: Option<&[u8]>) -> Directive {
+        /*
+         *  Apply the overrides of the directories containing the site.
+         *  The headers are merged, the nearest directory wins, and so does
+         *  its auth.
+         *
+         *  Arguments:
+         *      resource_path: Resource path from the request.
+         *      authorization: Value of the Authorization header.
+         */
+        if resource_path.rsplit(|byte| *byte == b'/').next() == Some(OVERRIDE_FILE.as_bytes()) {
+            return Directive::Hidden;
+        }
+        let mut headers: Vec<(String, String)> = Vec::new();
+        let mut auth: Option<&BasicAuth> = None;
+        for dir in self
+            .dirs
+            .iter()
+            .filter(|dir| resource_path.starts_with(&dir.prefix))
+        {
+            if let Some((_, to, status)) = dir
+                .redirects
+                .iter()
+                .find(|(from, _, _)| from == resource_path)
+            {
+                return Directive::Redirect(*status, to.clone());
+            }
+            for (name, value) in &dir.headers {
+                headers.retain(|(set, _)| !set.eq_ignore_ascii_case(name));
+                headers.push((name.clone(), value.clone()));
+            }
+            auth = dir.auth.as_ref().or(auth);
+        }
+        if let Some(auth) = auth
+            && !auth.allows(authorization)
+        {
+            return Directive::Unauthorized(auth.realm.clone());
+        }
+        Directive::Serve(headers)
+    }
+}
+
+impl DirectoryOverride {
+    fn new(prefix: Vec<u8>, config: DirectoryConfig, file: &Path) -> Self {
+        let mut headers: Vec<(String, String)> = config.headers.into_iter().collect();
+        headers.sort();
+        let auth: Option<BasicAuth> = config
+            .auth
+            .map(|auth| BasicAuth::new(auth.realm, &auth.users));
+        let mut redirects: Vec<(Vec<u8>, String, HttpResponseStatus)> = Vec::new();
+        for redirect in config.redirects {
+            let status: HttpResponseStatus = match redirect.status {
+                301 => HttpResponseStatus::MovedPermanently,
+                302 => HttpResponseStatus::Found,
+                307 => HttpResponseStatus::TemporaryRedirect,
+                308 => HttpResponseStatus::PermanentRedirect,
+                other => {
+                    println!(
+                        "[WARNING] {}: {other} is not a redirect status, {} is skipped.",
+                        file.display(),
+                        redirect.from
+                    );
+                    continue;
+                }
+            };
+            let mut from: Vec<u8> = prefix.clone();
+            from.extend_from_slice(redirect.from.trim_start_matches('/').as_bytes());
+            redirects.push((from, redirect.to, status));
+        }
+        DirectoryOverride {
+            prefix,
+            headers,
+            auth,
+            redirects,
+        }
+    }
+}
+
+fn directory_prefix(html_dir: &Path, file: &Path) -> Option<Vec<u8>> {
+    /*
+     *  Returns:
+     *      Resource path of the file's directory, with both slashes,
+     *      e.g. /docs/
+     */
+    let dir: PathBuf = file.parent()?.strip_prefix(html_dir).ok()?.to_path_buf();
+    let mut prefix: Vec<u8> = vec![b'/'];
+    for component in dir.components() {
+        prefix.extend_from_slice(component.as_os_str().to_str()?.as_bytes());
+        prefix.push(b'/');
+    }
+    Some(prefix)
+}
+
+fn default_redirect_status() -> u16 {
+    302
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn overrides_test() {
+        let html_dir: PathBuf =
+            std::env::temp_dir().join(format!("diana_overrides_{}", std::process::id()));
+        std::fs::create_dir_all(html_dir.join("docs/private")).unwrap();
+        std::fs::write(
+            html_dir.join("docs").join(OVERRIDE_FILE),
+            "[headers]\nCache-Control = \"max-age=60\"\nX-Section = \"docs\"\n\n[[redirects]]\nfrom = \"old.html\"\nto = \"/docs/new.html\"\nstatus = 301\n",
+        )
+        .unwrap();
+        std::fs::write(
+            html_dir.join("docs/private").join(OVERRIDE_FILE),
+            "[headers]\nCache-Control = \"no-store\"\n\n[auth]\nrealm = \"Staff\"\nusers = { admin = \"secret\" }\n",
+        )
+        .unwrap();
+        let overrides: Overrides = Overrides::load(&html_dir);
+        std::fs::remove_dir_all(&html_dir).unwrap();
+        assert_eq!(overrides.len(), 2);
+
+        assert_eq!(
+            overrides.resolve(b"/docs/old.html", None),
+            Directive::Redirect(
+                HttpResponseStatus::MovedPermanently,
+                String::from("/docs/new.html")
+            )
+        );
+        assert_eq!(
+            overrides.resolve(b"/docs/private/plan.html", None),
+            Directive::Unauthorized(String::from("Staff"))
+        );
+        assert_eq!(base64::encode(b"admin:secret"), "YWRtaW46c2VjcmV0");
+        assert_eq!(
+            overrides.resolve(b"/docs/private/plan.html", Some(b"Basic YWRtaW46c2VjcmV0")),
+            Directive::Serve(vec![
+                (String::from("X-Section"), String::from("docs")),
+                (String::from("Cache-Control"), String::from("no-store")),
+            ])
+        );
+        assert_eq!(
+            overrides.resolve(b"/docs/private/.diana", None),
+            Directive::Hidden
+        );
+        assert_eq!(
+            overrides.resolve(b"/index.html", None),
+            Directive::Serve(Vec::new())
+        );
+    }
+}
@@ -0,0 +1,86 @@
+use std::any::Any;
+use std::io;
+use tokio::io::AsyncWriteExt;
+use tokio::net::TcpStream;
+
+#[derive(Debug)]
+pub struct SpareHandle {
+    /*
+     *  Second handle of the connection's socket, that outlives the handler,
+     *  so the client is still answered, if the handler panics and its
+     *  stream is dropped while unwinding.
+     */
+    stream: std::net::TcpStream,
+}
+
+impl SpareHandle {
+    pub fn split(inc_stream: TcpStream) -> io::Result<(TcpStream, SpareHandle)> {
+        /*
+         *  Returns:
+         *      The stream for the handler and the spare handle of its socket.
+         */
+        let stream: std::net::TcpStream = inc_stream.into_std()?;
+        let spare: std::net::TcpStream = stream.try_clone()?;
+        Ok((TcpStream::from_std(stream)?, SpareHandle { stream: spare }))
+    }
+
+    pub async fn answer(self, response: &[u8]) -> io::Result<()> {
+        /*
+         *  Write the response and close the connection.
+         */
+        let mut stream: TcpStream = TcpStream::from_std(self.stream)?;
+        stream.write_all(response).await?;
+        stream.shutdown().await
+    }
+}
+
+pub fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    /*
+     *  Returns:
+     *      Message of the panic, the payloads of panic! are &str or String.
+     */
+    payload
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("unknown panic")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::AsyncReadExt;
+    use tokio::net::TcpListener;
+
+    #[tokio::test]
+    async fn panics_test() {
+        let listener: TcpListener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let mut client: TcpStream = TcpStream::connect(listener.local_addr().unwrap())
+            .await
+            .unwrap();
+        let (inc_stream, _) = listener.accept().await.unwrap();
+        let (inc_stream, spare) = SpareHandle::split(inc_stream).unwrap();
+
+        /* The handler's stream is gone with the task, the spare one answers */
+        let handler = tokio::spawn(async move {
+            let _owned: TcpStream = inc_stream;
+            panic!("index out of bounds");
+        });
+        let payload = handler.await.unwrap_err().into_panic();
+        assert_eq!(panic_message(&*payload), "index out of bounds");
+        spare
+            .answer(b"HTTP/1.1 500 Internal Server Error\r\n\r\n")
+            .await
+            .unwrap();
+        let mut response: Vec<u8> = Vec::new();
+        client.read_to_end(&mut response).await.unwrap();
+        assert_eq!(response, b"HTTP/1.1 500 Internal Server Error\r\n\r\n");
+
+        let formatted = tokio::spawn(async { panic!("{} is missing", "site") })
+            .await
+            .unwrap_err()
+            .into_panic();
+        assert_eq!(panic_message(&*formatted), "site is missing");
+        assert_eq!(panic_message(&42), "unknown panic");
+    }
+}
@@ -0,0 +1,177 @@
+use std::cmp;
+use std::ops::{Deref, DerefMut};
+use std::sync::{Arc, Mutex};
+
+/* Capacity of the buffers, that the requests of a connection are read into */
+pub const CONNECTION_BUFFER_SIZE: usize = 8192;
+/* Buffers, that grew past this, are freed instead, so one large request doesn't pin the memory */
+const MAX_POOLED_CAPACITY: usize = 64 * 1024;
+
+#[derive(Debug)]
+pub struct BufferPool {
+    /*
+     *  Buffers of the connections, reused by the next connections and
+     *  the next requests of the connection, instead of allocated anew.
+     *
+     *  Attributes:
+     *      buffer_size: Capacity of the new buffers.
+     *      max_pooled: The number of the free buffers kept.
+     *      free: The free buffers, empty but with their capacity.
+     */
+    buffer_size: usize,
+    max_pooled: usize,
+    free: Arc<Mutex<Vec<Vec<u8>>>>,
+}
+
+#[derive(Debug)]
+pub struct PooledBuffer {
+    /*
+     *  Buffer taken from the pool, it is cleared and returned once
+     *  it is dropped.
+     */
+    buffer: Vec<u8>,
+    max_pooled: usize,
+    free: Arc<Mutex<Vec<Vec<u8>>>>,
+}
+
+impl BufferPool {
+    pub fn new(buffer_size: usize, max_pooled: usize) -> Self {
+        BufferPool {
+            buffer_size,
+            max_pooled,
+            free: Arc::new(Mutex::new(Vec::with_capacity(max_pooled))),
+        }
+    }
+
+    pub fn take(&self) -> PooledBuffer {
+        /*
+         *  Returns:
+         *      An empty buffer, a free one if there is any.
+         */
+        let buffer: Vec<u8> = self
+            .free
+            .lock()
+            .unwrap()
+            .pop()
+            .unwrap_or_else(|| Vec::with_capacity(self.buffer_size));
+        PooledBuffer {
+            buffer,
+            max_pooled: self.max_pooled,
+            free: Arc::clone(&self.free),
+        }
+    }
+
+    pub fn pooled(&self) -> usize {
+        self.free.lock().unwrap().len()
+    }
+}
+
+pub fn grow_for_head(buffer: &mut Vec<u8>, max_head_size: usize) {
+    /*
+     *  Make room for the next read of the request head. The full buffer
+     *  doubles, so the large heads, e.g. with big cookies, take a few
+     *  reads, but it never grows much past the limit of the head.
+     *
+     *  Arguments:
+     *      buffer: Buffer of the connection.
+     *      max_head_size: The maximum length of the request head.
+     */
+    if buffer.len() < buffer.capacity() {
+        return;
+    }
+    let additional: usize = cmp::max(buffer.capacity(), CONNECTION_BUFFER_SIZE)
+        .min(max_head_size.saturating_sub(buffer.len()))
+        .max(1);
+    buffer.reserve_exact(additional);
+}
+
+impl Deref for PooledBuffer {
+    type Target = Vec<u8>;
+
+    fn deref(&self) -> &Vec<u8> {
+        &self.buffer
+    }
+}
+
+impl DerefMut for PooledBuffer {
+    fn deref_mut(&mut self) -> &mut Vec<u8> {
+        &mut self.buffer
+    }
+}
+
+impl Drop for PooledBuffer {
+    fn drop(&mut self) {
+        /* The buffers moved out by mem::take have no capacity left to reuse */
+        if self.buffer.capacity() == 0 || self.buffer.capacity() > MAX_POOLED_CAPACITY {
+            return;
+        }
+        let mut free = self.free.lock().unwrap();
+        if free.len() < self.max_pooled {
+            let mut buffer: Vec<u8> = std::mem::take(&mut self.buffer);
+            buffer.clear();
+            free.push(buffer);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn buffer_pool_test() {
+        let pool: BufferPool = BufferPool::new(1024, 2);
+        let mut first: PooledBuffer = pool.take();
+        first.extend_from_slice(b"GET / HTTP/1.1\r\n\r\n");
+        let first_ptr: *const u8 = first.as_ptr();
+        drop(first);
+        assert_eq!(pool.pooled(), 1);
+
+        /* The buffer is reused, empty */
+        let second: PooledBuffer = pool.take();
+        assert!(second.is_empty());
+        assert_eq!(second.as_ptr(), first_ptr);
+        assert!(second.capacity() >= 1024);
+        assert_eq!(pool.pooled(), 0);
+
+        /* Only max_pooled buffers are kept */
+        let third: PooledBuffer = pool.take();
+        let fourth: PooledBuffer = pool.take();
+        drop((second, third, fourth));
+        assert_eq!(pool.pooled(), 2);
+
+        /* The grown and the taken buffers aren't */
+        let mut grown: PooledBuffer = pool.take();
+        grown.reserve(MAX_POOLED_CAPACITY + 1);
+        drop(grown);
+        let mut taken: PooledBuffer = pool.take();
+        let _moved: Vec<u8> = std::mem::take(&mut *taken);
+        drop(taken);
+        assert_eq!(pool.pooled(), 0);
+    }
+
+    #[test]
+    fn grow_for_head_test() {
+        let mut buffer: Vec<u8> = Vec::with_capacity(1024);
+        buffer.resize(512, b'a');
+        grow_for_head(&mut buffer, 32 * 1024);
+        assert_eq!(buffer.capacity(), 1024);
+
+        /* The full buffer doubles, at least to the size of the new ones */
+        buffer.resize(1024, b'a');
+        grow_for_head(&mut buffer, 32 * 1024);
+        assert!(buffer.capacity() >= 1024 + CONNECTION_BUFFER_SIZE);
+        let capacity: usize = buffer.capacity();
+        buffer.resize(capacity, b'a');
+        grow_for_head(&mut buffer, 32 * 1024);
+        assert!(buffer.capacity() >= 2 * capacity);
+
+        /* Near the limit only the rest of the head, and a byte to see it is exceeded */
+        let mut buffer: Vec<u8> = vec![b'a'; 30 * 1024];
+        grow_for_head(&mut buffer, 32 * 1024);
+        assert!(buffer.capacity() >= 32 * 1024 && buffer.capacity() < 48 * 1024);
+        buffer.resize(buffer.capacity(), b'a');
+        grow_for_head(&mut buffer, 32 * 1024);
+        assert!(buffer.capacity() > buffer.len());
+    }
+}
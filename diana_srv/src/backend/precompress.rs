@@ -0,0 +1,165 @@
+use crate::backend::compression::{ContentCoding, SUPPORTED_CODINGS, is_compressible};
+use crate::utils::readers::files::list_files;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/* Extensions of the encoded files, that precompress writes next to the sites */
+const ENCODED_EXTENSIONS: [&str; 2] = ["gz", "br"];
+
+#[derive(Debug, Default, PartialEq)]
+pub struct PrecompressReport {
+    /*
+     *  Outcome of the precompression of the directory.
+     *
+     *  Attributes:
+     *      written: Encoded files written next to the sites.
+     *      up_to_date: Encoded files newer than their sites, left alone.
+     *      not_smaller: Encodings, that didn't shrink the site, they
+     *      aren't written and their old files are removed.
+     *      saved_bytes: Bytes saved by the written files together.
+     */
+    pub written: usize,
+    pub up_to_date: usize,
+    pub not_smaller: usize,
+    pub saved_bytes: u64,
+}
+
+pub fn precompress(dir: &Path) -> Result<PrecompressReport, io::Error> {
+    /*
+     *  Encode every compressible site of the directory at the highest level
+     *  with every supported encoding, e.g. app.js gets app.js.gz and
+     *  app.js.br next to it.
+     *
+     *  Arguments:
+     *      dir: The resource directory.
+     *
+     *  Returns:
+     *      What was written, or the error of the first site, that failed
+     *      to be read or written.
+     */
+    if !dir.is_dir() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("{} is not a directory", dir.display()),
+        ));
+    }
+    let mut report: PrecompressReport = PrecompressReport::default();
+    for file in list_files(dir) {
+        if !file
+            .to_str()
+            .is_some_and(|path| is_compressible(path.as_bytes()))
+        {
+            continue;
+        }
+        let mut content: Option<Vec<u8>> = None;
+        for coding in SUPPORTED_CODINGS {
+            if fresh_encoded_path(&file, *coding).is_some() {
+                report.up_to_date += 1;
+                continue;
+            }
+            let encoded_file: PathBuf = encoded_path(&file, *coding);
+            if content.is_none() {
+                content = Some(fs::read(&file)?);
+            }
+            let content: &[u8] = content.as_deref().unwrap_or_default();
+            let encoded: Vec<u8> = coding.encode_max(content)?;
+            if encoded.len() >= content.len() {
+                report.not_smaller += 1;
+                let _ = fs::remove_file(&encoded_file);
+                continue;
+            }
+            fs::write(&encoded_file, &encoded)?;
+            report.written += 1;
+            report.saved_bytes += (content.len() - encoded.len()) as u64;
+        }
+    }
+    Ok(report)
+}
+
+pub fn encoded_path(file: &Path, coding: ContentCoding) -> PathBuf {
+    /*
+     *  Returns:
+     *      Path of the encoded file next to the site, e.g. app.js.gz
+     */
+    let mut encoded = file.as_os_str().to_owned();
+    encoded.push(".");
+    encoded.push(coding.file_extension());
+    PathBuf::from(encoded)
+}
+
+pub fn fresh_encoded_path(file: &Path, coding: ContentCoding) -> Option<PathBuf> {
+    /*
+     *  Returns:
+     *      Path of the encoded file next to the site, None if it is missing
+     *      or older than the site, e.g. the site was edited since.
+     */
+    let modified: SystemTime = fs::metadata(file).and_then(|m| m.modified()).ok()?;
+    let encoded_file: PathBuf = encoded_path(file, coding);
+    fs::metadata(&encoded_file)
+        .and_then(|metadata| metadata.modified())
+        .is_ok_and(|encoded| encoded >= modified)
+        .then_some(encoded_file)
+}
+
+pub fn is_encoded_sibling(file: &Path) -> bool {
+    /*
+     *  Returns:
+     *      True if the file is the encoded copy of the site next to it,
+     *      e.g. app.js.gz next to app.js. It isn't a site of its own.
+     */
+    file.extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| ENCODED_EXTENSIONS.contains(&extension))
+        && file.with_extension("").is_file()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::read::GzDecoder;
+    use std::io::Read;
+
+    #[test]
+    fn precompress_test() {
+        let dir: PathBuf =
+            std::env::temp_dir().join(format!("diana_precompress_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("css")).unwrap();
+        let page: Vec<u8> = b"<p>diana</p>".repeat(100);
+        fs::write(dir.join("index.html"), &page).unwrap();
+        fs::write(dir.join("css/site.css"), b"a{}").unwrap();
+        fs::write(dir.join("logo.png"), &page).unwrap();
+
+        let codings: usize = SUPPORTED_CODINGS.len();
+        let report: PrecompressReport = precompress(&dir).unwrap();
+        assert_eq!(report.written, codings);
+        assert_eq!(report.not_smaller, codings);
+        assert!(report.saved_bytes > 0);
+        let mut decoded: Vec<u8> = Vec::new();
+        GzDecoder::new(fs::File::open(dir.join("index.html.gz")).unwrap())
+            .read_to_end(&mut decoded)
+            .unwrap();
+        assert_eq!(decoded, page);
+        assert!(!dir.join("css/site.css.gz").exists());
+        assert!(!dir.join("logo.png.gz").exists());
+        assert!(is_encoded_sibling(&dir.join("index.html.gz")));
+        assert!(!is_encoded_sibling(&dir.join("index.html")));
+        assert_eq!(
+            fresh_encoded_path(&dir.join("index.html"), ContentCoding::Gzip),
+            Some(dir.join("index.html.gz"))
+        );
+        assert_eq!(
+            fresh_encoded_path(&dir.join("logo.png"), ContentCoding::Gzip),
+            None
+        );
+
+        /* The encoded files newer than their sites aren't encoded again */
+        let report: PrecompressReport = precompress(&dir).unwrap();
+        assert_eq!(report.written, 0);
+        assert_eq!(report.up_to_date, codings);
+        assert!(precompress(&dir.join("missing")).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,297 @@
+use crate::backend::server::{HttpResponseStatus, RequestType};
+use diana_http::dates::parse_http_date;
+use diana_http::headers::HeaderMap;
+use diana_http::ranges::{RangeSelection, parse_range};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, Weak};
+use tokio::sync::{Mutex as AsyncMutex, OwnedMutexGuard};
+
+#[derive(Debug, Default)]
+pub struct PathLocks {
+    /*
+     *  Locks of the resource paths, that the modifying handlers hold while
+     *  they check the preconditions and write, so two writers of the same
+     *  path never interleave. The locks of the paths, that nobody holds,
+     *  are dropped.
+     */
+    locks: Mutex<HashMap<Vec<u8>, Weak<AsyncMutex<()>>>>,
+}
+
+#[derive(Debug)]
+pub struct PathGuard {
+    /*
+     *  The path is locked, until the guard is dropped.
+     */
+    _guard: OwnedMutexGuard<()>,
+}
+
+impl PathLocks {
+    fn lock_of(&self, resource_path: &[u8]) -> Arc<AsyncMutex<()>> {
+        let mut locks = self.locks.lock().unwrap();
+        locks.retain(|_, lock| lock.strong_count() > 0);
+        if let Some(lock) = locks.get(resource_path).and_then(Weak::upgrade) {
+            return lock;
+        }
+        let lock: Arc<AsyncMutex<()>> = Arc::new(AsyncMutex::new(()));
+        locks.insert(resource_path.to_vec(), Arc::downgrade(&lock));
+        lock
+    }
+
+    pub async fn lock(&self, resource_path: &[u8]) -> PathGuard {
+        /*
+         *  Wait for the other writers of the path, e.g. the concurrent PUTs.
+         */
+        PathGuard {
+            _guard: self.lock_of(resource_path).lock_owned().await,
+        }
+    }
+
+    pub fn try_lock(&self, resource_path: &[u8]) -> Result<PathGuard, HttpResponseStatus> {
+        /*
+         *  Lock the path without waiting.
+         *
+         *  Returns:
+         *      The guard, or 409 if the path is being modified. WebDAV
+         *      handlers answer 423 instead, their clients retry on their own.
+         */
+        self.lock_of(resource_path)
+            .try_lock_owned()
+            .map(|guard| PathGuard { _guard: guard })
+            .map_err(|_| HttpResponseStatus::Conflict)
+    }
+}
+
+pub fn entity_tag(content: &[u8]) -> String {
+    /*
+     *  Strong validator of the content, FNV-1a of the bytes, so it is
+     *  the same across restarts.
+     *
+     *  Returns:
+     *      The quoted tag, e.g. "5f0e3c1a9b2d7e40-12"
+     */
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in content {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    format!("\"{hash:016x}-{:x}\"", content.len())
+}
+
+pub fn check_preconditions(
+    headers: &HeaderMap,
+    method: RequestType,
+    current: Option<&str>,
+) -> Result<(), HttpResponseStatus> {
+    /*
+     *  Evaluate If-Match and If-None-Match of the request, as RFC 9110
+     *  section 13.2.2 orders them.
+     *
+     *  Arguments:
+     *      headers: Headers of the request.
+     *      method: Method of the request.
+     *      current: Entity tag of the resource, None if it doesn't exist.
+     *
+     *  Returns:
+     *      Ok if the request may proceed, 304 if GET or HEAD of the client's
+     *      copy is still fresh, 412 if the client's copy is stale or
+     *      the modified one is fresh, or 400 if the tags are malformed.
+     */
+    if let Some(if_match) = headers.get_joined("if-match") {
+        let tags: Vec<&[u8]> = list_members(&if_match).ok_or(HttpResponseStatus::BadRequest)?;
+        let matched: bool = match current {
+            Some(_) if tags == [b"*"] => true,
+            /* The weak tags never match strongly */
+            Some(current) => !current.starts_with("W/") && tags.contains(&current.as_bytes()),
+            None => false,
+        };
+        if !matched {
+            return Err(HttpResponseStatus::PreconditionFailed);
+        }
+    }
+    if let Some(if_none_match) = headers.get_joined("if-none-match") {
+        let tags: Vec<&[u8]> =
+            list_members(&if_none_match).ok_or(HttpResponseStatus::BadRequest)?;
+        let matched: bool = match current {
+            Some(_) if tags == [b"*"] => true,
+            Some(current) => tags
+                .iter()
+                .any(|tag| opaque_tag(tag) == opaque_tag(current.as_bytes())),
+            None => false,
+        };
+        if matched && matches!(method, RequestType::Get | RequestType::Head) {
+            return Err(HttpResponseStatus::NotModified);
+        }
+        if matched {
+            return Err(HttpResponseStatus::PreconditionFailed);
+        }
+    }
+    Ok(())
+}
+
+pub fn select_range(headers: &HeaderMap, etag: &str, modified: u64, len: usize) -> RangeSelection {
+    /*
+     *  Evaluate Range of the GET request with its If-Range, so the download
+     *  is resumed only if the file didn't change since the client got
+     *  the first part.
+     *
+     *  Arguments:
+     *      headers: Headers of the request.
+     *      etag: Entity tag of the site.
+     *      modified: Seconds since the epoch, when the site was modified.
+     *      len: Length of the site.
+     *
+     *  Returns:
+     *      The range to serve, the whole site if If-Range doesn't match.
+     */
+    let Some(range) = headers.get("range") else {
+        return RangeSelection::Full;
+    };
+    if let Some(if_range) = headers.get("if-range")
+        && !if_range_matches(if_range.trim_ascii(), etag, modified)
+    {
+        return RangeSelection::Full;
+    }
+    parse_range(range, len)
+}
+
+fn if_range_matches(if_range: &[u8], etag: &str, modified: u64) -> bool {
+    /*
+     *  The entity tag is compared strongly, so the weak ones never match.
+     *  The date has to be the exact time of the modification.
+     */
+    if if_range.starts_with(b"\"") || if_range.starts_with(b"W/") {
+        return if_range == etag.as_bytes();
+    }
+    std::str::from_utf8(if_range)
+        .ok()
+        .and_then(parse_http_date)
+        .is_some_and(|date| date == modified)
+}
+
+fn list_members(value: &[u8]) -> Option<Vec<&[u8]>> {
+    /*
+     *  Split the list of the entity tags, e.g. "a", W/"b"
+     *
+     *  Returns:
+     *      The tags, None if one of them isn't quoted.
+     */
+    value
+        .split(|byte| *byte == b',')
+        .map(<[u8]>::trim_ascii)
+        .filter(|tag| !tag.is_empty())
+        .map(|tag| {
+            let quoted: &[u8] = tag.strip_prefix(b"W/").unwrap_or(tag);
+            let valid: bool = tag == b"*"
+                || (quoted.len() >= 2 && quoted.starts_with(b"\"") && quoted.ends_with(b"\""));
+            valid.then_some(tag)
+        })
+        .collect()
+}
+
+fn opaque_tag(tag: &[u8]) -> &[u8] {
+    tag.strip_prefix(b"W/").unwrap_or(tag)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn preconditions_test() {
+        let tag: String = entity_tag(b"diana");
+        assert_eq!(tag, entity_tag(b"diana"));
+        assert_ne!(tag, entity_tag(b"selene"));
+
+        let request = |headers: &str| {
+            HeaderMap::parse(format!("PUT /a HTTP/1.1\r\n{headers}\r\n\r\n").as_bytes())
+        };
+        let modifying = |headers: &HeaderMap, current: Option<&str>| {
+            check_preconditions(headers, RequestType::Post, current)
+        };
+        assert!(modifying(&request("Host: a"), None).is_ok());
+        assert!(modifying(&request(&format!("If-Match: \"x\", {tag}")), Some(&tag)).is_ok());
+        assert_eq!(
+            modifying(&request(&format!("If-Match: W/{tag}")), Some(&tag)),
+            Err(HttpResponseStatus::PreconditionFailed)
+        );
+        assert_eq!(
+            modifying(&request("If-Match: *"), None),
+            Err(HttpResponseStatus::PreconditionFailed)
+        );
+        assert!(modifying(&request("If-None-Match: *"), None).is_ok());
+        assert_eq!(
+            modifying(&request(&format!("If-None-Match: W/{tag}")), Some(&tag)),
+            Err(HttpResponseStatus::PreconditionFailed)
+        );
+        assert_eq!(
+            modifying(&request("If-Match: unquoted"), Some(&tag)),
+            Err(HttpResponseStatus::BadRequest)
+        );
+
+        /* The fresh copy of GET isn't sent again */
+        assert_eq!(
+            check_preconditions(
+                &request(&format!("If-None-Match: \"x\", W/{tag}")),
+                RequestType::Get,
+                Some(&tag)
+            ),
+            Err(HttpResponseStatus::NotModified)
+        );
+        assert_eq!(
+            check_preconditions(
+                &request(&format!("If-Match: W/{tag}")),
+                RequestType::Get,
+                Some(&format!("W/{tag}"))
+            ),
+            Err(HttpResponseStatus::PreconditionFailed)
+        );
+        assert!(
+            check_preconditions(
+                &request("If-None-Match: \"x\""),
+                RequestType::Head,
+                Some(&tag)
+            )
+            .is_ok()
+        );
+
+        /* The download is resumed only from the same file */
+        let (etag, modified): (&str, u64) = ("\"5f0e3c1a-3e8\"", 784111777);
+        let ranged = |headers: &str| select_range(&request(headers), etag, modified, 1000);
+        assert_eq!(ranged("Host: a"), RangeSelection::Full);
+        assert_eq!(
+            ranged("Range: bytes=500-"),
+            RangeSelection::Partial(500..1000)
+        );
+        assert_eq!(
+            ranged(&format!("Range: bytes=500-\r\nIf-Range: {etag}")),
+            RangeSelection::Partial(500..1000)
+        );
+        assert_eq!(
+            ranged("Range: bytes=500-\r\nIf-Range: Sun, 06 Nov 1994 08:49:37 GMT"),
+            RangeSelection::Partial(500..1000)
+        );
+        assert_eq!(
+            ranged("Range: bytes=500-\r\nIf-Range: \"changed-3e8\""),
+            RangeSelection::Full
+        );
+        assert_eq!(
+            ranged(&format!("Range: bytes=500-\r\nIf-Range: W/{etag}")),
+            RangeSelection::Full
+        );
+        assert_eq!(
+            ranged("Range: bytes=500-\r\nIf-Range: Sun, 06 Nov 1994 08:49:38 GMT"),
+            RangeSelection::Full
+        );
+        assert_eq!(ranged("Range: bytes=2000-"), RangeSelection::Unsatisfiable);
+
+        let locks: PathLocks = PathLocks::default();
+        let guard: PathGuard = locks.lock(b"/a").await;
+        assert_eq!(
+            locks.try_lock(b"/a").unwrap_err(),
+            HttpResponseStatus::Conflict
+        );
+        assert!(locks.try_lock(b"/b").is_ok());
+        drop(guard);
+        assert!(locks.try_lock(b"/a").is_ok());
+    }
+}
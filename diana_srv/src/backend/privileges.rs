@@ -0,0 +1,141 @@
+use std::io;
+use std::path::Path;
+
+#[cfg(unix)]
+use std::ffi::CString;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Identity {
+    /*
+     *  Unprivileged user, that the server switches to.
+     *
+     *  Attributes:
+     *      uid: ID of the user.
+     *      gid: ID of the primary group.
+     */
+    pub uid: u32,
+    pub gid: u32,
+}
+
+#[cfg(unix)]
+pub fn resolve_identity(user: &str, group: Option<&str>) -> io::Result<Identity> {
+    /*
+     *  Look the user and the group up in the system databases. It must be
+     *  done before chroot, the databases are outside of it.
+     *
+     *  Arguments:
+     *      user: Name or ID of the user.
+     *      group: Name or ID of the group, the user's primary group if missing.
+     *
+     *  Returns:
+     *      The IDs, or the error if the user or the group doesn't exist.
+     */
+    let not_found = |kind: &str, name: &str| {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("{kind} {name} doesn't exist"),
+        )
+    };
+    let c_user: CString = CString::new(user).map_err(|_| not_found("user", user))?;
+    /* SAFETY: the name is NUL-terminated, the entry is copied out right away */
+    let passwd: *mut libc::passwd = unsafe { libc::getpwnam(c_user.as_ptr()) };
+    let (uid, primary_gid): (u32, u32) = if !passwd.is_null() {
+        unsafe { ((*passwd).pw_uid, (*passwd).pw_gid) }
+    } else {
+        let uid: u32 = user.parse().map_err(|_| not_found("user", user))?;
+        (uid, uid)
+    };
+    let gid: u32 = match group {
+        None => primary_gid,
+        Some(group) => {
+            let c_group: CString = CString::new(group).map_err(|_| not_found("group", group))?;
+            /* SAFETY: the name is NUL-terminated, the entry is copied out right away */
+            let entry: *mut libc::group = unsafe { libc::getgrnam(c_group.as_ptr()) };
+            if !entry.is_null() {
+                unsafe { (*entry).gr_gid }
+            } else {
+                group.parse().map_err(|_| not_found("group", group))?
+            }
+        }
+    };
+    Ok(Identity { uid, gid })
+}
+
+#[cfg(unix)]
+pub fn drop_privileges(identity: Identity, chroot_dir: Option<&Path>) -> io::Result<()> {
+    /*
+     *  Give up root, once the listener is bound. The group goes first,
+     *  it can't be changed after the user is.
+     *
+     *  Arguments:
+     *      identity: User and group to switch to.
+     *      chroot_dir: Directory, that becomes the root of the filesystem.
+     */
+    let check = |ret: libc::c_int| {
+        if ret == 0 {
+            Ok(())
+        } else {
+            Err(io::Error::last_os_error())
+        }
+    };
+    if let Some(dir) = chroot_dir {
+        std::os::unix::fs::chroot(dir)?;
+        std::env::set_current_dir("/")?;
+    }
+    /* SAFETY: plain syscalls, they only take the IDs */
+    unsafe {
+        check(libc::setgroups(1, &identity.gid))?;
+        check(libc::setgid(identity.gid))?;
+        check(libc::setuid(identity.uid))?;
+    }
+    /* Root must not be reachable again, e.g. through the saved ID */
+    if identity.uid != 0 && unsafe { libc::setuid(0) } == 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            "root privileges could be regained",
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn resolve_identity(_user: &str, _group: Option<&str>) -> io::Result<Identity> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "switching the user is only supported on unix",
+    ))
+}
+
+#[cfg(not(unix))]
+pub fn drop_privileges(_identity: Identity, _chroot_dir: Option<&Path>) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "switching the user is only supported on unix",
+    ))
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolve_identity_test() {
+        assert_eq!(
+            resolve_identity("root", None).unwrap(),
+            Identity { uid: 0, gid: 0 }
+        );
+        assert_eq!(
+            resolve_identity("1000", Some("1001")).unwrap(),
+            Identity {
+                uid: 1000,
+                gid: 1001
+            }
+        );
+        assert_eq!(
+            resolve_identity("no-such-user-diana", None)
+                .unwrap_err()
+                .kind(),
+            io::ErrorKind::NotFound
+        );
+    }
+}
@@ -0,0 +1,286 @@
+use std::fmt;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+/* \r\n\r\n\0\r\nQUIT\n */
+const V2_SIGNATURE: [u8; 12] = [13, 10, 13, 10, 0, 13, 10, 81, 85, 73, 84, 10];
+/* PROXY */
+const V1_PREFIX: [u8; 6] = [80, 82, 79, 88, 89, 32];
+/* The longest v1 header, including the CRLF */
+const V1_MAX_LEN: usize = 107;
+
+#[derive(Debug, PartialEq)]
+pub enum ProxyHeaderError {
+    /*
+     *  Reasons to drop the connection, that should start with the PROXY
+     *  protocol header.
+     */
+    Missing,
+    Incomplete,
+    Malformed,
+    UnsupportedVersion,
+}
+
+impl fmt::Display for ProxyHeaderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let msg: &str = match self {
+            Self::Missing => "PROXY protocol header is missing",
+            Self::Incomplete => "PROXY protocol header is incomplete",
+            Self::Malformed => "PROXY protocol header is malformed",
+            Self::UnsupportedVersion => "PROXY protocol version is not supported",
+        };
+        write!(f, "{msg}")
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct ProxyHeader {
+    /*
+     *  Header sent by the load balancer before the request.
+     *
+     *  Attributes:
+     *      source: Address of the real client, None if the balancer doesn't
+     *      forward it, e.g. for its own health checks.
+     *      len: Length of the header, the request starts right after it.
+     */
+    pub source: Option<SocketAddr>,
+    pub len: usize,
+}
+
+pub fn parse_proxy_header(buffer: &[u8]) -> Result<ProxyHeader, ProxyHeaderError> {
+    /*
+     *  Parse the PROXY protocol header, both the text v1 and the binary v2.
+     *
+     *  Arguments:
+     *      buffer: Bytes read from the accepted connection.
+     *
+     *  Returns:
+     *      The header, or the reason, why the connection must be dropped.
+     *      Incomplete if the header might continue in the next read.
+     */
+    if buffer.starts_with(&V2_SIGNATURE) {
+        parse_v2(buffer)
+    } else if buffer.starts_with(&V1_PREFIX) {
+        parse_v1(buffer)
+    } else if V2_SIGNATURE.starts_with(buffer) || V1_PREFIX.starts_with(buffer) {
+        Err(ProxyHeaderError::Incomplete)
+    } else {
+        Err(ProxyHeaderError::Missing)
+    }
+}
+
+pub async fn read_proxy_header<R: AsyncRead + Unpin>(
+    stream: &mut R,
+    buffer: &mut Vec<u8>,
+) -> Result<ProxyHeader, io::Error> {
+    /*
+     *  Read until the whole PROXY protocol header is buffered, the balancer
+     *  might send it in several segments. The reads are bounded by the size
+     *  of the header, v1 ends at its CRLF and v2 at its address length.
+     *
+     *  Arguments:
+     *      stream: Stream of the accepted connection.
+     *      buffer: Bytes read from it so far, the next reads are appended.
+     *
+     *  Returns:
+     *      The header, or the error, why the connection must be dropped.
+     */
+    loop {
+        match parse_proxy_header(buffer) {
+            Err(ProxyHeaderError::Incomplete) => {}
+            parsed => {
+                return parsed
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()));
+            }
+        }
+        if stream.read_buf(buffer).await? == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                ProxyHeaderError::Incomplete.to_string(),
+            ));
+        }
+    }
+}
+
+fn parse_v1(buffer: &[u8]) -> Result<ProxyHeader, ProxyHeaderError> {
+    /*
+     *  PROXY TCP4 192.168.0.1 192.168.0.11 56324 443\r\n
+     */
+    let window: &[u8] = &buffer[..buffer.len().min(V1_MAX_LEN)];
+    let eol_idx: usize =
+        window
+            .windows(2)
+            .position(|pair| pair == b"\r\n")
+            .ok_or(if window.len() < V1_MAX_LEN {
+                ProxyHeaderError::Incomplete
+            } else {
+                ProxyHeaderError::Malformed
+            })?;
+    let line: &str =
+        std::str::from_utf8(&window[..eol_idx]).map_err(|_| ProxyHeaderError::Malformed)?;
+    let fields: Vec<&str> = line.split(' ').collect();
+    let len: usize = eol_idx + 2;
+
+    match fields.get(1).copied() {
+        Some("UNKNOWN") => Ok(ProxyHeader { source: None, len }),
+        Some("TCP4") | Some("TCP6") if fields.len() == 6 => {
+            let ip: IpAddr = fields[2].parse().map_err(|_| ProxyHeaderError::Malformed)?;
+            let port: u16 = fields[4].parse().map_err(|_| ProxyHeaderError::Malformed)?;
+            if ip.is_ipv4() != (fields[1] == "TCP4") {
+                return Err(ProxyHeaderError::Malformed);
+            }
+            Ok(ProxyHeader {
+                source: Some(SocketAddr::new(ip, port)),
+                len,
+            })
+        }
+        _ => Err(ProxyHeaderError::Malformed),
+    }
+}
+
+fn parse_v2(buffer: &[u8]) -> Result<ProxyHeader, ProxyHeaderError> {
+    /*
+     *  Signature, version with the command, family with the protocol,
+     *  the length of the addresses and the addresses themselves.
+     */
+    let fixed: &[u8] = buffer.get(12..16).ok_or(ProxyHeaderError::Incomplete)?;
+    if fixed[0] >> 4 != 2 {
+        return Err(ProxyHeaderError::UnsupportedVersion);
+    }
+    let addrs_len: usize = u16::from_be_bytes([fixed[2], fixed[3]]).into();
+    let len: usize = 16 + addrs_len;
+    let addrs: &[u8] = buffer.get(16..len).ok_or(ProxyHeaderError::Incomplete)?;
+
+    /* LOCAL command, the connection was made by the balancer itself */
+    if fixed[0] & 0x0F == 0 {
+        return Ok(ProxyHeader { source: None, len });
+    }
+    if fixed[0] & 0x0F != 1 {
+        return Err(ProxyHeaderError::Malformed);
+    }
+    let source: Option<SocketAddr> = match fixed[1] >> 4 {
+        /* AF_INET */
+        1 => {
+            let raw: &[u8] = addrs.get(..12).ok_or(ProxyHeaderError::Malformed)?;
+            let ip: [u8; 4] = raw[..4].try_into().unwrap();
+            Some(SocketAddr::new(
+                IpAddr::V4(Ipv4Addr::from(ip)),
+                u16::from_be_bytes([raw[8], raw[9]]),
+            ))
+        }
+        /* AF_INET6 */
+        2 => {
+            let raw: &[u8] = addrs.get(..36).ok_or(ProxyHeaderError::Malformed)?;
+            let ip: [u8; 16] = raw[..16].try_into().unwrap();
+            Some(SocketAddr::new(
+                IpAddr::V6(Ipv6Addr::from(ip)),
+                u16::from_be_bytes([raw[32], raw[33]]),
+            ))
+        }
+        /* AF_UNSPEC or AF_UNIX, there is no address to take over */
+        _ => None,
+    };
+    Ok(ProxyHeader { source, len })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use tokio::io::AsyncWriteExt;
+
+    #[test]
+    fn parse_proxy_header_test() {
+        let v1: &[u8] = b"PROXY TCP4 192.168.0.1 192.168.0.11 56324 443\r\nGET / HTTP/1.1\r\n";
+        assert_eq!(
+            parse_proxy_header(v1),
+            Ok(ProxyHeader {
+                source: Some("192.168.0.1:56324".parse().unwrap()),
+                len: 47,
+            })
+        );
+        assert_eq!(
+            parse_proxy_header(b"PROXY UNKNOWN\r\n"),
+            Ok(ProxyHeader {
+                source: None,
+                len: 15
+            })
+        );
+        assert_eq!(
+            parse_proxy_header(b"PROXY TCP4 ::1 ::1 1 2\r\n"),
+            Err(ProxyHeaderError::Malformed)
+        );
+        assert_eq!(
+            parse_proxy_header(b"GET / HTTP/1.1\r\n"),
+            Err(ProxyHeaderError::Missing)
+        );
+
+        let mut v2: Vec<u8> = V2_SIGNATURE.to_vec();
+        v2.extend_from_slice(&[
+            0x21, 0x11, 0, 12, 10, 0, 0, 1, 10, 0, 0, 2, 0x1F, 0x90, 0, 80,
+        ]);
+        v2.extend_from_slice(b"GET / HTTP/1.1\r\n");
+        assert_eq!(
+            parse_proxy_header(&v2),
+            Ok(ProxyHeader {
+                source: Some("10.0.0.1:8080".parse().unwrap()),
+                len: 28,
+            })
+        );
+
+        /* The parts of the header, that the next read completes */
+        for part in [
+            &b""[..],
+            b"PRO",
+            b"PROXY TCP4 192.168.0.1",
+            &v2[..14],
+            &v2[..20],
+        ] {
+            assert_eq!(parse_proxy_header(part), Err(ProxyHeaderError::Incomplete));
+        }
+        v2[12] = 0x31;
+        assert_eq!(
+            parse_proxy_header(&v2),
+            Err(ProxyHeaderError::UnsupportedVersion)
+        );
+        assert_eq!(
+            parse_proxy_header(&[b'X'; V1_MAX_LEN]),
+            Err(ProxyHeaderError::Missing)
+        );
+        let mut endless: Vec<u8> = V1_PREFIX.to_vec();
+        endless.resize(V1_MAX_LEN, b'1');
+        assert_eq!(
+            parse_proxy_header(&endless),
+            Err(ProxyHeaderError::Malformed)
+        );
+    }
+
+    #[tokio::test]
+    async fn read_proxy_header_test() {
+        let (mut client, mut server) = tokio::io::duplex(256);
+        let sender = tokio::spawn(async move {
+            /* The balancer splits the header between two segments */
+            client.write_all(b"PROXY TCP4 192.168.0.1 ").await.unwrap();
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            client
+                .write_all(b"192.168.0.11 56324 443\r\nGET / HTTP/1.1\r\n")
+                .await
+                .unwrap();
+        });
+        let mut buffer: Vec<u8> = Vec::new();
+        let header: ProxyHeader = read_proxy_header(&mut server, &mut buffer).await.unwrap();
+        assert_eq!(header.source, Some("192.168.0.1:56324".parse().unwrap()));
+        assert_eq!(&buffer[header.len..], b"GET / HTTP/1.1\r\n");
+        sender.await.unwrap();
+
+        /* The connection, that closes in the middle of the header */
+        let (mut truncated, mut server) = tokio::io::duplex(256);
+        truncated.write_all(&V2_SIGNATURE).await.unwrap();
+        drop(truncated);
+        let e: io::Error = read_proxy_header(&mut server, &mut Vec::new())
+            .await
+            .unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::UnexpectedEof);
+    }
+}
@@ -0,0 +1,437 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::io;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::io::AsyncWrite;
+
+const SECS_PER_DAY: u64 = 86400;
+/* Name, type and help of the metric with the getter of its value */
+type UsageSeries = (
+    &'static str,
+    &'static str,
+    &'static str,
+    fn(&HostUsage, u64) -> u64,
+);
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct HostQuota {
+    /*
+     *  Entry of the [[host_quotas]] table in the config.
+     *
+     *  Attributes:
+     *      host: Served host, that the quota applies to, e.g. example.com
+     *      max_bytes_per_day: Bytes of the responses per UTC day, the host
+     *      is answered with 429 once they are used up.
+     *      max_concurrent: The maximum number of the host's requests handled
+     *      at once, the others are answered with 503.
+     *      max_cache_bytes: Bytes of the sites, that the host's requests can
+     *      load into the cache. The other sites are served without caching.
+     */
+    pub host: String,
+    #[serde(default)]
+    pub max_bytes_per_day: Option<u64>,
+    #[serde(default)]
+    pub max_concurrent: Option<u64>,
+    #[serde(default)]
+    pub max_cache_bytes: Option<u64>,
+}
+
+#[derive(Debug, Default)]
+pub struct HostUsage {
+    /*
+     *  Usage of the host, shared by its requests.
+     *
+     *  Attributes:
+     *      day: Days since the epoch, that bytes_today belongs to.
+     *      bytes_today: Bytes of the responses sent today.
+     *      in_flight: The host's requests being handled right now.
+     *      cached: Sites loaded into the cache by the host with their sizes.
+     *      Evicted sites are counted, until the cache is flushed.
+     *      rejections: Requests refused because of the quota.
+     */
+    day: AtomicU64,
+    bytes_today: AtomicU64,
+    in_flight: AtomicU64,
+    cached: Mutex<HashMap<Vec<u8>, u64>>,
+    rejections: AtomicU64,
+}
+
+impl HostUsage {
+    fn bytes_today(&self, today: u64) -> u64 {
+        /*
+         *  Returns:
+         *      Bytes sent today, the counter starts over with the new day.
+         */
+        let day: u64 = self.day.load(Ordering::Relaxed);
+        if day != today
+            && self
+                .day
+                .compare_exchange(day, today, Ordering::Relaxed, Ordering::Relaxed)
+                .is_ok()
+        {
+            self.bytes_today.store(0, Ordering::Relaxed);
+        }
+        self.bytes_today.load(Ordering::Relaxed)
+    }
+
+    fn cache_bytes(&self) -> u64 {
+        self.cached.lock().unwrap().values().sum()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum QuotaExceeded {
+    /*
+     *  Quota, that refused the request, with the seconds until
+     *  the request could succeed.
+     */
+    Bandwidth(u64),
+    Concurrency,
+}
+
+#[derive(Debug)]
+pub struct QuotaPermit {
+    /*
+     *  Proof, that the request fits in the host's quota. The request
+     *  stops counting as in flight, when the permit is dropped.
+     *
+     *  Attributes:
+     *      usage: Usage of the host.
+     *      max_cache_bytes: Cache quota of the host.
+     */
+    usage: Arc<HostUsage>,
+    max_cache_bytes: Option<u64>,
+}
+
+impl QuotaPermit {
+    pub fn usage(&self) -> Arc<HostUsage> {
+        Arc::clone(&self.usage)
+    }
+
+    pub fn may_cache(&self, resource_path: &[u8], size: u64) -> bool {
+        /*
+         *  Reserve the space of the site in the host's cache quota.
+         *
+         *  Arguments:
+         *      resource_path: Resource path of the site.
+         *      size: Bytes of the site.
+         *
+         *  Returns:
+         *      True if the site can be cached.
+         */
+        let Some(max_cache_bytes) = self.max_cache_bytes else {
+            return true;
+        };
+        let mut cached = self.usage.cached.lock().unwrap();
+        let others: u64 = cached
+            .iter()
+            .filter(|(path, _)| path.as_slice() != resource_path)
+            .map(|(_, size)| size)
+            .sum();
+        if others + size > max_cache_bytes {
+            return false;
+        }
+        cached.insert(resource_path.to_vec(), size);
+        true
+    }
+}
+
+impl Drop for QuotaPermit {
+    fn drop(&mut self) {
+        self.usage.in_flight.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct QuotaTracker {
+    /*
+     *  Quotas of the served hosts with their usage.
+     */
+    hosts: Vec<(HostQuota, Arc<HostUsage>)>,
+}
+
+impl QuotaTracker {
+    pub fn new(quotas: &[HostQuota]) -> Self {
+        QuotaTracker {
+            hosts: quotas
+                .iter()
+                .map(|quota| (quota.clone(), Arc::new(HostUsage::default())))
+                .collect(),
+        }
+    }
+
+    pub fn try_acquire(&self, host: Option<&[u8]>) -> Result<Option<QuotaPermit>, QuotaExceeded> {
+        /*
+         *  Count the request against the quota of its host.
+         *
+         *  Arguments:
+         *      host: Requested host without the port.
+         *
+         *  Returns:
+         *      The permit, None if the host has no quota, or the quota,
+         *      that is used up.
+         */
+        let Some(host) = host else {
+            return Ok(None);
+        };
+        let Some((quota, usage)) = self
+            .hosts
+            .iter()
+            .find(|(quota, _)| quota.host.as_bytes().eq_ignore_ascii_case(host))
+        else {
+            return Ok(None);
+        };
+        let now: u64 = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_secs())
+            .unwrap_or_default();
+        if let Some(max_bytes) = quota.max_bytes_per_day
+            && usage.bytes_today(now / SECS_PER_DAY) >= max_bytes
+        {
+            usage.rejections.fetch_add(1, Ordering::Relaxed);
+            return Err(QuotaExceeded::Bandwidth(SECS_PER_DAY - now % SECS_PER_DAY));
+        }
+        let in_flight: u64 = usage.in_flight.fetch_add(1, Ordering::Relaxed);
+        let permit: QuotaPermit = QuotaPermit {
+            usage: Arc::clone(usage),
+            max_cache_bytes: quota.max_cache_bytes,
+        };
+        if quota.max_concurrent.is_some_and(|max| in_flight >= max) {
+            usage.rejections.fetch_add(1, Ordering::Relaxed);
+            return Err(QuotaExceeded::Concurrency);
+        }
+        Ok(Some(permit))
+    }
+
+    pub fn forget_cached(&self, resource_path: Option<&[u8]>) {
+        /*
+         *  Give the cache quota back, after the sites left the cache.
+         *
+         *  Arguments:
+         *      resource_path: The invalidated site, None if the whole cache
+         *      was flushed.
+         */
+        for (_, usage) in &self.hosts {
+            let mut cached = usage.cached.lock().unwrap();
+            match resource_path {
+                Some(resource_path) => {
+                    cached.remove(resource_path);
+                }
+                None => cached.clear(),
+            }
+        }
+    }
+
+    pub fn render(&self) -> String {
+        /*
+         *  Render the usage of the hosts in the Prometheus text format.
+         *
+         *  Returns:
+         *      The gauges and counters with the host label, empty if no
+         *      quota is configured.
+         */
+        let mut rendered: String = String::new();
+        if self.hosts.is_empty() {
+            return rendered;
+        }
+        let today: u64 = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_secs() / SECS_PER_DAY)
+            .unwrap_or_default();
+        let series: [UsageSeries; 4] = [
+            (
+                "diana_host_bytes_today",
+                "gauge",
+                "Bytes sent to the host today.",
+                |usage, today| usage.bytes_today(today),
+            ),
+            (
+                "diana_host_requests_in_flight",
+                "gauge",
+                "Requests of the host being handled.",
+                |usage, _| usage.in_flight.load(Ordering::Relaxed),
+            ),
+            (
+                "diana_host_cache_bytes",
+                "gauge",
+                "Bytes of the sites cached by the host.",
+                |usage, _| usage.cache_bytes(),
+            ),
+            (
+                "diana_host_quota_rejections_total",
+                "counter",
+                "Requests refused because of the host's quota.",
+                |usage, _| usage.rejections.load(Ordering::Relaxed),
+            ),
+        ];
+        for (name, kind, help, value_of) in series {
+            let _ = write!(rendered, "# HELP {name} {help}\n# TYPE {name} {kind}\n");
+            for (quota, usage) in &self.hosts {
+                let _ = writeln!(
+                    rendered,
+                    "{name}{{host=\"{}\"}} {}",
+                    quota.host,
+                    value_of(usage, today)
+                );
+            }
+        }
+        rendered
+    }
+}
+
+pub struct Metered<W> {
+    /*
+     *  Writer, that counts the written bytes against the host's
+     *  daily quota.
+     *
+     *  Attributes:
+     *      inner: The wrapped writer.
+     *      usage: Usage of the host, None if the host has no quota.
+     */
+    inner: W,
+    usage: Option<Arc<HostUsage>>,
+}
+
+impl<W: AsyncWrite + Unpin> Metered<W> {
+    pub fn new(inner: W, usage: Option<Arc<HostUsage>>) -> Self {
+        Metered { inner, usage }
+    }
+}
+
+impl<W: AsyncWrite + Unpin> AsyncWrite for Metered<W> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<Result<usize, io::Error>> {
+        let written: Poll<Result<usize, io::Error>> = Pin::new(&mut self.inner).poll_write(cx, buf);
+        if let Poll::Ready(Ok(sz)) = written
+            && let Some(usage) = &self.usage
+        {
+            usage.bytes_today.fetch_add(sz as u64, Ordering::Relaxed);
+        }
+        written
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), io::Error>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<(), io::Error>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::AsyncWriteExt;
+
+    #[tokio::test]
+    async fn quota_tracker_test() {
+        let tracker: QuotaTracker = QuotaTracker::new(&[HostQuota {
+            host: String::from("tenant.example"),
+            max_bytes_per_day: Some(10),
+            max_concurrent: Some(1),
+            max_cache_bytes: Some(100),
+        }]);
+        assert!(tracker.try_acquire(None).unwrap().is_none());
+        assert!(
+            tracker
+                .try_acquire(Some(b"other.example"))
+                .unwrap()
+                .is_none()
+        );
+
+        let permit: QuotaPermit = tracker
+            .try_acquire(Some(b"Tenant.Example"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            tracker.try_acquire(Some(b"tenant.example")).unwrap_err(),
+            QuotaExceeded::Concurrency
+        );
+        assert!(permit.may_cache(b"/a.html", 60));
+        assert!(permit.may_cache(b"/a.html", 80));
+        assert!(!permit.may_cache(b"/b.html", 40));
+        tracker.forget_cached(Some(b"/a.html"));
+        assert!(permit.may_cache(b"/b.html", 40));
+
+        let mut out = Metered::new(Vec::new(), Some(permit.usage()));
+        out.write_all(b"0123456789").await.unwrap();
+        drop(permit);
+        assert!(matches!(
+            tracker.try_acquire(Some(b"tenant.example")),
+            Err(QuotaExceeded::Bandwidth(_))
+        ));
+
+        let rendered: String = tracker.render();
+        assert!(rendered.contains("diana_host_bytes_today{host=\"tenant.example\"} 10\n"));
+        assert!(rendered.contains("diana_host_cache_bytes{host=\"tenant.example\"} 40\n"));
+        assert!(
+            rendered.contains("diana_host_quota_rejections_total{host=\"tenant.example\"} 2\n")
+        );
+        assert!(rendered.contains("diana_host_requests_in_flight{host=\"tenant.example\"} 0\n"));
+    }
+
+    #[tokio::test]
+    async fn quota_hosts_test() {
+        let quota = |host: &str| HostQuota {
+            host: String::from(host),
+            max_bytes_per_day: Some(5),
+            max_concurrent: Some(1),
+            max_cache_bytes: None,
+        };
+        let tracker: QuotaTracker = QuotaTracker::new(&[quota("a.example"), quota("b.example")]);
+        let in_flight = |host: &str| -> u64 {
+            let (_, usage) = tracker
+                .hosts
+                .iter()
+                .find(|(quota, _)| quota.host == host)
+                .unwrap();
+            usage.in_flight.load(Ordering::Relaxed)
+        };
+
+        /* The hosts are counted apart, the busy one doesn't refuse the other */
+        let first: QuotaPermit = tracker.try_acquire(Some(b"a.example")).unwrap().unwrap();
+        assert_eq!(
+            tracker.try_acquire(Some(b"a.example")).unwrap_err(),
+            QuotaExceeded::Concurrency
+        );
+        let other: QuotaPermit = tracker.try_acquire(Some(b"b.example")).unwrap().unwrap();
+        assert_eq!((in_flight("a.example"), in_flight("b.example")), (1, 1));
+
+        /* The dropped permit gives its slot back */
+        drop(first);
+        assert_eq!(in_flight("a.example"), 0);
+        let second: QuotaPermit = tracker.try_acquire(Some(b"a.example")).unwrap().unwrap();
+
+        /* The host over its bytes is refused until the next day, the other isn't */
+        let mut out = Metered::new(Vec::new(), Some(other.usage()));
+        out.write_all(b"01234").await.unwrap();
+        drop(other);
+        match tracker.try_acquire(Some(b"b.example")) {
+            Err(QuotaExceeded::Bandwidth(retry_after_secs)) => {
+                assert!((1..=SECS_PER_DAY).contains(&retry_after_secs));
+            }
+            refused => panic!("{refused:?}"),
+        }
+        drop(second);
+        assert!(tracker.try_acquire(Some(b"a.example")).unwrap().is_some());
+        assert_eq!((in_flight("a.example"), in_flight("b.example")), (0, 0));
+
+        let rendered: String = tracker.render();
+        assert!(rendered.contains("diana_host_bytes_today{host=\"a.example\"} 0\n"));
+        assert!(rendered.contains("diana_host_bytes_today{host=\"b.example\"} 5\n"));
+        assert!(rendered.contains("diana_host_quota_rejections_total{host=\"a.example\"} 1\n"));
+        assert!(rendered.contains("diana_host_quota_rejections_total{host=\"b.example\"} 1\n"));
+    }
+}
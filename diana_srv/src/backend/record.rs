@@ -0,0 +1,314 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::net::{Shutdown, SocketAddr, TcpStream, ToSocketAddrs};
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::Mutex;
+use std::task::{Context, Poll};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::io::AsyncWrite;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Direction {
+    /*
+     *  Side of the connection, that sent the recorded bytes. The marker
+     *  starts the record line, > for the requests and < for the responses.
+     */
+    Request,
+    Response,
+}
+
+impl Direction {
+    fn marker(&self) -> char {
+        match self {
+            Self::Request => '>',
+            Self::Response => '<',
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Record {
+    /*
+     *  Raw bytes sent over the connection. The record is the line
+     *  <marker> <unix millis> <peer> <length>, followed by the bytes and
+     *  the line break, so the bytes are kept as they are.
+     *
+     *  Attributes:
+     *      direction: Side, that sent the bytes.
+     *      timestamp_ms: Milliseconds since the epoch, when they were sent.
+     *      peer: Address of the client.
+     *      bytes: The recorded bytes.
+     */
+    pub direction: Direction,
+    pub timestamp_ms: u128,
+    pub peer: SocketAddr,
+    pub bytes: Vec<u8>,
+}
+
+impl Record {
+    pub fn write_to<W: Write>(&self, out: &mut W) -> Result<(), io::Error> {
+        write!(
+            out,
+            "{} {} {} {}\r\n",
+            self.direction.marker(),
+            self.timestamp_ms,
+            self.peer,
+            self.bytes.len()
+        )?;
+        out.write_all(&self.bytes)?;
+        out.write_all(b"\r\n")
+    }
+}
+
+pub fn parse_records(input: &[u8]) -> Result<Vec<Record>, io::Error> {
+    /*
+     *  Parse the recording back into the records.
+     *
+     *  Arguments:
+     *      input: Content of the recording file.
+     *
+     *  Returns:
+     *      The records in the order, they were written.
+     */
+    let invalid = |msg: &str| io::Error::new(io::ErrorKind::InvalidData, msg.to_string());
+    let mut records: Vec<Record> = Vec::new();
+    let mut rest: &[u8] = input;
+    while !rest.is_empty() {
+        let eol_idx: usize = rest
+            .windows(2)
+            .position(|pair| pair == b"\r\n")
+            .ok_or_else(|| invalid("Record line is not terminated"))?;
+        let line: &str = std::str::from_utf8(&rest[..eol_idx])
+            .map_err(|_| invalid("Record line is not UTF-8"))?;
+        let fields: Vec<&str> = line.split(' ').collect();
+        let [marker, timestamp_ms, peer, len] = fields[..] else {
+            return Err(invalid("Record line must have 4 fields"));
+        };
+        let direction: Direction = match marker {
+            ">" => Direction::Request,
+            "<" => Direction::Response,
+            _ => return Err(invalid("Unknown record marker")),
+        };
+        let timestamp_ms: u128 = timestamp_ms
+            .parse()
+            .map_err(|_| invalid("Invalid record timestamp"))?;
+        let peer: SocketAddr = peer.parse().map_err(|_| invalid("Invalid record peer"))?;
+        let len: usize = len.parse().map_err(|_| invalid("Invalid record length"))?;
+        let body_start: usize = eol_idx + 2;
+        let bytes: &[u8] = rest
+            .get(body_start..body_start + len)
+            .ok_or_else(|| invalid("Record is truncated"))?;
+        records.push(Record {
+            direction,
+            timestamp_ms,
+            peer,
+            bytes: bytes.to_vec(),
+        });
+        rest = rest
+            .get(body_start + len..)
+            .and_then(|rest| rest.strip_prefix(b"\r\n"))
+            .ok_or_else(|| invalid("Record is not terminated"))?;
+    }
+    Ok(records)
+}
+
+#[derive(Debug)]
+pub struct Recorder {
+    /*
+     *  Appends the traffic of all the connections to the recording.
+     *
+     *  Attributes:
+     *      path: Path of the recording file.
+     *      file: The opened recording, shared by the connections.
+     *      responses: Record the responses too, not only the requests.
+     */
+    path: PathBuf,
+    file: Mutex<File>,
+    responses: bool,
+}
+
+impl Recorder {
+    pub fn open(dir: &Path, responses: bool) -> Result<Self, io::Error> {
+        /*
+         *  Start the new recording in the directory, every start of
+         *  the server gets its own file.
+         */
+        fs::create_dir_all(dir)?;
+        let path: PathBuf = dir.join(format!("diana-{}.rec", now_ms()));
+        let file: File = OpenOptions::new().create(true).append(true).open(&path)?;
+        Ok(Recorder {
+            path,
+            file: Mutex::new(file),
+            responses,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn records_responses(&self) -> bool {
+        self.responses
+    }
+
+    pub fn request(&self, peer: SocketAddr, bytes: &[u8]) {
+        self.append(Direction::Request, peer, bytes);
+    }
+
+    pub fn response(&self, peer: SocketAddr, bytes: &[u8]) {
+        if self.responses {
+            self.append(Direction::Response, peer, bytes);
+        }
+    }
+
+    fn append(&self, direction: Direction, peer: SocketAddr, bytes: &[u8]) {
+        let record: Record = Record {
+            direction,
+            timestamp_ms: now_ms(),
+            peer,
+            bytes: bytes.to_vec(),
+        };
+        let mut file = self.file.lock().unwrap();
+        if let Err(e) = record.write_to(&mut *file) {
+            println!("[WARNING] Failed to record the traffic: {e}");
+        }
+    }
+}
+
+pub struct Teed<W> {
+    /*
+     *  Writer, that keeps the copy of everything written through it, so
+     *  the streamed responses can be recorded.
+     *
+     *  Attributes:
+     *      inner: The wrapped writer.
+     *      captured: Copy of the written bytes, None if nothing is captured.
+     */
+    inner: W,
+    pub captured: Option<Vec<u8>>,
+}
+
+impl<W: AsyncWrite + Unpin> Teed<W> {
+    pub fn new(inner: W, capture: bool) -> Self {
+        Teed {
+            inner,
+            captured: capture.then(Vec::new),
+        }
+    }
+}
+
+impl<W: AsyncWrite + Unpin> AsyncWrite for Teed<W> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<Result<usize, io::Error>> {
+        let written: Poll<Result<usize, io::Error>> = Pin::new(&mut self.inner).poll_write(cx, buf);
+        if let Poll::Ready(Ok(sz)) = written
+            && let Some(captured) = self.captured.as_mut()
+        {
+            captured.extend_from_slice(&buf[..sz]);
+        }
+        written
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), io::Error>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<(), io::Error>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
+pub fn replay_request(
+    target: &str,
+    request: &[u8],
+    timeout: Duration,
+) -> Result<Vec<u8>, io::Error> {
+    /*
+     *  Send the recorded request on the new connection and read everything
+     *  the server answers, until it closes the connection or times out.
+     *
+     *  Arguments:
+     *      target: Address of the server, e.g. 127.0.0.1:8080
+     *      request: Raw bytes of the request.
+     *      timeout: The maximum time of the connect and of each read.
+     *
+     *  Returns:
+     *      Raw bytes of the response.
+     */
+    let addr: SocketAddr = target
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| io::Error::from(io::ErrorKind::AddrNotAvailable))?;
+    let mut stream: TcpStream = TcpStream::connect_timeout(&addr, timeout)?;
+    stream.set_read_timeout(Some(timeout))?;
+    stream.write_all(request)?;
+    /* Nothing else is sent, so the server closes after the answer */
+    stream.shutdown(Shutdown::Write)?;
+    let mut response: Vec<u8> = Vec::new();
+    match stream.read_to_end(&mut response) {
+        Ok(_) => Ok(response),
+        /* The server kept the connection open, what came so far is the answer */
+        Err(e)
+            if matches!(
+                e.kind(),
+                io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+            ) =>
+        {
+            Ok(response)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+pub fn status_line(response: &[u8]) -> &[u8] {
+    response
+        .windows(2)
+        .position(|pair| pair == b"\r\n")
+        .map_or(response, |eol_idx| &response[..eol_idx])
+}
+
+fn now_ms() -> u128 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_millis())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn records_round_trip_test() {
+        let peer: SocketAddr = "127.0.0.1:5000".parse().unwrap();
+        let records: Vec<Record> = vec![
+            Record {
+                direction: Direction::Request,
+                timestamp_ms: 1,
+                peer,
+                /* The bytes might contain the line breaks of their own */
+                bytes: b"GET / HTTP/1.1\r\n\r\n".to_vec(),
+            },
+            Record {
+                direction: Direction::Response,
+                timestamp_ms: 2,
+                peer,
+                bytes: vec![0, 13, 10, 255],
+            },
+        ];
+        let mut recording: Vec<u8> = Vec::new();
+        for record in &records {
+            record.write_to(&mut recording).unwrap();
+        }
+        assert_eq!(parse_records(&recording).unwrap(), records);
+        assert!(parse_records(&recording[..recording.len() - 3]).is_err());
+        assert!(parse_records(b"? 1 127.0.0.1:5000 0\r\n\r\n").is_err());
+    }
+}
@@ -0,0 +1,154 @@
+use crate::backend::metrics::Metrics;
+use crate::backend::status::StatusSnapshot;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+#[derive(Debug, Serialize)]
+pub struct ShutdownReport {
+    /*
+     *  Statistics of the whole run, logged when the server shuts down,
+     *  e.g. for the CI runs, that nobody scrapes the metrics of.
+     *
+     *  Attributes:
+     *      uptime_secs: How long the server ran.
+     *      requests: Requests answered by the server.
+     *      client_errors: Requests answered with 4xx.
+     *      server_errors: Requests answered with 5xx.
+     *      errors_by_status: The errors by their status code.
+     *      peak_connections: The most connections open at once.
+     *      client_disconnects: Handlers cancelled by the clients leaving.
+     *      handler_panics: Connection handlers, that panicked.
+     *      cache: Counters of the cache.
+     */
+    pub uptime_secs: u64,
+    pub requests: u64,
+    pub client_errors: u64,
+    pub server_errors: u64,
+    pub errors_by_status: BTreeMap<usize, u64>,
+    pub peak_connections: u64,
+    pub client_disconnects: u64,
+    pub handler_panics: u64,
+    pub cache: CacheReport,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CacheReport {
+    /*
+     *  Counters of the cache, with the sites it held at the shutdown.
+     */
+    pub hits: u64,
+    pub misses: u64,
+    pub evictions: u64,
+    pub bypasses: u64,
+    pub cached_sites: u64,
+    pub capacity: u64,
+}
+
+impl ShutdownReport {
+    pub fn collect(metrics: &Metrics, status: &StatusSnapshot) -> Self {
+        /*
+         *  Arguments:
+         *      metrics: Counters of the server.
+         *      status: State of the status page, with the uptime and
+         *      the occupancy of the cache.
+         */
+        let load = |counter: &AtomicU64| counter.load(Ordering::Relaxed);
+        ShutdownReport {
+            uptime_secs: status.uptime_secs,
+            requests: load(&metrics.requests),
+            client_errors: load(&metrics.client_errors),
+            server_errors: load(&metrics.server_errors),
+            errors_by_status: metrics.error_statuses(),
+            peak_connections: load(&metrics.peak_connections),
+            client_disconnects: load(&metrics.client_disconnects),
+            handler_panics: load(&metrics.handler_panics),
+            cache: CacheReport {
+                hits: load(&metrics.cache_hits),
+                misses: load(&metrics.cache_misses),
+                evictions: load(&metrics.cache_evictions),
+                bypasses: load(&metrics.cache_bypasses),
+                cached_sites: status.cached_sites,
+                capacity: status.cache_capacity,
+            },
+        }
+    }
+
+    pub fn log(&self) {
+        println!(
+            "[INFO] Served {} requests in {} s, {} client errors, {} server errors.",
+            self.requests, self.uptime_secs, self.client_errors, self.server_errors
+        );
+        if !self.errors_by_status.is_empty() {
+            let statuses: Vec<String> = self
+                .errors_by_status
+                .iter()
+                .map(|(status, count)| format!("{status}: {count}"))
+                .collect();
+            println!("[INFO] Errors by status: {}.", statuses.join(", "));
+        }
+        println!(
+            "[INFO] Peak of {} connections, {} disconnects, {} panics.",
+            self.peak_connections, self.client_disconnects, self.handler_panics
+        );
+        println!(
+            "[INFO] Cache: {} hits, {} misses, {} evictions, {} of {} sites cached.",
+            self.cache.hits,
+            self.cache.misses,
+            self.cache.evictions,
+            self.cache.cached_sites,
+            self.cache.capacity
+        );
+    }
+
+    pub fn write(&self, path: &Path) -> Result<(), io::Error> {
+        /*
+         *  Write the report as JSON, replacing the report of the last run.
+         */
+        let json: Vec<u8> = serde_json::to_vec_pretty(self).map_err(io::Error::other)?;
+        fs::write(path, json)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::metrics::OpenConnection;
+    use crate::backend::status::StatusBoard;
+    use std::sync::Arc;
+
+    #[test]
+    fn shutdown_report_test() {
+        let metrics: Arc<Metrics> = Arc::new(Metrics::default());
+        for status in [200, 404, 404, 500, 503, 304] {
+            metrics.count_response(status);
+        }
+        Metrics::add(&metrics.cache_hits, 5);
+        let open: Vec<OpenConnection> = (0..3)
+            .map(|_| OpenConnection::new(Arc::clone(&metrics)))
+            .collect();
+        drop(open);
+        let _open: OpenConnection = OpenConnection::new(Arc::clone(&metrics));
+
+        let board: StatusBoard = StatusBoard::default();
+        board.set_cache_occupancy(2, 1024);
+        let report: ShutdownReport = ShutdownReport::collect(&metrics, &board.snapshot(&metrics));
+        assert_eq!(report.requests, 6);
+        assert_eq!(report.peak_connections, 3);
+        assert_eq!(
+            report.errors_by_status,
+            BTreeMap::from([(404, 2), (500, 1), (503, 1)])
+        );
+
+        let path = std::env::temp_dir().join(format!("diana_report_{}.json", std::process::id()));
+        report.write(&path).unwrap();
+        let written: serde_json::Value = serde_json::from_slice(&fs::read(&path).unwrap()).unwrap();
+        assert_eq!(written["errors_by_status"]["404"], 2);
+        assert_eq!(written["cache"]["hits"], 5);
+        assert_eq!(written["cache"]["cached_sites"], 2);
+        fs::remove_file(&path).unwrap();
+    }
+}
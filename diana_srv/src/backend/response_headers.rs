@@ -0,0 +1,181 @@
+use crate::utils::patterns::glob_match;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::OnceLock;
+
+/* Identity sent in the Server header, unless the config overrides it */
+pub const DEFAULT_SERVER_IDENTITY: &str =
+    concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"));
+
+/* Headers, that tell the software of the upstream */
+const IDENTIFYING_HEADERS: [&str; 5] = [
+    "server",
+    "x-powered-by",
+    "x-aspnet-version",
+    "x-aspnetmvc-version",
+    "x-generator",
+];
+
+/* The configured identity, it is set once when the server starts */
+static SERVER_IDENTITY: OnceLock<String> = OnceLock::new();
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct HeaderRule {
+    /*
+     *  Entry of the [[response_headers]] table in the config.
+     *
+     *  Attributes:
+     *      path: Glob of the paths, that the headers are added to. A single
+     *      star stays in the directory, the double star crosses directories.
+     *      headers: Headers to send, replacing the ones of the same name.
+     *      An empty value strips the header, including the default ones.
+     */
+    pub path: String,
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+}
+
+pub fn apply_header_rules(
+    rules: &[HeaderRule],
+    resource_path: &[u8],
+    headers: &mut Vec<(String, String)>,
+) {
+    /*
+     *  Merge the headers of the matching rules into the response headers.
+     *  The rules are applied in their order, so the later ones win.
+     *
+     *  Arguments:
+     *      rules: The configured rules.
+     *      resource_path: Resource path from the request.
+     *      headers: Headers of the response, updated in place.
+     */
+    for rule in rules {
+        if !glob_match(rule.path.as_bytes(), resource_path) {
+            continue;
+        }
+        for (name, value) in &rule.headers {
+            headers.retain(|(set, _)| !set.eq_ignore_ascii_case(name));
+            headers.push((name.clone(), value.clone()));
+        }
+    }
+}
+
+pub fn set_server_identity(identity: &str) {
+    /*
+     *  Set the value of the Server header, the empty one suppresses it.
+     *  Only the first call takes effect, the process serves one config.
+     */
+    let _ = SERVER_IDENTITY.set(String::from(identity));
+}
+
+pub fn server_identity() -> &'static str {
+    SERVER_IDENTITY
+        .get()
+        .map_or(DEFAULT_SERVER_IDENTITY, String::as_str)
+}
+
+pub fn strip_identifying_headers(head: &[u8]) -> Vec<u8> {
+    /*
+     *  Drop the headers, that reveal the software of the upstream, from
+     *  its response head, e.g. Server and X-Powered-By.
+     *
+     *  Arguments:
+     *      head: The status line and the headers, up to the empty line.
+     *
+     *  Returns:
+     *      The head without the identifying headers.
+     */
+    let mut stripped: Vec<u8> = Vec::with_capacity(head.len());
+    for (line_idx, line) in head.split_inclusive(|byte| *byte == b'\n').enumerate() {
+        let name: &[u8] = line
+            .iter()
+            .position(|byte| *byte == b':')
+            .map_or(&[], |colon_idx| line[..colon_idx].trim_ascii());
+        let identifying: bool = line_idx > 0
+            && IDENTIFYING_HEADERS
+                .iter()
+                .any(|header| name.eq_ignore_ascii_case(header.as_bytes()));
+        if !identifying {
+            stripped.extend_from_slice(line);
+        }
+    }
+    stripped
+}
+
+pub fn strip_removed(headers: &mut Vec<(String, String)>) {
+    /*
+     *  Drop every header, that has an entry with the empty value. The empty
+     *  entries are the removal markers left by the rules.
+     */
+    let removed: Vec<String> = headers
+        .iter()
+        .filter(|(_, value)| value.is_empty())
+        .map(|(name, _)| name.to_ascii_lowercase())
+        .collect();
+    if !removed.is_empty() {
+        headers.retain(|(name, _)| !removed.contains(&name.to_ascii_lowercase()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn apply_header_rules_test() {
+        let rules: Vec<HeaderRule> = toml::from_str::<toml::Table>(
+            r#"
+            [[rules]]
+            path = "/staging/**"
+            headers = { "X-Robots-Tag" = "noindex", "Access-Control-Allow-Origin" = "" }
+
+            [[rules]]
+            path = "/staging/*.css"
+            headers = { "x-robots-tag" = "none" }
+            "#,
+        )
+        .unwrap()["rules"]
+            .clone()
+            .try_into()
+            .unwrap();
+
+        let mut headers: Vec<(String, String)> = Vec::new();
+        apply_header_rules(&rules, b"/index.html", &mut headers);
+        assert!(headers.is_empty());
+
+        apply_header_rules(&rules, b"/staging/docs/a.html", &mut headers);
+        assert!(headers.contains(&(String::from("X-Robots-Tag"), String::from("noindex"))));
+
+        let mut headers: Vec<(String, String)> = vec![(
+            String::from("Access-Control-Allow-Origin"),
+            String::from("*"),
+        )];
+        apply_header_rules(&rules, b"/staging/site.css", &mut headers);
+        assert_eq!(
+            headers,
+            vec![
+                (String::from("Access-Control-Allow-Origin"), String::new()),
+                (String::from("x-robots-tag"), String::from("none")),
+            ]
+        );
+
+        let mut head: Vec<(String, String)> = vec![(
+            String::from("access-control-allow-origin"),
+            String::from("*"),
+        )];
+        head.extend(headers);
+        strip_removed(&mut head);
+        assert_eq!(
+            head,
+            vec![(String::from("x-robots-tag"), String::from("none"))]
+        );
+
+        assert_eq!(
+            strip_identifying_headers(
+                b"HTTP/1.1 200 OK\r\nserver: Apache/2.4\r\nX-Powered-By: PHP\r\nX-Server-Id: 1\r\n\r\n"
+            ),
+            b"HTTP/1.1 200 OK\r\nX-Server-Id: 1\r\n\r\n".to_vec()
+        );
+        assert!(server_identity().starts_with("diana_srv/"));
+    }
+}
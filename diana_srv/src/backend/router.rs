@@ -0,0 +1,292 @@
+use crate::backend::http::{Request, Response};
+use crate::backend::listing::percent_decode;
+use crate::backend::server::{HttpResponseStatus, RequestType};
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::fmt;
+use std::future::Future;
+use std::sync::Arc;
+use tokio::time::timeout_at;
+
+#[async_trait]
+pub trait Handler: Send + Sync {
+    /*
+     *  Handler of the requests on the registered route. The implementors
+     *  keep their own state, e.g. the database pools, behind &self.
+     */
+    async fn handle(&self, req: Request) -> Response;
+}
+
+#[async_trait]
+impl<F, Fut> Handler for F
+where
+    F: Fn(Request) -> Fut + Send + Sync,
+    Fut: Future<Output = Response> + Send,
+{
+    async fn handle(&self, req: Request) -> Response {
+        self(req).await
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct Router {
+    /*
+     *  Table of the handlers by the method and the resource path. Segments
+     *  of the path starting with a colon match any value, e.g. /users/:id
+     */
+    routes: HashMap<(RequestType, Vec<u8>), Arc<dyn Handler>>,
+}
+
+impl Router {
+    pub fn route(&mut self, method: RequestType, path: &str, handler: impl Handler + 'static) {
+        /*
+         *  Register the handler, it replaces the one registered before
+         *  for the same method and path.
+         *
+         *  Arguments:
+         *      method: HTTP method of the route.
+         *      path: Resource path, e.g. /api/data or /users/:id
+         *      handler: Handler of the requests.
+         */
+        self.routes
+            .insert((method, Vec::from(path.as_bytes())), Arc::new(handler));
+    }
+
+    pub fn get(&mut self, path: &str, handler: impl Handler + 'static) {
+        self.route(RequestType::Get, path, handler);
+    }
+
+    pub fn post(&mut self, path: &str, handler: impl Handler + 'static) {
+        self.route(RequestType::Post, path, handler);
+    }
+
+    pub fn find(&self, method: RequestType, path: &[u8]) -> Option<Arc<dyn Handler>> {
+        /*
+         *  Returns:
+         *      The handler of the route, None if nothing is registered.
+         */
+        self.resolve(method, path).map(|(handler, _)| handler)
+    }
+
+    pub fn resolve(&self, method: RequestType, path: &[u8]) -> Option<RouteMatch> {
+        /*
+         *  Find the handler, the exact route wins over the one with
+         *  the parameters, and the route with fewer parameters wins over
+         *  the one with more, e.g. /users/me over /users/:id
+         *
+         *  Returns:
+         *      The handler with the values of the route parameters, None
+         *      if nothing is registered.
+         */
+        if let Some(handler) = self.routes.get(&(method, path.to_vec())) {
+            return Some((Arc::clone(handler), Vec::new()));
+        }
+        self.routes
+            .iter()
+            .filter(|((routed, pattern), _)| {
+                *routed == method && pattern.windows(2).any(|w| w == b"/:")
+            })
+            .filter_map(|((_, pattern), handler)| {
+                match_params(pattern, path).map(|params| (Arc::clone(handler), params))
+            })
+            .min_by_key(|(_, params)| params.len())
+    }
+
+    pub fn len(&self) -> usize {
+        self.routes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.routes.is_empty()
+    }
+}
+
+/* The handler with the names and the values of the route parameters */
+pub type RouteMatch = (Arc<dyn Handler>, Vec<(String, String)>);
+
+fn match_params(pattern: &[u8], route: &[u8]) -> Option<Vec<(String, String)>> {
+    /*
+     *  Match the path against the route with the parameters.
+     *
+     *  Returns:
+     *      The names with the percent-decoded values, None if the path
+     *      doesn't match or a value is empty.
+     */
+    let mut params: Vec<(String, String)> = Vec::new();
+    let mut segments = route.split(|byte| *byte == b'/');
+    for expected in pattern.split(|byte| *byte == b'/') {
+        let segment: &[u8] = segments.next()?;
+        match expected.strip_prefix(b":") {
+            Some(name) if !segment.is_empty() => {
+                /* The plus is literal in the path, only the query encodes spaces with it */
+                let escaped: Vec<u8> = segment
+                    .iter()
+                    .flat_map(|byte| match byte {
+                        b'+' => b"%2B".to_vec(),
+                        byte => vec![*byte],
+                    })
+                    .collect();
+                params.push((
+                    String::from_utf8_lossy(name).into_owned(),
+                    percent_decode(&escaped)?,
+                ));
+            }
+            None if expected == segment => {}
+            _ => return None,
+        }
+    }
+    segments.next().is_none().then_some(params)
+}
+
+pub async fn handle_with_deadline(handler: Arc<dyn Handler>, req: Request) -> Response {
+    /*
+     *  Run the handler, cancelling it when the request's deadline passes.
+     *
+     *  Returns:
+     *      The handler's response, or 504 if it didn't finish in time.
+     */
+    let deadline = req.deadline();
+    let path: String = String::from_utf8_lossy(&req.path).into_owned();
+    match timeout_at(deadline, handler.handle(req)).await {
+        Ok(response) => response,
+        Err(_) => {
+            println!("[WARNING] Handler of {path} exceeded the deadline.");
+            Response::new(HttpResponseStatus::GatewayTimeout)
+        }
+    }
+}
+
+impl fmt::Debug for Router {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let routes: Vec<String> = self
+            .routes
+            .keys()
+            .map(|(method, path)| format!("{method:?} {}", String::from_utf8_lossy(path)))
+            .collect();
+        f.debug_struct("Router").field("routes", &routes).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::http::RequestBody;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+    use tokio::time::{Instant, sleep};
+
+    struct CountingHandler {
+        hits: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl Handler for CountingHandler {
+        async fn handle(&self, _req: Request) -> Response {
+            let hits: usize = self.hits.fetch_add(1, Ordering::Relaxed) + 1;
+            Response::new(HttpResponseStatus::Ok).with_body(hits.to_string().into_bytes())
+        }
+    }
+
+    #[tokio::test]
+    async fn router_dispatch_test() {
+        let mut router = Router::default();
+        router.get(
+            "/count",
+            CountingHandler {
+                hits: AtomicUsize::new(0),
+            },
+        );
+        router.post("/echo", |mut req: Request| async move {
+            Response::new(HttpResponseStatus::Ok).with_body(req.body().await.unwrap())
+        });
+
+        let request = |method: RequestType, path: &[u8], body: &[u8]| {
+            Request::new(
+                method,
+                path.to_vec(),
+                b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n",
+                RequestBody::buffered(body.to_vec()),
+                "127.0.0.1:4000".parse().unwrap(),
+                Instant::now() + Duration::from_secs(5),
+            )
+        };
+
+        let counter = router.find(RequestType::Get, b"/count").unwrap();
+        counter
+            .handle(request(RequestType::Get, b"/count", b""))
+            .await;
+        let second: Response = counter
+            .handle(request(RequestType::Get, b"/count", b""))
+            .await;
+        assert_eq!(second.body, b"2".to_vec());
+
+        let echo = router.find(RequestType::Post, b"/echo").unwrap();
+        let echoed: Response = echo
+            .handle(request(RequestType::Post, b"/echo", b"hi"))
+            .await;
+        assert_eq!(echoed.body, b"hi".to_vec());
+        assert_eq!(
+            request(RequestType::Get, b"/", b"").header("HOST"),
+            Some(&b"localhost"[..])
+        );
+
+        assert!(router.find(RequestType::Get, b"/echo").is_none());
+        assert!(router.find(RequestType::Post, b"/echo").is_some());
+
+        router.get("/users/:id/posts/:post", |req: Request| async move {
+            Response::new(HttpResponseStatus::Ok).with_body(req.path)
+        });
+        router.get("/users/me/posts/:post", |req: Request| async move {
+            Response::new(HttpResponseStatus::Ok).with_body(req.path)
+        });
+        let (_, params) = router
+            .resolve(RequestType::Get, b"/users/a%20b+c/posts/7")
+            .unwrap();
+        assert_eq!(
+            params,
+            [
+                (String::from("id"), String::from("a b+c")),
+                (String::from("post"), String::from("7"))
+            ]
+        );
+        let (_, params) = router
+            .resolve(RequestType::Get, b"/users/me/posts/7")
+            .unwrap();
+        assert_eq!(params, [(String::from("post"), String::from("7"))]);
+        assert!(
+            router
+                .resolve(RequestType::Get, b"/users//posts/7")
+                .is_none()
+        );
+        assert!(
+            router
+                .resolve(RequestType::Get, b"/users/1/posts")
+                .is_none()
+        );
+        assert!(
+            router
+                .resolve(RequestType::Get, b"/users/1/posts/7/x")
+                .is_none()
+        );
+    }
+
+    #[tokio::test]
+    async fn handle_with_deadline_test() {
+        let mut router = Router::default();
+        router.get("/slow", |req: Request| async move {
+            sleep(req.remaining() + Duration::from_millis(50)).await;
+            Response::new(HttpResponseStatus::Ok)
+        });
+        let request: Request = Request::new(
+            RequestType::Get,
+            b"/slow".to_vec(),
+            b"GET /slow HTTP/1.1\r\n\r\n",
+            RequestBody::buffered(Vec::new()),
+            "127.0.0.1:4000".parse().unwrap(),
+            Instant::now() + Duration::from_millis(20),
+        );
+        let handler = router.find(RequestType::Get, b"/slow").unwrap();
+        let response: Response = handle_with_deadline(handler, request).await;
+        assert_eq!(response.status, HttpResponseStatus::GatewayTimeout);
+    }
+}
@@ -0,0 +1,205 @@
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time::{Instant, MissedTickBehavior};
+
+#[async_trait]
+pub trait Job: Send + Sync {
+    /*
+     *  Periodic job of the scheduler. A job never overlaps with itself,
+     *  the next run waits for the previous one.
+     */
+    async fn run(&self);
+}
+
+#[async_trait]
+impl<F, Fut> Job for F
+where
+    F: Fn() -> Fut + Send + Sync,
+    Fut: Future<Output = ()> + Send,
+{
+    async fn run(&self) {
+        self().await
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(tag = "job", rename_all = "snake_case")]
+pub enum JobKind {
+    /*
+     *  Jobs, that can be scheduled from the config.
+     */
+    CacheFlush,
+    CacheReload,
+    HealthProbe { url: String },
+    Command { command: Vec<String> },
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct JobConfig {
+    /*
+     *  Entry of the [[scheduled_jobs]] table in the config.
+     *
+     *  Attributes:
+     *      name: Name of the job in the logs, the kind of the job if missing.
+     *      every_secs: Interval between the runs, the first run is one
+     *      interval after the start.
+     *      kind: The job, e.g. job = "health_probe" with its url, or
+     *      job = "command" with command = ["certbot", "renew", "--quiet"]
+     */
+    #[serde(default)]
+    pub name: Option<String>,
+    pub every_secs: u64,
+    #[serde(flatten)]
+    pub kind: JobKind,
+}
+
+impl JobConfig {
+    pub fn name(&self) -> String {
+        if let Some(name) = &self.name {
+            return name.clone();
+        }
+        String::from(match self.kind {
+            JobKind::CacheFlush => "cache_flush",
+            JobKind::CacheReload => "cache_reload",
+            JobKind::HealthProbe { .. } => "health_probe",
+            JobKind::Command { .. } => "command",
+        })
+    }
+}
+
+struct ScheduledJob {
+    name: String,
+    every: Duration,
+    job: Arc<dyn Job>,
+}
+
+#[derive(Default)]
+pub struct Scheduler {
+    /*
+     *  Jobs run at their intervals on the runtime of the server.
+     *
+     *  Attributes:
+     *      pending: Jobs registered before the scheduler started.
+     *      running: Names of the started jobs.
+     *      started: Jobs registered from now on start right away.
+     */
+    pending: Vec<ScheduledJob>,
+    running: Vec<String>,
+    started: bool,
+}
+
+impl fmt::Debug for Scheduler {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Scheduler")
+            .field("pending", &self.pending.len())
+            .field("running", &self.running)
+            .finish()
+    }
+}
+
+impl Scheduler {
+    pub fn add(&mut self, name: &str, every: Duration, job: impl Job + 'static) {
+        /*
+         *  Register the job.
+         *
+         *  Arguments:
+         *      name: Name of the job in the logs.
+         *      every: Interval between the runs, at least a second.
+         *      job: The job, a struct implementing Job or an async closure.
+         */
+        let scheduled: ScheduledJob = ScheduledJob {
+            name: String::from(name),
+            every: every.max(Duration::from_secs(1)),
+            job: Arc::new(job),
+        };
+        if self.started {
+            self.running.push(scheduled.name.clone());
+            tokio::spawn(run_periodically(scheduled));
+        } else {
+            self.pending.push(scheduled);
+        }
+    }
+
+    pub fn start(&mut self) {
+        /*
+         *  Spawn the registered jobs, it must be called within the runtime.
+         */
+        self.started = true;
+        for scheduled in self.pending.drain(..) {
+            println!(
+                "[INFO] Scheduled the job {} every {:?}.",
+                scheduled.name, scheduled.every
+            );
+            self.running.push(scheduled.name.clone());
+            tokio::spawn(run_periodically(scheduled));
+        }
+    }
+
+    pub fn running(&self) -> &[String] {
+        &self.running
+    }
+}
+
+async fn run_periodically(scheduled: ScheduledJob) {
+    /*
+     *  The late runs are delayed, so the slow job doesn't run in a burst
+     *  to catch up.
+     */
+    let mut ticker = tokio::time::interval_at(Instant::now() + scheduled.every, scheduled.every);
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    loop {
+        ticker.tick().await;
+        let started: Instant = Instant::now();
+        scheduled.job.run().await;
+        let elapsed: Duration = started.elapsed();
+        if elapsed > scheduled.every {
+            println!(
+                "[WARNING] The job {} took {elapsed:?}, longer than its interval.",
+                scheduled.name
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    #[tokio::test(start_paused = true)]
+    async fn scheduler_test() {
+        let runs: Arc<AtomicU64> = Arc::new(AtomicU64::new(0));
+        let mut scheduler: Scheduler = Scheduler::default();
+        let counter: Arc<AtomicU64> = Arc::clone(&runs);
+        scheduler.add("count", Duration::from_secs(10), move || {
+            let counter: Arc<AtomicU64> = Arc::clone(&counter);
+            async move {
+                counter.fetch_add(1, Ordering::Relaxed);
+            }
+        });
+        tokio::time::sleep(Duration::from_secs(30)).await;
+        assert_eq!(runs.load(Ordering::Relaxed), 0);
+
+        scheduler.start();
+        tokio::time::sleep(Duration::from_secs(5)).await;
+        assert_eq!(runs.load(Ordering::Relaxed), 0);
+        tokio::time::sleep(Duration::from_secs(21)).await;
+        assert_eq!(runs.load(Ordering::Relaxed), 2);
+        assert_eq!(scheduler.running(), [String::from("count")]);
+
+        let config: JobConfig = toml::from_str(
+            r#"
+            every_secs = 60
+            job = "health_probe"
+            url = "http://127.0.0.1:9000/health"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.name(), "health_probe");
+        assert!(matches!(config.kind, JobKind::HealthProbe { .. }));
+    }
+}
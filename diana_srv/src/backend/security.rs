@@ -0,0 +1,134 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct SecurityHeaderValues {
+    /*
+     *  Values of the security headers. Missing values fall back to the
+     *  defaults, an empty value stops the header from being sent.
+     */
+    pub strict_transport_security: Option<String>,
+    pub x_content_type_options: Option<String>,
+    pub x_frame_options: Option<String>,
+    pub content_security_policy: Option<String>,
+    pub referrer_policy: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct SecurityHeaders {
+    /*
+     *  The [security_headers] table of the config, the headers are added
+     *  to every response.
+     *
+     *  Attributes:
+     *      values: Values used for all the hosts.
+     *      hosts: Overrides of the values per served host, e.g.
+     *      [security_headers.hosts."example.com"]
+     */
+    #[serde(flatten)]
+    pub values: SecurityHeaderValues,
+    pub hosts: HashMap<String, SecurityHeaderValues>,
+}
+
+impl SecurityHeaders {
+    pub fn headers_for(&self, host: Option<&[u8]>) -> Vec<(String, String)> {
+        /*
+         *  Resolve the headers of the host, its overrides come first, then
+         *  the configured values and then the defaults.
+         *
+         *  Arguments:
+         *      host: Requested host without the port.
+         *
+         *  Returns:
+         *      Headers to send, as (name, value) pairs.
+         */
+        let overrides: Option<&SecurityHeaderValues> = host.and_then(|host| {
+            self.hosts
+                .iter()
+                .find(|(name, _)| name.as_bytes().eq_ignore_ascii_case(host))
+                .map(|(_, values)| values)
+        });
+        let resolve = |pick: fn(&SecurityHeaderValues) -> &Option<String>,
+                       default: Option<&str>|
+         -> Option<String> {
+            overrides
+                .and_then(|values| pick(values).clone())
+                .or_else(|| pick(&self.values).clone())
+                .or(default.map(String::from))
+                .filter(|value| !value.is_empty())
+        };
+
+        /* HSTS is only honored over TLS, so it is opt-in */
+        let headers: [(&str, Option<String>); 5] = [
+            (
+                "Strict-Transport-Security",
+                resolve(|values| &values.strict_transport_security, None),
+            ),
+            (
+                "X-Content-Type-Options",
+                resolve(|values| &values.x_content_type_options, Some("nosniff")),
+            ),
+            (
+                "X-Frame-Options",
+                resolve(|values| &values.x_frame_options, Some("SAMEORIGIN")),
+            ),
+            (
+                "Content-Security-Policy",
+                resolve(
+                    |values| &values.content_security_policy,
+                    Some("default-src 'self'"),
+                ),
+            ),
+            (
+                "Referrer-Policy",
+                resolve(
+                    |values| &values.referrer_policy,
+                    Some("strict-origin-when-cross-origin"),
+                ),
+            ),
+        ];
+        headers
+            .into_iter()
+            .filter_map(|(name, value)| Some((String::from(name), value?)))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn security_headers_test() {
+        let config: SecurityHeaders = toml::from_str(
+            r#"
+            strict_transport_security = "max-age=63072000"
+            referrer_policy = ""
+
+            [hosts."Example.com"]
+            x_frame_options = "DENY"
+            strict_transport_security = ""
+            "#,
+        )
+        .unwrap();
+
+        let defaults: Vec<(String, String)> = config.headers_for(Some(b"localhost"));
+        assert!(defaults.contains(&(
+            String::from("Strict-Transport-Security"),
+            String::from("max-age=63072000")
+        )));
+        assert!(defaults.contains(&(String::from("X-Frame-Options"), String::from("SAMEORIGIN"))));
+        assert!(!defaults.iter().any(|(name, _)| name == "Referrer-Policy"));
+
+        let overridden: Vec<(String, String)> = config.headers_for(Some(b"example.com"));
+        assert!(overridden.contains(&(String::from("X-Frame-Options"), String::from("DENY"))));
+        assert!(
+            !overridden
+                .iter()
+                .any(|(name, _)| name == "Strict-Transport-Security")
+        );
+        assert_eq!(overridden.len(), 3);
+    }
+}
@@ -1,381 +1,3892 @@
+pub mod capabilities;
+pub mod check;
+
+use crate::backend::access::{AccessDecision, AccessRule, check_access};
+use crate::backend::acme::challenge_response;
+use crate::backend::assets::{HashedAssets, IMMUTABLE_CACHE_CONTROL, is_hashed_asset};
+use crate::backend::cache::disk::DiskCache;
+use crate::backend::cache::{
+    CacheStatus, MemoryCache, SiteCache, SiteContent, requests_revalidation,
+};
+use crate::backend::chaos::{Chaos, ChaosPlan, ChaosRule, Fault};
+use crate::backend::client::HttpClient;
+use crate::backend::compression::{ContentCoding, is_compressible, negotiate_coding};
+use crate::backend::connection::Connection;
+use crate::backend::dev::{LiveReload, RELOAD_PATH, inject_reload_script, is_html};
+use crate::backend::disconnect::Disconnect;
+use crate::backend::dump::ParseDumps;
+use crate::backend::errors::RequestError;
+use crate::backend::fds::{FD_RESERVE, FdUsage, fd_usage, is_fd_exhaustion, next_accept_backoff};
+use crate::backend::forwarded::{Cidr, client_ip};
+use crate::backend::hooks::{PostProcess, PostProcessors, PreProcess, PreProcessors, content_type};
+use crate::backend::http::{Request, RequestBody, Response, body_error_status};
+use crate::backend::limits::{ConcurrencyLimit, ConcurrencyLimiter, ConcurrencyPermit, Shed};
+use crate::backend::listen::{ListenerConfig, accept_any, bind, listen_addrs, parse_listen_ip};
+use crate::backend::listing::{FileListing, resolve_under};
+use crate::backend::logging::{LoggingConfig, Logs};
+use crate::backend::manifest::{ManifestEntry, MimeTypes, SiteManifest, set_mime_types};
+use crate::backend::markdown::{MarkdownRenderer, RENDERED_CONTENT_TYPE, is_markdown};
+use crate::backend::memory::{MemoryBudget, Reservation};
+use crate::backend::metrics::{Metrics, OpenConnection};
+use crate::backend::mirror::{Mirror, MirrorConfig, MirrorResponse};
+use crate::backend::overrides::{BasicAuth, Directive, Overrides};
+use crate::backend::panics::{SpareHandle, panic_message};
+use crate::backend::pool::{BufferPool, CONNECTION_BUFFER_SIZE, PooledBuffer, grow_for_head};
+use crate::backend::precompress::fresh_encoded_path;
+use crate::backend::preconditions::{PathGuard, PathLocks, check_preconditions, select_range};
+use crate::backend::privileges::{drop_privileges, resolve_identity};
+use crate::backend::proxy_protocol::read_proxy_header;
+use crate::backend::quotas::{
+    HostQuota, HostUsage, Metered, QuotaExceeded, QuotaPermit, QuotaTracker,
+};
+use crate::backend::record::{Recorder, Teed};
+use crate::backend::report::ShutdownReport;
+use crate::backend::response_headers::{
+    HeaderRule, apply_header_rules, server_identity, set_server_identity, strip_removed,
+};
+use crate::backend::router::{Handler, RouteMatch, Router, handle_with_deadline};
+use crate::backend::scheduler::{Job, JobConfig, JobKind, Scheduler};
+use crate::backend::security::SecurityHeaders;
+use crate::backend::server::capabilities::CAPABILITIES_PATH;
+use crate::backend::service;
+#[cfg(feature = "sqlite")]
+use crate::backend::shortener::SqliteLinks;
+use crate::backend::shortener::{LinkStore, ShortLink, TomlLinks};
+use crate::backend::signals::{Hangup, Reopen, Terminate};
+use crate::backend::slowlog::{RequestClock, SlowRequest, SlowRequests};
+use crate::backend::spa::SpaFallback;
+use crate::backend::status::{RequestId, STATUS_PAGE, StatusBoard, response_status};
+#[cfg(feature = "sqlite")]
+use crate::backend::storage::sqlite::SqliteStore;
+use crate::backend::storage::{FileStorage, Storage};
+use crate::backend::throttle::{BandwidthLimit, BandwidthRule, BandwidthShaper, Throttled};
+use crate::backend::upgrade::{UpgradeRoute, find_upgrade_route, is_upgrade_request, pass_through};
+use crate::backend::userdir::{UserDirs, is_user_path};
+use crate::backend::webhooks::{WebhookConfig, WebhookEvent, Webhooks};
+use crate::utils::patterns::glob_match;
 use crate::utils::readers::buffers::constants::{
-    CONTENT_LENGTH_FIELD, DOT_HTML, GET_REQUEST, POST_REQUEST, RESOURCE_HTML_DIR, SITE_NOT_FOUND,
-    SPACE,
+    CONNECT_REQUEST, GET_REQUEST, GZIP_ENCODING, HEAD_REQUEST, POST_REQUEST, RESOURCE_HTML_DIR,
+    SITE_NOT_FOUND, SPACE, TRACE_REQUEST, X_GZIP_ENCODING,
 };
-use crate::utils::readers::buffers::{extract_number, find_in_buffer, read_tcpstream};
-use crate::utils::readers::files::{bytes_to_path, check_if_file_exists, read_to_bytes, read_toml};
-use serde::Deserialize;
+use crate::utils::readers::buffers::inflate_gzip;
+use crate::utils::readers::files::{
+    bytes_to_path, check_if_file_exists, list_files, read_to_bytes,
+};
+use async_trait::async_trait;
+use diana_http::codec::response_head;
+use diana_http::dates::http_date;
+use diana_http::headers::HeaderMap;
+use diana_http::normalize::{lowercase_host, normalize_path, split_target};
+use diana_http::parser::{ParserState, RequestHead, RequestParser};
+use diana_http::ranges::{RangeSelection, content_range};
+pub use diana_http::status::{HttpResponseStatus, RequestType};
+use diana_http::validation::{
+    check_header_syntax, check_host, check_message_framing, check_path, request_host,
+    split_request_target, trace_echo,
+};
+use serde::{Deserialize, Serialize};
+use std::any::Any;
+use std::borrow::Cow;
+use std::cmp;
 use std::collections::HashMap;
-use std::net::SocketAddr;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::path::PathBuf;
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, Mutex, OnceLock, RwLock};
+use std::time::Duration;
 use std::{io, path::Path};
-use tokio::io::AsyncWriteExt;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::net::{TcpListener, TcpStream};
+use tokio::time::{Instant, timeout};
 
-pub enum HttpResponseStatus {
+/* The maximum length of the body, that is read whole before it is handled */
+const MAX_BUFFERED_BODY: usize = 8192;
+/* How long the scheduled health probe waits for the upstream */
+const HEALTH_PROBE_TIMEOUT: Duration = Duration::from_secs(10);
+/* How long the shutdown waits for the webhook endpoints */
+const SHUTDOWN_WEBHOOK_WAIT: Duration = Duration::from_secs(5);
+/* How long the shutdown waits for the open connections to finish */
+const SHUTDOWN_DRAIN_WAIT: Duration = Duration::from_secs(10);
+/* How often the draining shutdown checks the open connections */
+const SHUTDOWN_DRAIN_POLL: Duration = Duration::from_millis(50);
+/* Status of the requests, that the client left before they were answered, as nginx logs it */
+const CLIENT_CLOSED_REQUEST: usize = 499;
+/* Idle connections to the mirrored upstream, the package managers fetch in parallel */
+const MIRROR_IDLE_CONNECTIONS: usize = 8;
+/* Request type, resource path, query and the authority of the absolute target */
+type RequestLine = (RequestType, Vec<u8>, Option<Vec<u8>>, Option<Vec<u8>>);
+
+struct Exchange {
     /*
-     * Defines all status codes
+     *  The request being answered, as the features of handle_request see it.
+     *
+     *  Attributes:
+     *      request_type: HTTP method of the request.
+     *      resource_path: Resource path without the query.
+     *      query: Query of the request target, None if it has none.
+     *      inc_addr: Effective address of the client.
+     *      deadline: Instant, when the handlers must be finished.
+     *      extra_headers: Headers sent after the default ones.
+     *      bandwidth_limits: Limits, that the response is shaped by.
+     *      quota_usage: Usage of the host's quota, the response is counted in it.
      */
-    Ok = 200,
-    NoContent = 204,
-    NotModified = 304,
-    BadRequest = 400,
-    Forbidden = 403,
-    NotFound = 404,
-    IamATeapot = 418,
+    request_type: RequestType,
+    resource_path: Vec<u8>,
+    query: Option<Vec<u8>>,
+    inc_addr: SocketAddr,
+    deadline: Instant,
+    extra_headers: Vec<(String, String)>,
+    bandwidth_limits: Vec<Arc<BandwidthLimit>>,
+    quota_usage: Option<Arc<HostUsage>>,
 }
 
-impl HttpResponseStatus {
-    pub fn value(&self) -> usize {
-        /*
-         *  Accessor.
-         *
-         *  Returns:
-         *      Status code, that this enum owns.
-         */
-        match self {
-            Self::Ok => 200,
-            Self::NoContent => 204,
-            Self::NotModified => 304,
-            Self::BadRequest => 400,
-            Self::Forbidden => 403,
-            Self::NotFound => 404,
-            Self::IamATeapot => 418,
-        }
-    }
+enum RouteBody {
+    /*
+     *  Body of the request to the registered route, as far as it was read.
+     */
+    Buffered(Vec<u8>),
+    /* The received part with the length of the rest, that the handler reads */
+    Streamed(Vec<u8>, usize),
 }
 
-#[derive(PartialEq, Debug)]
-pub enum RequestType {
+/*
+ *  Handler of the POST requests, it receives the route, the request body and
+ *  the storage, and returns the status with the response body.
+ */
+pub type PostHandler = fn(&[u8], &[u8], &dyn Storage) -> (HttpResponseStatus, Vec<u8>);
+
+struct StoragePostHandler {
     /*
-     * Specify HTTP methods
+     *  Adapter registering the PostHandler functions on the router.
+     *
+     *  Attributes:
+     *      handler: The wrapped function.
+     *      storage: Storage passed to the function.
+     *      path_locks: Locks of the routes, the concurrent writers of
+     *      the same route are refused with 409.
      */
-    Get = 0,
-    Post = 1,
-    Invalid = -1,
+    handler: PostHandler,
+    storage: Option<Arc<dyn Storage>>,
+    path_locks: Arc<PathLocks>,
 }
 
-impl RequestType {
-    pub fn value(&self) -> usize {
-        /*
-         *  Accessor. Returns offsets to parse the HTML request.
-         *
-         *  Returns:
-         *      HTTP request method, that this enum owns.
-         */
-        match self {
-            Self::Get => 3,
-            Self::Post => 4,
-            Self::Invalid => usize::MAX,
+#[async_trait]
+impl Handler for StoragePostHandler {
+    async fn handle(&self, mut req: Request) -> Response {
+        let Some(storage) = self.storage.clone() else {
+            return Response::new(HttpResponseStatus::InternalServerError);
+        };
+        /* The route is held while its body is read and stored */
+        let _guard: PathGuard = match self.path_locks.try_lock(&req.path) {
+            Ok(guard) => guard,
+            Err(status) => return Response::new(status),
+        };
+        let body: Vec<u8> = match req.body().await {
+            Ok(body) => body,
+            Err(e) => {
+                println!("[ERROR] Failed to read the body: {e}");
+                return Response::new(body_error_status(&e));
+            }
+        };
+        /* The storage writes block, so they don't run on the async workers */
+        let handler: PostHandler = self.handler;
+        let route: Vec<u8> = req.path.clone();
+        match tokio::task::spawn_blocking(move || handler(&route, &body, storage.as_ref())).await {
+            Ok((status, content)) => Response::new(status).with_body(content),
+            Err(e) => {
+                println!("[ERROR] POST handler failed: {e}");
+                Response::new(HttpResponseStatus::InternalServerError)
+            }
         }
     }
 }
 
-#[derive(Debug, Clone, Deserialize, Default)]
-pub struct ThreadSharedState {
+#[derive(Debug)]
+pub struct SharedState {
     /*
-     *  Structure for safe thread sharing.
+     *  State shared by all the connections. Whatever changes while
+     *  the server runs is behind its own lock or atomic, so the connections
+     *  only need &self.
      *
      *  Attributes:
-     *      cur_connected_hosts: The tracker of the number of concurrent hosts.
-     *      This will be used for logic of disconnecting the users.
      *      cached_sites: Keeps recently visited sites for better and faster
-     *      search results.
+     *      search results, in the memory or on the disk.
      *      resource_html_dir: Holds name of the resource directory in bytes.
+     *      chroot_html_dir: The resource directory, once the server is
+     *      confined to it, i.e. the root of the filesystem.
+     *      router: Handlers of the registered routes, they can be registered
+     *      while serving.
+     *      storage: Backend, that POST handlers persist the submitted data to.
+     *      path_locks: Locks of the routes, that the writing handlers hold.
+     *      kv_store: SQLite key-value store, present if it is configured.
+     *      short_links: Slugs of the link shortener with their targets,
+     *      present if the shortener is enabled.
+     *      limiter: Semaphores of the global and per route concurrency limits.
+     *      shaper: Bandwidth limits of the response writes.
+     *      quotas: Quotas of the served hosts with their usage.
+     *      metrics: Counters exposed on the metrics endpoint.
+     *      live_reload: Watcher of the resource directory, present in
+     *      the development mode or if the resources are watched. It is
+     *      started once the server serves.
+     *      overrides: Headers, auth and redirects of the directories, read
+     *      from their .diana files on startup and on SIGHUP.
+     *      recorder: Recording of the raw traffic, present if it is enabled.
+     *      It is opened once the server serves.
+     *      dumps: Dumps of the requests, that failed to parse, present in
+     *      the debug mode. It is opened once the server serves.
+     *      webhooks: Notifier of the webhook endpoints, started once
+     *      the server serves.
+     *      scheduler: Periodic jobs, they start once the server serves.
+     *      logs: The access and the error log, opened once the server serves.
+     *      status: Recent requests and the gauges of the status page.
+     *      slow_requests: The slowest requests, that exceeded the threshold.
+     *      memory: Budget of the cache and the buffers.
+     *      buffers: Buffers of the connections, reused across the requests.
+     *      assets: Hashed names of the assets, empty unless they are enabled.
+     *      markdown: Renderer of the .md sites, present if it is enabled.
+     *      user_dirs: Homepages of the users, present if they are enabled.
+     *      pre_processors: Hooks, that see the requests before the handlers
+     *      of their routes.
+     *      post_processors: Hooks, that modify the responses before they are
+     *      written.
+     *      chaos: Injector of the faults, that the testing rules ask for.
      */
-    #[serde(skip)]
-    pub cur_connected_hosts: u32,
-    #[serde(skip)]
-    pub cached_sites: HashMap<Vec<u8>, Vec<u8>>,
-    #[serde(skip)]
+    pub cached_sites: Arc<dyn SiteCache>,
     pub resource_html_dir: Vec<u8>,
+    pub chroot_html_dir: OnceLock<Vec<u8>>,
+    pub router: RwLock<Router>,
+    pub storage: Option<Arc<dyn Storage>>,
+    pub path_locks: Arc<PathLocks>,
+    #[cfg(feature = "sqlite")]
+    pub kv_store: Option<Arc<SqliteStore>>,
+    pub short_links: Option<Arc<dyn LinkStore>>,
+    pub limiter: ConcurrencyLimiter,
+    pub shaper: BandwidthShaper,
+    pub quotas: QuotaTracker,
+    pub metrics: Arc<Metrics>,
+    pub live_reload: OnceLock<LiveReload>,
+    pub overrides: Arc<RwLock<Overrides>>,
+    pub manifest: RwLock<SiteManifest>,
+    pub recorder: OnceLock<Arc<Recorder>>,
+    pub dumps: OnceLock<ParseDumps>,
+    pub webhooks: OnceLock<Webhooks>,
+    pub scheduler: Mutex<Scheduler>,
+    pub logs: OnceLock<Arc<Logs>>,
+    pub status: Arc<StatusBoard>,
+    pub slow_requests: SlowRequests,
+    pub memory: MemoryBudget,
+    pub buffers: BufferPool,
+    pub assets: RwLock<HashedAssets>,
+    pub markdown: Option<MarkdownRenderer>,
+    pub user_dirs: Option<UserDirs>,
+    pub pre_processors: RwLock<PreProcessors>,
+    pub post_processors: RwLock<PostProcessors>,
+    pub mirror: Option<Mirror>,
+    pub chaos: Chaos,
 }
 
-#[derive(Debug, Deserialize, Clone)]
-pub struct Server {
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct ServerConfig {
     /*
-     *  The implementation of server instance, responsible for:
-     *      - handling incoming connections,
-     *      - validating, sanitizing user requests,
-     *      - responding to the requests,
-     *      - fetching correct sites.
+     *  Configuration of the server, read from the TOML config. It is never
+     *  modified once the server serves. The DIANA_ variables of
+     *  the environment override the file, e.g. DIANA_PORT=9090 or
+     *  DIANA_LOGGING__KEEP=14, and --set key=value overrides both.
+     *  The strings of the file may refer to any variable, e.g.
+     *  upstream = "${API_HOST}:9000", the missing ones fail the loading.
      *
      *  Attributes:
      *      ip: Keeps host's ip, that is used to connect to this server.
+     *      IPv6 literals might be in the brackets, e.g. [::1]
      *      port: Keeps host's port, that will be used to connect to this server.
+     *      dual_stack: Bind the counterpart of the ip in the other family too,
+     *      e.g. :: with 0.0.0.0 or ::1 with 127.0.0.1
+     *      listeners: Listeners bound next to the one of ip and port, each
+     *      with its own settings, e.g. the PROXY protocol. See ListenerConfig.
      *      max_connected_hosts: The maximum number of hosts (users) that
      *      can be connected at one time.h If the current number of hosts
      *      connected exceeds this number, the server will refuse further
      *      attempts of connections.
      *      timeout_in_secs: The maximum time for host connection if it
      *      doesn't respond
-     *      shared_state: Structure that is needed for safe thread sharing.
+     *      handler_timeout_in_secs: The maximum time for the handler to
+     *      respond, it is bounded by the connection timeout.
+     *      storage_dir: Directory of the default file storage.
+     *      persist_post_routes: POST routes, which bodies are persisted
+     *      to the storage without a custom handler.
+     *      sqlite_path: Database of the SQLite store, it replaces the file
+     *      storage. Requires the sqlite feature.
+     *      max_decompressed_body_size: The maximum size of the gzip encoded
+     *      request body after it is decompressed.
+     *      max_request_body_size: The maximum size of the body, that the handlers
+     *      can buffer. Streamed bodies aren't limited.
+     *      max_concurrent_requests: The maximum number of requests handled
+     *      at one time, unlimited if it is missing.
+     *      concurrency_limits: Limits of the concurrent requests per route
+     *      prefix.
+     *      reserved_priority_slots: Slots of max_concurrent_requests kept for
+     *      the priority routes, the other requests are refused before them.
+     *      priority_routes: Route prefixes, that can take the reserved slots,
+     *      e.g. /health. The admin, metrics and status paths are always
+     *      included.
+     *      request_queue_depth: Requests waiting for a free slot of
+     *      the concurrency limits, the requests over it are refused at once.
+     *      No request waits, if it is 0.
+     *      request_queue_wait_ms: The longest wait in the queue, the request
+     *      is refused afterwards.
+     *      retry_after_secs: Value of the Retry-After header, sent when the
+     *      request is refused because of the limits.
+     *      memory_budget: Bytes, that the cache, the request bodies in flight
+     *      and the response buffers may hold together, unlimited if it is
+     *      missing. Over it the cache is shrunk and the large bodies are
+     *      refused with 503.
+     *      large_body_size: Requests with Content-Length of at least this many
+     *      bytes are refused, when they don't fit into the memory budget.
+     *      max_header_size: The maximum length of the request line with
+     *      the headers, longer ones are refused with 431. The buffer of
+     *      the connection grows up to it, as the head is read.
+     *      server_names: Hosts served by this server, requests for other
+     *      hosts are answered with 421. Any host is served if it is empty.
+     *      lowercase_host: Lowercase the Host header, before the request is
+     *      matched against the names and the per-host rules.
+     *      max_cached_sites: The maximum number of sites kept in the cache.
+     *      cache_dir: Directory of the on-disk cache, it replaces the memory
+     *      cache. The instances with the same directory share the cache.
+     *      mmap_threshold: Sites of at least this many bytes are memory-mapped
+     *      instead of read to the heap. Requires the mmap feature.
+     *      compression: Encode the text sites with the encoding, that
+     *      the client accepts. The encoded sites are cached next to them.
+     *      compression_min_size: Sites smaller than this many bytes are
+     *      sent as they are.
+     *      watch_resources: Poll the resource directory and drop the changed
+     *      sites with their encodings from the cache. It is always on in
+     *      the development mode.
+     *      allow_cache_bypass: Let the clients force re-reading of the site
+     *      with Cache-Control: no-cache.
+     *      cache_bypass_clients: Clients trusted to bypass the cache, all
+     *      of them are trusted if it is empty.
+     *      file_listing_path: Route of the JSON listing of the resource
+     *      directories, e.g. /api/files?path=/docs. It is disabled if
+     *      the route is empty. The listed directory is guarded by the auth
+     *      of its overrides, the same as its sites, and the override files
+     *      are hidden.
+     *      short_links_path: Prefix of the short links, e.g. /r serves /r/abc
+     *      as the redirect. The shortener is disabled if it is empty.
+     *      short_links_file: TOML file of the short links, used if the SQLite
+     *      store isn't configured. Links are managed with
+     *      POST <admin_path>/links/add and POST <admin_path>/links/remove
+     *      metrics_path: Route of the metrics endpoint, it is disabled if
+     *      the route is empty.
+     *      hide_capabilities: Don't serve the features of the server from
+     *      /.well-known/diana.json, see Capabilities.
+     *      admin_path: Prefix of the admin commands, e.g. POST /admin/cache/flush
+     *      admin_clients: Clients allowed to run the admin commands and to
+     *      see the status page.
+     *      status_path: Route of the status page, its live updates are
+     *      streamed from <status_path>/events. It is disabled if the route
+     *      is empty.
+     *      status_users: Users and their passwords, that the status page
+     *      asks for with Basic auth. Only admin_clients are checked if it
+     *      is empty.
+     *      prewarm_globs: Sites matching these globs are loaded into the cache
+     *      on startup and on SIGHUP.
+     *      hashed_assets: Assets matching these globs are served under
+     *      the names with their content hash too, e.g. app.1a2b3c4d.js,
+     *      with the immutable Cache-Control. They are hashed on startup,
+     *      on SIGHUP and when they change.
+     *      asset_manifest: File in the resource directory, that the hashed
+     *      names are written to by the original ones, as JSON.
+     *      render_markdown: Serve the .md sites rendered to HTML pages,
+     *      the rendered page is what is cached.
+     *      markdown_template: File of the page, that the rendered sites are
+     *      put into in place of {{content}}, with their first heading in
+     *      place of {{title}}. A plain page is used if it is empty.
+     *      userdir: Directory in the homes of the users, e.g. public_html,
+     *      that /~alice/ is served from. The homepages are disabled if it
+     *      is empty. They aren't cached, so the edits show up at once.
+     *      userdir_root: Directory of the homes of the users.
+     *      mime: Media types of the extensions, added to the built-in ones or
+     *      replacing them, and the overrides per path glob, see MimeTypes.
+     *      trusted_proxies: Address blocks of the proxies, e.g. 10.0.0.0/8,
+     *      which Forwarded and X-Forwarded-For headers are believed. The headers
+     *      of the other peers are ignored.
+     *      security_headers: Security headers added to the responses, with
+     *      the overrides per served host.
+     *      spa_fallback: Page of the single-page app, e.g. index.html, that
+     *      answers the GET requests of the missing sites with 200, optionally
+     *      per served host. The API prefixes and the files stay not found.
+     *      response_headers: Extra headers per path glob, an empty value
+     *      strips the header, including the default ones.
+     *      access_rules: Methods and address blocks allowed per route prefix,
+     *      checked before anything else answers the request.
+     *      upgrade_routes: Route prefixes, which upgrade requests (WebSocket)
+     *      are passed through to the upstreams.
+     *      chaos: Latency and failures injected per path glob, to test
+     *      the clients against the server. Disabled if empty.
+     *      allow_trace: Echo the TRACE requests, without the credentials.
+     *      TRACE is answered with 405 otherwise, as CONNECT always is.
+     *      server_header: Value of the Server header, the name and the version
+     *      of the crate if it is missing. An empty value suppresses it.
+     *      hide_upstream_identity: Strip the headers, that reveal
+     *      the upstream's software, e.g. Server and X-Powered-By, from
+     *      the passed through responses.
+     *      upstream_idle_timeout_secs: How long the idle connection to
+     *      the upstream is kept for the next request.
+     *      upstream_max_lifetime_secs: Connections to the upstream older
+     *      than this are closed instead of reused, so the upstream's
+     *      changes, e.g. of DNS, are picked up.
+     *      mirror: Caching proxy of the upstream, e.g. a crates or an apt
+     *      mirror, with the files kept on the disk, see MirrorConfig.
+     *      bandwidth_limit: Bytes per second of all the responses together.
+     *      connection_bandwidth_limit: Bytes per second of every connection.
+     *      bandwidth_limits: Bytes per second of the routes, optionally of
+     *      a single served host.
+     *      host_quotas: Daily bytes, concurrent requests and cache bytes
+     *      allowed to the served hosts, e.g. the tenants of the shared hosting.
+     *      acme_challenge_dir: Directory, where the ACME client puts the HTTP-01
+     *      key authorizations. They are served under /.well-known/acme-challenge/
+     *      pid_file: File, that the PID of the server is written to. The server
+     *      refuses to start, while the process from the file is running.
+     *      It is left behind, when the server switched the user or chrooted,
+     *      since it can't be removed anymore, the next start replaces it.
+     *      daemon_log: File, that the output is appended to, when the server
+     *      runs with --daemon.
+     *      user: User, that the server switches to after binding the port,
+     *      e.g. www-data. The server must be started as root.
+     *      group: Group to switch to, the user's primary group if missing.
+     *      chroot: Confine the server to the resource directory, that
+     *      the sites are served from, when it switches the user. The files
+     *      opened afterwards, e.g. the logs and the stored bodies, are
+     *      resolved inside it, so their paths must be relative.
+     *      worker_threads: Worker threads of the runtime, one per CPU core
+     *      if it is missing.
+     *      max_blocking_threads: The maximum number of threads running
+     *      the blocking work, e.g. the file reads, 512 if it is missing.
+     *      dev_mode: Local development, the sites are never served from
+     *      the cache and the browsers reload when the resource directory
+     *      changes. Enabled with --dev.
+     *      record_dir: Directory, that the raw requests are recorded to for
+     *      debugging, diana-replay sends them back to the server.
+     *      record_responses: Record the raw responses next to the requests.
+     *      debug_dump: Dump the requests, that fail to parse, in hex with
+     *      the client address and the time, so the parser bugs can be
+     *      reported with the exact bytes.
+     *      debug_dump_dir: Directory of the dumps.
+     *      debug_dump_max_files: Dumps kept in the directory, no more are
+     *      written until they are removed.
+     *      debug_dump_max_bytes: Bytes of the request in a single dump.
+     *      slow_request_threshold_ms: Requests taking longer are logged with
+     *      the time of reading, parsing, handling and writing. They aren't
+     *      if it is missing.
+     *      slow_request_count: The slowest requests kept for
+     *      GET <admin_path>/requests/slow
+     *      shutdown_report: File, that the statistics of the run are written
+     *      to as JSON on the graceful shutdown. They are always logged.
+     *      webhooks: Endpoints notified about the start, the shutdown and
+     *      the error rate of the server, see WebhookConfig.
+     *      scheduled_jobs: Jobs run at the intervals, e.g. the cache flush,
+     *      the health probe of the upstream or the command renewing
+     *      the certificates, see JobConfig.
+     *      logging: The access and the error log with their rotation, see
+     *      LoggingConfig. SIGUSR1 reopens them, e.g. after logrotate.
      *
      */
     ip: String,
     port: u16,
+    #[serde(default)]
+    dual_stack: bool,
+    #[serde(default)]
+    listeners: Vec<ListenerConfig>,
     max_connected_hosts: u32,
     timeout_in_secs: u32,
+    #[serde(default = "default_handler_timeout_in_secs")]
+    handler_timeout_in_secs: u32,
+    #[serde(default = "default_storage_dir")]
+    storage_dir: String,
+    #[serde(default)]
+    persist_post_routes: Vec<String>,
+    #[serde(default)]
+    sqlite_path: Option<String>,
+    #[serde(default = "default_max_decompressed_body_size")]
+    max_decompressed_body_size: usize,
+    #[serde(default = "default_max_request_body_size")]
+    max_request_body_size: usize,
+    #[serde(default)]
+    max_concurrent_requests: Option<usize>,
+    #[serde(default)]
+    concurrency_limits: Vec<ConcurrencyLimit>,
+    #[serde(default)]
+    reserved_priority_slots: usize,
+    #[serde(default)]
+    priority_routes: Vec<String>,
+    #[serde(default)]
+    request_queue_depth: usize,
+    #[serde(default = "default_request_queue_wait_ms")]
+    request_queue_wait_ms: u64,
+    #[serde(default = "default_retry_after_secs")]
+    retry_after_secs: u32,
+    #[serde(default)]
+    memory_budget: Option<usize>,
+    #[serde(default = "default_large_body_size")]
+    large_body_size: usize,
+    #[serde(default = "default_max_header_size")]
+    max_header_size: usize,
+    #[serde(default)]
+    server_names: Vec<String>,
+    #[serde(default)]
+    lowercase_host: bool,
+    #[serde(default = "default_max_cached_sites")]
+    max_cached_sites: usize,
+    #[serde(default)]
+    cache_dir: Option<String>,
+    #[serde(default)]
+    mmap_threshold: Option<u64>,
+    #[serde(default)]
+    compression: bool,
+    #[serde(default = "default_compression_min_size")]
+    compression_min_size: usize,
+    #[serde(default)]
+    watch_resources: bool,
+    #[serde(default)]
+    allow_cache_bypass: bool,
+    #[serde(default)]
+    cache_bypass_clients: Vec<IpAddr>,
+    #[serde(default = "default_metrics_path")]
+    metrics_path: String,
+    #[serde(default)]
+    hide_capabilities: bool,
+    #[serde(default)]
+    file_listing_path: String,
+    #[serde(default)]
+    short_links_path: String,
+    #[serde(default = "default_short_links_file")]
+    short_links_file: String,
+    #[serde(default = "default_admin_path")]
+    admin_path: String,
+    #[serde(default = "default_admin_clients")]
+    admin_clients: Vec<IpAddr>,
+    #[serde(default = "default_status_path")]
+    status_path: String,
+    #[serde(default)]
+    status_users: HashMap<String, String>,
+    #[serde(default)]
+    prewarm_globs: Vec<String>,
+    #[serde(default)]
+    hashed_assets: Vec<String>,
+    #[serde(default = "default_asset_manifest")]
+    asset_manifest: String,
+    #[serde(default)]
+    render_markdown: bool,
+    #[serde(default)]
+    markdown_template: String,
+    #[serde(default)]
+    userdir: String,
+    #[serde(default = "default_userdir_root")]
+    userdir_root: String,
+    #[serde(default)]
+    mime: MimeTypes,
+    #[serde(default)]
+    trusted_proxies: Vec<Cidr>,
+    #[serde(default)]
+    security_headers: SecurityHeaders,
+    #[serde(default)]
+    spa_fallback: SpaFallback,
+    #[serde(default)]
+    response_headers: Vec<HeaderRule>,
+    #[serde(default)]
+    access_rules: Vec<AccessRule>,
+    #[serde(default)]
+    upgrade_routes: Vec<UpgradeRoute>,
+    #[serde(default)]
+    chaos: Vec<ChaosRule>,
+    #[serde(default)]
+    allow_trace: bool,
+    #[serde(default)]
+    server_header: Option<String>,
+    #[serde(default)]
+    hide_upstream_identity: bool,
+    #[serde(default = "default_upstream_idle_timeout_secs")]
+    upstream_idle_timeout_secs: u64,
+    #[serde(default = "default_upstream_max_lifetime_secs")]
+    upstream_max_lifetime_secs: u64,
+    #[serde(default)]
+    mirror: MirrorConfig,
+    #[serde(default)]
+    bandwidth_limit: Option<u64>,
+    #[serde(default)]
+    connection_bandwidth_limit: Option<u64>,
+    #[serde(default)]
+    bandwidth_limits: Vec<BandwidthRule>,
+    #[serde(default)]
+    host_quotas: Vec<HostQuota>,
+    #[serde(default)]
+    acme_challenge_dir: Option<String>,
+    #[serde(default)]
+    pid_file: Option<String>,
+    #[serde(default = "default_daemon_log")]
+    daemon_log: String,
+    #[serde(default)]
+    user: Option<String>,
+    #[serde(default)]
+    group: Option<String>,
+    #[serde(default)]
+    chroot: bool,
+    #[serde(default)]
+    worker_threads: Option<usize>,
+    #[serde(default)]
+    max_blocking_threads: Option<usize>,
+    #[serde(default)]
+    dev_mode: bool,
+    #[serde(default)]
+    record_dir: Option<String>,
+    #[serde(default)]
+    record_responses: bool,
+    #[serde(default)]
+    debug_dump: bool,
+    #[serde(default = "default_debug_dump_dir")]
+    debug_dump_dir: String,
+    #[serde(default = "default_debug_dump_max_files")]
+    debug_dump_max_files: usize,
+    #[serde(default = "default_debug_dump_max_bytes")]
+    debug_dump_max_bytes: usize,
+    #[serde(default)]
+    slow_request_threshold_ms: Option<u64>,
+    #[serde(default = "default_slow_request_count")]
+    slow_request_count: usize,
+    #[serde(default)]
+    shutdown_report: Option<String>,
+    #[serde(default)]
+    webhooks: WebhookConfig,
+    #[serde(default)]
+    scheduled_jobs: Vec<JobConfig>,
+    #[serde(default)]
+    logging: LoggingConfig,
+}
+
+impl ServerConfig {
+    pub fn pid_file(&self) -> Option<&Path> {
+        /*
+         *  Accessor.
+         *
+         *  Returns:
+         *      Path of the PID file, if it is configured.
+         */
+        self.pid_file.as_deref().map(Path::new)
+    }
+
+    pub fn daemon_log(&self) -> &Path {
+        /*
+         *  Accessor.
+         *
+         *  Returns:
+         *      Path of the log, that the daemon writes to.
+         */
+        Path::new(&self.daemon_log)
+    }
+}
 
-    #[serde(skip)]
-    shared_state: ThreadSharedState,
+#[derive(Debug, Clone)]
+pub struct Server {
+    /*
+     *  The implementation of server instance, responsible for:
+     *      - handling incoming connections,
+     *      - validating, sanitizing user requests,
+     *      - responding to the requests,
+     *      - fetching correct sites.
+     *
+     *  Clones are cheap and share everything, so every connection can be
+     *  handled by its own clone.
+     *
+     *  Attributes:
+     *      config: The immutable configuration.
+     *      shared_state: State shared by the connections, e.g. the cache
+     *      and the counters.
+     */
+    config: Arc<ServerConfig>,
+    shared_state: Arc<SharedState>,
 }
 
 impl Server {
-    #[tokio::main]
-    pub async fn new(toml_config: &Path) -> Result<Self, io::Error> {
+    pub fn new(toml_config: &Path) -> Result<Self, io::Error> {
         /*
          *  Constructor of the server instance.
          *
          *  Arguments:
          *      toml_config: Path for the server's config, it must contain all
-         *      attributes listed in the structure definition, except
-         *      those marked with #[serde(skip)].
+         *      attributes of ServerConfig, that have no default.
          *
          *  Returns:
          *      It returns Result<...> since the function might return
          *      the server instance or fail due to the incorrect configuration.
          */
+        Server::with_config(Server::load_config(toml_config)?)
+    }
 
-        let mut cfg: Server = read_toml(toml_config)?;
-        let mut ss: ThreadSharedState = ThreadSharedState {
-            cur_connected_hosts: 0,
-            cached_sites: HashMap::new(),
+    #[tokio::main]
+    pub async fn with_config(cfg: ServerConfig) -> Result<Self, io::Error> {
+        /*
+         *  Constructor of the server instance from the config, that is
+         *  read already, e.g. with the layers of the command line.
+         */
+        if let Some(identity) = &cfg.server_header {
+            set_server_identity(identity);
+        }
+        set_mime_types(cfg.mime.clone());
+        let mut priority_routes: Vec<String> = cfg.priority_routes.clone();
+        for route in [&cfg.admin_path, &cfg.metrics_path, &cfg.status_path] {
+            if !route.is_empty() {
+                priority_routes.push(route.clone());
+            }
+        }
+        let storage: Arc<dyn Storage> = Arc::new(FileStorage::new(&cfg.storage_dir)?);
+        let cached_sites: Arc<dyn SiteCache> = match &cfg.cache_dir {
+            Some(cache_dir) => {
+                Arc::new(DiskCache::open(Path::new(cache_dir), cfg.max_cached_sites)?)
+            }
+            None => Arc::new(MemoryCache::new(cfg.max_cached_sites)),
+        };
+        let mut ss: SharedState = SharedState {
+            cached_sites,
             resource_html_dir: Vec::from(RESOURCE_HTML_DIR),
+            chroot_html_dir: OnceLock::new(),
+            router: RwLock::new(Router::default()),
+            storage: Some(storage),
+            #[cfg(feature = "sqlite")]
+            kv_store: None,
+            short_links: None,
+            limiter: ConcurrencyLimiter::new(cfg.max_concurrent_requests, &cfg.concurrency_limits)
+                .with_priority(cfg.reserved_priority_slots, &priority_routes)
+                .with_queue(
+                    cfg.request_queue_depth,
+                    Duration::from_millis(cfg.request_queue_wait_ms),
+                ),
+            shaper: BandwidthShaper::new(
+                cfg.bandwidth_limit,
+                cfg.connection_bandwidth_limit,
+                &cfg.bandwidth_limits,
+            ),
+            quotas: QuotaTracker::new(&cfg.host_quotas),
+            metrics: Arc::new(Metrics::default()),
+            live_reload: OnceLock::new(),
+            overrides: Arc::default(),
+            path_locks: Arc::default(),
+            manifest: RwLock::new(SiteManifest::default()),
+            recorder: OnceLock::new(),
+            dumps: OnceLock::new(),
+            webhooks: OnceLock::new(),
+            scheduler: Mutex::new(Scheduler::default()),
+            logs: OnceLock::new(),
+            status: Arc::new(StatusBoard::default()),
+            slow_requests: SlowRequests::new(
+                cfg.slow_request_threshold_ms.map(Duration::from_millis),
+                cfg.slow_request_count,
+            ),
+            memory: MemoryBudget::new(cfg.memory_budget),
+            /* A connection holds its buffer and the one of the pipelined requests */
+            buffers: BufferPool::new(CONNECTION_BUFFER_SIZE, 2 * cfg.max_connected_hosts as usize),
+            assets: RwLock::new(HashedAssets::default()),
+            markdown: None,
+            user_dirs: (!cfg.userdir.is_empty())
+                .then(|| UserDirs::new(Path::new(&cfg.userdir_root), &cfg.userdir)),
+            pre_processors: RwLock::new(PreProcessors::default()),
+            post_processors: RwLock::new(PostProcessors::default()),
+            mirror: None,
+            chaos: Chaos::new(cfg.chaos.clone()),
         };
 
+        if let Some(db_path) = &cfg.sqlite_path {
+            #[cfg(feature = "sqlite")]
+            {
+                let store: Arc<SqliteStore> =
+                    Arc::new(SqliteStore::open(Path::new(db_path)).await?);
+                ss.storage = Some(store.clone());
+                ss.kv_store = Some(store);
+            }
+            #[cfg(not(feature = "sqlite"))]
+            println!("[WARNING] {db_path} is ignored, the server is built without sqlite.");
+        }
+        if !cfg.short_links_path.is_empty() {
+            #[cfg(feature = "sqlite")]
+            if let Some(store) = &ss.kv_store {
+                ss.short_links = Some(Arc::new(SqliteLinks::new(Arc::clone(store))));
+            }
+            if ss.short_links.is_none() {
+                ss.short_links = Some(Arc::new(TomlLinks::open(Path::new(&cfg.short_links_file))?));
+            }
+        }
+
+        if !cfg.mirror.upstream.is_empty() {
+            let client: HttpClient = HttpClient::new(
+                MIRROR_IDLE_CONNECTIONS,
+                Duration::from_secs(cfg.mirror.timeout_secs),
+            )
+            .with_pool_limits(
+                Duration::from_secs(cfg.upstream_idle_timeout_secs),
+                Duration::from_secs(cfg.upstream_max_lifetime_secs),
+            )
+            .with_metrics(Arc::clone(&ss.metrics));
+            ss.mirror = Some(Mirror::open(&cfg.mirror, client, Arc::clone(&ss.metrics))?);
+        }
+        if ss.chaos.is_enabled() {
+            println!(
+                "[WARNING] The chaos rules fail the requests on purpose, never serve with them."
+            );
+        }
+
+        if cfg.render_markdown {
+            ss.markdown = Some(MarkdownRenderer::load(&cfg.markdown_template)?);
+        }
+
         let mut site_not_found_path_buf: Vec<u8> = ss.resource_html_dir.clone();
         site_not_found_path_buf.extend(Vec::from(SITE_NOT_FOUND));
         let site_not_found_path = bytes_to_path(&site_not_found_path_buf);
         let site_not_found_content: Vec<u8> = read_to_bytes(site_not_found_path.as_path());
         ss.cached_sites
-            .insert(SITE_NOT_FOUND.to_vec(), site_not_found_content);
+            .pin(SITE_NOT_FOUND, SiteContent::from(site_not_found_content))
+            .await;
+        *ss.overrides.write().unwrap() = Overrides::load(&bytes_to_path(&ss.resource_html_dir));
+        *ss.manifest.get_mut().unwrap() =
+            SiteManifest::build(&bytes_to_path(&ss.resource_html_dir));
+
+        let srv: Server = Server {
+            config: Arc::new(cfg),
+            shared_state: Arc::new(ss),
+        };
 
-        cfg.shared_state = ss;
+        for route in &srv.config.persist_post_routes {
+            srv.register_post(route, persist_body);
+        }
+        srv.route_file_listing();
+
+        srv.hash_assets().await;
+        for glob in &srv.config.prewarm_globs {
+            srv.prewarm_cache(glob.as_bytes()).await;
+        }
 
-        Ok(cfg)
+        Ok(srv)
     }
 
-    #[tokio::main]
-    pub async fn run(&mut self) {
+    pub fn config(&self) -> &ServerConfig {
         /*
-         * The main function, that creates TCPListener based on the full address,
-         * accepts incoming connections and moves it onto light threads.
-         * The incoming streams and addresses are moved to the function,
-         * that handles the connections.
+         *  Accessor.
+         *
+         *  Returns:
+         *      The configuration, that the server runs with.
          */
+        &self.config
+    }
 
-        /* Construct full address */
-        let full_addr: String = format!("{}:{}", self.ip, self.port);
-        let listener = TcpListener::bind(&full_addr).await.unwrap();
-        loop {
-            let (inc_stream, inc_addr) = listener.accept().await.unwrap();
-            self.conn_handler(inc_stream, inc_addr).await;
-        }
+    pub fn pid_file(&self) -> Option<&Path> {
+        self.config.pid_file()
     }
-    pub fn read_request_type(&self, buffer: &Vec<u8>) -> RequestType {
+
+    pub fn html_dir(&self) -> &[u8] {
         /*
-         *  Get the type of the request.
-         *
-         *  Parameters:
-         *      buffer: Bytes of the stream, that was read into the vector.
+         *  Accessor.
          *
          *  Returns:
-         *      It returns either GET or POST enum.
+         *      The resource directory, the root of the filesystem once
+         *      the server is confined to it.
          */
+        self.shared_state
+            .chroot_html_dir
+            .get()
+            .unwrap_or(&self.shared_state.resource_html_dir)
+    }
 
-        if buffer[0..3] == *GET_REQUEST {
-            return RequestType::Get;
+    fn route_file_listing(&self) {
+        /*
+         *  Register the listing of the resource directory, if it is enabled.
+         *  It is registered again, when the directory moves to the root.
+         */
+        if !self.config.file_listing_path.is_empty() {
+            let html_dir: PathBuf = bytes_to_path(self.html_dir());
+            self.route(
+                RequestType::Get,
+                &self.config.file_listing_path,
+                FileListing::new(&html_dir, Arc::clone(&self.shared_state.overrides)),
+            );
         }
+    }
 
-        if buffer[0..4] == *POST_REQUEST {
-            return RequestType::Post;
-        }
-        RequestType::Invalid
+    pub fn enable_dev_mode(&mut self) {
+        /*
+         *  Switch to the development mode, see the dev_mode attribute.
+         *  It must be enabled before the server serves.
+         */
+        Arc::make_mut(&mut self.config).dev_mode = true;
     }
 
-    pub fn read_resource(&self, buffer: &Vec<u8>, req_type: &RequestType) -> Vec<u8> {
+    pub fn daemon_log(&self) -> &Path {
+        self.config.daemon_log()
+    }
+
+    #[cfg(feature = "sqlite")]
+    pub fn kv_store(&self) -> Option<Arc<SqliteStore>> {
         /*
-         *  Read what resource user requests.
-         *
-         *  Parameters:
-         *      buffer: Bytes of the stream, that was read into the vector.
-         *      req_type: Get the request type.
+         *  Accessor.
          *
          *  Returns:
-         *      Resource in bytes.
+         *      The SQLite store shared by the handlers, if it is configured.
          */
-
-        /* Extract the offset value */
-        let request_offset: usize = req_type.value();
-        if request_offset == usize::MAX {
-            return Vec::new();
-        }
-        let mut vec_to_return: Vec<u8> = Vec::new();
-        for byte in buffer[request_offset + 1..].iter() {
-            if *byte == SPACE {
-                return vec_to_return;
-            }
-            vec_to_return.push(*byte);
-        }
-        Vec::new()
+        self.shared_state.kv_store.clone()
     }
 
-    pub fn fetch_resource(&mut self, resource_path: &Vec<u8>) -> &Vec<u8> {
+    pub fn register_post(&self, route: &str, handler: PostHandler) {
         /*
-         *  Fetch the data requested by user.
+         *  Register the handler for the POST requests on the given route.
+         *  It replaces the handler, that was registered before.
          *
-         *  Parameters:
-         *      resource_path: Resource path from the request.
+         *  Arguments:
+         *      route: Resource path, e.g. /api/data
+         *      handler: Function, that will handle the requests.
+         */
+        let storage: Option<Arc<dyn Storage>> = self.shared_state.storage.clone();
+        let path_locks: Arc<PathLocks> = Arc::clone(&self.shared_state.path_locks);
+        self.route(
+            RequestType::Post,
+            route,
+            StoragePostHandler {
+                handler,
+                storage,
+                path_locks,
+            },
+        );
+    }
+
+    pub fn route(&self, method: RequestType, route: &str, handler: impl Handler + 'static) {
+        /*
+         *  Register the handler on the router.
          *
-         *  Returns:
-         *      The contents of the resource.
+         *  Arguments:
+         *      method: HTTP method of the route.
+         *      route: Resource path, e.g. /api/data
+         *      handler: Handler of the requests, a struct implementing
+         *      Handler or an async closure taking the Request.
          */
+        self.shared_state
+            .router
+            .write()
+            .unwrap()
+            .route(method, route, handler);
+    }
 
-        // TODO: Check all files beforehand
-        // TODO: Add bad site handling, for now it returns nothing.
-        if resource_path.is_empty() {
-            // TODO: Change it to the welcome site later
-            return &self.shared_state.cached_sites[SITE_NOT_FOUND];
-        }
+    pub fn pre_process(&self, hook: impl PreProcess + 'static) {
+        /*
+         *  Register the hook of the requests, that the routes answer, e.g.
+         *  the authentication or the tracing.
+         *
+         *  Arguments:
+         *      hook: Closure taking the &mut Request, or a struct
+         *      implementing PreProcess. It attaches its values to
+         *      the extensions of the request, or answers the request.
+         */
+        self.shared_state.pre_processors.write().unwrap().add(hook);
+    }
 
-        if !self.shared_state.cached_sites.contains_key(resource_path) {
-            let mut path_on_server: Vec<u8> = self.shared_state.resource_html_dir.clone();
-            path_on_server.extend_from_slice(resource_path);
+    pub fn post_process(&self, content_type: &str, hook: impl PostProcess + 'static) {
+        /*
+         *  Register the hook of the responses, the sites and the answers of
+         *  the handlers alike.
+         *
+         *  Arguments:
+         *      content_type: Media type of the processed responses, e.g.
+         *      text/html, either part may be the wildcard.
+         *      hook: Closure taking the &mut Response, or a struct
+         *      implementing PostProcess.
+         */
+        self.shared_state
+            .post_processors
+            .write()
+            .unwrap()
+            .add(content_type, hook);
+    }
 
-            let path: String = String::from(std::str::from_utf8(&path_on_server).unwrap());
-            if !check_if_file_exists(&path) {
-                return &self.shared_state.cached_sites[SITE_NOT_FOUND];
-            }
+    pub fn schedule(&self, name: &str, every: Duration, job: impl Job + 'static) {
+        /*
+         *  Run the job periodically, while the server serves. Jobs registered
+         *  before serving start with the server.
+         *
+         *  Arguments:
+         *      name: Name of the job in the logs.
+         *      every: Interval between the runs.
+         *      job: The job, a struct implementing Job or an async closure.
+         */
+        self.shared_state
+            .scheduler
+            .lock()
+            .unwrap()
+            .add(name, every, job);
+    }
 
-            let site: Vec<u8> = read_to_bytes(Path::new(&path));
+    fn schedule_configured_jobs(&self) {
+        /*
+         *  Register the jobs of the [[scheduled_jobs]] table.
+         */
+        for job_cfg in &self.config.scheduled_jobs {
+            let srv: Server = self.clone();
+            let kind: Arc<JobKind> = Arc::new(job_cfg.kind.clone());
+            let client: Arc<HttpClient> = Arc::new(
+                HttpClient::new(1, HEALTH_PROBE_TIMEOUT)
+                    .with_pool_limits(
+                        Duration::from_secs(self.config.upstream_idle_timeout_secs),
+                        Duration::from_secs(self.config.upstream_max_lifetime_secs),
+                    )
+                    .with_metrics(Arc::clone(&self.shared_state.metrics)),
+            );
+            let name: String = job_cfg.name();
+            let job_name: Arc<str> = Arc::from(name.as_str());
+            self.schedule(&name, Duration::from_secs(job_cfg.every_secs), move || {
+                let (srv, kind) = (srv.clone(), Arc::clone(&kind));
+                let (client, job_name) = (Arc::clone(&client), Arc::clone(&job_name));
+                async move { srv.run_job(&job_name, &kind, &client).await }
+            });
+        }
+    }
 
-            /* Failed to read */
-            if site.is_empty() {
-                return &self.shared_state.cached_sites[SITE_NOT_FOUND];
+    async fn run_job(&self, name: &str, kind: &JobKind, client: &HttpClient) {
+        /*
+         *  Run the job from the config, the failures are only logged.
+         */
+        match kind {
+            JobKind::CacheFlush => {
+                self.flush_cache().await;
+            }
+            JobKind::CacheReload => self.reload_cache().await,
+            JobKind::HealthProbe { url } => match client.get(url).await {
+                Ok(response) if (200..300).contains(&response.status) => {}
+                Ok(response) => {
+                    println!("[WARNING] Job {name}: {url} answered {}.", response.status)
+                }
+                Err(e) => println!("[WARNING] Job {name}: {url} failed: {e}"),
+            },
+            JobKind::Command { command } => {
+                let Some((program, args)) = command.split_first() else {
+                    return;
+                };
+                match tokio::process::Command::new(program)
+                    .args(args)
+                    .status()
+                    .await
+                {
+                    Ok(status) if status.success() => {}
+                    Ok(status) => println!("[WARNING] Job {name}: {program} exited with {status}."),
+                    Err(e) => println!("[ERROR] Job {name}: Failed to run {program}: {e}"),
+                }
             }
-            /*
-             * We can allow for to_vec, because loading will occurr
-             * limited number of times
-             */
-            self.shared_state
-                .cached_sites
-                .insert(resource_path.to_vec(), site);
         }
-        &self.shared_state.cached_sites[resource_path]
     }
 
-    pub fn read_request_body(&self, buffer: &Vec<u8>) -> Vec<u8> {
+    pub fn run(&self) {
         /*
-         *  Get the actual request body, by reading two consecutive \r\n sequences.
-         *
-         *  Parameters:
-         *      buffer: Bytes of the stream, that was read into the vector.
-         *
-         *  Returns:
-         *      Returns vector with body or empty vector that indicates
-         *      the fail to read or might mean the handshake.
+         *  Start the runtime configured by worker_threads and
+         *  max_blocking_threads, and serve on it until the termination.
          */
-        let pattern: &[u8] = CONTENT_LENGTH_FIELD;
-        /* Find the position in the buffer of Content-Length field. */
-        let content_field_idx: usize = find_in_buffer(buffer, pattern);
-        if content_field_idx == usize::MAX {
-            return Vec::new();
+        let mut builder = tokio::runtime::Builder::new_multi_thread();
+        if let Some(workers) = self.config.worker_threads {
+            builder.worker_threads(workers.max(1));
         }
-
-        let offset_start: usize = pattern.len();
-        /* Pass only the slice, since the function definition requires this */
-        let body_length = extract_number(&buffer[content_field_idx + offset_start..]);
-
-        /* Too big body */
-        if body_length > 8192 {
-            return Vec::new();
+        if let Some(max_blocking) = self.config.max_blocking_threads {
+            builder.max_blocking_threads(max_blocking.max(1));
+        }
+        match builder.enable_all().build() {
+            Ok(runtime) => runtime.block_on(self.serve()),
+            Err(e) => println!("[ERROR] Failed to start the runtime: {e}"),
         }
-
-        let buffer_sz: usize = buffer.len();
-        // TODO: Very vulnerable, we assume that the content is valid.
-        buffer[(buffer_sz - body_length as usize)..].to_vec()
     }
 
-    async fn conn_handler(&mut self, mut inc_stream: TcpStream, inc_addr: SocketAddr) {
+    async fn serve(&self) {
         /*
-         *  Handles each incoming connection. It will read the incoming requests,
-         *  create appropiate responses and send them out.
-         *
-         *  Arguments:
-         *      inc_stream: Incoming stream from the host's request.
-         *      inc_addr: The address, that the request comes from.
+         * The main function, that creates TCPListener based on the full address,
+         * accepts incoming connections and moves it onto light threads.
+         * The incoming streams and addresses are moved to the function,
+         * that handles the connections.
          */
 
-        /* Make sure, that the incoming stream is readable */
-        let _ = inc_stream.readable().await;
-
-        /* Try to read the content, if fail exit earlier */
-        let vec_buf: Vec<u8> = match read_tcpstream(&inc_stream) {
-            Ok(vec) => vec,
+        /* Construct full addresses, the dual stack listens on both families */
+        let cfg: &ServerConfig = &self.config;
+        let mut full_addrs: Vec<(SocketAddr, bool)> = match parse_listen_ip(&cfg.ip)
+            .and_then(|ip| listen_addrs(ip, cfg.port, cfg.dual_stack))
+        {
+            Ok(full_addrs) => full_addrs.into_iter().map(|addr| (addr, false)).collect(),
             Err(e) => {
-                println!("[ERROR] {e}");
+                println!("[ERROR] Invalid listen address: {e}.");
                 return;
             }
         };
-
-        /* Try to read the request type */
-        let request_type: RequestType = self.read_request_type(&vec_buf);
-        if request_type == RequestType::Invalid {
-            println!("[ERROR] Invalid request type.");
-            return;
+        for listener in &cfg.listeners {
+            match parse_listen_ip(&listener.ip) {
+                Ok(ip) => {
+                    full_addrs.push((SocketAddr::new(ip, listener.port), listener.proxy_protocol))
+                }
+                Err(e) => {
+                    println!("[ERROR] Invalid listen address: {e}.");
+                    return;
+                }
+            }
+        }
+        /* Whether each listener expects the PROXY protocol header, by its index */
+        let proxied: Vec<bool> = full_addrs.iter().map(|(_, proxied)| *proxied).collect();
+        let mut listeners: Vec<TcpListener> = Vec::with_capacity(full_addrs.len());
+        for (full_addr, proxied) in full_addrs.iter().copied() {
+            match bind(full_addr) {
+                Ok(listener) => {
+                    if proxied {
+                        println!("[INFO] Listening on {full_addr} behind the PROXY protocol.");
+                    } else {
+                        println!("[INFO] Listening on {full_addr}.");
+                    }
+                    listeners.push(listener);
+                }
+                Err(e) => {
+                    println!("[ERROR] Failed to listen on {full_addr}: {e}");
+                    return;
+                }
+            }
         }
 
-        /* Try to read the resource path */
-        let resource_path: Vec<u8> = self.read_resource(&vec_buf, &request_type);
-        if resource_path.is_empty() {
-            println!("[ERROR] Failed to read the resource.");
-            return;
+        /* Privileged ports are bound, root isn't needed anymore */
+        if let Some(user) = &cfg.user {
+            let html_dir: PathBuf = bytes_to_path(&self.shared_state.resource_html_dir);
+            let chroot_dir: Option<&Path> = cfg.chroot.then_some(html_dir.as_path());
+            match resolve_identity(user, cfg.group.as_deref())
+                .and_then(|identity| drop_privileges(identity, chroot_dir))
+            {
+                Ok(()) if cfg.chroot => {
+                    /* The sites are read from the root from now on */
+                    let _ = self.shared_state.chroot_html_dir.set(b"/".to_vec());
+                    self.route_file_listing();
+                    println!(
+                        "[INFO] Serving as the user {user}, confined to {}.",
+                        html_dir.display()
+                    );
+                }
+                Ok(()) => println!("[INFO] Serving as the user {user}."),
+                Err(e) => {
+                    println!("[ERROR] Failed to switch to the user {user}: {e}");
+                    return;
+                }
+            }
+        }
+        /* The logs belong to the user, so they can be rotated without root */
+        match Logs::open(&cfg.logging) {
+            Ok(logs) => {
+                let logs: Arc<Logs> = Arc::new(logs);
+                if cfg.logging.rotates() {
+                    let rotated: Arc<Logs> = Arc::clone(&logs);
+                    self.schedule(
+                        "log_rotation",
+                        Duration::from_secs(cfg.logging.check_interval_secs),
+                        move || {
+                            let logs: Arc<Logs> = Arc::clone(&rotated);
+                            async move {
+                                let _ =
+                                    tokio::task::spawn_blocking(move || logs.rotate_if_due()).await;
+                            }
+                        },
+                    );
+                }
+                let _ = self.shared_state.logs.set(logs);
+            }
+            Err(e) => {
+                println!("[ERROR] Failed to open the logs: {e}");
+                return;
+            }
+        }
+        let conn_timeout = Duration::from_secs(cfg.timeout_in_secs.into());
+        let mut hangup: Hangup = Hangup::new();
+        let mut reopen: Reopen = Reopen::new();
+        let mut terminate: Terminate = Terminate::new();
+        if cfg.dev_mode || cfg.watch_resources {
+            let html_dir: PathBuf = bytes_to_path(self.html_dir());
+            if cfg.dev_mode {
+                println!(
+                    "[INFO] Development mode, watching {} for changes.",
+                    html_dir.display()
+                );
+            }
+            let srv: Server = self.clone();
+            let watcher: LiveReload = LiveReload::spawn(&html_dir, move |changed| {
+                let srv: Server = srv.clone();
+                tokio::spawn(async move { srv.invalidate_files(&changed).await });
+            });
+            let _ = self.shared_state.live_reload.set(watcher);
+        }
+        if let Some(record_dir) = &cfg.record_dir {
+            match Recorder::open(Path::new(record_dir), cfg.record_responses) {
+                Ok(recorder) => {
+                    println!(
+                        "[WARNING] Recording the raw traffic to {}.",
+                        recorder.path().display()
+                    );
+                    let _ = self.shared_state.recorder.set(Arc::new(recorder));
+                }
+                Err(e) => println!("[ERROR] Failed to start the recording: {e}"),
+            }
+        }
+        if cfg.debug_dump {
+            match ParseDumps::open(
+                Path::new(&cfg.debug_dump_dir),
+                cfg.debug_dump_max_files,
+                cfg.debug_dump_max_bytes,
+            ) {
+                Ok(dumps) => {
+                    println!(
+                        "[WARNING] Dumping the malformed requests to {}.",
+                        dumps.dir().display()
+                    );
+                    let _ = self.shared_state.dumps.set(dumps);
+                }
+                Err(e) => println!("[ERROR] Failed to open the dump directory: {e}"),
+            }
         }
 
-        /* Try to read the body */
-        let read_body_result: Vec<u8> = self.read_request_body(&vec_buf);
-        if read_body_result.is_empty() && request_type == RequestType::Post {
-            println!("[WARNING] Failed to read the body. Assume the handshake.");
-            let site_content = self.fetch_resource(&read_body_result);
-            let response: Vec<u8> = format_message(site_content);
-            inc_stream.write_all(&response).await.unwrap();
-            return;
+        if !cfg.webhooks.urls.is_empty() {
+            let webhooks: Webhooks = Webhooks::spawn(&cfg.webhooks);
+            webhooks.notify(WebhookEvent::Started {
+                addrs: full_addrs
+                    .iter()
+                    .map(|(addr, _)| addr.to_string())
+                    .collect(),
+            });
+            webhooks.watch_error_rate(Arc::clone(&self.shared_state.metrics));
+            let _ = self.shared_state.webhooks.set(webhooks);
         }
 
-        let site_content: &Vec<u8> = self.fetch_resource(&resource_path);
-        let response: Vec<u8> = format_message(site_content);
-        inc_stream.write_all(&response).await.unwrap();
-    }
-}
+        self.schedule_configured_jobs();
+        if let Some(every) = service::watchdog_interval() {
+            self.schedule("watchdog", every, || async { service::watchdog() });
+        }
+        self.shared_state.scheduler.lock().unwrap().start();
 
-pub fn format_message(site_content: &Vec<u8>) -> Vec<u8> {
-    /*
-     *  Format the HTTP response.
-     *
-     *  Arguments:
-     *      site_content: The buffer vector of site in the user's request.
-     *
-     *  Returns:
-     *      Response in bytes.
-     * */
-    let sz: usize = site_content.len();
-    let status: usize = RequestType::Get.value();
-    let mut response: Vec<u8> = format!(
-        "HTTP/1.1 {status} OK\r\nAccess-Control-Allow-Origin: *\r\nContent-Length: {sz}\r\n\r\n"
-    )
-    .as_bytes()
-    .to_vec();
-    response.extend(site_content);
-    return response;
-}
+        /* Every connection takes a descriptor, so check there is enough of them */
+        if let Some(usage) = fd_usage() {
+            let needed: usize = cfg.max_connected_hosts as usize + FD_RESERVE;
+            if usage.headroom() < needed {
+                println!(
+                    "[WARNING] Only {} file descriptors are left for {} hosts, raise the limit ({}).",
+                    usage.headroom(),
+                    cfg.max_connected_hosts,
+                    usage.limit
+                );
+            }
+        }
 
-mod tests {
-    use std::env;
+        /* The process manager starts the dependents only now, not on spawn */
+        service::ready();
+        let mut accept_backoff: Option<Duration> = None;
+        loop {
+            let accepted = tokio::select! {
+                accepted = accept_any(&listeners) => accepted,
+                _ = hangup.recv() => {
+                    println!("[INFO] SIGHUP received, reloading the cache.");
+                    service::reloading();
+                    self.reload_cache().await;
+                    service::ready();
+                    continue;
+                }
+                _ = reopen.recv() => {
+                    if let Some(logs) = self.shared_state.logs.get() {
+                        logs.reopen();
+                    }
+                    continue;
+                }
+                _ = terminate.recv() => {
+                    println!("[INFO] Termination requested, shutting down.");
+                    service::stopping();
+                    break;
+                }
+            };
+            /* Accept errors are mostly transient, e.g. the client reset the connection
+             * or the descriptors ran out, so the listener backs off and retries */
+            let (listener_idx, inc_stream, inc_addr) = match accepted {
+                Ok(connection) => {
+                    accept_backoff = None;
+                    connection
+                }
+                Err(e) => {
+                    Metrics::increment(&self.shared_state.metrics.accept_errors);
+                    let backoff: Duration = next_accept_backoff(accept_backoff);
+                    accept_backoff = Some(backoff);
+                    if is_fd_exhaustion(&e) {
+                        let usage: Option<FdUsage> = fd_usage();
+                        println!(
+                            "[ERROR] Out of file descriptors ({usage:?}), retrying in {backoff:?}."
+                        );
+                    } else {
+                        println!(
+                            "[ERROR] Failed to accept the connection: {e}, retrying in {backoff:?}."
+                        );
+                    }
+                    tokio::time::sleep(backoff).await;
+                    continue;
+                }
+            };
+            /* The connection is counted on the accept, not once its task starts */
+            let metrics: &Arc<Metrics> = &self.shared_state.metrics;
+            if metrics.open_connections.load(Ordering::Relaxed) >= cfg.max_connected_hosts.into() {
+                Metrics::increment(&metrics.refused_connections);
+                println!("[WARNING] Too many hosts, refusing {inc_addr}.");
+                continue;
+            }
+            let open: OpenConnection = OpenConnection::new(Arc::clone(metrics));
+            let srv: Server = self.clone();
+            let proxy_protocol: bool = proxied[listener_idx];
+            tokio::spawn(async move {
+                srv.serve_connection(inc_stream, inc_addr, proxy_protocol, conn_timeout, open)
+                    .await
+            });
+        }
+        /* The report counts the requests, that are still being answered */
+        let open: u64 = self
+            .drain_connections(cmp::min(conn_timeout, SHUTDOWN_DRAIN_WAIT))
+            .await;
+        if open > 0 {
+            println!("[WARNING] Shutting down with {open} connections still open.");
+        }
+        self.report_shutdown();
+        if let Some(webhooks) = self.shared_state.webhooks.get() {
+            webhooks
+                .notify_now(WebhookEvent::Shutdown, SHUTDOWN_WEBHOOK_WAIT)
+                .await;
+        }
+    }
 
-    use crate::backend::server::Server;
-    use crate::utils;
+    async fn drain_connections(&self, wait: Duration) -> u64 {
+        /*
+         *  Wait for the open connections to finish, no new ones are accepted
+         *  anymore.
+         *
+         *  Arguments:
+         *      wait: How long the connections are waited for at most.
+         *
+         *  Returns:
+         *      The number of the connections, that are still open.
+         */
+        let metrics: &Metrics = &self.shared_state.metrics;
+        let deadline: Instant = Instant::now() + wait;
+        loop {
+            let open: u64 = metrics.open_connections.load(Ordering::Relaxed);
+            if open == 0 || Instant::now() >= deadline {
+                return open;
+            }
+            tokio::time::sleep(SHUTDOWN_DRAIN_POLL).await;
+        }
+    }
+
+    fn report_shutdown(&self) {
+        /*
+         *  Log the statistics of the run, and write them to the configured
+         *  file, e.g. for the CI runs without the metrics scraping.
+         */
+        let cached_sites: &dyn SiteCache = self.shared_state.cached_sites.as_ref();
+        let board: &StatusBoard = &self.shared_state.status;
+        board.set_cache_occupancy(cached_sites.len(), cached_sites.capacity());
+        let metrics: &Metrics = &self.shared_state.metrics;
+        let report: ShutdownReport = ShutdownReport::collect(metrics, &board.snapshot(metrics));
+        report.log();
+        if let Some(path) = &self.config.shutdown_report {
+            match report.write(Path::new(path)) {
+                Ok(()) => println!("[INFO] Shutdown report written to {path}."),
+                Err(e) => println!("[ERROR] Failed to write the shutdown report to {path}: {e}"),
+            }
+        }
+    }
+
+    pub async fn flush_cache(&self) -> usize {
+        /*
+         *  Remove every cached site, except the error pages.
+         *
+         *  Returns:
+         *      The number of removed sites.
+         */
+        let flushed: usize = self.shared_state.cached_sites.clear().await;
+        self.shared_state.quotas.forget_cached(None);
+        println!("[INFO] Flushed {flushed} sites from the cache.");
+        flushed
+    }
+
+    pub async fn prewarm_cache(&self, glob: &[u8]) -> usize {
+        /*
+         *  Walk the resource directory and load the matching sites into
+         *  the cache.
+         *
+         *  Arguments:
+         *      glob: Pattern of the paths relative to the resource directory,
+         *      e.g. *.html
+         *
+         *  Returns:
+         *      The number of loaded sites.
+         */
+        let glob: &[u8] = glob.strip_prefix(b"/").unwrap_or(glob);
+        let html_dir: PathBuf = bytes_to_path(self.html_dir());
+        let mut loaded: usize = 0;
+        for file in list_files(&html_dir) {
+            let Some(relative) = file.strip_prefix(&html_dir).ok().and_then(|p| p.to_str()) else {
+                continue;
+            };
+            if !glob_match(glob, relative.as_bytes()) {
+                continue;
+            }
+            let site: SiteContent = match SiteContent::load(&file, self.config.mmap_threshold) {
+                Ok(site) if !site.is_empty() => site,
+                _ => continue,
+            };
+            let resource_path: Vec<u8> = format!("/{relative}").into_bytes();
+            let site: SiteContent = self.render_markdown(&resource_path, site);
+            let evicted: usize = self
+                .shared_state
+                .cached_sites
+                .put(&resource_path, site)
+                .await;
+            Metrics::add(&self.shared_state.metrics.cache_evictions, evicted as u64);
+            loaded += 1;
+        }
+        println!("[INFO] Prewarmed {loaded} sites into the cache.");
+        loaded
+    }
+
+    pub async fn invalidate_files(&self, files: &[PathBuf]) -> usize {
+        /*
+         *  Drop the sites, that changed on the disk, from the cache.
+         *
+         *  Arguments:
+         *      files: Paths of the changed files in the resource directory.
+         *
+         *  Returns:
+         *      The number of dropped sites.
+         */
+        let html_dir: PathBuf = bytes_to_path(self.html_dir());
+        let cached_sites: &dyn SiteCache = self.shared_state.cached_sites.as_ref();
+        let mut invalidated: usize = 0;
+        for file in files {
+            self.shared_state
+                .manifest
+                .write()
+                .unwrap()
+                .refresh(&html_dir, file);
+            let Some(relative) = file.strip_prefix(&html_dir).ok().and_then(|p| p.to_str()) else {
+                continue;
+            };
+            let resource_path: Vec<u8> = format!("/{relative}").into_bytes();
+            /* The error page is pinned under its file name */
+            self.shared_state.quotas.forget_cached(Some(&resource_path));
+            if cached_sites.invalidate(&resource_path).await
+                || cached_sites.invalidate(relative.as_bytes()).await
+            {
+                invalidated += 1;
+            }
+        }
+        if invalidated > 0 {
+            println!("[INFO] Dropped {invalidated} changed sites from the cache.");
+        }
+        let assets_changed: bool = files.iter().any(|file| {
+            file.strip_prefix(&html_dir)
+                .ok()
+                .and_then(|p| p.to_str())
+                .is_some_and(|relative| is_hashed_asset(&self.config.hashed_assets, relative))
+        });
+        if assets_changed {
+            self.hash_assets().await;
+        }
+        invalidated
+    }
+
+    pub async fn hash_assets(&self) {
+        /*
+         *  Hash the configured assets again and write their manifest.
+         *  The manifest is refreshed in the cache, so the pages fetching
+         *  it see the new names.
+         */
+        if self.config.hashed_assets.is_empty() {
+            return;
+        }
+        let html_dir: PathBuf = bytes_to_path(self.html_dir());
+        let assets: HashedAssets = HashedAssets::build(
+            &html_dir,
+            &self.config.hashed_assets,
+            &self.config.asset_manifest,
+        );
+        let manifest_file: PathBuf = html_dir.join(&self.config.asset_manifest);
+        match assets.write_manifest(&manifest_file) {
+            Ok(true) => {
+                self.shared_state
+                    .manifest
+                    .write()
+                    .unwrap()
+                    .refresh(&html_dir, &manifest_file);
+                let manifest_path: Vec<u8> =
+                    format!("/{}", self.config.asset_manifest).into_bytes();
+                self.shared_state
+                    .cached_sites
+                    .invalidate(&manifest_path)
+                    .await;
+            }
+            Ok(false) => {}
+            Err(e) => println!(
+                "[ERROR] Failed to write the asset manifest {}: {e}",
+                manifest_file.display()
+            ),
+        }
+        println!("[INFO] Hashed {} assets.", assets.len());
+        *self.shared_state.assets.write().unwrap() = assets;
+    }
+
+    pub async fn reload_cache(&self) {
+        /*
+         *  Flush the cache and prewarm it with the configured globs.
+         *  The directory overrides and the manifest are read again too.
+         */
+        let html_dir: PathBuf = bytes_to_path(self.html_dir());
+        let overrides: Overrides = Overrides::load(&html_dir);
+        *self.shared_state.overrides.write().unwrap() = overrides;
+        let manifest: SiteManifest = SiteManifest::build(&html_dir);
+        *self.shared_state.manifest.write().unwrap() = manifest;
+        self.flush_cache().await;
+        self.hash_assets().await;
+        for glob in &self.config.prewarm_globs {
+            self.prewarm_cache(glob.as_bytes()).await;
+        }
+    }
+
+    pub async fn handle_admin(
+        &self,
+        request_type: RequestType,
+        resource_path: &[u8],
+        body: &[u8],
+        client: IpAddr,
+    ) -> Option<(HttpResponseStatus, Vec<u8>)> {
+        /*
+         *  Run the admin command, that is addressed by the resource path:
+         *      POST <admin_path>/cache/flush
+         *      POST <admin_path>/cache/prewarm with the glob as the body
+         *      POST <admin_path>/links/add with abc https://example.com [permanent]
+         *      POST <admin_path>/links/remove with the slug as the body
+         *      POST <admin_path>/webhooks/cert-renewed with the domains as
+         *      the body, e.g. from the deploy hook of the ACME client
+         *      GET <admin_path>/requests/slow lists the slowest requests
+         *      in JSON
+         *
+         *  Arguments:
+         *      request_type: Method of the request, only the listings are GET.
+         *      resource_path: Resource path from the request.
+         *      body: The request body.
+         *      client: Address of the client.
+         *
+         *  Returns:
+         *      The status with the response body, or None if the path isn't
+         *      an admin command.
+         */
+        let command: &[u8] = resource_path.strip_prefix(self.config.admin_path.as_bytes())?;
+        if !command.starts_with(b"/") {
+            return None;
+        }
+        /* Other GET requests under the prefix are the sites */
+        if request_type == RequestType::Get && command != b"/requests/slow" {
+            return None;
+        }
+        if !self.config.admin_clients.contains(&client) {
+            println!("[WARNING] {client} isn't allowed to run admin commands.");
+            return Some((HttpResponseStatus::Forbidden, Vec::new()));
+        }
+        match command {
+            b"/cache/flush" => {
+                let flushed: usize = self.flush_cache().await;
+                Some((
+                    HttpResponseStatus::Ok,
+                    format!("flushed {flushed}\n").into_bytes(),
+                ))
+            }
+            b"/cache/prewarm" if !body.trim_ascii().is_empty() => {
+                let loaded: usize = self.prewarm_cache(body.trim_ascii()).await;
+                Some((
+                    HttpResponseStatus::Ok,
+                    format!("prewarmed {loaded}\n").into_bytes(),
+                ))
+            }
+            b"/cache/prewarm" => Some((HttpResponseStatus::BadRequest, Vec::new())),
+            b"/links/add" | b"/links/remove" => Some(self.manage_links(command, body).await),
+            b"/requests/slow" => {
+                let slowest: Vec<SlowRequest> = self.shared_state.slow_requests.slowest();
+                match serde_json::to_vec_pretty(&slowest) {
+                    Ok(mut listed) => {
+                        listed.push(b'\n');
+                        Some((HttpResponseStatus::Ok, listed))
+                    }
+                    Err(e) => {
+                        println!("[ERROR] Failed to serialize the slow requests: {e}");
+                        Some((HttpResponseStatus::InternalServerError, Vec::new()))
+                    }
+                }
+            }
+            b"/webhooks/cert-renewed" => {
+                let domains: Vec<String> = String::from_utf8_lossy(body)
+                    .split_ascii_whitespace()
+                    .map(String::from)
+                    .collect();
+                println!("[INFO] Certificate of {domains:?} renewed.");
+                if let Some(webhooks) = self.shared_state.webhooks.get() {
+                    webhooks.notify(WebhookEvent::CertRenewed { domains });
+                }
+                Some((HttpResponseStatus::Ok, b"ok\n".to_vec()))
+            }
+            _ => Some((HttpResponseStatus::NotFound, Vec::new())),
+        }
+    }
+
+    async fn manage_links(&self, command: &[u8], body: &[u8]) -> (HttpResponseStatus, Vec<u8>) {
+        /*
+         *  Add or remove the short link.
+         *
+         *  Returns:
+         *      The status with the response body.
+         */
+        let Some(links) = &self.shared_state.short_links else {
+            return (
+                HttpResponseStatus::NotFound,
+                b"the link shortener is disabled\n".to_vec(),
+            );
+        };
+        let changed: Result<bool, io::Error> = if command == b"/links/add" {
+            match ShortLink::parse(body) {
+                Ok((slug, link)) => links.put(&slug, &link).await.map(|_| true),
+                Err(e) => {
+                    return (
+                        HttpResponseStatus::BadRequest,
+                        format!("{e}\n").into_bytes(),
+                    );
+                }
+            }
+        } else {
+            match std::str::from_utf8(body.trim_ascii()) {
+                Ok(slug) => links.remove(slug).await,
+                Err(_) => return (HttpResponseStatus::BadRequest, Vec::new()),
+            }
+        };
+        match changed {
+            Ok(true) => (HttpResponseStatus::Ok, b"ok\n".to_vec()),
+            Ok(false) => (HttpResponseStatus::NotFound, b"no such link\n".to_vec()),
+            Err(e) => {
+                println!("[ERROR] Failed to update the short links: {e}");
+                (HttpResponseStatus::InternalServerError, Vec::new())
+            }
+        }
+    }
+
+    async fn resolve_short_link(&self, resource_path: &[u8]) -> Option<ShortLink> {
+        /*
+         *  Look up the slug of the short link path, e.g. /r/abc
+         *
+         *  Returns:
+         *      The link, None if the path isn't a known short link.
+         */
+        let links: &Arc<dyn LinkStore> = self.shared_state.short_links.as_ref()?;
+        let slug: &[u8] = resource_path
+            .strip_prefix(
+                self.config
+                    .short_links_path
+                    .trim_end_matches('/')
+                    .as_bytes(),
+            )?
+            .strip_prefix(b"/")?;
+        match links.get(std::str::from_utf8(slug).ok()?).await {
+            Ok(link) => link,
+            Err(e) => {
+                println!("[ERROR] Failed to read the short links: {e}");
+                None
+            }
+        }
+    }
+
+    pub fn read_request_type(&self, buffer: &[u8]) -> RequestType {
+        /*
+         *  Get the type of the request.
+         *
+         *  Parameters:
+         *      buffer: Bytes of the stream, that was read into the vector.
+         *
+         *  Returns:
+         *      It returns either GET or POST enum.
+         */
+
+        if buffer.starts_with(GET_REQUEST) {
+            return RequestType::Get;
+        }
+
+        if buffer.starts_with(POST_REQUEST) {
+            return RequestType::Post;
+        }
+
+        if buffer.starts_with(HEAD_REQUEST) {
+            return RequestType::Head;
+        }
+
+        if buffer.starts_with(TRACE_REQUEST) {
+            return RequestType::Trace;
+        }
+        RequestType::Invalid
+    }
+
+    pub fn read_resource(&self, buffer: &[u8], req_type: &RequestType) -> Vec<u8> {
+        /*
+         *  Read what resource user requests.
+         *
+         *  Parameters:
+         *      buffer: Bytes of the stream, that was read into the vector.
+         *      req_type: Get the request type.
+         *
+         *  Returns:
+         *      Resource in bytes.
+         */
+
+        /* Extract the offset value */
+        let request_offset: usize = req_type.value();
+        if request_offset == usize::MAX {
+            return Vec::new();
+        }
+        let Some(target) = buffer.get(request_offset + 1..) else {
+            return Vec::new();
+        };
+        let mut vec_to_return: Vec<u8> = Vec::new();
+        for byte in target.iter() {
+            if *byte == SPACE {
+                return vec_to_return;
+            }
+            vec_to_return.push(*byte);
+        }
+        Vec::new()
+    }
+
+    pub async fn fetch_resource(
+        &self,
+        resource_path: &[u8],
+        bypass_cache: bool,
+        quota: Option<&QuotaPermit>,
+    ) -> (SiteContent, Option<CacheStatus>) {
+        /*
+         *  Fetch the data requested by user, the missing sites are answered
+         *  with the not found page.
+         *
+         *  Parameters:
+         *      resource_path: Resource path from the request.
+         *      bypass_cache: Read the resource from the disk, even if it
+         *      is cached.
+         *      quota: Quota of the requested host.
+         *
+         *  Returns:
+         *      The contents of the resource and how it was fetched, None
+         *      for the not found page, that isn't a site of the cache.
+         */
+        match self.fetch_site(resource_path, bypass_cache, quota).await {
+            Some((site, cache_status)) => (site, Some(cache_status)),
+            None => (self.site_not_found().await, None),
+        }
+    }
+
+    async fn fetch_site(
+        &self,
+        resource_path: &[u8],
+        bypass_cache: bool,
+        quota: Option<&QuotaPermit>,
+    ) -> Option<(SiteContent, CacheStatus)> {
+        /*
+         *  Fetch the data requested by user.
+         *
+         *  Parameters:
+         *      resource_path: Resource path from the request.
+         *      bypass_cache: Read the resource from the disk, even if it
+         *      is cached.
+         *      quota: Quota of the requested host, the site isn't cached
+         *      if it doesn't fit in the host's cache bytes.
+         *
+         *  Returns:
+         *      The contents of the resource and how it was fetched, None if
+         *      it doesn't exist.
+         */
+
+        // TODO: Check all files beforehand
+        // TODO: Add bad site handling, for now it returns nothing.
+        if resource_path.is_empty() {
+            // TODO: Change it to the welcome site later
+            return None;
+        }
+        /* The homepages change outside of the watched directory, so they aren't cached */
+        if let Some(user_dirs) = &self.shared_state.user_dirs
+            && is_user_path(resource_path)
+        {
+            let file: PathBuf = user_dirs.resolve(resource_path)?;
+            let site: SiteContent = match SiteContent::load(&file, self.config.mmap_threshold) {
+                Ok(site) if !site.is_empty() => site,
+                _ => return None,
+            };
+            Metrics::increment(&self.shared_state.metrics.cache_misses);
+            return Some((self.render_markdown(resource_path, site), CacheStatus::Miss));
+        }
+
+        let cached: Option<SiteContent> = self.shared_state.cached_sites.get(resource_path).await;
+        let is_cached: bool = cached.is_some();
+        if let Some(site) = cached
+            && !bypass_cache
+        {
+            Metrics::increment(&self.shared_state.metrics.cache_hits);
+            return Some((site, CacheStatus::Hit));
+        }
+
+        let mut path_on_server: Vec<u8> = self.html_dir().to_vec();
+        path_on_server.extend_from_slice(resource_path);
+
+        let Ok(path) = std::str::from_utf8(&path_on_server) else {
+            return None;
+        };
+        let path: String = String::from(path);
+        if !check_if_file_exists(&path) {
+            return None;
+        }
+
+        let site: SiteContent =
+            match SiteContent::load(Path::new(&path), self.config.mmap_threshold) {
+                Ok(site) if !site.is_empty() => site,
+                /* Failed to read */
+                _ => return None,
+            };
+        let site: SiteContent = self.render_markdown(resource_path, site);
+        let metrics: &Metrics = &self.shared_state.metrics;
+        if let Some(quota) = quota
+            && !quota.may_cache(resource_path, site.len() as u64)
+        {
+            Metrics::increment(&metrics.cache_misses);
+            return Some((site, CacheStatus::Miss));
+        }
+        /*
+         * We can allow for to_vec, because loading will occurr
+         * limited number of times
+         */
+        let evicted: usize = self
+            .shared_state
+            .cached_sites
+            .put(resource_path, site.clone())
+            .await;
+        Metrics::add(&metrics.cache_evictions, evicted as u64);
+        let cache_status: CacheStatus = if is_cached {
+            Metrics::increment(&metrics.cache_bypasses);
+            CacheStatus::Bypass
+        } else {
+            Metrics::increment(&metrics.cache_misses);
+            CacheStatus::Miss
+        };
+        Some((site, cache_status))
+    }
+
+    fn site_entry(&self, resource_path: &[u8]) -> Option<ManifestEntry> {
+        /*
+         *  Returns:
+         *      The manifest entry of the site, the homepages of the users
+         *      and the sites missing from the manifest are described
+         *      on demand.
+         */
+        if let Some(user_dirs) = &self.shared_state.user_dirs
+            && is_user_path(resource_path)
+        {
+            let file: PathBuf = user_dirs.resolve(resource_path)?;
+            return ManifestEntry::of(&file, file.to_str()?.as_bytes());
+        }
+        if let Some(entry) = self
+            .shared_state
+            .manifest
+            .read()
+            .unwrap()
+            .get(resource_path)
+        {
+            return Some(entry.clone());
+        }
+        /* The site added since the manifest was built is described on its first request */
+        let html_dir: PathBuf = bytes_to_path(self.html_dir());
+        let file: PathBuf = resolve_under(&html_dir, std::str::from_utf8(resource_path).ok()?)?;
+        let mut manifest = self.shared_state.manifest.write().unwrap();
+        manifest.refresh(&html_dir, &file);
+        manifest.get(resource_path).cloned()
+    }
+
+    fn post_process_site(
+        &self,
+        site: &[u8],
+        status: HttpResponseStatus,
+        extra_headers: &mut Vec<(String, String)>,
+    ) -> Option<Response> {
+        /*
+         *  Give the site to the hooks as the response, it is served as they
+         *  leave it.
+         *
+         *  Arguments:
+         *      site: Content of the site.
+         *      status: Status of the site, 404 for the not found page.
+         *      extra_headers: Headers of the response so far, the hooks'
+         *      changes replace them.
+         *
+         *  Returns:
+         *      The processed response, None if no hook matches the site.
+         */
+        let post_processors = self.shared_state.post_processors.read().unwrap();
+        if !post_processors.applies_to(content_type(extra_headers)) {
+            return None;
+        }
+        let mut response: Response = Response::new(status).with_body(site.to_vec());
+        response.headers = std::mem::take(extra_headers);
+        post_processors.apply(&mut response);
+        *extra_headers = std::mem::take(&mut response.headers);
+        Some(response)
+    }
+
+    fn renders_markdown(&self, resource_path: &[u8]) -> bool {
+        self.shared_state.markdown.is_some() && is_markdown(resource_path)
+    }
+
+    fn render_markdown(&self, resource_path: &[u8], site: SiteContent) -> SiteContent {
+        /*
+         *  Returns:
+         *      The page rendered from the .md site if the rendering is
+         *      enabled, the site as it is otherwise.
+         */
+        match &self.shared_state.markdown {
+            Some(renderer) if is_markdown(resource_path) => {
+                SiteContent::from(renderer.render_page(&site))
+            }
+            _ => site,
+        }
+    }
+
+    async fn encode_site(
+        &self,
+        resource_path: &[u8],
+        site: &SiteContent,
+        coding: ContentCoding,
+    ) -> Option<SiteContent> {
+        /*
+         *  Get the encoded site from the cache, or from its file written by
+         *  precompress, or encode it. It is kept in the cache for the next
+         *  requests.
+         *
+         *  Returns:
+         *      The encoded site, None if it failed to encode.
+         */
+        let metrics: &Metrics = &self.shared_state.metrics;
+        let cached: Option<SiteContent> = self
+            .shared_state
+            .cached_sites
+            .get_encoded(resource_path, coding)
+            .await;
+        if cached.is_some() {
+            Metrics::increment(&metrics.compressed_hits);
+            return cached;
+        }
+        if let Some(encoded) = self.precompressed_site(resource_path, coding) {
+            self.shared_state
+                .cached_sites
+                .put_encoded(resource_path, coding, encoded.clone())
+                .await;
+            return Some(encoded);
+        }
+        let encoded: SiteContent = match coding.encode(site) {
+            Ok(encoded) => SiteContent::from(encoded),
+            Err(e) => {
+                println!(
+                    "[ERROR] Failed to encode the site with {}: {e}",
+                    coding.token()
+                );
+                return None;
+            }
+        };
+        Metrics::increment(&metrics.compressions);
+        self.shared_state
+            .cached_sites
+            .put_encoded(resource_path, coding, encoded.clone())
+            .await;
+        Some(encoded)
+    }
+
+    fn precompressed_site(
+        &self,
+        resource_path: &[u8],
+        coding: ContentCoding,
+    ) -> Option<SiteContent> {
+        /*
+         *  Returns:
+         *      The encoded file of the site, None if precompress didn't
+         *      write it or the site changed since. The rendered sites and
+         *      the homepages of the users are always encoded on the fly.
+         */
+        if self.renders_markdown(resource_path)
+            || (self.shared_state.user_dirs.is_some() && is_user_path(resource_path))
+        {
+            return None;
+        }
+        let html_dir: PathBuf = bytes_to_path(self.html_dir());
+        let file: PathBuf = resolve_under(&html_dir, std::str::from_utf8(resource_path).ok()?)?;
+        let encoded_file: PathBuf = fresh_encoded_path(&file, coding)?;
+        SiteContent::load(&encoded_file, self.config.mmap_threshold)
+            .ok()
+            .filter(|encoded| !encoded.is_empty())
+    }
+
+    async fn site_not_found(&self) -> SiteContent {
+        /*
+         *  The error page is pinned in the cache, it isn't counted in the
+         *  statistics of the cache, so the misses of the sites don't look
+         *  like hits. If it failed to load, the page is empty.
+         */
+        self.shared_state
+            .cached_sites
+            .get(SITE_NOT_FOUND)
+            .await
+            .unwrap_or_else(|| SiteContent::from(Vec::new()))
+    }
+
+    pub fn may_bypass_cache(&self, client: IpAddr) -> bool {
+        /*
+         *  Check if the client is trusted to force re-reading of the sites.
+         */
+        let cfg: &ServerConfig = &self.config;
+        cfg.allow_cache_bypass
+            && (cfg.cache_bypass_clients.is_empty() || cfg.cache_bypass_clients.contains(&client))
+    }
+
+    fn parsed_head(&self, buffer: &[u8]) -> Option<RequestHead> {
+        /*
+         *  Frame the request with the parser, that the connection was read
+         *  with, so the body doesn't depend on the spelling of the headers
+         *  and no other header is taken for Content-Length.
+         *
+         *  Parameters:
+         *      buffer: Bytes of the stream, that was read into the vector.
+         *
+         *  Returns:
+         *      The head, None if it is incomplete or refused.
+         */
+        match RequestParser::new(self.config.max_header_size).advance(buffer) {
+            Ok(ParserState::Body(head)) | Ok(ParserState::Complete(head)) => Some(head),
+            _ => None,
+        }
+    }
+
+    fn buffered_body_framing(&self, buffer: &[u8]) -> Result<Option<(usize, usize)>, RequestError> {
+        /*
+         *  Locate the body after the blank line, that ends the head.
+         *
+         *  Parameters:
+         *      buffer: Bytes of the stream, that was read into the vector.
+         *
+         *  Returns:
+         *      Index, where the body starts, with its length from
+         *      Content-Length. None if the request has no body.
+         *      Error if the body is too large to be buffered.
+         */
+        let Some(head) = self.parsed_head(buffer).filter(|head| head.body_len > 0) else {
+            return Ok(None);
+        };
+        /* Too big body */
+        if head.body_len > MAX_BUFFERED_BODY {
+            return Err(RequestError::BodyTooLarge);
+        }
+        Ok(Some((head.head_len, head.body_len)))
+    }
+
+    pub fn declared_body_len(&self, buffer: &[u8]) -> usize {
+        /*
+         *  Returns:
+         *      Length of the body from Content-Length, zero if it is missing.
+         */
+        self.parsed_head(buffer).map_or(0, |head| head.body_len)
+    }
+
+    pub async fn relieve_memory(&self, body_len: usize) -> bool {
+        /*
+         *  Shrink the cache, if the memory budget is exceeded, and check,
+         *  that the body fits in.
+         *
+         *  Arguments:
+         *      body_len: Length of the request body, the small ones are
+         *      always admitted.
+         *
+         *  Returns:
+         *      False if the large body doesn't fit into the budget.
+         */
+        let budget: &MemoryBudget = &self.shared_state.memory;
+        let cached_sites: &dyn SiteCache = self.shared_state.cached_sites.as_ref();
+        if budget.is_exceeded(cached_sites.heap_bytes())
+            && let Some(target) = budget.cache_target()
+        {
+            let evicted: usize = cached_sites.shrink(target).await;
+            if evicted > 0 {
+                Metrics::add(&self.shared_state.metrics.memory_evictions, evicted as u64);
+                println!(
+                    "[WARNING] Memory budget exceeded, evicted {evicted} sites from the cache."
+                );
+            }
+        }
+        if body_len < self.config.large_body_size
+            || budget.admits(cached_sites.heap_bytes(), body_len)
+        {
+            return true;
+        }
+        Metrics::increment(&self.shared_state.metrics.shed_requests);
+        false
+    }
+
+    pub fn missing_body_len(&self, buffer: &[u8]) -> Result<usize, RequestError> {
+        /*
+         *  Returns:
+         *      Number of the body bytes, that haven't arrived with the buffer.
+         */
+        Ok(match self.buffered_body_framing(buffer)? {
+            Some((body_start, body_length)) => {
+                body_length.saturating_sub(buffer.len() - body_start)
+            }
+            None => 0,
+        })
+    }
+
+    pub fn read_request_body<'a>(&self, buffer: &'a [u8]) -> Result<Cow<'a, [u8]>, RequestError> {
+        /*
+         *  Get the actual request body, that follows the blank line.
+         *
+         *  Parameters:
+         *      buffer: Bytes of the stream, that was read into the vector.
+         *
+         *  Returns:
+         *      Returns the body, borrowed from the buffer unless it was
+         *      decoded, the empty one might mean the handshake. Error if the body is too large, incomplete
+         *      or can't be decoded.
+         */
+        let Some((body_start, body_length)) = self.buffered_body_framing(buffer)? else {
+            return Ok(Cow::Borrowed(&[]));
+        };
+        /* The rest of the body must be read from the stream first */
+        let body: &[u8] = buffer
+            .get(body_start..body_start + body_length)
+            .ok_or(RequestError::IncompleteBody)?;
+
+        /* Decode the body, so the handlers never see the gzip stream */
+        let headers: HeaderMap = HeaderMap::parse(buffer);
+        match headers.get("content-encoding") {
+            Some(coding)
+                if coding.eq_ignore_ascii_case(GZIP_ENCODING)
+                    || coding.eq_ignore_ascii_case(X_GZIP_ENCODING) =>
+            {
+                inflate_gzip(body, self.config.max_decompressed_body_size)
+                    .map(Cow::Owned)
+                    .map_err(RequestError::InvalidEncoding)
+            }
+            _ => Ok(Cow::Borrowed(body)),
+        }
+    }
+
+    pub fn split_request_body<'a>(&self, buffer: &'a [u8]) -> Option<(&'a [u8], usize)> {
+        /*
+         *  Split the body, that is framed with Content-Length, into the part
+         *  read with the headers and the length of the rest.
+         *
+         *  Arguments:
+         *      buffer: Bytes of the stream, that was read into the vector.
+         *
+         *  Returns:
+         *      The received bytes of the body with the number of the bytes
+         *      still to read. None if the body is empty, missing or encoded,
+         *      the encoded bodies are always buffered.
+         */
+        if HeaderMap::parse(buffer).contains("content-encoding") {
+            return None;
+        }
+        let head: RequestHead = self.parsed_head(buffer).filter(|head| head.body_len > 0)?;
+        let received: &[u8] = &buffer[head.head_len..];
+        let received: &[u8] = &received[..cmp::min(received.len(), head.body_len)];
+        Some((received, head.body_len - received.len()))
+    }
+
+    async fn serve_connection(
+        &self,
+        inc_stream: TcpStream,
+        inc_addr: SocketAddr,
+        proxy_protocol: bool,
+        conn_timeout: Duration,
+        _open: OpenConnection,
+    ) {
+        /*
+         *  Handle the connection on its own task, so a panic of the handler
+         *  is caught, instead of taking the server down with it.
+         *
+         *  Arguments:
+         *      inc_stream: Incoming stream from the host's request.
+         *      inc_addr: The address, that the request comes from.
+         *      proxy_protocol: The listener expects the PROXY protocol header.
+         *      conn_timeout: How long the connection may be handled.
+         *      _open: Counts the connection as open, until the handler
+         *      finishes, times out or panics.
+         */
+        let answering: Arc<Mutex<Option<RequestId>>> = Arc::default();
+        let (inc_stream, spare) = match SpareHandle::split(inc_stream) {
+            Ok(split) => split,
+            Err(e) => {
+                println!("[ERROR] {inc_addr}: Failed to set up the connection: {e}");
+                return;
+            }
+        };
+        let srv: Server = self.clone();
+        let slot: Arc<Mutex<Option<RequestId>>> = Arc::clone(&answering);
+        let handled = tokio::spawn(async move {
+            let handler = srv.handle_connection(inc_stream, inc_addr, proxy_protocol, &slot);
+            timeout(conn_timeout, handler).await
+        })
+        .await;
+        match handled {
+            Ok(Ok(())) => {}
+            Ok(Err(_)) => println!("[WARNING] Connection with {inc_addr} timed out."),
+            Err(e) if e.is_panic() => {
+                let pending: Option<RequestId> = *answering.lock().unwrap();
+                self.handler_panicked(inc_addr, e.into_panic(), spare, pending)
+                    .await
+            }
+            Err(e) => println!("[ERROR] {inc_addr}: Connection handler failed: {e}"),
+        }
+    }
+
+    fn log_panic(&self, inc_addr: SocketAddr, payload: &(dyn Any + Send), id: Option<RequestId>) {
+        /*
+         *  Log and count the panic of the handler, with the request, that
+         *  was being handled.
+         */
+        Metrics::increment(&self.shared_state.metrics.handler_panics);
+        let message: &str = panic_message(payload);
+        match id.and_then(|id| self.shared_state.status.pending(id)) {
+            Some(request) => println!(
+                "[ERROR] {inc_addr}: Handler panicked on \"{}\": {message}",
+                request.request
+            ),
+            None => {
+                println!("[ERROR] {inc_addr}: Handler panicked between the requests: {message}")
+            }
+        }
+    }
+
+    async fn handler_panicked(
+        &self,
+        inc_addr: SocketAddr,
+        payload: Box<dyn Any + Send>,
+        spare: SpareHandle,
+        pending: Option<RequestId>,
+    ) {
+        /*
+         *  Answer the request, that was being handled, when the connection
+         *  handler panicked, with 500. The connection is closed either way.
+         *
+         *  Arguments:
+         *      inc_addr: The address, that the request comes from.
+         *      payload: Payload of the panic.
+         *      spare: Spare handle of the connection's socket.
+         *      pending: The last request of the connection, it is answered
+         *      unless it was answered before the panic.
+         */
+        self.log_panic(inc_addr, &*payload, pending);
+        let status: usize = HttpResponseStatus::InternalServerError.value();
+        let Some(request) = pending.and_then(|id| self.shared_state.status.finish(id, status))
+        else {
+            return;
+        };
+        self.shared_state.metrics.count_response(status);
+        if let Some(logs) = self.shared_state.logs.get() {
+            logs.access(&request);
+        }
+        let response: Vec<u8> = format_response(
+            HttpResponseStatus::InternalServerError,
+            &[(String::from("Connection"), String::from("close"))],
+            &[],
+        );
+        if let Err(e) = spare.answer(&response).await {
+            println!("[WARNING] {inc_addr}: Failed to answer after the panic: {e}");
+        }
+    }
+
+    async fn handle_connection<S: Connection>(
+        &self,
+        mut inc_stream: S,
+        mut inc_addr: SocketAddr,
+        proxy_protocol: bool,
+        answering: &Mutex<Option<RequestId>>,
+    ) {
+        /*
+         *  Handles each incoming connection. It will read the incoming requests,
+         *  create appropiate responses and send them out.
+         *
+         *  Arguments:
+         *      inc_stream: Incoming stream from the host's request.
+         *      inc_addr: The address, that the request comes from.
+         *      proxy_protocol: The listener expects the PROXY protocol header.
+         *      answering: The request being answered, for the panics, that
+         *      unwind the whole connection.
+         */
+
+        /* Handlers must finish before the connection times out */
+        let now: Instant = Instant::now();
+        let deadline: Instant = cmp::min(
+            now + Duration::from_secs(self.config.handler_timeout_in_secs.into()),
+            now + Duration::from_secs(self.config.timeout_in_secs.into()),
+        );
+
+        /* Try to read the content, if fail exit earlier */
+        let mut vec_buf: PooledBuffer = self.shared_state.buffers.take();
+        match inc_stream.read_buf(&mut *vec_buf).await {
+            Ok(sz) => println!("[INFO] Read {sz} bytes"),
+            Err(e) => {
+                println!("[ERROR] {inc_addr}: {e}");
+                return;
+            }
+        }
+
+        /* The balancer tells the real client address before the request */
+        if proxy_protocol {
+            match tokio::time::timeout_at(
+                deadline,
+                read_proxy_header(&mut inc_stream, &mut vec_buf),
+            )
+            .await
+            {
+                Ok(Ok(header)) => {
+                    if let Some(source) = header.source {
+                        inc_addr = source;
+                    }
+                    vec_buf.drain(..header.len);
+                }
+                Ok(Err(e)) => {
+                    println!("[ERROR] {inc_addr}: {e}.");
+                    let _ = inc_stream.shutdown().await;
+                    return;
+                }
+                Err(_) => {
+                    println!("[ERROR] {inc_addr}: PROXY protocol header timed out.");
+                    let _ = inc_stream.shutdown().await;
+                    return;
+                }
+            }
+        }
+
+        /* Pipelined requests are answered in order, as they were sent */
+        let mut parser: RequestParser = RequestParser::new(self.config.max_header_size);
+        let mut read_started: Instant = now;
+        loop {
+            /* The head might be split between several reads */
+            let head: RequestHead = loop {
+                match parser.advance(&vec_buf) {
+                    Ok(ParserState::Body(head)) | Ok(ParserState::Complete(head)) => break head,
+                    Ok(_) => {}
+                    Err(e) => {
+                        println!("[ERROR] {inc_addr}: {e}.");
+                        /* The rejected bytes are the most useful to replay */
+                        if let Some(recorder) = self.shared_state.recorder.get() {
+                            recorder.request(inc_addr, &vec_buf);
+                        }
+                        self.dump_malformed(inc_addr, &vec_buf, &e.to_string());
+                        let id: RequestId = self.shared_state.status.begin(inc_addr.ip(), &vec_buf);
+                        let close: Vec<(String, String)> =
+                            vec![(String::from("Connection"), String::from("close"))];
+                        let response: Vec<u8> = format_response(e.status(), &close, &[]);
+                        let _ = id
+                            .scope(self.reply(&mut inc_stream, inc_addr, &response))
+                            .await;
+                        let _ = inc_stream.shutdown().await;
+                        return;
+                    }
+                }
+                grow_for_head(&mut vec_buf, self.config.max_header_size);
+                match inc_stream.read_buf(&mut *vec_buf).await {
+                    Ok(0) => {
+                        println!(
+                            "[WARNING] {inc_addr}: Connection closed before the request was complete."
+                        );
+                        return;
+                    }
+                    Ok(sz) => println!("[INFO] Read {sz} bytes"),
+                    Err(e) => {
+                        println!("[ERROR] {inc_addr}: {e}");
+                        return;
+                    }
+                }
+            };
+
+            /* The next requests stay in the buffer */
+            let complete: bool = vec_buf.len() >= head.message_len();
+            let mut next: PooledBuffer = self.shared_state.buffers.take();
+            if complete {
+                next.extend_from_slice(&vec_buf[head.message_len()..]);
+                vec_buf.truncate(head.message_len());
+            }
+            if let Some(recorder) = self.shared_state.recorder.get() {
+                recorder.request(inc_addr, &vec_buf);
+            }
+            let id: RequestId = self.shared_state.status.begin(inc_addr.ip(), &vec_buf);
+            *answering.lock().unwrap() = Some(id);
+            let clock: Arc<RequestClock> = Arc::new(RequestClock::new(read_started, &vec_buf));
+            let answered: Option<S> = id
+                .scope(Arc::clone(&clock).scope(self.handle_request(
+                    inc_stream,
+                    &mut vec_buf,
+                    inc_addr,
+                    deadline,
+                )))
+                .await;
+            self.log_if_slow(inc_addr, &clock);
+            inc_stream = match answered {
+                Some(inc_stream) => inc_stream,
+                None => return,
+            };
+            read_started = Instant::now();
+            /* The grown buffer is kept, the client sends the same headers again */
+            if vec_buf.capacity() > next.capacity() {
+                vec_buf.clear();
+                vec_buf.extend_from_slice(&next);
+            } else {
+                vec_buf = next;
+            }
+            /* An unread part of the body would be taken for the next request */
+            if !complete || !head.keep_alive || vec_buf.is_empty() {
+                return;
+            }
+            parser.reset();
+        }
+    }
+
+    async fn handle_request<S: Connection>(
+        &self,
+        mut inc_stream: S,
+        vec_buf: &mut Vec<u8>,
+        mut inc_addr: SocketAddr,
+        deadline: Instant,
+    ) -> Option<S> {
+        /*
+         *  Answer a single request of the connection. Each feature answering
+         *  the request has its own method, they are tried in their order.
+         *
+         *  Arguments:
+         *      inc_stream: Stream of the connection.
+         *      vec_buf: Bytes of the request, with the received part of the body.
+         *      inc_addr: The address, that the request comes from.
+         *      deadline: Instant, when the handlers must be finished.
+         *
+         *  Returns:
+         *      The stream, if the connection can be used for the next request.
+         *      None if it must be closed, e.g. after the malformed request.
+         */
+
+        let cfg: &ServerConfig = &self.config;
+        if cfg.lowercase_host {
+            lowercase_host(vec_buf);
+        }
+        let (request_type, resource_path, query, target_authority) =
+            match self.validate_request(vec_buf) {
+                Ok(request_line) => request_line,
+                Err(e) => {
+                    if e.is_malformed() {
+                        self.dump_malformed(inc_addr, vec_buf, &e.to_string());
+                    }
+                    return self.reject(inc_stream, inc_addr, e).await;
+                }
+            };
+        RequestClock::parsed();
+
+        /* Requests relayed by the trusted proxies carry the client address */
+        if !cfg.trusted_proxies.is_empty() {
+            let client: IpAddr = client_ip(vec_buf, inc_addr.ip(), &cfg.trusted_proxies);
+            if client != inc_addr.ip() {
+                println!("[INFO] {inc_addr}: Forwarded for {client}.");
+                inc_addr = SocketAddr::new(client, inc_addr.port());
+            }
+        }
+
+        let host: Option<&[u8]> = request_host(vec_buf, target_authority.as_deref());
+        let mut extra_headers: Vec<(String, String)> = cfg.security_headers.headers_for(host);
+        apply_header_rules(&cfg.response_headers, &resource_path, &mut extra_headers);
+        let bandwidth_limits: Vec<Arc<BandwidthLimit>> =
+            self.shared_state.shaper.limits_for(host, &resource_path);
+        let spa_page: Option<Vec<u8>> = cfg.spa_fallback.page_for(host, &resource_path);
+        let mut exchange: Exchange = Exchange {
+            request_type,
+            resource_path,
+            query,
+            inc_addr,
+            deadline,
+            extra_headers,
+            bandwidth_limits,
+            quota_usage: None,
+        };
+
+        /* The access rules are evaluated before any route answers */
+        match check_access(
+            &cfg.access_rules,
+            request_type,
+            &exchange.resource_path,
+            inc_addr.ip(),
+        ) {
+            AccessDecision::Allow => {}
+            AccessDecision::Forbidden => {
+                println!("[WARNING] {inc_addr}: Access denied by the rules.");
+                return self
+                    .respond(inc_stream, &exchange, HttpResponseStatus::Forbidden, &[])
+                    .await;
+            }
+            AccessDecision::MethodNotAllowed(allowed) => {
+                exchange
+                    .extra_headers
+                    .push((String::from("Allow"), allowed));
+                return self
+                    .respond(
+                        inc_stream,
+                        &exchange,
+                        HttpResponseStatus::MethodNotAllowed,
+                        &[],
+                    )
+                    .await;
+            }
+        }
+
+        /* TRACE echoes the request back, it is never routed */
+        if request_type == RequestType::Trace {
+            exchange
+                .extra_headers
+                .push((String::from("Content-Type"), String::from("message/http")));
+            return self
+                .respond(
+                    inc_stream,
+                    &exchange,
+                    HttpResponseStatus::Ok,
+                    &trace_echo(vec_buf),
+                )
+                .await;
+        }
+
+        /* The testing rules delay the request or fail it on purpose */
+        if let Some(fault) = self.inject_chaos(&exchange).await {
+            if fault == Fault::Drop {
+                let _ = inc_stream.shutdown().await;
+                return None;
+            }
+            return self
+                .respond(
+                    inc_stream,
+                    &exchange,
+                    HttpResponseStatus::InternalServerError,
+                    &[],
+                )
+                .await;
+        }
+
+        /* Queue the request, if the server is too busy, or refuse it once the queue is full */
+        let _permit: ConcurrencyPermit = match self.admit(&exchange.resource_path).await {
+            Ok(permit) => permit,
+            Err(shed) => {
+                println!("[WARNING] {shed}, refusing {inc_addr}.");
+                exchange.extra_headers.push((
+                    String::from("Retry-After"),
+                    cfg.retry_after_secs.to_string(),
+                ));
+                return self
+                    .respond(
+                        inc_stream,
+                        &exchange,
+                        HttpResponseStatus::ServiceUnavailable,
+                        &[],
+                    )
+                    .await;
+            }
+        };
+
+        /* The hosts with the quota are refused, once they use it up */
+        let quota: Option<QuotaPermit> = match self.shared_state.quotas.try_acquire(host) {
+            Ok(quota) => quota,
+            Err(exceeded) => {
+                let (status, retry_after_secs): (HttpResponseStatus, u64) = match exceeded {
+                    QuotaExceeded::Bandwidth(secs) => (HttpResponseStatus::TooManyRequests, secs),
+                    QuotaExceeded::Concurrency => (
+                        HttpResponseStatus::ServiceUnavailable,
+                        cfg.retry_after_secs.into(),
+                    ),
+                };
+                println!("[WARNING] Quota of the host exceeded, refusing {inc_addr}.");
+                exchange
+                    .extra_headers
+                    .push((String::from("Retry-After"), retry_after_secs.to_string()));
+                return self.respond(inc_stream, &exchange, status, &[]).await;
+            }
+        };
+        exchange.quota_usage = quota.as_ref().map(QuotaPermit::usage);
+
+        /* Switched connections outlive the connection timeout, so they are detached */
+        if let Some(route) = find_upgrade_route(&cfg.upgrade_routes, &exchange.resource_path)
+            && is_upgrade_request(vec_buf)
+        {
+            let vec_buf: Vec<u8> = std::mem::take(vec_buf);
+            self.upgrade(inc_stream, inc_addr, vec_buf, route, _permit, quota);
+            return None;
+        }
+
+        /* Over the memory budget the large bodies are refused, not buffered */
+        let declared_body_len: usize = self.declared_body_len(vec_buf);
+        if !self.relieve_memory(declared_body_len).await {
+            println!("[WARNING] Memory budget exceeded, refusing the body of {inc_addr}.");
+            exchange.extra_headers.extend([
+                (
+                    String::from("Retry-After"),
+                    cfg.retry_after_secs.to_string(),
+                ),
+                (String::from("Connection"), String::from("close")),
+            ]);
+            let _ = self
+                .respond(
+                    inc_stream,
+                    &exchange,
+                    HttpResponseStatus::ServiceUnavailable,
+                    &[],
+                )
+                .await;
+            return None;
+        }
+        let _body_memory: Reservation = self.shared_state.memory.reserve(declared_body_len);
+
+        /* Try to read the body */
+        /* The buffered body might still be on the way, e.g. the encoded one */
+        if self.split_request_body(vec_buf).is_none()
+            && let Err(e) = self
+                .read_body_remainder(&mut inc_stream, vec_buf, deadline)
+                .await
+        {
+            return self.reject(inc_stream, inc_addr, e).await;
+        }
+        let streamed_body: Option<(&[u8], usize)> = self.split_request_body(vec_buf);
+        let read_body_result: Cow<[u8]> = match &streamed_body {
+            Some((_, remaining)) if *remaining > 0 => Cow::Borrowed(&[]),
+            _ => match self.read_request_body(vec_buf) {
+                Ok(body) => body,
+                Err(e) => return self.reject(inc_stream, inc_addr, e).await,
+            },
+        };
+        if read_body_result.is_empty()
+            && request_type == RequestType::Post
+            && streamed_body.is_none()
+        {
+            println!("[WARNING] Failed to read the body. Assume the handshake.");
+            let (site_content, _) = self.fetch_resource(&read_body_result, false, None).await;
+            return self
+                .respond(inc_stream, &exchange, HttpResponseStatus::Ok, &site_content)
+                .await;
+        }
+
+        /* ACME servers validate the domain before anything else is routed */
+        if request_type == RequestType::Get
+            && let Some(challenge_dir) = &cfg.acme_challenge_dir
+            && let Some((status, content)) =
+                challenge_response(Path::new(challenge_dir), &exchange.resource_path)
+        {
+            exchange
+                .extra_headers
+                .push((String::from("Content-Type"), String::from("text/plain")));
+            return self.respond(inc_stream, &exchange, status, &content).await;
+        }
+
+        /* Admin commands take precedence over the registered routes */
+        if matches!(request_type, RequestType::Post | RequestType::Get)
+            && let Some((status, content)) = self
+                .handle_admin(
+                    request_type,
+                    &exchange.resource_path,
+                    &read_body_result,
+                    inc_addr.ip(),
+                )
+                .await
+        {
+            return self.respond(inc_stream, &exchange, status, &content).await;
+        }
+
+        /* The mirrored files come from the upstream, not the resource directory */
+        if matches!(request_type, RequestType::Get | RequestType::Head)
+            && let Some(mirror) = &self.shared_state.mirror
+            && let Some(upstream_path) = mirror.upstream_path(&exchange.resource_path)
+        {
+            let upstream_target: Vec<u8> = match &exchange.query {
+                Some(query) => [upstream_path, b"?", query].concat(),
+                None => upstream_path.to_vec(),
+            };
+            return self
+                .serve_mirror(inc_stream, mirror, &upstream_target, &mut exchange)
+                .await;
+        }
+
+        /* Short links redirect before the sites are looked up */
+        if request_type == RequestType::Get
+            && let Some(link) = self.resolve_short_link(&exchange.resource_path).await
+        {
+            return self.serve_short_link(inc_stream, link, &mut exchange).await;
+        }
+
+        /* The status page and its event stream, the stream is detached as well */
+        if request_type == RequestType::Get && !cfg.status_path.is_empty() {
+            let status_path: &[u8] = cfg.status_path.as_bytes();
+            let events: bool = exchange.resource_path.strip_prefix(status_path) == Some(b"/events");
+            if exchange.resource_path == status_path || events {
+                return self
+                    .serve_status_page(inc_stream, vec_buf, events, &mut exchange)
+                    .await;
+            }
+        }
+
+        /* The event stream stays open, so it is detached and doesn't hold the permit */
+        if request_type == RequestType::Get
+            && cfg.dev_mode
+            && exchange.resource_path == RELOAD_PATH.as_bytes()
+            && let Some(live_reload) = self.shared_state.live_reload.get().cloned()
+        {
+            self.answered(HttpResponseStatus::Ok.value());
+            tokio::spawn(async move { live_reload.serve_events(inc_stream).await });
+            return None;
+        }
+
+        /* Registered routes are answered by their handlers */
+        let route: Option<RouteMatch> = self
+            .shared_state
+            .router
+            .read()
+            .unwrap()
+            .resolve(request_type, &exchange.resource_path);
+        if let Some(route) = route {
+            let body: RouteBody = match streamed_body {
+                Some((received, remaining)) if remaining > 0 => {
+                    RouteBody::Streamed(received.to_vec(), remaining)
+                }
+                _ => RouteBody::Buffered(read_body_result.into_owned()),
+            };
+            return self
+                .serve_route(inc_stream, vec_buf, route, body, exchange)
+                .await;
+        }
+
+        if matches!(request_type, RequestType::Get | RequestType::Head)
+            && !cfg.hide_capabilities
+            && exchange.resource_path == CAPABILITIES_PATH
+        {
+            return self.serve_capabilities(inc_stream, &mut exchange).await;
+        }
+
+        if !cfg.metrics_path.is_empty() && exchange.resource_path == cfg.metrics_path.as_bytes() {
+            return self.serve_metrics(inc_stream, &mut exchange).await;
+        }
+
+        self.serve_site(inc_stream, vec_buf, spa_page, quota.as_ref(), exchange)
+            .await
+    }
+
+    async fn respond<S: Connection>(
+        &self,
+        mut inc_stream: S,
+        exchange: &Exchange,
+        status: HttpResponseStatus,
+        body: &[u8],
+    ) -> Option<S> {
+        /*
+         *  Answer the request with the whole response at once, HEAD gets
+         *  the same headers as GET, the body is left out.
+         *
+         *  Arguments:
+         *      inc_stream: Stream of the connection.
+         *      exchange: The request, its extra headers are sent.
+         *      status: Status of the response.
+         *      body: Body of the response.
+         *
+         *  Returns:
+         *      The stream, None if the response couldn't be written.
+         */
+        let mut response: Vec<u8> = format_head(status, &exchange.extra_headers, Some(body.len()));
+        if exchange.request_type != RequestType::Head {
+            response.extend_from_slice(body);
+        }
+        self.reply(&mut inc_stream, exchange.inc_addr, &response)
+            .await
+            .ok()?;
+        Some(inc_stream)
+    }
+
+    async fn inject_chaos(&self, exchange: &Exchange) -> Option<Fault> {
+        /*
+         *  Delay the request by the testing rules.
+         *
+         *  Returns:
+         *      The fault, that the request must fail with, None if it is
+         *      answered as usual.
+         */
+        if !self.shared_state.chaos.is_enabled() {
+            return None;
+        }
+        let plan: ChaosPlan = self.shared_state.chaos.plan(&exchange.resource_path);
+        tokio::time::sleep(plan.delay).await;
+        let fault: Fault = plan.fault?;
+        Metrics::increment(&self.shared_state.metrics.injected_faults);
+        println!("[WARNING] {}: Injected {fault:?} fault.", exchange.inc_addr);
+        Some(fault)
+    }
+
+    async fn admit(&self, resource_path: &[u8]) -> Result<ConcurrencyPermit, Shed> {
+        /*
+         *  Take the slot of the concurrency limits, the request waits in
+         *  the queue, if there is no free one.
+         *
+         *  Returns:
+         *      The permit, or why the request is shed.
+         */
+        let limiter: &ConcurrencyLimiter = &self.shared_state.limiter;
+        if let Some(permit) = limiter.try_acquire(resource_path) {
+            return Ok(permit);
+        }
+        let waited: Result<ConcurrencyPermit, Shed> = limiter.wait(resource_path).await;
+        let metrics: &Metrics = &self.shared_state.metrics;
+        if !matches!(waited, Err(Shed::Saturated)) {
+            Metrics::increment(&metrics.queued_requests);
+        }
+        if matches!(waited, Err(Shed::Expired)) {
+            Metrics::increment(&metrics.queue_timeouts);
+        }
+        waited
+    }
+
+    fn upgrade<S: Connection>(
+        &self,
+        inc_stream: S,
+        inc_addr: SocketAddr,
+        vec_buf: Vec<u8>,
+        route: &UpgradeRoute,
+        permit: ConcurrencyPermit,
+        quota: Option<QuotaPermit>,
+    ) {
+        /*
+         *  Pass the switched connection through to the upstream of the route
+         *  on its own task. The permits are held, until it is closed.
+         *
+         *  Arguments:
+         *      inc_stream: Stream of the connection.
+         *      inc_addr: The address, that the request comes from.
+         *      vec_buf: Bytes of the upgrade request, they are sent first.
+         *      route: The upgrade route matching the request.
+         *      permit: Slot of the concurrency limits.
+         *      quota: Slot of the host's quota.
+         */
+        let upstream: String = route.upstream.clone();
+        let hide_identity: bool = self.config.hide_upstream_identity;
+        tokio::spawn(async move {
+            let _permit: ConcurrencyPermit = permit;
+            let _quota: Option<QuotaPermit> = quota;
+            match pass_through(inc_stream, &vec_buf, &upstream, hide_identity).await {
+                Ok((sent, received)) => println!(
+                    "[INFO] {inc_addr}: Upgraded connection closed, sent {sent} bytes, received {received} bytes."
+                ),
+                Err(e) => println!("[ERROR] {inc_addr}: Upgrade to {upstream} failed: {e}"),
+            }
+        });
+    }
+
+    async fn serve_mirror<S: Connection>(
+        &self,
+        inc_stream: S,
+        mirror: &Mirror,
+        upstream_target: &[u8],
+        exchange: &mut Exchange,
+    ) -> Option<S> {
+        /*
+         *  Answer with the mirrored file, fetched from the upstream if it
+         *  isn't fresh on the disk.
+         *
+         *  Arguments:
+         *      inc_stream: Stream of the connection.
+         *      mirror: The mirror of the upstream.
+         *      upstream_target: Path of the file on the upstream with its query.
+         *      exchange: The request, the kept headers of the upstream are added.
+         */
+        let (status, body): (HttpResponseStatus, Vec<u8>) =
+            match mirror.fetch(upstream_target).await {
+                Ok(MirrorResponse {
+                    status,
+                    headers,
+                    body,
+                }) => {
+                    exchange.extra_headers.extend(headers);
+                    let status: HttpResponseStatus =
+                        HttpResponseStatus::from_value(status as usize)
+                            .unwrap_or(HttpResponseStatus::BadGateway);
+                    (status, body)
+                }
+                Err(e) => {
+                    println!(
+                        "[ERROR] {}: Mirror failed to fetch the file: {e}",
+                        exchange.inc_addr
+                    );
+                    (HttpResponseStatus::BadGateway, Vec::new())
+                }
+            };
+        self.respond(inc_stream, exchange, status, &body).await
+    }
+
+    async fn serve_short_link<S: Connection>(
+        &self,
+        inc_stream: S,
+        link: ShortLink,
+        exchange: &mut Exchange,
+    ) -> Option<S> {
+        /*
+         *  Redirect to the target of the short link.
+         */
+        let status: HttpResponseStatus = if link.permanent {
+            HttpResponseStatus::MovedPermanently
+        } else {
+            HttpResponseStatus::Found
+        };
+        exchange
+            .extra_headers
+            .push((String::from("Location"), link.url));
+        self.respond(inc_stream, exchange, status, &[]).await
+    }
+
+    async fn serve_status_page<S: Connection>(
+        &self,
+        inc_stream: S,
+        vec_buf: &[u8],
+        events: bool,
+        exchange: &mut Exchange,
+    ) -> Option<S> {
+        /*
+         *  Answer with the status dashboard or its event stream, the stream
+         *  stays open, so it is detached.
+         *
+         *  Arguments:
+         *      inc_stream: Stream of the connection.
+         *      vec_buf: Bytes of the request, with its credentials.
+         *      events: The event stream is requested, not the dashboard.
+         *      exchange: The request.
+         */
+        let inc_addr: SocketAddr = exchange.inc_addr;
+        if let Err(status) = self.status_auth(vec_buf, inc_addr.ip()) {
+            println!("[WARNING] {inc_addr}: Refused the status page.");
+            exchange.extra_headers.push((
+                String::from("WWW-Authenticate"),
+                String::from("Basic realm=\"Status\""),
+            ));
+            return self.respond(inc_stream, exchange, status, &[]).await;
+        }
+        if events {
+            self.answered(HttpResponseStatus::Ok.value());
+            let board: Arc<StatusBoard> = Arc::clone(&self.shared_state.status);
+            let metrics: Arc<Metrics> = Arc::clone(&self.shared_state.metrics);
+            tokio::spawn(async move { board.serve_events(&metrics, inc_stream).await });
+            return None;
+        }
+        /* The dashboard is a single page with the inline script */
+        exchange
+            .extra_headers
+            .retain(|(name, _)| !name.eq_ignore_ascii_case("Content-Security-Policy"));
+        exchange.extra_headers.extend([
+            (
+                String::from("Content-Security-Policy"),
+                String::from(
+                    "default-src 'self'; script-src 'unsafe-inline'; style-src 'unsafe-inline'",
+                ),
+            ),
+            (
+                String::from("Content-Type"),
+                String::from("text/html; charset=utf-8"),
+            ),
+            (String::from("Cache-Control"), String::from("no-store")),
+        ]);
+        self.respond(
+            inc_stream,
+            exchange,
+            HttpResponseStatus::Ok,
+            STATUS_PAGE.as_bytes(),
+        )
+        .await
+    }
+
+    async fn serve_route<S: Connection>(
+        &self,
+        inc_stream: S,
+        vec_buf: &[u8],
+        (handler, params): RouteMatch,
+        body: RouteBody,
+        exchange: Exchange,
+    ) -> Option<S> {
+        /*
+         *  Answer with the handler of the registered route. The handler runs
+         *  on its own task, it is cancelled if the client leaves.
+         *
+         *  Arguments:
+         *      inc_stream: Stream of the connection.
+         *      vec_buf: Bytes of the request, the headers are parsed from it.
+         *      handler: The handler with the values of the route parameters.
+         *      body: The body, the rest of the streamed one is read by
+         *      the handler itself.
+         *      exchange: The request.
+         *
+         *  Returns:
+         *      The stream, if the handler read the whole body.
+         */
+        let Exchange {
+            request_type,
+            resource_path,
+            query,
+            inc_addr,
+            deadline,
+            extra_headers,
+            bandwidth_limits,
+            quota_usage,
+        } = exchange;
+        let (read_half, mut write_half) = S::split(inc_stream);
+        let (body, mut read_half): (RequestBody, Option<S::ReadHalf>) = match body {
+            RouteBody::Streamed(received, remaining) => (
+                RequestBody::streamed(
+                    received,
+                    read_half.take(remaining as u64),
+                    remaining,
+                    self.config.max_request_body_size,
+                ),
+                None,
+            ),
+            RouteBody::Buffered(body) => (RequestBody::buffered(body), Some(read_half)),
+        };
+        let disconnect: Disconnect = Disconnect::default();
+        let mut request: Request = Request::new(
+            request_type,
+            resource_path,
+            vec_buf,
+            body,
+            inc_addr,
+            deadline,
+        )
+        .with_query(query)
+        .with_params(params)
+        .with_disconnect(disconnect.clone());
+        let answered: Option<Response> = self
+            .shared_state
+            .pre_processors
+            .read()
+            .unwrap()
+            .apply(&mut request);
+        /* The handler runs on its own task, so its panic is answered on this stream */
+        let mut handler_task = match answered {
+            Some(response) => tokio::spawn(async move { response }),
+            None => tokio::spawn(handle_with_deadline(handler, request)),
+        };
+        /* The handler streaming the body sees the client leave on its own */
+        let handled = match read_half.as_mut() {
+            Some(read_half) => tokio::select! {
+                handled = &mut handler_task => Some(handled),
+                () = S::peer_closed(read_half) => None,
+            },
+            None => Some((&mut handler_task).await),
+        };
+        let mut response: Response = match handled {
+            Some(Ok(response)) => response,
+            Some(Err(e)) => {
+                if e.is_panic() {
+                    self.log_panic(inc_addr, &*e.into_panic(), RequestId::current());
+                }
+                Response::new(HttpResponseStatus::InternalServerError)
+            }
+            None => {
+                disconnect.close();
+                handler_task.abort();
+                Metrics::increment(&self.shared_state.metrics.client_disconnects);
+                println!("[INFO] {inc_addr}: Client left, its handler was cancelled.");
+                self.answered(CLIENT_CLOSED_REQUEST);
+                return None;
+            }
+        };
+        /* Headers set by the handler win over the security ones */
+        for (name, value) in extra_headers {
+            if !response
+                .headers
+                .iter()
+                .any(|(set, _)| set.eq_ignore_ascii_case(&name))
+            {
+                response.headers.push((name, value));
+            }
+        }
+        self.shared_state
+            .post_processors
+            .read()
+            .unwrap()
+            .apply(&mut response);
+        let recorder: Option<Arc<Recorder>> = self.shared_state.recorder.get().cloned();
+        let capture: bool = recorder
+            .as_ref()
+            .is_some_and(|recorder| recorder.records_responses());
+        self.answered(response.status.value());
+        let _buffer: Reservation = self.shared_state.memory.reserve(response.body.len());
+        let mut out = Teed::new(
+            Metered::new(
+                Throttled::new(&mut write_half, bandwidth_limits),
+                quota_usage,
+            ),
+            capture,
+        );
+        if let Err(e) = response.write_to(&mut out).await {
+            println!("[ERROR] {inc_addr}: Failed to write the response: {e}");
+            return None;
+        }
+        if let (Some(recorder), Some(captured)) = (recorder, out.captured) {
+            recorder.response(inc_addr, &captured);
+        }
+        /* The handler might leave a part of the streamed body unread */
+        S::reunite(read_half?, write_half)
+    }
+
+    async fn serve_capabilities<S: Connection>(
+        &self,
+        inc_stream: S,
+        exchange: &mut Exchange,
+    ) -> Option<S> {
+        /*
+         *  Answer with the features and the limits of the server as JSON.
+         */
+        exchange.extra_headers.push((
+            String::from("Content-Type"),
+            String::from("application/json"),
+        ));
+        let body: Vec<u8> = serde_json::to_vec(&self.config.capabilities()).unwrap_or_default();
+        self.respond(inc_stream, exchange, HttpResponseStatus::Ok, &body)
+            .await
+    }
+
+    async fn serve_metrics<S: Connection>(
+        &self,
+        inc_stream: S,
+        exchange: &mut Exchange,
+    ) -> Option<S> {
+        /*
+         *  Answer with the counters and the quotas in the Prometheus text
+         *  format.
+         */
+        exchange.extra_headers.push((
+            String::from("Content-Type"),
+            String::from("text/plain; version=0.0.4"),
+        ));
+        let mut rendered: String = self.shared_state.metrics.render();
+        rendered.push_str(&self.shared_state.quotas.render());
+        self.respond(
+            inc_stream,
+            exchange,
+            HttpResponseStatus::Ok,
+            rendered.as_bytes(),
+        )
+        .await
+    }
+
+    async fn fetch_page(
+        &self,
+        exchange: &mut Exchange,
+        spa_page: Option<Vec<u8>>,
+        bypass_cache: bool,
+        quota: Option<&QuotaPermit>,
+    ) -> Option<(SiteContent, CacheStatus)> {
+        /*
+         *  Fetch the site of the request. The routes of the single-page app
+         *  are answered with its page, the resource path becomes the page's.
+         *
+         *  Arguments:
+         *      exchange: The request.
+         *      spa_page: Page of the single-page app of the host.
+         *      bypass_cache: Read the site from the disk.
+         *      quota: Slot of the host's quota, the read is counted in it.
+         *
+         *  Returns:
+         *      The site with its cache status, None if it doesn't exist.
+         */
+        let fetched: Option<(SiteContent, CacheStatus)> = self
+            .fetch_site(&exchange.resource_path, bypass_cache, quota)
+            .await;
+        if fetched.is_some() {
+            return fetched;
+        }
+        let page: Vec<u8> = spa_page?;
+        let fetched: Option<(SiteContent, CacheStatus)> =
+            self.fetch_site(&page, bypass_cache, quota).await;
+        if fetched.is_some() {
+            exchange.resource_path = page;
+        }
+        fetched
+    }
+
+    async fn serve_site<S: Connection>(
+        &self,
+        mut inc_stream: S,
+        vec_buf: &[u8],
+        spa_page: Option<Vec<u8>>,
+        quota: Option<&QuotaPermit>,
+        mut exchange: Exchange,
+    ) -> Option<S> {
+        /*
+         *  Answer with the site from the resource directory, as the overrides
+         *  of its directories, the preconditions and the ranges of the request
+         *  allow.
+         *
+         *  Arguments:
+         *      inc_stream: Stream of the connection.
+         *      vec_buf: Bytes of the request, the headers are parsed from it.
+         *      spa_page: Page of the single-page app of the host.
+         *      quota: Slot of the host's quota, the read is counted in it.
+         *      exchange: The request.
+         */
+        let cfg: &ServerConfig = &self.config;
+        let request_type: RequestType = exchange.request_type;
+        let inc_addr: SocketAddr = exchange.inc_addr;
+
+        /* The hashed name serves the original asset */
+        let hashed_original: Option<Vec<u8>> = self
+            .shared_state
+            .assets
+            .read()
+            .unwrap()
+            .original(&exchange.resource_path)
+            .map(<[u8]>::to_vec);
+        let is_hashed: bool = hashed_original.is_some();
+        if let Some(original) = hashed_original {
+            exchange.resource_path = original;
+        }
+
+        /* Directory overrides apply to the sites only, not to the routes */
+        let headers: HeaderMap = HeaderMap::parse(vec_buf);
+        let directive: Directive = self
+            .shared_state
+            .overrides
+            .read()
+            .unwrap()
+            .resolve(&exchange.resource_path, headers.get("authorization"));
+        match directive {
+            Directive::Serve(headers) => exchange.extra_headers.extend(headers),
+            Directive::Redirect(status, location) => {
+                exchange
+                    .extra_headers
+                    .push((String::from("Location"), location));
+                return self.respond(inc_stream, &exchange, status, &[]).await;
+            }
+            Directive::Unauthorized(realm) => {
+                exchange.extra_headers.push((
+                    String::from("WWW-Authenticate"),
+                    format!("Basic realm=\"{realm}\""),
+                ));
+                return self
+                    .respond(inc_stream, &exchange, HttpResponseStatus::Unauthorized, &[])
+                    .await;
+            }
+            Directive::Hidden => {
+                let site_content: SiteContent = self.site_not_found().await;
+                return self
+                    .respond(
+                        inc_stream,
+                        &exchange,
+                        HttpResponseStatus::NotFound,
+                        &site_content,
+                    )
+                    .await;
+            }
+        }
+        /* The content of the hashed name never changes, so it is cached for good */
+        if is_hashed {
+            exchange
+                .extra_headers
+                .retain(|(name, _)| !name.eq_ignore_ascii_case("cache-control"));
+            exchange.extra_headers.push((
+                String::from("Cache-Control"),
+                String::from(IMMUTABLE_CACHE_CONTROL),
+            ));
+        }
+
+        let bypass_cache: bool = cfg.dev_mode
+            || (self.may_bypass_cache(inc_addr.ip()) && requests_revalidation(vec_buf));
+        let recorder: Option<Arc<Recorder>> = self.shared_state.recorder.get().cloned();
+        let fetched: Option<(SiteContent, CacheStatus)> = self
+            .fetch_page(&mut exchange, spa_page, bypass_cache, quota)
+            .await;
+        let Exchange {
+            resource_path,
+            mut extra_headers,
+            bandwidth_limits,
+            quota_usage,
+            ..
+        } = exchange;
+        let (site, cache_status): (SiteContent, Option<CacheStatus>) = match fetched {
+            Some((site, cache_status)) => (site, Some(cache_status)),
+            None => (self.site_not_found().await, None),
+        };
+        /* The not found page is sent with 404 */
+        let site_status: HttpResponseStatus = match cache_status {
+            Some(_) => HttpResponseStatus::Ok,
+            None => HttpResponseStatus::NotFound,
+        };
+        if let Some(cache_status) = cache_status {
+            extra_headers.push((
+                String::from("X-Diana-Cache"),
+                String::from(cache_status.value()),
+            ));
+        }
+        let entry: Option<ManifestEntry> = self.site_entry(&resource_path);
+        if let Some(entry) = &entry {
+            let mime: &str = if self.renders_markdown(&resource_path) {
+                RENDERED_CONTENT_TYPE
+            } else {
+                entry.mime
+            };
+            extra_headers.extend([
+                (String::from("Content-Type"), String::from(mime)),
+                (String::from("Last-Modified"), http_date(entry.modified)),
+            ]);
+        }
+        let validators: Option<(String, u64)> = entry
+            .as_ref()
+            .map(|entry| (entry.etag.clone(), entry.modified));
+        let etag: Option<String> = entry.map(|entry| entry.etag);
+        let processed: Option<Response> =
+            self.post_process_site(&site, site_status, &mut extra_headers);
+        let content: &[u8] = processed.as_ref().map_or(&site, |response| &response.body);
+        let injected: Vec<u8>;
+        let mut encoded: Option<SiteContent> = None;
+        let site_content: &[u8] = if cfg.dev_mode && is_html(&resource_path) {
+            extra_headers.push((String::from("Cache-Control"), String::from("no-store")));
+            injected = inject_reload_script(content);
+            &injected
+        } else if cfg.compression && is_compressible(&resource_path) {
+            extra_headers.push((String::from("Vary"), String::from("Accept-Encoding")));
+            if let Some(coding) = negotiate_coding(headers.get_joined("accept-encoding").as_deref())
+                .filter(|_| content.len() >= cfg.compression_min_size)
+            {
+                /* The processed sites might differ each time, so they aren't cached */
+                encoded = match processed {
+                    Some(_) => coding.encode(content).ok().map(SiteContent::from),
+                    None => self.encode_site(&resource_path, &site, coding).await,
+                };
+                if encoded.is_some() {
+                    extra_headers.push((
+                        String::from("Content-Encoding"),
+                        String::from(coding.token()),
+                    ));
+                }
+            }
+            /* The encoded bytes differ from the file, so the tag is weak */
+            let weak: &str = if encoded.is_some() || processed.is_some() {
+                "W/"
+            } else {
+                ""
+            };
+            extra_headers.extend(etag.map(|etag| (String::from("ETag"), format!("{weak}{etag}"))));
+            encoded.as_deref().unwrap_or(content)
+        } else {
+            let weak: &str = if processed.is_some() { "W/" } else { "" };
+            extra_headers.extend(etag.map(|etag| (String::from("ETag"), format!("{weak}{etag}"))));
+            content
+        };
+        /* The client's copy is still fresh, so only the validators are sent */
+        let sent_tag: Option<&str> = extra_headers
+            .iter()
+            .rev()
+            .find(|(name, _)| name.eq_ignore_ascii_case("etag"))
+            .map(|(_, value)| value.as_str());
+        if let Some(current) = sent_tag
+            && let Err(refused) = check_preconditions(&headers, request_type, Some(current))
+        {
+            let response: Vec<u8> = format_head(refused, &extra_headers, Some(0));
+            self.reply(&mut inc_stream, inc_addr, &response)
+                .await
+                .ok()?;
+            return Some(inc_stream);
+        }
+        /* Only the site as it is, with its strong tag, is served in parts */
+        let mut status: HttpResponseStatus = processed
+            .as_ref()
+            .map_or(site_status, |response| response.status);
+        let mut body: &[u8] = site_content;
+        if let Some((etag, modified)) = validators
+            && encoded.is_none()
+            && processed.is_none()
+            && !(cfg.dev_mode && is_html(&resource_path))
+        {
+            extra_headers.push((String::from("Accept-Ranges"), String::from("bytes")));
+            let selection: RangeSelection = select_range(&headers, &etag, modified, body.len());
+            extra_headers.extend(
+                content_range(&selection, body.len())
+                    .map(|value| (String::from("Content-Range"), value)),
+            );
+            match selection {
+                RangeSelection::Partial(range) => {
+                    status = HttpResponseStatus::PartialContent;
+                    body = &body[range];
+                }
+                RangeSelection::Unsatisfiable => {
+                    status = HttpResponseStatus::RangeNotSatisfiable;
+                    body = &[];
+                }
+                RangeSelection::Full => {}
+            }
+        }
+        /* The site is written as is, so mapped sites aren't copied to the heap */
+        let mut out = Metered::new(
+            Throttled::new(&mut inc_stream, bandwidth_limits),
+            quota_usage,
+        );
+        let head: Vec<u8> = format_head(status, &extra_headers, Some(body.len()));
+        /* HEAD gets the same headers as GET, the body is left out */
+        if request_type == RequestType::Head {
+            body = &[];
+        }
+        if let Err(e) = out.write_all(&head).await {
+            println!("[ERROR] {inc_addr}: Failed to write the response: {e}");
+            return None;
+        }
+        if let Err(e) = out.write_all(body).await {
+            println!("[ERROR] {inc_addr}: Failed to write the response: {e}");
+            return None;
+        }
+        if let Some(recorder) = recorder
+            && recorder.records_responses()
+        {
+            recorder.response(inc_addr, &[head.as_slice(), body].concat());
+        }
+        self.answered(status.value());
+        Some(inc_stream)
+    }
+
+    fn validate_request(&self, vec_buf: &[u8]) -> Result<RequestLine, RequestError> {
+        /*
+         *  Check the request, before anything is answered from it.
+         *
+         *  Arguments:
+         *      vec_buf: Bytes of the request.
+         *
+         *  Returns:
+         *      The request type, the resource path, the query and the
+         *      authority from the absolute target, or why the request is
+         *      refused.
+         */
+
+        /* Ambiguous framing might be a smuggled request, never serve it */
+        check_message_framing(vec_buf)?;
+        check_header_syntax(vec_buf)?;
+
+        /* Tunnels aren't offered, TRACE only if it is enabled */
+        let request_type: RequestType = self.read_request_type(vec_buf);
+        if vec_buf.starts_with(CONNECT_REQUEST)
+            || (request_type == RequestType::Trace && !self.config.allow_trace)
+        {
+            return Err(RequestError::MethodNotAllowed);
+        }
+        if request_type == RequestType::Invalid {
+            return Err(RequestError::UnsupportedMethod);
+        }
+        let request_target: Vec<u8> = self.read_resource(vec_buf, &request_type);
+        if request_target.is_empty() {
+            return Err(RequestError::MissingTarget);
+        }
+        let (resource_path, target_authority) = split_request_target(&request_target);
+        check_path(&resource_path)?;
+
+        /* Equivalent paths, e.g. //a/./b and /a/b, must name the same site */
+        let (resource_path, query): (&[u8], Option<&[u8]>) = split_target(&resource_path);
+        let resource_path: Vec<u8> = normalize_path(resource_path);
+        let query: Option<Vec<u8>> = query.map(<[u8]>::to_vec);
+        let target_authority: Option<Vec<u8>> = target_authority.map(|mut authority| {
+            if self.config.lowercase_host {
+                authority.make_ascii_lowercase();
+            }
+            authority
+        });
+
+        /* Make sure, that the request is meant for this server */
+        check_host(
+            vec_buf,
+            target_authority.as_deref(),
+            &self.config.server_names,
+        )?;
+        Ok((request_type, resource_path, query, target_authority))
+    }
+
+    async fn read_body_remainder<S: Connection>(
+        &self,
+        inc_stream: &mut S,
+        vec_buf: &mut Vec<u8>,
+        deadline: Instant,
+    ) -> Result<(), RequestError> {
+        /*
+         *  Read the part of the buffered body, that didn't arrive with
+         *  the head. Nothing past the body is read, it belongs to the next
+         *  request.
+         *
+         *  Arguments:
+         *      inc_stream: Stream of the connection.
+         *      vec_buf: Bytes of the request, the remainder is appended.
+         *      deadline: Instant, when the handlers must be finished.
+         */
+        let mut missing: usize = self.missing_body_len(vec_buf)?;
+        while missing > 0 {
+            let mut chunk: Vec<u8> = Vec::with_capacity(missing);
+            let read: usize = tokio::time::timeout_at(
+                deadline,
+                (&mut *inc_stream).take(missing as u64).read_buf(&mut chunk),
+            )
+            .await
+            .map_err(|_| RequestError::IncompleteBody)??;
+            if read == 0 {
+                return Err(RequestError::IncompleteBody);
+            }
+            vec_buf.extend_from_slice(&chunk);
+            missing -= read;
+        }
+        Ok(())
+    }
+
+    fn dump_malformed(&self, inc_addr: SocketAddr, vec_buf: &[u8], reason: &str) {
+        /*
+         *  Dump the request, that failed to parse, in the debug mode.
+         */
+        if let Some(dumps) = self.shared_state.dumps.get()
+            && let Some(path) = dumps.dump(inc_addr, vec_buf, reason)
+        {
+            println!(
+                "[INFO] {inc_addr}: Dumped the request to {}.",
+                path.display()
+            );
+        }
+    }
+
+    async fn reject<S: Connection>(
+        &self,
+        mut inc_stream: S,
+        inc_addr: SocketAddr,
+        e: RequestError,
+    ) -> Option<S> {
+        /*
+         *  Answer the refused request with the status of the error.
+         *
+         *  Returns:
+         *      The stream, if the connection can be used for the next request.
+         */
+        println!("[ERROR] {inc_addr}: {e}.");
+        if !e.closes_connection() {
+            let mut headers: Vec<(String, String)> = Vec::new();
+            if matches!(e, RequestError::MethodNotAllowed) {
+                let allowed: &str = if self.config.allow_trace {
+                    "GET, HEAD, POST, TRACE"
+                } else {
+                    "GET, HEAD, POST"
+                };
+                headers.push((String::from("Allow"), String::from(allowed)));
+            }
+            let response: Vec<u8> = format_response(e.status(), &headers, &[]);
+            self.reply(&mut inc_stream, inc_addr, &response)
+                .await
+                .ok()?;
+            return Some(inc_stream);
+        }
+        let close: Vec<(String, String)> =
+            vec![(String::from("Connection"), String::from("close"))];
+        let response: Vec<u8> = format_response(e.status(), &close, &[]);
+        let _ = self.reply(&mut inc_stream, inc_addr, &response).await;
+        let _ = inc_stream.shutdown().await;
+        None
+    }
+
+    async fn reply<S: Connection>(
+        &self,
+        inc_stream: &mut S,
+        inc_addr: SocketAddr,
+        response: &[u8],
+    ) -> Result<(), io::Error> {
+        /*
+         *  Write the whole response to the client, it is recorded if
+         *  the recording of the responses is enabled.
+         *
+         *  Arguments:
+         *      inc_stream: Stream of the connection.
+         *      inc_addr: The address, that the request comes from.
+         *      response: The formatted response.
+         */
+        if let Some(recorder) = self.shared_state.recorder.get() {
+            recorder.response(inc_addr, response);
+        }
+        if let Some(status) = response_status(response) {
+            self.answered(status);
+        }
+        let _buffer: Reservation = self.shared_state.memory.reserve(response.len());
+        inc_stream.write_all(response).await
+    }
+
+    fn answered(&self, status: usize) {
+        /*
+         *  Count the answered request and update the status page.
+         *
+         *  Arguments:
+         *      status: Status code of the answer.
+         */
+        self.shared_state.metrics.count_response(status);
+        RequestClock::answered(status);
+        let board: &StatusBoard = &self.shared_state.status;
+        if let Some(id) = RequestId::current()
+            && let Some(request) = board.finish(id, status)
+            && let Some(logs) = self.shared_state.logs.get()
+        {
+            logs.access(&request);
+        }
+        let cached_sites: &dyn SiteCache = self.shared_state.cached_sites.as_ref();
+        board.set_cache_occupancy(cached_sites.len(), cached_sites.capacity());
+    }
+
+    fn log_if_slow(&self, inc_addr: SocketAddr, clock: &RequestClock) {
+        /*
+         *  Log the request, that exceeded the slow request threshold, with
+         *  the time of its phases.
+         */
+        let Some(slow) = self.shared_state.slow_requests.record(inc_addr.ip(), clock) else {
+            return;
+        };
+        Metrics::increment(&self.shared_state.metrics.slow_requests);
+        println!(
+            "[WARNING] {inc_addr}: Slow request \"{}\" ({}) took {:.1} ms: read {:.1} ms, parse {:.1} ms, handler {:.1} ms, write {:.1} ms.",
+            slow.request,
+            slow.status,
+            slow.total_ms,
+            slow.read_ms,
+            slow.parse_ms,
+            slow.handler_ms,
+            slow.write_ms
+        );
+    }
+
+    fn status_auth(&self, vec_buf: &[u8], client: IpAddr) -> Result<(), HttpResponseStatus> {
+        /*
+         *  Check, that the client may see the status page.
+         *
+         *  Returns:
+         *      The status to refuse the client with, if it may not.
+         */
+        let cfg: &ServerConfig = &self.config;
+        if !cfg.admin_clients.contains(&client) {
+            return Err(HttpResponseStatus::Forbidden);
+        }
+        if !cfg.status_users.is_empty()
+            && !BasicAuth::new(String::from("Status"), &cfg.status_users)
+                .allows(HeaderMap::parse(vec_buf).get("authorization"))
+        {
+            return Err(HttpResponseStatus::Unauthorized);
+        }
+        Ok(())
+    }
+}
+
+fn default_handler_timeout_in_secs() -> u32 {
+    10
+}
+
+fn default_storage_dir() -> String {
+    String::from("resource/storage/")
+}
+
+fn default_short_links_file() -> String {
+    String::from("resource/short_links.toml")
+}
+
+fn default_max_decompressed_body_size() -> usize {
+    1024 * 1024
+}
+
+fn default_max_request_body_size() -> usize {
+    1024 * 1024
+}
+
+fn default_request_queue_wait_ms() -> u64 {
+    1000
+}
+
+fn default_retry_after_secs() -> u32 {
+    1
+}
+
+fn default_large_body_size() -> usize {
+    1024 * 1024
+}
+
+fn default_max_header_size() -> usize {
+    32 * 1024
+}
+
+fn default_max_cached_sites() -> usize {
+    1024
+}
+
+fn default_compression_min_size() -> usize {
+    1024
+}
+
+fn default_metrics_path() -> String {
+    String::from("/metrics")
+}
+
+fn default_admin_path() -> String {
+    String::from("/admin")
+}
+
+fn default_status_path() -> String {
+    String::from("/status")
+}
+
+fn default_admin_clients() -> Vec<IpAddr> {
+    vec![
+        IpAddr::V4(Ipv4Addr::LOCALHOST),
+        IpAddr::V6(Ipv6Addr::LOCALHOST),
+    ]
+}
+
+fn default_asset_manifest() -> String {
+    String::from("asset-manifest.json")
+}
+
+fn default_userdir_root() -> String {
+    String::from("/home")
+}
+
+fn default_upstream_idle_timeout_secs() -> u64 {
+    30
+}
+
+fn default_upstream_max_lifetime_secs() -> u64 {
+    300
+}
+
+fn default_daemon_log() -> String {
+    String::from("diana_srv.log")
+}
+
+fn default_debug_dump_dir() -> String {
+    String::from("resource/dumps")
+}
+
+fn default_debug_dump_max_files() -> usize {
+    100
+}
+
+fn default_debug_dump_max_bytes() -> usize {
+    64 * 1024
+}
+
+fn default_slow_request_count() -> usize {
+    20
+}
+
+pub fn persist_body(
+    route: &[u8],
+    body: &[u8],
+    storage: &dyn Storage,
+) -> (HttpResponseStatus, Vec<u8>) {
+    /*
+     *  Default POST handler, it appends the body to the route's records.
+     *
+     *  Returns:
+     *      204 if the body was stored, 500 otherwise.
+     */
+    match storage.append(route, body) {
+        Ok(()) => (HttpResponseStatus::NoContent, Vec::new()),
+        Err(e) => {
+            println!("[ERROR] Failed to persist the body: {e}");
+            (HttpResponseStatus::InternalServerError, Vec::new())
+        }
+    }
+}
+
+pub fn format_message(status: HttpResponseStatus, site_content: &[u8]) -> Vec<u8> {
+    /*
+     *  Format the HTTP response.
+     *
+     *  Arguments:
+     *      status: Status of the response.
+     *      site_content: The buffer vector of site in the user's request.
+     *
+     *  Returns:
+     *      Response in bytes.
+     * */
+    format_response(status, &[], site_content)
+}
+
+pub fn format_response(
+    status: HttpResponseStatus,
+    extra_headers: &[(String, String)],
+    site_content: &[u8],
+) -> Vec<u8> {
+    /*
+     *  Format the HTTP response with additional headers.
+     *
+     *  Arguments:
+     *      status: Status of the response.
+     *      extra_headers: Headers sent after the default ones.
+     *      site_content: The buffer vector of site in the user's request.
+     *
+     *  Returns:
+     *      Response in bytes.
+     * */
+    let mut response: Vec<u8> = format_head(status, extra_headers, Some(site_content.len()));
+    response.extend(site_content);
+    response
+}
+
+pub fn format_head(
+    status: HttpResponseStatus,
+    extra_headers: &[(String, String)],
+    content_length: Option<usize>,
+) -> Vec<u8> {
+    /*
+     *  Format the status line and the headers of the HTTP response.
+     *
+     *  Arguments:
+     *      status: Status of the response.
+     *      extra_headers: Headers sent after the default ones.
+     *      content_length: Length of the body, None if the body is streamed.
+     *      An extra header with the empty value strips the header.
+     *
+     *  Returns:
+     *      The head in bytes, terminated with the empty line.
+     * */
+    let mut headers: Vec<(String, String)> = vec![(
+        String::from("Access-Control-Allow-Origin"),
+        String::from("*"),
+    )];
+    /* The rules might set the Server header of their own */
+    if !server_identity().is_empty()
+        && !extra_headers
+            .iter()
+            .any(|(name, _)| name.eq_ignore_ascii_case("server"))
+    {
+        headers.insert(0, (String::from("Server"), String::from(server_identity())));
+    }
+    /* 204 must not carry the Content-Length, 304 would send the wrong one */
+    if let Some(length) = content_length
+        && !matches!(
+            status,
+            HttpResponseStatus::NoContent | HttpResponseStatus::NotModified
+        )
+    {
+        headers.push((String::from("Content-Length"), length.to_string()));
+    }
+    headers.extend_from_slice(extra_headers);
+    strip_removed(&mut headers);
+    response_head(status, &headers)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::backend::precompress::encoded_path;
+    use crate::backend::server::Server;
+    use crate::utils;
 
     use super::*;
+    use flate2::{Compression, write::GzEncoder};
+    use std::io::Write;
+    use std::sync::atomic::Ordering;
     const TEST_POST_REQUEST: &[u8] = b"POST /api/data HTTP/1.1\r\n\
             Host: example.com\r\n\
             Content-Type: application/json\r\n\
@@ -390,14 +3901,821 @@ mod tests {
         Server::new(cfg).unwrap()
     }
 
+    impl Server {
+        async fn conn_handler<S: Connection>(&self, inc_stream: S, inc_addr: SocketAddr) {
+            /* The tests drive the connections directly, without the panic handling */
+            self.handle_connection(inc_stream, inc_addr, false, &Mutex::default())
+                .await
+        }
+
+        async fn exchange(&self, request_line: &str) -> String {
+            /* Send the request on its own connection and read the whole response */
+            let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let mut client: TcpStream = TcpStream::connect(listener.local_addr().unwrap())
+                .await
+                .unwrap();
+            client
+                .write_all(
+                    format!(
+                        "{request_line} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n"
+                    )
+                    .as_bytes(),
+                )
+                .await
+                .unwrap();
+            let (inc_stream, inc_addr) = listener.accept().await.unwrap();
+            let open: OpenConnection = OpenConnection::new(Arc::clone(&self.shared_state.metrics));
+            self.serve_connection(inc_stream, inc_addr, false, Duration::from_secs(5), open)
+                .await;
+            let mut response: Vec<u8> = Vec::new();
+            client.read_to_end(&mut response).await.unwrap();
+            String::from_utf8_lossy(&response).into_owned()
+        }
+    }
+
     #[test]
     fn read_request_body_test() {
         let srv = server_init();
-        let res = srv.read_request_body(&Vec::from(TEST_POST_REQUEST));
+        let res = srv.read_request_body(TEST_POST_REQUEST).unwrap();
         let request_body: Vec<u8> = Vec::from(b"{\"key\":\"value\",\"number\":42}");
         assert_eq!(res, request_body);
     }
 
+    #[test]
+    fn body_framing_test() {
+        let srv = server_init();
+        /* Only the header itself frames the body, in any spelling */
+        for request in [
+            &b"POST /api/data HTTP/1.1\r\nHost: a\r\ncontent-length: 7\r\n\r\npayload"[..],
+            b"POST /api/data HTTP/1.1\r\nHost: a\r\nContent-Length:7\r\n\r\npayload",
+            b"POST /api/data HTTP/1.1\r\nX-Note: Content-Length: 3\r\nContent-Length: 7\r\n\r\npayload",
+        ] {
+            assert_eq!(srv.declared_body_len(request), 7);
+            assert_eq!(&*srv.read_request_body(request).unwrap(), b"payload");
+            assert_eq!(srv.split_request_body(request), Some((&b"payload"[..], 0)));
+        }
+        let fake: &[u8] = b"POST /api/data HTTP/1.1\r\nX-Note: Content-Length: 300\r\n\r\n";
+        assert_eq!(srv.missing_body_len(fake).unwrap(), 0);
+        assert_eq!(srv.split_request_body(fake), None);
+    }
+
+    #[test]
+    fn read_request_body_gzip_test() {
+        let srv = server_init();
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(b"name=diana").unwrap();
+        let compressed: Vec<u8> = encoder.finish().unwrap();
+
+        let mut request: Vec<u8> = format!(
+            "POST /api/data HTTP/1.1\r\nContent-Encoding: gzip\r\nContent-Length: {}\r\n\r\n",
+            compressed.len()
+        )
+        .into_bytes();
+        request.extend(&compressed);
+        assert_eq!(
+            srv.read_request_body(&request).unwrap(),
+            b"name=diana".to_vec()
+        );
+
+        /* The header names and the coding are case-insensitive */
+        let mut request: Vec<u8> = format!(
+            "POST /api/data HTTP/1.1\r\ncontent-encoding: GZIP\r\ncontent-length: {}\r\n\r\n",
+            compressed.len()
+        )
+        .into_bytes();
+        request.extend(&compressed);
+        assert_eq!(srv.split_request_body(&request), None);
+        assert_eq!(
+            srv.read_request_body(&request).unwrap(),
+            b"name=diana".to_vec()
+        );
+    }
+
+    #[test]
+    fn read_body_remainder_test() {
+        let srv = server_init();
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(b"name=diana").unwrap();
+        let compressed: Vec<u8> = encoder.finish().unwrap();
+        let mut request: Vec<u8> = format!(
+            "POST /api/data HTTP/1.1\r\nContent-Encoding: gzip\r\nContent-Length: {}\r\n\r\n",
+            compressed.len()
+        )
+        .into_bytes();
+        let head_len: usize = request.len();
+        request.extend(&compressed);
+        /* The next pipelined request must stay unread */
+        request.extend_from_slice(b"GET / HTTP/1.1\r\n\r\n");
+
+        let mut vec_buf: Vec<u8> = request[..head_len + 3].to_vec();
+        assert!(matches!(
+            srv.read_request_body(&vec_buf),
+            Err(RequestError::IncompleteBody)
+        ));
+        assert_eq!(
+            srv.missing_body_len(&vec_buf).unwrap(),
+            compressed.len() - 3
+        );
+
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime.block_on(async {
+            let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let mut client: TcpStream = TcpStream::connect(listener.local_addr().unwrap())
+                .await
+                .unwrap();
+            let (mut inc_stream, _) = listener.accept().await.unwrap();
+            let rest: Vec<u8> = request[head_len + 3..].to_vec();
+            tokio::spawn(async move {
+                for part in rest.chunks(5) {
+                    client.write_all(part).await.unwrap();
+                    tokio::time::sleep(Duration::from_millis(5)).await;
+                }
+            });
+            let deadline: Instant = Instant::now() + Duration::from_secs(5);
+            srv.read_body_remainder(&mut inc_stream, &mut vec_buf, deadline)
+                .await
+                .unwrap();
+        });
+        assert_eq!(vec_buf.len(), head_len + compressed.len());
+        assert_eq!(
+            srv.read_request_body(&vec_buf).unwrap(),
+            b"name=diana".to_vec()
+        );
+    }
+
+    #[test]
+    fn storage_conflict_test() {
+        let srv = server_init();
+        let handler: StoragePostHandler = StoragePostHandler {
+            handler: persist_body,
+            storage: srv.shared_state.storage.clone(),
+            path_locks: Arc::clone(&srv.shared_state.path_locks),
+        };
+        let request = || {
+            Request::new(
+                RequestType::Post,
+                TEST_POST_RESOURCE.to_vec(),
+                TEST_POST_REQUEST,
+                RequestBody::buffered(b"{}".to_vec()),
+                "127.0.0.1:4000".parse().unwrap(),
+                Instant::now() + Duration::from_secs(5),
+            )
+        };
+        tokio::runtime::Runtime::new().unwrap().block_on(async {
+            /* Another writer of the route is storing its body */
+            let guard: PathGuard = srv.shared_state.path_locks.lock(TEST_POST_RESOURCE).await;
+            let refused: Response = handler.handle(request()).await;
+            assert_eq!(refused.status, HttpResponseStatus::Conflict);
+            drop(guard);
+            let stored: Response = handler.handle(request()).await;
+            assert_eq!(stored.status, HttpResponseStatus::NoContent);
+        });
+    }
+
+    #[test]
+    fn precompressed_site_test() {
+        let srv = server_init();
+        let name: String = format!("precompressed_{}.html", std::process::id());
+        let resource_path: Vec<u8> = format!("/{name}").into_bytes();
+        let site_file: PathBuf = bytes_to_path(srv.html_dir()).join(&name);
+        let sibling: PathBuf = encoded_path(&site_file, ContentCoding::Gzip);
+        std::fs::write(&site_file, b"<p>diana</p>".repeat(100)).unwrap();
+        let written: Vec<u8> = ContentCoding::Gzip
+            .encode(b"<p>from the sibling</p>")
+            .unwrap();
+        std::fs::write(&sibling, &written).unwrap();
+        tokio::runtime::Runtime::new().unwrap().block_on(async {
+            /* The file written by precompress is served, not encoded again */
+            let (site, _) = srv.fetch_resource(&resource_path, false, None).await;
+            let encoded: SiteContent = srv
+                .encode_site(&resource_path, &site, ContentCoding::Gzip)
+                .await
+                .unwrap();
+            assert_eq!(&*encoded, written.as_slice());
+            let metrics: &Metrics = &srv.shared_state.metrics;
+            assert_eq!(metrics.compressions.load(Ordering::Relaxed), 0);
+            /* It isn't a site of its own */
+            let mut sibling_path: Vec<u8> = resource_path.clone();
+            sibling_path.extend_from_slice(b".gz");
+            assert!(srv.site_entry(&sibling_path).is_none());
+
+            /* The site edited since is encoded on the fly */
+            std::fs::write(&site_file, b"<p>edited</p>".repeat(100)).unwrap();
+            let edited: std::time::SystemTime =
+                std::fs::metadata(&sibling).unwrap().modified().unwrap() + Duration::from_secs(1);
+            std::fs::File::options()
+                .write(true)
+                .open(&site_file)
+                .unwrap()
+                .set_modified(edited)
+                .unwrap();
+            assert!(
+                srv.precompressed_site(&resource_path, ContentCoding::Gzip)
+                    .is_none()
+            );
+        });
+        std::fs::remove_file(&site_file).unwrap();
+        std::fs::remove_file(&sibling).unwrap();
+    }
+
+    #[test]
+    fn compressed_site_cache_test() {
+        let srv = server_init();
+        tokio::runtime::Runtime::new().unwrap().block_on(async {
+            let (site, _) = srv.fetch_resource(b"/index.html", false, None).await;
+            let encoded: SiteContent = srv
+                .encode_site(b"/index.html", &site, ContentCoding::Gzip)
+                .await
+                .unwrap();
+            let cached: SiteContent = srv
+                .encode_site(b"/index.html", &site, ContentCoding::Gzip)
+                .await
+                .unwrap();
+            assert_eq!(&*encoded, &*cached);
+            let metrics: &Metrics = &srv.shared_state.metrics;
+            assert_eq!(metrics.compressions.load(Ordering::Relaxed), 1);
+            assert_eq!(metrics.compressed_hits.load(Ordering::Relaxed), 1);
+
+            /* The watcher reports the changed file, so the site is encoded again */
+            let html_dir: PathBuf = bytes_to_path(RESOURCE_HTML_DIR);
+            let changed: [PathBuf; 1] = [html_dir.join("index.html")];
+            assert_eq!(srv.invalidate_files(&changed).await, 1);
+            let (site, cache_status) = srv.fetch_resource(b"/index.html", false, None).await;
+            assert_eq!(cache_status, Some(CacheStatus::Miss));
+            srv.encode_site(b"/index.html", &site, ContentCoding::Gzip)
+                .await
+                .unwrap();
+            assert_eq!(metrics.compressions.load(Ordering::Relaxed), 2);
+        });
+    }
+
+    #[test]
+    fn site_not_found_cache_test() {
+        let srv = server_init();
+        tokio::runtime::Runtime::new().unwrap().block_on(async {
+            /* The pinned error page is neither a hit nor a miss of the cache */
+            let (site, cache_status) = srv.fetch_resource(b"/missing.html", false, None).await;
+            assert!(!site.is_empty());
+            assert_eq!(cache_status, None);
+            let metrics: &Metrics = &srv.shared_state.metrics;
+            assert_eq!(metrics.cache_hits.load(Ordering::Relaxed), 0);
+            assert_eq!(metrics.cache_misses.load(Ordering::Relaxed), 0);
+        });
+    }
+
+    #[test]
+    fn head_matches_get_test() {
+        let srv = server_init();
+        /* The site added after the manifest was built */
+        let late_file: PathBuf =
+            bytes_to_path(srv.html_dir()).join(format!("late_{}.txt", std::process::id()));
+        std::fs::write(&late_file, b"added later").unwrap();
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime.block_on(async {
+            let path: String = format!("/late_{}.txt", std::process::id());
+            for (path, status) in [(path.as_str(), "200"), ("/missing.html", "404")] {
+                let get: String = srv.exchange(&format!("GET {path}")).await;
+                let head: String = srv.exchange(&format!("HEAD {path}")).await;
+                let (get_head, get_body) = get.split_once("\r\n\r\n").unwrap();
+                let (head_head, head_body) = head.split_once("\r\n\r\n").unwrap();
+                assert!(get_head.starts_with(&format!("HTTP/1.1 {status}")), "{get}");
+                assert!(get_head.contains(&format!("Content-Length: {}", get_body.len())));
+                /* The cache status differs, the first request read the site */
+                let fields = |head: &str| -> Vec<String> {
+                    head.lines()
+                        .filter(|line| !line.starts_with("X-Diana-Cache"))
+                        .map(String::from)
+                        .collect()
+                };
+                assert_eq!(fields(get_head), fields(head_head));
+                assert!(head_body.is_empty(), "{head}");
+            }
+            let late: String = srv.exchange(&format!("HEAD {path}")).await;
+            for field in ["Content-Type: text/plain", "ETag: ", "Last-Modified: "] {
+                assert!(late.contains(field), "{late}");
+            }
+        });
+        std::fs::remove_file(&late_file).unwrap();
+    }
+
+    #[test]
+    fn query_site_test() {
+        let srv = server_init();
+        tokio::runtime::Runtime::new().unwrap().block_on(async {
+            /* The query names no other site, e.g. the cache busting one */
+            let plain: String = srv.exchange("GET /index.html").await;
+            let busted: String = srv.exchange("GET /index.html?v=1").await;
+            assert!(plain.starts_with("HTTP/1.1 200"), "{plain}");
+            assert!(busted.starts_with("HTTP/1.1 200"), "{busted}");
+            assert_eq!(
+                plain.split_once("\r\n\r\n").unwrap().1,
+                busted.split_once("\r\n\r\n").unwrap().1
+            );
+        });
+    }
+
+    /* Requests, that used to panic or could panic the connection task */
+    const MALFORMED_REQUESTS: [&[u8]; 8] = [
+        b"G",
+        b"POS",
+        b"GET",
+        b"POST ",
+        b"GET /\xff\xfe HTTP/1.1\r\nHost: localhost\r\n\r\n",
+        b"POST / HTTP/1.1\r\nHost: localhost\r\nContent-Encoding: gzip\r\nContent-Length: 500\r\n\r\nshort",
+        b"POST / HTTP/1.1\r\nHost: localhost\r\nContent-Length: 99999999999999999999999\r\n\r\n",
+        b"POST / HTTP/1.1\r\nHost: localhost\r\nContent-Encoding: gzip\r\nContent-Length: 4\r\n\r\nnope",
+    ];
+
+    #[test]
+    fn malformed_request_test() {
+        let srv = server_init();
+        for request in MALFORMED_REQUESTS {
+            let _ = srv.read_request_type(request);
+            let _ = srv.read_resource(request, &RequestType::Post);
+            let _ = srv.read_request_body(request);
+            let _ = srv.split_request_body(request);
+            let _ = srv.validate_request(request);
+        }
+        assert!(matches!(
+            srv.read_request_body(MALFORMED_REQUESTS[5]),
+            Err(RequestError::IncompleteBody)
+        ));
+        assert!(matches!(
+            srv.read_request_body(MALFORMED_REQUESTS[7]),
+            Err(RequestError::InvalidEncoding(_))
+        ));
+        let refused: RequestError = srv
+            .validate_request(b"PUT / HTTP/1.1\r\nHost: localhost\r\n\r\n")
+            .unwrap_err();
+        assert_eq!(refused.status().value(), 501);
+    }
+
+    #[test]
+    fn malformed_connection_test() {
+        /* The server is loaded outside of the runtime, as main does */
+        let srv = server_init();
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime.block_on(async {
+            let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let addr: SocketAddr = listener.local_addr().unwrap();
+            for request in MALFORMED_REQUESTS {
+                let mut client: TcpStream = TcpStream::connect(addr).await.unwrap();
+                client.write_all(request).await.unwrap();
+                client.shutdown().await.unwrap();
+                let (inc_stream, inc_addr) = listener.accept().await.unwrap();
+                /* Every connection is handled by its own clone on the spawned task,
+                 * the handler must return, a panic would fail the test */
+                let conn_srv: Server = srv.clone();
+                tokio::spawn(async move { conn_srv.conn_handler(inc_stream, inc_addr).await })
+                    .await
+                    .unwrap();
+                let mut response: Vec<u8> = Vec::new();
+                let _ = client.read_to_end(&mut response).await;
+                assert!(
+                    response.is_empty() || response.starts_with(b"HTTP/1.1 "),
+                    "{}",
+                    String::from_utf8_lossy(&response)
+                );
+            }
+        });
+    }
+
+    #[test]
+    fn post_process_test() {
+        let srv = server_init();
+        srv.post_process("text/html", |response: &mut Response| {
+            response.body.extend_from_slice(b"<!-- analytics -->");
+        });
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime.block_on(async {
+            let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let mut client: TcpStream = TcpStream::connect(listener.local_addr().unwrap())
+                .await
+                .unwrap();
+            client
+                .write_all(
+                    b"GET /index.html HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+                )
+                .await
+                .unwrap();
+            let (inc_stream, inc_addr) = listener.accept().await.unwrap();
+            let open: OpenConnection = OpenConnection::new(Arc::clone(&srv.shared_state.metrics));
+            srv.serve_connection(inc_stream, inc_addr, false, Duration::from_secs(5), open)
+                .await;
+            let mut response: Vec<u8> = Vec::new();
+            client.read_to_end(&mut response).await.unwrap();
+            let response: String = String::from_utf8(response).unwrap();
+            assert!(response.ends_with("<!-- analytics -->"), "{response}");
+            /* The processed site isn't the file, so its tag is weak */
+            assert!(response.contains("ETag: W/\""), "{response}");
+            assert!(!response.contains("Accept-Ranges"), "{response}");
+        });
+    }
+
+    #[test]
+    fn not_modified_test() {
+        let srv = server_init();
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime.block_on(async {
+            let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let mut responses: Vec<String> = Vec::new();
+            let mut etag: String = String::new();
+            for request in ["GET", "GET", "HEAD"] {
+                let mut client: TcpStream = TcpStream::connect(listener.local_addr().unwrap())
+                    .await
+                    .unwrap();
+                client
+                    .write_all(
+                        format!(
+                            "{request} /index.html HTTP/1.1\r\nHost: localhost\r\nIf-None-Match: {etag}\r\nConnection: close\r\n\r\n"
+                        )
+                        .as_bytes(),
+                    )
+                    .await
+                    .unwrap();
+                let (inc_stream, inc_addr) = listener.accept().await.unwrap();
+                let open: OpenConnection =
+                    OpenConnection::new(Arc::clone(&srv.shared_state.metrics));
+                srv.serve_connection(inc_stream, inc_addr, false, Duration::from_secs(5), open)
+                    .await;
+                let mut response: Vec<u8> = Vec::new();
+                client.read_to_end(&mut response).await.unwrap();
+                let response: String = String::from_utf8_lossy(&response).into_owned();
+                etag = response
+                    .lines()
+                    .find_map(|line| line.strip_prefix("ETag: "))
+                    .unwrap_or("\"none\"")
+                    .to_string();
+                responses.push(response);
+            }
+            assert!(responses[0].starts_with("HTTP/1.1 200"), "{}", responses[0]);
+            /* The client's copy is fresh, so the site isn't sent again */
+            for response in &responses[1..] {
+                assert!(response.starts_with("HTTP/1.1 304"), "{response}");
+                assert!(response.ends_with("\r\n\r\n"), "{response}");
+                assert!(!response.contains("Content-Length"), "{response}");
+            }
+        });
+    }
+
+    #[test]
+    fn client_disconnect_test() {
+        async fn slow(req: Request) -> Response {
+            tokio::time::sleep(Duration::from_secs(30)).await;
+            assert!(req.disconnected().is_closed());
+            Response::new(HttpResponseStatus::Ok)
+        }
+        let srv = server_init();
+        srv.route(RequestType::Get, "/slow", slow);
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime.block_on(async {
+            let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let mut client: TcpStream = TcpStream::connect(listener.local_addr().unwrap())
+                .await
+                .unwrap();
+            client
+                .write_all(b"GET /slow HTTP/1.1\r\nHost: localhost\r\n\r\n")
+                .await
+                .unwrap();
+            let (inc_stream, inc_addr) = listener.accept().await.unwrap();
+            tokio::spawn(async move {
+                tokio::time::sleep(Duration::from_millis(100)).await;
+                drop(client);
+            });
+
+            /* The handler is cancelled long before it would finish */
+            tokio::time::timeout(
+                Duration::from_secs(5),
+                srv.serve_connection(
+                    inc_stream,
+                    inc_addr,
+                    false,
+                    Duration::from_secs(60),
+                    OpenConnection::new(Arc::clone(&srv.shared_state.metrics)),
+                ),
+            )
+            .await
+            .unwrap();
+            let metrics: &Metrics = &srv.shared_state.metrics;
+            assert_eq!(metrics.client_disconnects.load(Ordering::Relaxed), 1);
+            assert_eq!(metrics.client_errors.load(Ordering::Relaxed), 1);
+        });
+    }
+
+    #[test]
+    fn memory_connection_test() {
+        async fn hello(req: Request) -> Response {
+            Response::new(HttpResponseStatus::Ok)
+                .with_header("Content-Type", "text/plain")
+                .with_body(format!("hello {}", req.param("name").unwrap_or("?")).into_bytes())
+        }
+        let srv = server_init();
+        srv.route(RequestType::Get, "/hello/:name", hello);
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime.block_on(async {
+            let (mut client, inc_stream) = tokio::io::duplex(64 * 1024);
+            let inc_addr: SocketAddr = "127.0.0.1:40000".parse().unwrap();
+
+            /* Pipelined requests are answered in order, the last one closes the connection */
+            client
+                .write_all(
+                    b"GET /hello/diana HTTP/1.1\r\nHost: localhost\r\n\r\n\
+                      GET /hello/srv HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+                )
+                .await
+                .unwrap();
+            srv.conn_handler(inc_stream, inc_addr).await;
+            let mut response: Vec<u8> = Vec::new();
+            client.read_to_end(&mut response).await.unwrap();
+            let answer = |body: &str| -> String {
+                format!(
+                    "HTTP/1.1 200 OK\r\nServer: {}\r\nAccess-Control-Allow-Origin: *\r\n\
+                     Content-Length: {}\r\nContent-Type: text/plain\r\n\
+                     X-Content-Type-Options: nosniff\r\nX-Frame-Options: SAMEORIGIN\r\n\
+                     Content-Security-Policy: default-src 'self'\r\n\
+                     Referrer-Policy: strict-origin-when-cross-origin\r\n\r\n{body}",
+                    server_identity(),
+                    body.len()
+                )
+            };
+            assert_eq!(
+                String::from_utf8_lossy(&response),
+                answer("hello diana") + &answer("hello srv")
+            );
+        });
+    }
+
+    #[test]
+    fn proxy_header_split_test() {
+        async fn peer(req: Request) -> Response {
+            Response::new(HttpResponseStatus::Ok).with_body(req.peer.to_string().into_bytes())
+        }
+        let srv = server_init();
+        srv.route(RequestType::Get, "/peer", peer);
+        tokio::runtime::Runtime::new().unwrap().block_on(async {
+            let (mut client, inc_stream) = tokio::io::duplex(64 * 1024);
+            let inc_addr: SocketAddr = "127.0.0.1:40000".parse().unwrap();
+            let handled = tokio::spawn({
+                let srv: Server = srv.clone();
+                async move {
+                    srv.handle_connection(inc_stream, inc_addr, true, &Mutex::default())
+                        .await
+                }
+            });
+
+            /* The balancer sends the header in two segments */
+            client.write_all(b"PROXY TCP4 192.168.0.1 ").await.unwrap();
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            client
+                .write_all(
+                    b"192.168.0.11 56324 443\r\n\
+                      GET /peer HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+                )
+                .await
+                .unwrap();
+            handled.await.unwrap();
+            let mut response: Vec<u8> = Vec::new();
+            client.read_to_end(&mut response).await.unwrap();
+            let response: String = String::from_utf8_lossy(&response).into_owned();
+            assert!(response.starts_with("HTTP/1.1 200"), "{response}");
+            assert!(
+                response.ends_with("\r\n\r\n192.168.0.1:56324"),
+                "{response}"
+            );
+        });
+    }
+
+    #[test]
+    fn request_hooks_test() {
+        struct User(String);
+        async fn whoami(req: Request) -> Response {
+            let user: &User = req.extensions().get::<User>().unwrap();
+            Response::new(HttpResponseStatus::Ok).with_body(user.0.clone().into_bytes())
+        }
+        let srv = server_init();
+        srv.route(RequestType::Get, "/whoami", whoami);
+        srv.pre_process(|req: &mut Request| {
+            let user: String = String::from_utf8_lossy(req.header("X-User")?).into_owned();
+            req.extensions_mut().insert(User(user));
+            None
+        });
+        srv.pre_process(|req: &mut Request| {
+            /* Only the authenticated requests reach the handler */
+            (req.extensions().get::<User>().is_none())
+                .then(|| Response::new(HttpResponseStatus::Unauthorized))
+        });
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime.block_on(async {
+            let (mut client, inc_stream) = tokio::io::duplex(64 * 1024);
+            client
+                .write_all(
+                    b"GET /whoami HTTP/1.1\r\nHost: localhost\r\nX-User: diana\r\n\r\n\
+                      GET /whoami HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+                )
+                .await
+                .unwrap();
+            let inc_addr: SocketAddr = "127.0.0.1:40000".parse().unwrap();
+            srv.conn_handler(inc_stream, inc_addr).await;
+            let mut response: Vec<u8> = Vec::new();
+            client.read_to_end(&mut response).await.unwrap();
+            let response: String = String::from_utf8_lossy(&response).into_owned();
+            assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{response}");
+            assert!(
+                response.contains("\r\n\r\ndianaHTTP/1.1 401 Unauthorized\r\n"),
+                "{response}"
+            );
+        });
+    }
+
+    #[test]
+    fn binary_body_test() {
+        async fn echo(mut req: Request) -> Response {
+            let body: Vec<u8> = req.body().await.unwrap_or_default();
+            Response::new(HttpResponseStatus::Ok)
+                .with_header("Content-Type", "application/octet-stream")
+                .with_body(body)
+        }
+        let srv = server_init();
+        srv.route(RequestType::Post, "/echo", echo);
+        srv.register_post("/binary/upload", persist_body);
+        let stored_file: PathBuf = FileStorage::new(&srv.config.storage_dir)
+            .unwrap()
+            .route_file(b"/binary/upload");
+        let _ = std::fs::remove_file(&stored_file);
+        /* Every byte value, with the invalid UTF-8 and the end of the head inside */
+        let mut state: u32 = 0x9e37_79b9;
+        let mut payloads: Vec<Vec<u8>> = vec![(0..=255).collect(), b"\xff\r\n\r\n\xfe\0".to_vec()];
+        payloads.push(
+            (0..10_000)
+                .map(|_| {
+                    state ^= state << 13;
+                    state ^= state >> 17;
+                    state ^= state << 5;
+                    state as u8
+                })
+                .collect(),
+        );
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime.block_on(async {
+            let inc_addr: SocketAddr = "127.0.0.1:40000".parse().unwrap();
+            for payload in &payloads {
+                for route in ["/echo", "/binary/upload"] {
+                    let (mut client, inc_stream) = tokio::io::duplex(64 * 1024);
+                    let mut request: Vec<u8> = format!(
+                        "POST {route} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\
+                         Content-Length: {}\r\n\r\n",
+                        payload.len()
+                    )
+                    .into_bytes();
+                    request.extend_from_slice(payload);
+                    client.write_all(&request).await.unwrap();
+                    srv.conn_handler(inc_stream, inc_addr).await;
+                    let mut response: Vec<u8> = Vec::new();
+                    client.read_to_end(&mut response).await.unwrap();
+                    if route == "/echo" {
+                        assert!(response.starts_with(b"HTTP/1.1 200 OK\r\n"));
+                        assert!(response.ends_with(payload));
+                    } else {
+                        assert!(response.starts_with(b"HTTP/1.1 204 No Content\r\n"));
+                    }
+                }
+            }
+        });
+
+        /* The stored bodies read back byte for byte */
+        let storage: &Arc<dyn Storage> = srv.shared_state.storage.as_ref().unwrap();
+        let stored: Vec<Vec<u8>> = storage.read_all(b"/binary/upload").unwrap();
+        assert_eq!(stored, payloads);
+        std::fs::remove_file(&stored_file).unwrap();
+    }
+
+    #[test]
+    fn large_head_test() {
+        async fn cookie(req: Request) -> Response {
+            let len: usize = req.header("Cookie").map_or(0, <[u8]>::len);
+            Response::new(HttpResponseStatus::Ok).with_body(len.to_string().into_bytes())
+        }
+        let srv = server_init();
+        srv.route(RequestType::Get, "/cookie", cookie);
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime.block_on(async {
+            let mut request: Vec<u8> = Vec::new();
+            for cookie_len in [20 * 1024, 100, 40 * 1024] {
+                request.extend_from_slice(b"GET /cookie HTTP/1.1\r\nHost: localhost\r\nCookie: ");
+                request.resize(request.len() + cookie_len, b'c');
+                request.extend_from_slice(b"\r\n\r\n");
+            }
+            let (mut client, inc_stream) = tokio::io::duplex(128 * 1024);
+            client.write_all(&request).await.unwrap();
+            let inc_addr: SocketAddr = "127.0.0.1:40000".parse().unwrap();
+            srv.conn_handler(inc_stream, inc_addr).await;
+            let mut response: Vec<u8> = Vec::new();
+            client.read_to_end(&mut response).await.unwrap();
+
+            /* The head grows past the first read, the one over the limit is refused */
+            let response: String = String::from_utf8_lossy(&response).into_owned();
+            let statuses: Vec<&str> = response
+                .match_indices("HTTP/1.1 ")
+                .map(|(idx, _)| &response[idx + 9..idx + 12])
+                .collect();
+            assert_eq!(statuses, ["200", "200", "431"]);
+            assert!(response.contains("\r\n\r\n20480HTTP/1.1"));
+            assert!(response.contains("\r\n\r\n100HTTP/1.1"));
+        });
+    }
+
+    #[test]
+    fn chaos_connection_test() {
+        let mut srv = server_init();
+        let rule = |path: &str, error_percent: u8, drop_percent: u8| ChaosRule {
+            path: String::from(path),
+            latency_ms: 50,
+            error_percent,
+            drop_percent,
+            ..ChaosRule::default()
+        };
+        Arc::get_mut(&mut srv.shared_state).unwrap().chaos =
+            Chaos::with_seed(vec![rule("/error", 100, 0), rule("/drop", 0, 100)], 1);
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime.block_on(async {
+            let inc_addr: SocketAddr = "127.0.0.1:40000".parse().unwrap();
+            for (path, status) in [("/error", "HTTP/1.1 500"), ("/drop", "")] {
+                let (mut client, inc_stream) = tokio::io::duplex(64 * 1024);
+                let request: String = format!("GET {path} HTTP/1.1\r\nHost: localhost\r\n\r\n");
+                client.write_all(request.as_bytes()).await.unwrap();
+                let started: Instant = Instant::now();
+                srv.conn_handler(inc_stream, inc_addr).await;
+                assert!(started.elapsed() >= Duration::from_millis(50));
+                let mut response: Vec<u8> = Vec::new();
+                client.read_to_end(&mut response).await.unwrap();
+                assert!(response.starts_with(status.as_bytes()));
+                assert_eq!(response.is_empty(), status.is_empty());
+            }
+        });
+        let metrics: &Metrics = &srv.shared_state.metrics;
+        assert_eq!(metrics.injected_faults.load(Ordering::Relaxed), 2);
+    }
+
+    #[test]
+    fn drain_connections_test() {
+        let srv = server_init();
+        tokio::runtime::Runtime::new().unwrap().block_on(async {
+            let metrics: &Arc<Metrics> = &srv.shared_state.metrics;
+            assert_eq!(srv.drain_connections(Duration::from_secs(5)).await, 0);
+
+            /* The finished connection is waited for */
+            let open: OpenConnection = OpenConnection::new(Arc::clone(metrics));
+            tokio::spawn(async move {
+                tokio::time::sleep(Duration::from_millis(100)).await;
+                drop(open);
+            });
+            assert_eq!(srv.drain_connections(Duration::from_secs(5)).await, 0);
+
+            /* The stuck one isn't waited for longer than the wait */
+            let _open: OpenConnection = OpenConnection::new(Arc::clone(metrics));
+            assert_eq!(srv.drain_connections(Duration::from_millis(100)).await, 1);
+        });
+    }
+
+    #[test]
+    fn handler_panic_test() {
+        async fn panicking(_req: Request) -> Response {
+            panic!("the handler has no response")
+        }
+        let srv = server_init();
+        srv.route(RequestType::Get, "/panic", panicking);
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime.block_on(async {
+            let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let mut client: TcpStream = TcpStream::connect(listener.local_addr().unwrap())
+                .await
+                .unwrap();
+            client
+                .write_all(b"GET /panic HTTP/1.1\r\nHost: localhost\r\n\r\n")
+                .await
+                .unwrap();
+            let (inc_stream, inc_addr) = listener.accept().await.unwrap();
+
+            /* The panic of the handler is answered, the server keeps serving */
+            let metrics: &Metrics = &srv.shared_state.metrics;
+            let open: OpenConnection = OpenConnection::new(Arc::clone(&srv.shared_state.metrics));
+            assert_eq!(metrics.open_connections.load(Ordering::Relaxed), 1);
+            srv.serve_connection(inc_stream, inc_addr, false, Duration::from_secs(5), open)
+                .await;
+            /* The panicked connection isn't counted as open anymore */
+            assert_eq!(metrics.open_connections.load(Ordering::Relaxed), 0);
+            let mut response: Vec<u8> = Vec::new();
+            client.read_to_end(&mut response).await.unwrap();
+            assert!(
+                response.starts_with(b"HTTP/1.1 500 Internal Server Error\r\n"),
+                "{:?}",
+                String::from_utf8_lossy(&response)
+            );
+            assert_eq!(metrics.handler_panics.load(Ordering::Relaxed), 1);
+            assert_eq!(metrics.server_errors.load(Ordering::Relaxed), 1);
+        });
+    }
+
     #[test]
     fn read_request_type_test() {
         let srv = server_init();
@@ -417,4 +4735,25 @@ mod tests {
             Vec::from(TEST_POST_RESOURCE)
         );
     }
+
+    #[test]
+    fn normalized_request_test() {
+        let srv = server_init();
+        for request in [
+            &b"GET //docs/./a/../index.html HTTP/1.1\r\nHost: localhost\r\n\r\n"[..],
+            &b"GET http://localhost/docs/%69ndex.html HTTP/1.1\r\nHost: localhost\r\n\r\n"[..],
+        ] {
+            let (_, resource_path, _, _) = srv.validate_request(request).unwrap();
+            assert_eq!(resource_path, b"/docs/index.html");
+        }
+
+        /* The query is split off once, the sites never see it */
+        let (_, resource_path, query, _) = srv
+            .validate_request(
+                b"GET /docs/./index.html?v=1&next=/a/../b HTTP/1.1\r\nHost: localhost\r\n\r\n",
+            )
+            .unwrap();
+        assert_eq!(resource_path, b"/docs/index.html");
+        assert_eq!(query.as_deref(), Some(&b"v=1&next=/a/../b"[..]));
+    }
 }
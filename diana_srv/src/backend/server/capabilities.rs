@@ -0,0 +1,127 @@
+use crate::backend::compression::{ContentCoding, SUPPORTED_CODINGS};
+use crate::backend::server::ServerConfig;
+use serde::Serialize;
+
+/* /.well-known/diana.json */
+pub const CAPABILITIES_PATH: &[u8] = b"/.well-known/diana.json";
+
+#[derive(Debug, Serialize)]
+pub struct Capabilities {
+    /*
+     *  Features of the server, served as JSON, so the tools and the load
+     *  balancers can configure themselves against it. The routes of
+     *  the administration are never listed.
+     *
+     *  Attributes:
+     *      version: Version of the server, missing if the Server header
+     *      is suppressed.
+     *      methods: Methods, that the server answers.
+     *      compression: Encodings of the text sites, empty if disabled.
+     *      ranges: Whether the byte ranges of the sites are served.
+     *      websocket: Route prefixes, which upgrade requests are passed
+     *      through to the upstreams.
+     *      proxy_prefixes: Route prefixes answered by the upstreams.
+     *      max_header_size: The maximum length of the request head.
+     *      max_request_body_size: The maximum length of the request body.
+     *      markdown: Whether the .md sites are rendered to HTML.
+     *      userdir: Whether the homepages of the users are served.
+     */
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version: Option<&'static str>,
+    pub methods: Vec<&'static str>,
+    pub compression: Vec<&'static str>,
+    pub ranges: bool,
+    pub websocket: Vec<String>,
+    pub proxy_prefixes: Vec<String>,
+    pub max_header_size: usize,
+    pub max_request_body_size: usize,
+    pub markdown: bool,
+    pub userdir: bool,
+}
+
+impl ServerConfig {
+    pub fn capabilities(&self) -> Capabilities {
+        /*
+         *  Describe the features, that the config enables.
+         */
+        let mut methods: Vec<&'static str> = vec!["GET", "HEAD", "POST"];
+        if self.allow_trace {
+            methods.push("TRACE");
+        }
+        let compression: Vec<&'static str> = if self.compression {
+            SUPPORTED_CODINGS.iter().map(ContentCoding::token).collect()
+        } else {
+            Vec::new()
+        };
+        let proxy_prefixes: Vec<String> = (!self.mirror.upstream.is_empty())
+            .then(|| self.mirror.prefix.clone())
+            .into_iter()
+            .collect();
+        Capabilities {
+            version: (self.server_header.as_deref() != Some(""))
+                .then_some(env!("CARGO_PKG_VERSION")),
+            methods,
+            compression,
+            ranges: true,
+            websocket: self
+                .upgrade_routes
+                .iter()
+                .map(|route| route.prefix.clone())
+                .collect(),
+            proxy_prefixes,
+            max_header_size: self.max_header_size,
+            max_request_body_size: self.max_request_body_size,
+            markdown: self.render_markdown,
+            userdir: !self.userdir.is_empty(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::backend::server::Server;
+    use serde_json::Value;
+    use std::path::{Path, PathBuf};
+
+    #[test]
+    fn capabilities_test() {
+        let cfg = Server::load_config(Path::new("resource/ServerConfig.toml")).unwrap();
+        let capabilities: Value = serde_json::to_value(cfg.capabilities()).unwrap();
+        assert_eq!(capabilities["ranges"], true);
+        assert!(
+            capabilities["methods"]
+                .as_array()
+                .unwrap()
+                .contains(&"GET".into())
+        );
+
+        let cfg_path: PathBuf = std::env::temp_dir().join("diana_capabilities_test.toml");
+        std::fs::write(
+            &cfg_path,
+            "ip = \"127.0.0.1\"\nport = 8080\nmax_connected_hosts = 1\ntimeout_in_secs = 1\n\
+             compression = true\nallow_trace = true\nserver_header = \"\"\n\
+             [[upgrade_routes]]\nprefix = \"/ws\"\nupstream = \"127.0.0.1:9000\"\n\
+             [mirror]\nupstream = \"http://127.0.0.1:9001/debian\"\nprefix = \"/debian\"\n",
+        )
+        .unwrap();
+        let cfg = Server::load_config(&cfg_path).unwrap();
+        let capabilities: Value = serde_json::to_value(cfg.capabilities()).unwrap();
+        assert!(capabilities.get("version").is_none());
+        assert_eq!(
+            capabilities["methods"],
+            serde_json::json!(["GET", "HEAD", "POST", "TRACE"])
+        );
+        assert!(
+            capabilities["compression"]
+                .as_array()
+                .unwrap()
+                .contains(&"gzip".into())
+        );
+        assert_eq!(capabilities["websocket"], serde_json::json!(["/ws"]));
+        assert_eq!(
+            capabilities["proxy_prefixes"],
+            serde_json::json!(["/debian"])
+        );
+        let _ = std::fs::remove_file(&cfg_path);
+    }
+}
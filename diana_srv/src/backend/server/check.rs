@@ -0,0 +1,483 @@
+use crate::backend::listen::{listen_addrs, parse_listen_ip};
+use crate::backend::privileges::resolve_identity;
+use crate::backend::scheduler::JobKind;
+use crate::backend::server::{Server, ServerConfig};
+use crate::utils::configs::layers::LayeredConfig;
+use crate::utils::patterns::glob_match;
+use crate::utils::readers::buffers::constants::{RESOURCE_HTML_DIR, SITE_NOT_FOUND};
+use crate::utils::readers::files::{bytes_to_path, list_files};
+use std::env;
+use std::io;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Default)]
+pub struct CheckReport {
+    /*
+     *  Result of the self-check.
+     *
+     *  Attributes:
+     *      passed: Descriptions of the checks, that passed.
+     *      problems: Descriptions of the problems found.
+     */
+    pub passed: Vec<String>,
+    pub problems: Vec<String>,
+}
+
+impl CheckReport {
+    fn check(&mut self, ok: bool, description: String) {
+        if ok {
+            self.passed.push(description);
+        } else {
+            self.problems.push(description);
+        }
+    }
+
+    pub fn is_ok(&self) -> bool {
+        self.problems.is_empty()
+    }
+
+    pub fn print(&self) {
+        /*
+         *  Print every check with the summary at the end.
+         */
+        for description in &self.passed {
+            println!("[CHECK] ok   {description}");
+        }
+        for description in &self.problems {
+            println!("[CHECK] FAIL {description}");
+        }
+        println!(
+            "[CHECK] {} checks, {} problems",
+            self.passed.len() + self.problems.len(),
+            self.problems.len()
+        );
+    }
+}
+
+impl Server {
+    pub fn load_config(toml_config: &Path) -> Result<ServerConfig, io::Error> {
+        /*
+         *  Read the config without starting anything, so it can be
+         *  audited before the deployment. The DIANA_ variables of
+         *  the environment override the file.
+         */
+        Server::load_layered(toml_config, &[]).map(|(cfg, _)| cfg)
+    }
+
+    pub fn load_layered(
+        toml_config: &Path,
+        overrides: &[String],
+    ) -> Result<(ServerConfig, LayeredConfig), io::Error> {
+        /*
+         *  Read the config with its layers: the built-in defaults, the file,
+         *  the DIANA_ variables of the environment and the command line.
+         *
+         *  Arguments:
+         *      toml_config: Path of the TOML config.
+         *      overrides: The key=value pairs of the command line.
+         *
+         *  Returns:
+         *      The config, with the layers telling where its values come from.
+         */
+        let layers: LayeredConfig = LayeredConfig::load(toml_config, env::vars(), overrides)?;
+        let cfg: ServerConfig = layers
+            .table()
+            .clone()
+            .try_into()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        Ok((cfg, layers))
+    }
+}
+
+impl ServerConfig {
+    pub fn self_check(&self) -> CheckReport {
+        /*
+         *  Audit the config: addresses, routes, and that every file or
+         *  directory it refers to exists. Nothing is created or modified.
+         *
+         *  Returns:
+         *      The report with every check performed.
+         */
+        let mut report: CheckReport = CheckReport::default();
+
+        match parse_listen_ip(&self.ip).and_then(|ip| listen_addrs(ip, self.port, self.dual_stack))
+        {
+            Ok(full_addrs) => {
+                for full_addr in full_addrs {
+                    report.check(true, format!("listen address {full_addr}"));
+                }
+            }
+            Err(e) => report.check(
+                false,
+                format!("listen address {}:{} ({e})", self.ip, self.port),
+            ),
+        }
+        for listener in &self.listeners {
+            match parse_listen_ip(&listener.ip) {
+                Ok(ip) => report.check(
+                    true,
+                    format!("listen address {}", SocketAddr::new(ip, listener.port)),
+                ),
+                Err(e) => report.check(
+                    false,
+                    format!("listen address {}:{} ({e})", listener.ip, listener.port),
+                ),
+            }
+        }
+
+        let html_dir: PathBuf = bytes_to_path(RESOURCE_HTML_DIR);
+        report.check(
+            html_dir.is_dir(),
+            format!("resource directory {}", html_dir.display()),
+        );
+        let error_page: PathBuf = html_dir.join(bytes_to_path(SITE_NOT_FOUND));
+        report.check(
+            error_page.is_file(),
+            format!("error page {}", error_page.display()),
+        );
+
+        let spa_pages = [&self.spa_fallback.file]
+            .into_iter()
+            .chain(self.spa_fallback.hosts.values());
+        for page in spa_pages.filter(|page| !page.is_empty()) {
+            let page_path: PathBuf = html_dir.join(page.trim_start_matches('/'));
+            report.check(
+                page_path.is_file(),
+                format!("single-page app page {}", page_path.display()),
+            );
+        }
+
+        let storage_dir: &Path = Path::new(&self.storage_dir);
+        let storage_parent: &Path = storage_dir.parent().unwrap_or(Path::new("."));
+        report.check(
+            storage_dir.is_dir()
+                || (!storage_dir.exists()
+                    && (storage_parent.as_os_str().is_empty() || storage_parent.is_dir())),
+            format!("storage directory {}", storage_dir.display()),
+        );
+        if let Some(cache_dir) = &self.cache_dir {
+            let cache_dir: &Path = Path::new(cache_dir);
+            let cache_parent: &Path = cache_dir.parent().unwrap_or(Path::new("."));
+            report.check(
+                cache_dir.is_dir()
+                    || (!cache_dir.exists()
+                        && (cache_parent.as_os_str().is_empty() || cache_parent.is_dir())),
+                format!("cache directory {}", cache_dir.display()),
+            );
+        }
+        if let Some(identity) = &self.server_header {
+            report.check(
+                identity
+                    .bytes()
+                    .all(|byte| byte == b' ' || byte.is_ascii_graphic()),
+                format!("Server header {identity:?}"),
+            );
+        }
+        let mime_types = self
+            .mime
+            .extra
+            .values()
+            .chain(self.mime.overrides.iter().map(|rule| &rule.content_type));
+        for mime in mime_types {
+            report.check(
+                mime.split_once('/')
+                    .is_some_and(|(main, sub)| !main.trim().is_empty() && !sub.trim().is_empty())
+                    && mime
+                        .bytes()
+                        .all(|byte| byte == b' ' || byte.is_ascii_graphic()),
+                format!("media type {mime:?}"),
+            );
+        }
+        if !self.mirror.upstream.is_empty() {
+            report.check(
+                self.mirror.upstream.starts_with("http://"),
+                format!("mirror upstream {}", self.mirror.upstream),
+            );
+            let mirror_dir: &Path = Path::new(&self.mirror.cache_dir);
+            let mirror_parent: &Path = mirror_dir.parent().unwrap_or(Path::new("."));
+            report.check(
+                mirror_dir.is_dir()
+                    || (!mirror_dir.exists()
+                        && (mirror_parent.as_os_str().is_empty() || mirror_parent.is_dir())),
+                format!("mirror directory {}", mirror_dir.display()),
+            );
+        }
+        if self.debug_dump {
+            let dump_dir: &Path = Path::new(&self.debug_dump_dir);
+            let dump_parent: &Path = dump_dir.parent().unwrap_or(Path::new("."));
+            report.check(
+                dump_dir.is_dir()
+                    || (!dump_dir.exists()
+                        && (dump_parent.as_os_str().is_empty() || dump_parent.is_dir())),
+                format!("debug dump directory {}", dump_dir.display()),
+            );
+            report.check(
+                self.debug_dump_max_bytes > 0,
+                format!("debug dump of {} bytes", self.debug_dump_max_bytes),
+            );
+        }
+        if let Some(challenge_dir) = &self.acme_challenge_dir {
+            report.check(
+                Path::new(challenge_dir).is_dir(),
+                format!("ACME challenge directory {challenge_dir}"),
+            );
+        }
+        if let Some(pid_file) = &self.pid_file {
+            let pid_dir: &Path = Path::new(pid_file).parent().unwrap_or(Path::new("."));
+            report.check(
+                pid_dir.as_os_str().is_empty() || pid_dir.is_dir(),
+                format!("PID file {pid_file}"),
+            );
+        }
+        if let Some(user) = &self.user {
+            report.check(
+                resolve_identity(user, self.group.as_deref()).is_ok(),
+                format!("user {user}"),
+            );
+        }
+        if self.chroot {
+            let mut paths: Vec<&str> = vec![&self.storage_dir];
+            paths.extend(self.acme_challenge_dir.as_deref());
+            paths.extend(self.cache_dir.as_deref());
+            if !self.short_links_path.is_empty() {
+                paths.push(&self.short_links_file);
+            }
+            report.check(
+                self.user.is_some() && paths.iter().all(|path| Path::new(path).is_relative()),
+                String::from("chroot (requires the user and the relative paths)"),
+            );
+        }
+        for (kind, threads) in [
+            ("worker threads", self.worker_threads),
+            ("max blocking threads", self.max_blocking_threads),
+        ] {
+            if let Some(threads) = threads {
+                report.check(threads > 0, format!("{threads} {kind}"));
+            }
+        }
+        if self.reserved_priority_slots > 0 {
+            report.check(
+                self.max_concurrent_requests
+                    .is_some_and(|max| self.reserved_priority_slots < max),
+                format!(
+                    "{} reserved priority slots (requires a larger max_concurrent_requests)",
+                    self.reserved_priority_slots
+                ),
+            );
+        }
+        if let Some(threshold) = self.mmap_threshold {
+            report.check(
+                cfg!(feature = "mmap"),
+                format!("mmap threshold {threshold} bytes (requires the mmap feature)"),
+            );
+        }
+        if let Some(db_path) = &self.sqlite_path {
+            report.check(
+                cfg!(feature = "sqlite"),
+                format!("sqlite database {db_path} (requires the sqlite feature)"),
+            );
+        }
+
+        for url in &self.webhooks.urls {
+            report.check(
+                url.starts_with("http://"),
+                format!("webhook {url} (only http:// is supported)"),
+            );
+        }
+        if let Some(threshold) = self.webhooks.error_rate_threshold {
+            report.check(
+                threshold > 0.0 && threshold <= 1.0,
+                format!("webhook error rate threshold {threshold}"),
+            );
+        }
+
+        for job in &self.scheduled_jobs {
+            let valid: bool = job.every_secs > 0
+                && match &job.kind {
+                    JobKind::HealthProbe { url } => url.starts_with("http://"),
+                    JobKind::Command { command } => !command.is_empty(),
+                    JobKind::CacheFlush | JobKind::CacheReload => true,
+                };
+            report.check(
+                valid,
+                format!("scheduled job {} every {}s", job.name(), job.every_secs),
+            );
+        }
+
+        let logs = [&self.logging.access_log, &self.logging.error_log];
+        for log in logs.into_iter().flatten() {
+            let log_dir: &Path = Path::new(log).parent().unwrap_or(Path::new("."));
+            report.check(
+                log_dir.as_os_str().is_empty() || log_dir.is_dir() || !log_dir.exists(),
+                format!("log {log}"),
+            );
+        }
+        if self.logging.rotates() {
+            report.check(
+                self.logging.check_interval_secs > 0,
+                format!(
+                    "log rotation checked every {}s",
+                    self.logging.check_interval_secs
+                ),
+            );
+        }
+
+        let mut routes: Vec<(&str, &str)> = vec![("metrics path", &self.metrics_path)];
+        routes.push(("admin path", &self.admin_path));
+        routes.push(("status path", &self.status_path));
+        routes.push(("file listing path", &self.file_listing_path));
+        routes.push(("short links path", &self.short_links_path));
+        for route in &self.persist_post_routes {
+            routes.push(("persisted route", route));
+        }
+        for limit in &self.concurrency_limits {
+            routes.push(("concurrency limit prefix", &limit.prefix));
+        }
+        for route in &self.priority_routes {
+            routes.push(("priority route", route));
+        }
+        for prefix in &self.spa_fallback.api_prefixes {
+            routes.push(("single-page app API prefix", prefix));
+        }
+        for rule in &self.bandwidth_limits {
+            routes.push(("bandwidth limit prefix", &rule.prefix));
+        }
+        for rule in &self.access_rules {
+            routes.push(("access rule path", &rule.path));
+            for method in &rule.allow_methods {
+                report.check(
+                    ["GET", "HEAD", "POST", "TRACE"].contains(&method.to_uppercase().as_str()),
+                    format!("access rule method {method}"),
+                );
+            }
+        }
+        for rule in &self.response_headers {
+            report.check(
+                rule.path.starts_with('/'),
+                format!("response headers path {}", rule.path),
+            );
+            for name in rule.headers.keys() {
+                report.check(
+                    !name.is_empty()
+                        && name
+                            .bytes()
+                            .all(|byte| byte.is_ascii_graphic() && byte != b':'),
+                    format!("response header name {name}"),
+                );
+            }
+        }
+        for route in &self.upgrade_routes {
+            routes.push(("upgrade route prefix", &route.prefix));
+        }
+        for rule in &self.chaos {
+            routes.push(("chaos path", &rule.path));
+            report.check(
+                u16::from(rule.error_percent) + u16::from(rule.drop_percent) <= 100,
+                format!("chaos percentages of {}", rule.path),
+            );
+        }
+        for (kind, route) in routes {
+            if !route.is_empty() {
+                report.check(route.starts_with('/'), format!("{kind} {route}"));
+            }
+        }
+
+        let html_files: Vec<PathBuf> = list_files(&html_dir);
+        for glob in &self.prewarm_globs {
+            let pattern: &[u8] = glob.strip_prefix('/').unwrap_or(glob).as_bytes();
+            let matches: usize = html_files
+                .iter()
+                .filter_map(|file| file.strip_prefix(&html_dir).ok()?.to_str())
+                .filter(|relative| glob_match(pattern, relative.as_bytes()))
+                .count();
+            report.check(
+                matches > 0,
+                format!("prewarm glob {glob} matches {matches} files"),
+            );
+        }
+
+        report
+    }
+}
+
+pub fn run_check(toml_config: &Path, overrides: &[String]) -> bool {
+    /*
+     *  Run the self-check of the config and print the report.
+     *
+     *  Arguments:
+     *      toml_config: Path of the TOML config.
+     *      overrides: The key=value pairs of the command line.
+     *
+     *  Returns:
+     *      True if no problems were found.
+     */
+    let report: CheckReport = match Server::load_layered(toml_config, overrides) {
+        Ok((cfg, _)) => {
+            let mut report: CheckReport = cfg.self_check();
+            report
+                .passed
+                .insert(0, format!("config {}", toml_config.display()));
+            report
+        }
+        Err(e) => CheckReport {
+            passed: Vec::new(),
+            problems: vec![format!("config {}: {e}", toml_config.display())],
+        },
+    };
+    report.print();
+    report.is_ok()
+}
+
+pub fn print_config(toml_config: &Path, overrides: &[String]) -> bool {
+    /*
+     *  Print the effective config, every value with the layer, that it
+     *  comes from. The values filled from the environment variables are
+     *  redacted, only the variables are named.
+     *
+     *  Returns:
+     *      True if the config was read.
+     */
+    let described: Result<Vec<String>, String> = Server::load_layered(toml_config, overrides)
+        .map_err(|e| e.to_string())
+        .and_then(|(cfg, layers)| {
+            let effective: toml::Table = toml::Table::try_from(&cfg).map_err(|e| e.to_string())?;
+            Ok(layers.describe(&effective))
+        });
+    match described {
+        Ok(lines) => {
+            for line in lines {
+                println!("{line}");
+            }
+            true
+        }
+        Err(e) => {
+            println!("[ERROR] config {}: {e}", toml_config.display());
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn self_check_test() {
+        let cfg = Server::load_config(Path::new("resource/ServerConfig.toml")).unwrap();
+        let report: CheckReport = cfg.self_check();
+        assert!(report.is_ok(), "{:?}", report.problems);
+
+        let broken_path: PathBuf = std::env::temp_dir().join("diana_self_check_test.toml");
+        std::fs::write(
+            &broken_path,
+            "ip = \"not an ip\"\nport = 8080\nmax_connected_hosts = 1\ntimeout_in_secs = 1\n\
+             admin_path = \"admin\"\nprewarm_globs = [\"*.missing\"]\n\
+             [mime]\nextra = { \"m3u8\" = \"application/vnd.apple.mpegurl\", \"x\" = \"bytes\" }\n",
+        )
+        .unwrap();
+        let broken = Server::load_config(&broken_path).unwrap();
+        assert_eq!(broken.self_check().problems.len(), 4);
+        let _ = std::fs::remove_file(&broken_path);
+    }
+}
@@ -0,0 +1,339 @@
+use std::time::Duration;
+
+#[cfg(target_os = "linux")]
+use std::io;
+
+/* Name of the service, that the Windows service manager knows the server by */
+pub const SERVICE_NAME: &str = "diana_srv";
+
+pub fn ready() {
+    /*
+     *  Tell the process manager, that the server listens, and so its
+     *  dependents can start: READY=1 to systemd, Running to the Windows
+     *  service manager. Without the manager it does nothing.
+     */
+    #[cfg(target_os = "linux")]
+    sd_notify_or_warn("READY=1\nSTATUS=Listening");
+    #[cfg(windows)]
+    windows::set_state(windows_service::service::ServiceState::Running);
+}
+
+pub fn reloading() {
+    /*
+     *  Tell the process manager, that the server reloads, ready() tells
+     *  it is done.
+     */
+    #[cfg(target_os = "linux")]
+    sd_notify_or_warn(&format!("RELOADING=1\nMONOTONIC_USEC={}", monotonic_usec()));
+}
+
+pub fn stopping() {
+    /*
+     *  Tell the process manager, that the server is shutting down.
+     */
+    #[cfg(target_os = "linux")]
+    sd_notify_or_warn("STOPPING=1");
+    #[cfg(windows)]
+    windows::set_state(windows_service::service::ServiceState::StopPending);
+}
+
+pub fn watchdog() {
+    /*
+     *  Tell systemd, that the server is alive, so it isn't restarted.
+     */
+    #[cfg(target_os = "linux")]
+    sd_notify_or_warn("WATCHDOG=1");
+}
+
+pub fn watchdog_interval() -> Option<Duration> {
+    /*
+     *  Returns:
+     *      How often the watchdog must be pinged, half of the timeout
+     *      systemd waits, None if the watchdog isn't enabled for this
+     *      process.
+     */
+    #[cfg(target_os = "linux")]
+    {
+        let usec: Option<String> = std::env::var("WATCHDOG_USEC").ok();
+        let pid: Option<String> = std::env::var("WATCHDOG_PID").ok();
+        parse_watchdog(usec.as_deref(), pid.as_deref(), std::process::id())
+    }
+    #[cfg(not(target_os = "linux"))]
+    None
+}
+
+pub fn parse_watchdog(usec: Option<&str>, pid: Option<&str>, own_pid: u32) -> Option<Duration> {
+    /*
+     *  Arguments:
+     *      usec: Value of WATCHDOG_USEC, the timeout in microseconds.
+     *      pid: Value of WATCHDOG_PID, the process that has to ping,
+     *      any process of the service if missing.
+     *      own_pid: ID of this process.
+     *
+     *  Returns:
+     *      Half of the timeout, None if the watchdog is disabled or meant
+     *      for another process.
+     */
+    if let Some(pid) = pid
+        && pid.trim().parse::<u32>().ok() != Some(own_pid)
+    {
+        return None;
+    }
+    let usec: u64 = usec?.trim().parse().ok().filter(|usec| *usec > 0)?;
+    Some(Duration::from_micros(usec / 2))
+}
+
+#[cfg(target_os = "linux")]
+fn sd_notify_or_warn(state: &str) {
+    if let Err(e) = sd_notify(state) {
+        println!("[WARNING] Failed to notify systemd: {e}");
+    }
+}
+
+#[cfg(target_os = "linux")]
+pub fn sd_notify(state: &str) -> io::Result<bool> {
+    /*
+     *  Send the state to the socket systemd passed in NOTIFY_SOCKET,
+     *  the socket is either a path or an abstract name starting with @.
+     *
+     *  Arguments:
+     *      state: Newline separated assignments, e.g. READY=1
+     *
+     *  Returns:
+     *      Whether the state was sent, false when not run by systemd.
+     */
+    use std::os::linux::net::SocketAddrExt;
+    use std::os::unix::net::{SocketAddr, UnixDatagram};
+
+    let Some(socket_path) = std::env::var_os("NOTIFY_SOCKET") else {
+        return Ok(false);
+    };
+    let socket_path: String = socket_path.to_string_lossy().into_owned();
+    let addr: SocketAddr = match socket_path.strip_prefix('@') {
+        Some(name) => SocketAddr::from_abstract_name(name)?,
+        None => SocketAddr::from_pathname(&socket_path)?,
+    };
+    let socket: UnixDatagram = UnixDatagram::unbound()?;
+    socket.send_to_addr(state.as_bytes(), &addr)?;
+    Ok(true)
+}
+
+#[cfg(target_os = "linux")]
+fn monotonic_usec() -> u64 {
+    /*
+     *  Returns:
+     *      The CLOCK_MONOTONIC in microseconds, that systemd matches
+     *      the reload with.
+     */
+    let mut now: libc::timespec = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    /* SAFETY: the timespec is valid and written by the call */
+    unsafe { libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut now) };
+    now.tv_sec as u64 * 1_000_000 + now.tv_nsec as u64 / 1_000
+}
+
+#[cfg(windows)]
+pub mod windows {
+    use super::SERVICE_NAME;
+    use crate::backend::server::{Server, ServerConfig};
+    use crate::backend::signals::request_termination;
+    use std::ffi::OsString;
+    use std::io;
+    use std::path::PathBuf;
+    use std::sync::{Mutex, OnceLock};
+    use std::time::Duration;
+    use windows_service::service::{
+        ServiceAccess, ServiceControl, ServiceControlAccept, ServiceErrorControl, ServiceExitCode,
+        ServiceInfo, ServiceStartType, ServiceState, ServiceStatus, ServiceType,
+    };
+    use windows_service::service_control_handler::{
+        self, ServiceControlHandlerResult, ServiceStatusHandle,
+    };
+    use windows_service::service_manager::{ServiceManager, ServiceManagerAccess};
+    use windows_service::{define_windows_service, service_dispatcher};
+
+    /* The config of the server, the service manager calls the entry point without it */
+    static SERVICE_CONFIG: Mutex<Option<ServerConfig>> = Mutex::new(None);
+    static STATUS_HANDLE: OnceLock<ServiceStatusHandle> = OnceLock::new();
+
+    define_windows_service!(ffi_service_main, service_main);
+
+    fn to_io(e: windows_service::Error) -> io::Error {
+        io::Error::other(e.to_string())
+    }
+
+    pub fn run_service(cfg: ServerConfig) -> io::Result<()> {
+        /*
+         *  Hand the process over to the service manager, that runs
+         *  the server until the service is stopped. It fails, when
+         *  the process wasn't started by the service manager.
+         *
+         *  Arguments:
+         *      cfg: Config of the server.
+         */
+        *SERVICE_CONFIG.lock().unwrap() = Some(cfg);
+        service_dispatcher::start(SERVICE_NAME, ffi_service_main).map_err(to_io)
+    }
+
+    fn service_main(_arguments: Vec<OsString>) {
+        let handler = |control: ServiceControl| match control {
+            ServiceControl::Stop | ServiceControl::Shutdown => {
+                request_termination();
+                ServiceControlHandlerResult::NoError
+            }
+            ServiceControl::Interrogate => ServiceControlHandlerResult::NoError,
+            _ => ServiceControlHandlerResult::NotImplemented,
+        };
+        let handle: ServiceStatusHandle =
+            match service_control_handler::register(SERVICE_NAME, handler) {
+                Ok(handle) => handle,
+                Err(e) => {
+                    println!("[ERROR] Failed to register the service: {e}");
+                    return;
+                }
+            };
+        let _ = STATUS_HANDLE.set(handle);
+        set_state(ServiceState::StartPending);
+
+        let cfg: Option<ServerConfig> = SERVICE_CONFIG.lock().unwrap().take();
+        let exit_code: u32 = match cfg {
+            Some(cfg) => match Server::with_config(cfg) {
+                Ok(srv) => {
+                    srv.run();
+                    0
+                }
+                Err(e) => {
+                    println!("[ERROR] Failed to start the server: {e}");
+                    1
+                }
+            },
+            None => 1,
+        };
+        set_status(ServiceState::Stopped, ServiceExitCode::Win32(exit_code));
+    }
+
+    pub fn set_state(state: ServiceState) {
+        /*
+         *  Report the state, when the server runs as the service.
+         */
+        set_status(state, ServiceExitCode::Win32(0));
+    }
+
+    fn set_status(state: ServiceState, exit_code: ServiceExitCode) {
+        let Some(handle) = STATUS_HANDLE.get() else {
+            return;
+        };
+        let controls_accepted: ServiceControlAccept = match state {
+            ServiceState::Running => ServiceControlAccept::STOP | ServiceControlAccept::SHUTDOWN,
+            _ => ServiceControlAccept::empty(),
+        };
+        let pending: bool = matches!(
+            state,
+            ServiceState::StartPending | ServiceState::StopPending
+        );
+        let status: ServiceStatus = ServiceStatus {
+            service_type: ServiceType::OWN_PROCESS,
+            current_state: state,
+            controls_accepted,
+            exit_code,
+            checkpoint: 0,
+            wait_hint: if pending {
+                Duration::from_secs(30)
+            } else {
+                Duration::default()
+            },
+            process_id: None,
+        };
+        if let Err(e) = handle.set_service_status(status) {
+            println!("[WARNING] Failed to report the service state: {e}");
+        }
+    }
+
+    pub fn install(launch_arguments: Vec<OsString>) -> io::Result<()> {
+        /*
+         *  Register the running binary as the service, started with
+         *  the system.
+         *
+         *  Arguments:
+         *      launch_arguments: Arguments the service manager starts
+         *      the binary with, they must contain --service.
+         */
+        let manager: ServiceManager = ServiceManager::local_computer(
+            None::<&str>,
+            ServiceManagerAccess::CONNECT | ServiceManagerAccess::CREATE_SERVICE,
+        )
+        .map_err(to_io)?;
+        let executable_path: PathBuf = std::env::current_exe()?;
+        let info: ServiceInfo = ServiceInfo {
+            name: OsString::from(SERVICE_NAME),
+            display_name: OsString::from("Diana HTTP server"),
+            service_type: ServiceType::OWN_PROCESS,
+            start_type: ServiceStartType::AutoStart,
+            error_control: ServiceErrorControl::Normal,
+            executable_path,
+            launch_arguments,
+            dependencies: Vec::new(),
+            account_name: None,
+            account_password: None,
+        };
+        manager
+            .create_service(&info, ServiceAccess::QUERY_STATUS)
+            .map_err(to_io)?;
+        Ok(())
+    }
+
+    pub fn uninstall() -> io::Result<()> {
+        /*
+         *  Remove the service, the running one is removed once it stops.
+         */
+        let manager: ServiceManager =
+            ServiceManager::local_computer(None::<&str>, ServiceManagerAccess::CONNECT)
+                .map_err(to_io)?;
+        manager
+            .open_service(SERVICE_NAME, ServiceAccess::DELETE)
+            .and_then(|service| service.delete())
+            .map_err(to_io)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn service_notify_test() {
+        assert_eq!(
+            parse_watchdog(Some("30000000"), None, 7),
+            Some(Duration::from_secs(15))
+        );
+        assert_eq!(
+            parse_watchdog(Some("30000000"), Some("7"), 7),
+            Some(Duration::from_secs(15))
+        );
+        assert_eq!(parse_watchdog(Some("30000000"), Some("8"), 7), None);
+        assert_eq!(parse_watchdog(Some("0"), None, 7), None);
+        assert_eq!(parse_watchdog(None, None, 7), None);
+
+        #[cfg(target_os = "linux")]
+        {
+            use std::os::unix::net::UnixDatagram;
+
+            let path: std::path::PathBuf =
+                std::env::temp_dir().join(format!("diana_notify_{}.sock", std::process::id()));
+            let _ = std::fs::remove_file(&path);
+            let socket: UnixDatagram = UnixDatagram::bind(&path).unwrap();
+            /* SAFETY: no other test reads NOTIFY_SOCKET */
+            unsafe { std::env::set_var("NOTIFY_SOCKET", &path) };
+            assert!(sd_notify("READY=1").unwrap());
+            unsafe { std::env::remove_var("NOTIFY_SOCKET") };
+            assert!(!sd_notify("READY=1").unwrap());
+
+            let mut buf: [u8; 64] = [0; 64];
+            let len: usize = socket.recv(&mut buf).unwrap();
+            assert_eq!(&buf[..len], b"READY=1");
+            std::fs::remove_file(&path).unwrap();
+        }
+    }
+}
@@ -0,0 +1,260 @@
+#[cfg(feature = "sqlite")]
+use crate::backend::storage::sqlite::SqliteStore;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt::Debug;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+#[cfg(feature = "sqlite")]
+use std::sync::Arc;
+use std::sync::Mutex;
+
+/* Slugs are kept short, so the links stay short */
+const MAX_SLUG_LEN: usize = 64;
+#[cfg(feature = "sqlite")]
+const KEY_PREFIX: &str = "link:";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ShortLink {
+    /*
+     *  Target of the slug.
+     *
+     *  Attributes:
+     *      url: Where the slug redirects, absolute http(s) URL or a path.
+     *      permanent: Answer with 301 instead of 302.
+     */
+    pub url: String,
+    #[serde(default)]
+    pub permanent: bool,
+}
+
+impl ShortLink {
+    pub fn parse(command: &[u8]) -> Result<(String, ShortLink), io::Error> {
+        /*
+         *  Parse the body of the admin command, e.g.
+         *  abc https://example.com/page permanent
+         *
+         *  Returns:
+         *      The slug with its link, or the error if they are invalid.
+         */
+        let invalid = |reason: &str| io::Error::new(io::ErrorKind::InvalidInput, reason);
+        let command: &str =
+            std::str::from_utf8(command).map_err(|_| invalid("the command isn't UTF-8"))?;
+        let mut parts = command.split_ascii_whitespace();
+        let (Some(slug), Some(url)) = (parts.next(), parts.next()) else {
+            return Err(invalid("expected the slug and the URL"));
+        };
+        let permanent: bool = match parts.next() {
+            None => false,
+            Some("permanent") => true,
+            Some(_) => return Err(invalid("expected permanent after the URL")),
+        };
+        if !is_valid_slug(slug) {
+            return Err(invalid(
+                "the slug may only contain letters, digits, - and _",
+            ));
+        }
+        if !is_valid_url(url) {
+            return Err(invalid("the URL must be http(s) or start with /"));
+        }
+        Ok((
+            String::from(slug),
+            ShortLink {
+                url: String::from(url),
+                permanent,
+            },
+        ))
+    }
+}
+
+#[async_trait]
+pub trait LinkStore: Debug + Send + Sync {
+    /*
+     *  Backend of the short links, the slugs map onto their targets.
+     */
+
+    async fn get(&self, slug: &str) -> Result<Option<ShortLink>, io::Error>;
+
+    async fn put(&self, slug: &str, link: &ShortLink) -> Result<(), io::Error>;
+
+    async fn remove(&self, slug: &str) -> Result<bool, io::Error>;
+}
+
+#[derive(Debug)]
+pub struct TomlLinks {
+    /*
+     *  Links kept in the TOML file, e.g.
+     *      [abc]
+     *      url = "https://example.com/page"
+     *
+     *  Attributes:
+     *      path: The TOML file, it is rewritten after every change.
+     *      links: The links read from the file.
+     */
+    path: PathBuf,
+    links: Mutex<BTreeMap<String, ShortLink>>,
+}
+
+impl TomlLinks {
+    pub fn open(path: &Path) -> Result<Self, io::Error> {
+        /*
+         *  Read the links, the missing file holds no links yet.
+         */
+        let links: BTreeMap<String, ShortLink> = match fs::read_to_string(path) {
+            Ok(content) => toml::from_str(&content)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(e) => return Err(e),
+        };
+        if let Some((slug, _)) = links
+            .iter()
+            .find(|(slug, link)| !is_valid_slug(slug) || !is_valid_url(&link.url))
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("invalid short link {slug} in {}", path.display()),
+            ));
+        }
+        Ok(TomlLinks {
+            path: path.to_path_buf(),
+            links: Mutex::new(links),
+        })
+    }
+
+    fn save(&self, links: &BTreeMap<String, ShortLink>) -> Result<(), io::Error> {
+        /*
+         *  Replace the file atomically, so the crash never leaves it half
+         *  written.
+         */
+        let content: String = toml::to_string(links).map_err(io::Error::other)?;
+        let tmp_path: PathBuf = self.path.with_extension("toml.tmp");
+        fs::write(&tmp_path, content)?;
+        fs::rename(&tmp_path, &self.path)
+    }
+}
+
+#[async_trait]
+impl LinkStore for TomlLinks {
+    async fn get(&self, slug: &str) -> Result<Option<ShortLink>, io::Error> {
+        Ok(self.links.lock().unwrap().get(slug).cloned())
+    }
+
+    async fn put(&self, slug: &str, link: &ShortLink) -> Result<(), io::Error> {
+        let mut links = self.links.lock().unwrap();
+        let previous: Option<ShortLink> = links.insert(String::from(slug), link.clone());
+        if let Err(e) = self.save(&links) {
+            /* Keep the memory in sync with the file */
+            match previous {
+                Some(previous) => links.insert(String::from(slug), previous),
+                None => links.remove(slug),
+            };
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    async fn remove(&self, slug: &str) -> Result<bool, io::Error> {
+        let mut links = self.links.lock().unwrap();
+        let Some(previous) = links.remove(slug) else {
+            return Ok(false);
+        };
+        if let Err(e) = self.save(&links) {
+            links.insert(String::from(slug), previous);
+            return Err(e);
+        }
+        Ok(true)
+    }
+}
+
+#[cfg(feature = "sqlite")]
+#[derive(Debug)]
+pub struct SqliteLinks {
+    /*
+     *  Links kept in the key-value table of the SQLite store, under
+     *  the link: keys.
+     */
+    store: Arc<SqliteStore>,
+}
+
+#[cfg(feature = "sqlite")]
+impl SqliteLinks {
+    pub fn new(store: Arc<SqliteStore>) -> Self {
+        SqliteLinks { store }
+    }
+}
+
+#[cfg(feature = "sqlite")]
+#[async_trait]
+impl LinkStore for SqliteLinks {
+    async fn get(&self, slug: &str) -> Result<Option<ShortLink>, io::Error> {
+        match self.store.get(&format!("{KEY_PREFIX}{slug}")).await? {
+            Some(value) => Ok(Some(serde_json::from_slice(&value)?)),
+            None => Ok(None),
+        }
+    }
+
+    async fn put(&self, slug: &str, link: &ShortLink) -> Result<(), io::Error> {
+        let value: Vec<u8> = serde_json::to_vec(link)?;
+        self.store.put(&format!("{KEY_PREFIX}{slug}"), &value).await
+    }
+
+    async fn remove(&self, slug: &str) -> Result<bool, io::Error> {
+        self.store.delete(&format!("{KEY_PREFIX}{slug}")).await
+    }
+}
+
+pub fn is_valid_slug(slug: &str) -> bool {
+    !slug.is_empty()
+        && slug.len() <= MAX_SLUG_LEN
+        && slug
+            .bytes()
+            .all(|byte| byte.is_ascii_alphanumeric() || byte == b'-' || byte == b'_')
+}
+
+fn is_valid_url(url: &str) -> bool {
+    /*
+     *  The URL ends up in the Location header, so the control characters
+     *  and the spaces are refused.
+     */
+    let has_scheme: bool = url.starts_with("https://") || url.starts_with("http://");
+    let is_path: bool = url.starts_with('/') && !url.starts_with("//");
+    (has_scheme || is_path) && url.bytes().all(|byte| byte.is_ascii_graphic())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn toml_links_test() {
+        let path: PathBuf =
+            std::env::temp_dir().join(format!("diana_links_{}.toml", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let links: TomlLinks = TomlLinks::open(&path).unwrap();
+        assert_eq!(links.get("abc").await.unwrap(), None);
+
+        let (slug, link) = ShortLink::parse(b"abc https://example.com/page permanent").unwrap();
+        links.put(&slug, &link).await.unwrap();
+        let (slug, link) = ShortLink::parse(b" docs /docs/index.html\n").unwrap();
+        assert!(!link.permanent);
+        links.put(&slug, &link).await.unwrap();
+
+        let reopened: TomlLinks = TomlLinks::open(&path).unwrap();
+        assert!(reopened.get("abc").await.unwrap().unwrap().permanent);
+        assert!(reopened.remove("docs").await.unwrap());
+        assert!(!reopened.remove("docs").await.unwrap());
+        assert_eq!(
+            TomlLinks::open(&path).unwrap().get("docs").await.unwrap(),
+            None
+        );
+
+        assert!(ShortLink::parse(b"a/b https://example.com").is_err());
+        assert!(ShortLink::parse(b"abc //evil.example").is_err());
+        assert!(ShortLink::parse(b"abc javascript:alert(1)").is_err());
+        assert!(ShortLink::parse(b"abc https://example.com 301").is_err());
+        assert!(ShortLink::parse(b"abc").is_err());
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,163 @@
+use tokio::sync::Notify;
+
+#[cfg(unix)]
+use tokio::signal::unix::{Signal, SignalKind, signal};
+
+/* Stop asked for by the process itself, e.g. the Windows service manager */
+static STOP_REQUESTED: Notify = Notify::const_new();
+
+pub fn request_termination() {
+    /*
+     *  Make the Terminate listener fire as if the signal came, also when
+     *  nothing is waiting yet.
+     */
+    STOP_REQUESTED.notify_one();
+}
+
+#[derive(Debug)]
+pub struct Hangup {
+    /*
+     *  Listener of SIGHUP, that operators send to make the server flush
+     *  and prewarm its cache. On the platforms without the signal it never
+     *  fires.
+     */
+    #[cfg(unix)]
+    inner: Option<Signal>,
+}
+
+impl Hangup {
+    pub fn new() -> Self {
+        #[cfg(unix)]
+        {
+            let inner: Option<Signal> = match signal(SignalKind::hangup()) {
+                Ok(sig) => Some(sig),
+                Err(e) => {
+                    println!("[WARNING] Failed to listen for SIGHUP: {e}");
+                    None
+                }
+            };
+            Hangup { inner }
+        }
+        #[cfg(not(unix))]
+        Hangup {}
+    }
+
+    pub async fn recv(&mut self) {
+        /*
+         *  Wait for the next signal.
+         */
+        #[cfg(unix)]
+        if let Some(sig) = self.inner.as_mut() {
+            sig.recv().await;
+            return;
+        }
+        std::future::pending::<()>().await
+    }
+}
+
+impl Default for Hangup {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Debug)]
+pub struct Terminate {
+    /*
+     *  Listener of SIGTERM, that asks the server to stop, e.g. from
+     *  the init system. Ctrl-C and request_termination stop the server
+     *  the same way.
+     */
+    #[cfg(unix)]
+    inner: Option<Signal>,
+}
+
+impl Terminate {
+    pub fn new() -> Self {
+        #[cfg(unix)]
+        {
+            let inner: Option<Signal> = match signal(SignalKind::terminate()) {
+                Ok(sig) => Some(sig),
+                Err(e) => {
+                    println!("[WARNING] Failed to listen for SIGTERM: {e}");
+                    None
+                }
+            };
+            Terminate { inner }
+        }
+        #[cfg(not(unix))]
+        Terminate {}
+    }
+
+    pub async fn recv(&mut self) {
+        /*
+         *  Wait for the signal, Ctrl-C or the requested termination.
+         */
+        #[cfg(unix)]
+        if let Some(sig) = self.inner.as_mut() {
+            tokio::select! {
+                _ = sig.recv() => {}
+                _ = tokio::signal::ctrl_c() => {}
+                _ = STOP_REQUESTED.notified() => {}
+            }
+            return;
+        }
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => {}
+            _ = STOP_REQUESTED.notified() => {}
+        }
+    }
+}
+
+impl Default for Terminate {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Debug)]
+pub struct Reopen {
+    /*
+     *  Listener of SIGUSR1, that logrotate sends after it moved the logs
+     *  away, so the server reopens them. On the platforms without
+     *  the signal it never fires.
+     */
+    #[cfg(unix)]
+    inner: Option<Signal>,
+}
+
+impl Reopen {
+    pub fn new() -> Self {
+        #[cfg(unix)]
+        {
+            let inner: Option<Signal> = match signal(SignalKind::user_defined1()) {
+                Ok(sig) => Some(sig),
+                Err(e) => {
+                    println!("[WARNING] Failed to listen for SIGUSR1: {e}");
+                    None
+                }
+            };
+            Reopen { inner }
+        }
+        #[cfg(not(unix))]
+        Reopen {}
+    }
+
+    pub async fn recv(&mut self) {
+        /*
+         *  Wait for the next signal.
+         */
+        #[cfg(unix)]
+        if let Some(sig) = self.inner.as_mut() {
+            sig.recv().await;
+            return;
+        }
+        std::future::pending::<()>().await
+    }
+}
+
+impl Default for Reopen {
+    fn default() -> Self {
+        Self::new()
+    }
+}
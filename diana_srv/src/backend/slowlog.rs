@@ -0,0 +1,247 @@
+use serde::Serialize;
+use std::future::Future;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::time::Instant;
+
+/* Request lines are cut to this length, as on the status page */
+const MAX_REQUEST_LINE: usize = 200;
+
+tokio::task_local! {
+    /* Clock of the request being answered, the replies mark it wherever they are sent */
+    static CLOCK: Arc<RequestClock>;
+}
+
+#[derive(Debug)]
+pub struct RequestClock {
+    /*
+     *  Instants of the phases of a single request.
+     *
+     *  Attributes:
+     *      started: The reading of the request started, the connection was
+     *      accepted or the previous request of it was answered.
+     *      head_read: The whole head was received.
+     *      parsed: The request line was validated.
+     *      answered: The response started to be written, with its status.
+     *      request: The request line.
+     */
+    started: Instant,
+    head_read: Instant,
+    parsed: OnceLock<Instant>,
+    answered: OnceLock<(Instant, usize)>,
+    request: String,
+}
+
+impl RequestClock {
+    pub fn new(started: Instant, request: &[u8]) -> Self {
+        /*
+         *  Start the clock once the head of the request is read.
+         *
+         *  Arguments:
+         *      started: Instant, when the reading of the request started.
+         *      request: Bytes of the request, only its first line is kept.
+         */
+        let line: &[u8] = request
+            .split(|byte| *byte == b'\n')
+            .next()
+            .unwrap_or(request)
+            .trim_ascii();
+        RequestClock {
+            started,
+            head_read: Instant::now(),
+            parsed: OnceLock::new(),
+            answered: OnceLock::new(),
+            request: String::from_utf8_lossy(&line[..line.len().min(MAX_REQUEST_LINE)])
+                .into_owned(),
+        }
+    }
+
+    pub async fn scope<F: Future>(self: Arc<Self>, answer: F) -> F::Output {
+        /*
+         *  Answer the request with this clock, so parsed() and answered()
+         *  mark it.
+         */
+        CLOCK.scope(self, answer).await
+    }
+
+    pub fn parsed() {
+        let _ = CLOCK.try_with(|clock| clock.parsed.set(Instant::now()));
+    }
+
+    pub fn answered(status: usize) {
+        let _ = CLOCK.try_with(|clock| clock.answered.set((Instant::now(), status)));
+    }
+
+    pub fn timing(&self, finished: Instant) -> RequestTiming {
+        /*
+         *  Split the time of the request into its phases. The phases,
+         *  that weren't marked, take no time, e.g. the parsing of
+         *  the request, that was refused.
+         *
+         *  Arguments:
+         *      finished: Instant, when the response was written.
+         */
+        let parsed: Instant = self.parsed.get().copied().unwrap_or(self.head_read);
+        let answered: Instant = self
+            .answered
+            .get()
+            .map_or(finished, |(answered, _)| *answered);
+        RequestTiming {
+            read: self.head_read - self.started,
+            parse: parsed.saturating_duration_since(self.head_read),
+            handler: answered.saturating_duration_since(parsed),
+            write: finished.saturating_duration_since(answered),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RequestTiming {
+    /*
+     *  Attributes:
+     *      read: Receiving the head of the request.
+     *      parse: Validating the request line.
+     *      handler: Producing the response, including reading the body.
+     *      write: Sending the response.
+     */
+    pub read: Duration,
+    pub parse: Duration,
+    pub handler: Duration,
+    pub write: Duration,
+}
+
+impl RequestTiming {
+    pub fn total(&self) -> Duration {
+        self.read + self.parse + self.handler + self.write
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SlowRequest {
+    /*
+     *  Request, that took longer than the threshold, as it is listed on
+     *  the admin endpoint. The times are in milliseconds.
+     */
+    pub timestamp: u64,
+    pub client: IpAddr,
+    pub request: String,
+    pub status: usize,
+    pub total_ms: f64,
+    pub read_ms: f64,
+    pub parse_ms: f64,
+    pub handler_ms: f64,
+    pub write_ms: f64,
+}
+
+#[derive(Debug)]
+pub struct SlowRequests {
+    /*
+     *  The slowest requests since the start.
+     *
+     *  Attributes:
+     *      threshold: Requests taking longer are logged and kept, none are
+     *      if it is missing.
+     *      capacity: The number of the slowest requests kept.
+     *      slowest: The kept requests, the slowest first.
+     */
+    threshold: Option<Duration>,
+    capacity: usize,
+    slowest: Mutex<Vec<SlowRequest>>,
+}
+
+impl SlowRequests {
+    pub fn new(threshold: Option<Duration>, capacity: usize) -> Self {
+        SlowRequests {
+            threshold,
+            capacity,
+            slowest: Mutex::new(Vec::with_capacity(capacity)),
+        }
+    }
+
+    pub fn record(&self, client: IpAddr, clock: &RequestClock) -> Option<SlowRequest> {
+        /*
+         *  Keep the answered request, if it exceeded the threshold and is
+         *  among the slowest ones.
+         *
+         *  Returns:
+         *      The request, if it exceeded the threshold, so it is logged.
+         */
+        let timing: RequestTiming = clock.timing(Instant::now());
+        if timing.total() <= self.threshold? {
+            return None;
+        }
+        let millis = |duration: Duration| duration.as_micros() as f64 / 1000.0;
+        let request: SlowRequest = SlowRequest {
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|elapsed| elapsed.as_secs())
+                .unwrap_or_default(),
+            client,
+            request: clock.request.clone(),
+            status: clock.answered.get().map_or(0, |(_, status)| *status),
+            total_ms: millis(timing.total()),
+            read_ms: millis(timing.read),
+            parse_ms: millis(timing.parse),
+            handler_ms: millis(timing.handler),
+            write_ms: millis(timing.write),
+        };
+        let mut slowest = self.slowest.lock().unwrap();
+        let idx: usize = slowest.partition_point(|kept| kept.total_ms >= request.total_ms);
+        if idx < self.capacity {
+            slowest.insert(idx, request.clone());
+            slowest.truncate(self.capacity);
+        }
+        Some(request)
+    }
+
+    pub fn slowest(&self) -> Vec<SlowRequest> {
+        self.slowest.lock().unwrap().clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn slow_requests_test() {
+        let client: IpAddr = "127.0.0.1".parse().unwrap();
+        let slow_requests: SlowRequests = SlowRequests::new(Some(Duration::from_millis(100)), 2);
+
+        for (idx, handler_ms) in [50u64, 300, 200, 400].into_iter().enumerate() {
+            let started: Instant = Instant::now();
+            tokio::time::advance(Duration::from_millis(10)).await;
+            let clock: Arc<RequestClock> = Arc::new(RequestClock::new(
+                started,
+                format!("GET /{idx} HTTP/1.1\r\nHost: a\r\n\r\n").as_bytes(),
+            ));
+            Arc::clone(&clock)
+                .scope(async {
+                    RequestClock::parsed();
+                    tokio::time::advance(Duration::from_millis(handler_ms)).await;
+                    RequestClock::answered(200);
+                    tokio::time::advance(Duration::from_millis(5)).await;
+                })
+                .await;
+            let recorded: Option<SlowRequest> = slow_requests.record(client, &clock);
+            assert_eq!(recorded.is_some(), handler_ms > 100);
+        }
+        /* Outside of the scope nothing is marked */
+        RequestClock::answered(500);
+
+        let slowest: Vec<SlowRequest> = slow_requests.slowest();
+        assert_eq!(slowest.len(), 2);
+        assert_eq!(slowest[0].request, "GET /3 HTTP/1.1");
+        assert_eq!(slowest[0].status, 200);
+        assert_eq!(slowest[0].read_ms, 10.0);
+        assert_eq!(slowest[0].handler_ms, 400.0);
+        assert_eq!(slowest[0].write_ms, 5.0);
+        assert_eq!(slowest[0].total_ms, 415.0);
+        assert_eq!(slowest[1].request, "GET /1 HTTP/1.1");
+
+        let disabled: SlowRequests = SlowRequests::new(None, 2);
+        let clock: RequestClock = RequestClock::new(Instant::now(), b"GET / HTTP/1.1\r\n\r\n");
+        assert!(disabled.record(client, &clock).is_none());
+    }
+}
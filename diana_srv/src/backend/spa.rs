@@ -0,0 +1,131 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct SpaFallback {
+    /*
+     *  The [spa_fallback] table of the config. The GET requests of the sites,
+     *  that don't exist, are answered with the app's page, so the client-side
+     *  router of the single-page app can show them.
+     *
+     *  Attributes:
+     *      file: Page served instead of the missing sites, relative to
+     *      the resource directory, e.g. index.html. Disabled if empty.
+     *      hosts: Pages per served host, e.g. "app.example.com" = "app/index.html",
+     *      an empty page disables the fallback of the host.
+     *      api_prefixes: Route prefixes of the API, which missing paths stay
+     *      not found.
+     */
+    pub file: String,
+    pub hosts: HashMap<String, String>,
+    pub api_prefixes: Vec<String>,
+}
+
+impl Default for SpaFallback {
+    fn default() -> Self {
+        SpaFallback {
+            file: String::new(),
+            hosts: HashMap::new(),
+            api_prefixes: vec![String::from("/api")],
+        }
+    }
+}
+
+impl SpaFallback {
+    pub fn page_for(&self, host: Option<&[u8]>, resource_path: &[u8]) -> Option<Vec<u8>> {
+        /*
+         *  Resolve the page, that answers the missing site.
+         *
+         *  Arguments:
+         *      host: Requested host without the port.
+         *      resource_path: Resource path of the missing site.
+         *
+         *  Returns:
+         *      Resource path of the page, e.g. /index.html, None if the site
+         *      stays not found. The paths of the API and of the files, e.g.
+         *      /app.js, are never answered with the page.
+         */
+        let file: &str = host
+            .and_then(|host| {
+                self.hosts
+                    .iter()
+                    .find(|(name, _)| name.as_bytes().eq_ignore_ascii_case(host))
+                    .map(|(_, file)| file.as_str())
+            })
+            .unwrap_or(&self.file);
+        if file.is_empty() {
+            return None;
+        }
+        if self
+            .api_prefixes
+            .iter()
+            .any(|prefix| is_under(resource_path, prefix.trim_end_matches('/').as_bytes()))
+        {
+            return None;
+        }
+        /* The missing asset is an error of the app, not its route */
+        let last_segment: &[u8] = resource_path
+            .rsplit(|byte| *byte == b'/')
+            .next()
+            .unwrap_or(resource_path);
+        if last_segment.contains(&b'.') {
+            return None;
+        }
+        let mut page: Vec<u8> = Vec::with_capacity(file.len() + 1);
+        if !file.starts_with('/') {
+            page.push(b'/');
+        }
+        page.extend_from_slice(file.as_bytes());
+        Some(page)
+    }
+}
+
+fn is_under(path: &[u8], prefix: &[u8]) -> bool {
+    /*
+     *  Returns:
+     *      True if the path is the prefix or below it, /api covers
+     *      /api/users but not /apidocs.
+     */
+    match path.strip_prefix(prefix) {
+        Some(rest) => rest.is_empty() || rest.starts_with(b"/"),
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spa_fallback_test() {
+        let config: SpaFallback = toml::from_str(
+            r#"
+            file = "index.html"
+
+            [hosts]
+            "App.example.com" = "/app/index.html"
+            "docs.example.com" = ""
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            config.page_for(Some(b"localhost"), b"/users/42?tab=posts"),
+            Some(b"/index.html".to_vec())
+        );
+        assert_eq!(
+            config.page_for(Some(b"app.example.com"), b"/settings"),
+            Some(b"/app/index.html".to_vec())
+        );
+        assert_eq!(
+            config.page_for(None, b"/apidocs"),
+            Some(b"/index.html".to_vec())
+        );
+        assert_eq!(config.page_for(None, b"/api"), None);
+        assert_eq!(config.page_for(None, b"/api/users"), None);
+        assert_eq!(config.page_for(None, b"/assets/app.js"), None);
+        assert_eq!(config.page_for(Some(b"docs.example.com"), b"/guide"), None);
+        assert_eq!(SpaFallback::default().page_for(None, b"/guide"), None);
+    }
+}
@@ -0,0 +1,41 @@
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+
+/* Head of every event stream, the stream is never cached */
+pub const EVENT_STREAM_HEAD: &[u8] =
+    b"HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nCache-Control: no-store\r\n\r\n";
+/* Comment sent on the idle stream, so the proxies don't close it */
+pub const KEEPALIVE_EVENT: &[u8] = b": keepalive\n\n";
+
+pub fn format_event(data: &str) -> Vec<u8> {
+    /*
+     *  Format the message event, every line of the data gets its own
+     *  data field, the browser joins them back.
+     */
+    let mut event: String = String::with_capacity(data.len() + 8);
+    for line in data.split('\n') {
+        event.push_str("data: ");
+        event.push_str(line);
+        event.push('\n');
+    }
+    event.push('\n');
+    event.into_bytes()
+}
+
+pub async fn send_event(stream: &mut (impl AsyncWrite + Unpin), event: &[u8]) -> bool {
+    /*
+     *  Returns:
+     *      False if the client closed the stream, e.g. closed the tab.
+     */
+    stream.write_all(event).await.is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format_event_test() {
+        assert_eq!(format_event("reload"), b"data: reload\n\n".to_vec());
+        assert_eq!(format_event("a\nb"), b"data: a\ndata: b\n\n".to_vec());
+    }
+}
@@ -0,0 +1,370 @@
+use crate::backend::metrics::Metrics;
+use crate::backend::sse::{EVENT_STREAM_HEAD, format_event, send_event};
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::fs;
+use std::future::Future;
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::io::AsyncWrite;
+
+tokio::task_local! {
+    /* Request being answered, the replies finish it wherever they are sent */
+    static ANSWERING: RequestId;
+}
+
+/* Requests kept for the dashboard */
+const RECENT_CAPACITY: usize = 50;
+/* Request lines are cut to this length, so the huge ones don't fill the memory */
+const MAX_REQUEST_LINE: usize = 200;
+/* How often the dashboard receives the snapshot */
+const REFRESH_INTERVAL: Duration = Duration::from_secs(2);
+/* The dashboard, it subscribes to the events below its own path */
+pub const STATUS_PAGE: &str = r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta charset="utf-8">
+<title>diana_srv status</title>
+<style>
+body { font-family: monospace; margin: 2em; }
+table { border-collapse: collapse; }
+td, th { padding: 0.2em 1em; text-align: left; border-bottom: 1px solid #ccc; }
+.error { color: #b00; }
+</style>
+</head>
+<body>
+<h1>diana_srv status</h1>
+<table id="summary"></table>
+<h2>Recent requests</h2>
+<table>
+<thead><tr><th>Time</th><th>Client</th><th>Request</th><th>Status</th></tr></thead>
+<tbody id="recent"></tbody>
+</table>
+<script>
+function cell(row, text, cls) {
+  var td = row.insertCell();
+  td.textContent = text;
+  if (cls) { td.className = cls; }
+}
+new EventSource(location.pathname.replace(/\/$/, "") + "/events").onmessage = function (event) {
+  var status = JSON.parse(event.data);
+  var summary = document.getElementById("summary");
+  summary.textContent = "";
+  [
+    ["Uptime", status.uptime_secs + " s"],
+    ["Open connections", status.open_connections],
+    ["Requests", status.requests],
+    ["Client errors", status.client_errors],
+    ["Server errors", status.server_errors],
+    ["Cached sites", status.cached_sites + " / " + status.cache_capacity],
+    ["Memory", status.memory_rss_bytes === null ? "n/a" : (status.memory_rss_bytes / 1048576).toFixed(1) + " MiB"]
+  ].forEach(function (pair) {
+    var row = summary.insertRow();
+    cell(row, pair[0]);
+    cell(row, pair[1]);
+  });
+  var recent = document.getElementById("recent");
+  recent.textContent = "";
+  status.recent.forEach(function (request) {
+    var row = recent.insertRow();
+    cell(row, new Date(request.timestamp * 1000).toLocaleTimeString());
+    cell(row, request.client);
+    cell(row, request.request);
+    cell(row, request.status, request.status >= 400 ? "error" : "");
+  });
+};
+</script>
+</body>
+</html>
+"#;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RecentRequest {
+    /*
+     *  Attributes:
+     *      timestamp: Seconds since the epoch, when the request came.
+     *      client: Address of the client.
+     *      request: The request line.
+     *      status: Status of the answer, 0 while it is being handled.
+     */
+    pub timestamp: u64,
+    pub client: IpAddr,
+    pub request: String,
+    pub status: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RequestId(u64);
+
+impl RequestId {
+    pub async fn scope<F: Future>(self, answer: F) -> F::Output {
+        /*
+         *  Answer the request within this scope, so its replies finish it
+         *  and not the request of another connection.
+         */
+        ANSWERING.scope(self, answer).await
+    }
+
+    pub fn current() -> Option<RequestId> {
+        ANSWERING.try_with(|id| *id).ok()
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct StatusSnapshot {
+    /*
+     *  State of the server, that the dashboard shows.
+     */
+    pub uptime_secs: u64,
+    pub open_connections: u64,
+    pub requests: u64,
+    pub client_errors: u64,
+    pub server_errors: u64,
+    pub cached_sites: u64,
+    pub cache_capacity: u64,
+    pub memory_rss_bytes: Option<u64>,
+    pub recent: Vec<RecentRequest>,
+}
+
+#[derive(Debug)]
+pub struct StatusBoard {
+    /*
+     *  State collected for the status page, shared with its event streams.
+     *
+     *  Attributes:
+     *      started: Instant, when the server started.
+     *      recent: The latest requests with their IDs, the newest is
+     *      the last one.
+     *      next_id: ID of the next request.
+     *      cached_sites: Sites in the cache, updated after every answer.
+     *      cache_capacity: The capacity of the cache.
+     */
+    started: Instant,
+    recent: Mutex<VecDeque<(RequestId, RecentRequest)>>,
+    next_id: AtomicU64,
+    cached_sites: AtomicU64,
+    cache_capacity: AtomicU64,
+}
+
+impl Default for StatusBoard {
+    fn default() -> Self {
+        StatusBoard {
+            started: Instant::now(),
+            recent: Mutex::new(VecDeque::with_capacity(RECENT_CAPACITY)),
+            next_id: AtomicU64::new(0),
+            cached_sites: AtomicU64::new(0),
+            cache_capacity: AtomicU64::new(0),
+        }
+    }
+}
+
+impl StatusBoard {
+    pub fn begin(&self, client: IpAddr, request: &[u8]) -> RequestId {
+        /*
+         *  Add the request, that is being handled, to the recent ones.
+         *
+         *  Arguments:
+         *      client: Address of the client.
+         *      request: Bytes of the request, only its first line is kept.
+         *
+         *  Returns:
+         *      ID of the request, that it is answered with.
+         */
+        let id: RequestId = RequestId(self.next_id.fetch_add(1, Ordering::Relaxed));
+        let line: &[u8] = request
+            .split(|byte| *byte == b'\n')
+            .next()
+            .unwrap_or(request)
+            .trim_ascii();
+        let line: &[u8] = &line[..line.len().min(MAX_REQUEST_LINE)];
+        let timestamp: u64 = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_secs())
+            .unwrap_or_default();
+        let mut recent = self.recent.lock().unwrap();
+        if recent.len() == RECENT_CAPACITY {
+            recent.pop_front();
+        }
+        recent.push_back((
+            id,
+            RecentRequest {
+                timestamp,
+                client,
+                request: String::from_utf8_lossy(line).into_owned(),
+                status: 0,
+            },
+        ));
+        id
+    }
+
+    pub fn pending(&self, id: RequestId) -> Option<RecentRequest> {
+        /*
+         *  Returns:
+         *      The request, if it isn't answered yet.
+         */
+        let recent = self.recent.lock().unwrap();
+        recent
+            .iter()
+            .find(|(request_id, request)| *request_id == id && request.status == 0)
+            .map(|(_, request)| request.clone())
+    }
+
+    pub fn finish(&self, id: RequestId, status: usize) -> Option<RecentRequest> {
+        /*
+         *  Set the status of the request, that isn't answered yet.
+         *
+         *  Returns:
+         *      The answered request, e.g. for the access log. None if it
+         *      was answered already or it isn't recent anymore.
+         */
+        let mut recent = self.recent.lock().unwrap();
+        let (_, request) = recent
+            .iter_mut()
+            .find(|(request_id, request)| *request_id == id && request.status == 0)?;
+        request.status = status;
+        Some(request.clone())
+    }
+
+    pub fn set_cache_occupancy(&self, cached_sites: usize, cache_capacity: usize) {
+        self.cached_sites
+            .store(cached_sites as u64, Ordering::Relaxed);
+        self.cache_capacity
+            .store(cache_capacity as u64, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self, metrics: &Metrics) -> StatusSnapshot {
+        StatusSnapshot {
+            uptime_secs: self.started.elapsed().as_secs(),
+            open_connections: metrics.open_connections.load(Ordering::Relaxed),
+            requests: metrics.requests.load(Ordering::Relaxed),
+            client_errors: metrics.client_errors.load(Ordering::Relaxed),
+            server_errors: metrics.server_errors.load(Ordering::Relaxed),
+            cached_sites: self.cached_sites.load(Ordering::Relaxed),
+            cache_capacity: self.cache_capacity.load(Ordering::Relaxed),
+            memory_rss_bytes: memory_rss(),
+            /* The newest requests first */
+            recent: self
+                .recent
+                .lock()
+                .unwrap()
+                .iter()
+                .rev()
+                .map(|(_, request)| request.clone())
+                .collect(),
+        }
+    }
+
+    pub async fn serve_events(&self, metrics: &Metrics, mut stream: impl AsyncWrite + Unpin) {
+        /*
+         *  Keep the event stream of the dashboard open, it sends
+         *  the snapshot every REFRESH_INTERVAL.
+         *
+         *  Arguments:
+         *      metrics: Counters of the server.
+         *      stream: Connection of the browser, that asked for the stream.
+         */
+        if !send_event(&mut stream, EVENT_STREAM_HEAD).await {
+            return;
+        }
+        let mut interval = tokio::time::interval(REFRESH_INTERVAL);
+        loop {
+            interval.tick().await;
+            let snapshot: String = match serde_json::to_string(&self.snapshot(metrics)) {
+                Ok(snapshot) => snapshot,
+                Err(e) => {
+                    println!("[ERROR] Failed to serialize the status: {e}");
+                    return;
+                }
+            };
+            if !send_event(&mut stream, &format_event(&snapshot)).await {
+                return;
+            }
+        }
+    }
+}
+
+pub fn response_status(response: &[u8]) -> Option<usize> {
+    /*
+     *  Returns:
+     *      The status code from the status line of the formatted response.
+     */
+    std::str::from_utf8(response.get(9..12)?).ok()?.parse().ok()
+}
+
+pub fn memory_rss() -> Option<u64> {
+    /*
+     *  Read the resident memory of the process from procfs.
+     *
+     *  Returns:
+     *      Resident bytes, None if procfs isn't available.
+     */
+    parse_vm_rss(&fs::read_to_string("/proc/self/status").ok()?)
+}
+
+fn parse_vm_rss(status: &str) -> Option<u64> {
+    /*
+     *  VmRSS:      5120 kB
+     */
+    let line: &str = status.lines().find(|line| line.starts_with("VmRSS:"))?;
+    let kib: u64 = line["VmRSS:".len()..]
+        .split_whitespace()
+        .next()?
+        .parse()
+        .ok()?;
+    Some(kib * 1024)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn status_board_test() {
+        let board: StatusBoard = StatusBoard::default();
+        let client: IpAddr = "127.0.0.1".parse().unwrap();
+        for idx in 0..RECENT_CAPACITY {
+            let id: RequestId = board.begin(
+                client,
+                format!("GET /{idx} HTTP/1.1\r\nHost: a\r\n\r\n").as_bytes(),
+            );
+            board.finish(id, 200);
+        }
+
+        /* The requests of the concurrent connections are answered by their IDs */
+        let slow: RequestId = board.begin(client, b"GET /slow HTTP/1.1\r\n\r\n");
+        let missing: RequestId = board.begin(client, b"GET /missing HTTP/1.1\r\n\r\n");
+        let status: usize = response_status(b"HTTP/1.1 404 Not Found\r\n\r\n").unwrap();
+        assert_eq!(
+            board.finish(missing, status).unwrap().request,
+            "GET /missing HTTP/1.1"
+        );
+        assert_eq!(board.finish(missing, 500), None);
+        assert_eq!(board.pending(slow).unwrap().request, "GET /slow HTTP/1.1");
+        board.finish(slow, 200);
+        assert_eq!(board.pending(slow), None);
+        board.set_cache_occupancy(3, 1024);
+
+        let snapshot: StatusSnapshot = board.snapshot(&Metrics::default());
+        assert_eq!(snapshot.recent.len(), RECENT_CAPACITY);
+        assert_eq!(snapshot.recent[0].request, "GET /missing HTTP/1.1");
+        assert_eq!(snapshot.recent[0].status, 404);
+        assert_eq!(
+            (
+                snapshot.recent[1].request.as_str(),
+                snapshot.recent[1].status
+            ),
+            ("GET /slow HTTP/1.1", 200)
+        );
+        assert_eq!(
+            snapshot.recent[2].request,
+            format!("GET /{} HTTP/1.1", RECENT_CAPACITY - 1)
+        );
+        assert_eq!(snapshot.cached_sites, 3);
+        assert_eq!(
+            parse_vm_rss("Name:\tdiana_srv\nVmRSS:\t    5120 kB\n"),
+            Some(5120 * 1024)
+        );
+    }
+}
@@ -0,0 +1,195 @@
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+
+use crate::utils::formatters::base64;
+use serde_json::json;
+use std::fmt::Debug;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub trait Storage: Debug + Send + Sync {
+    /*
+     *  Persistence backend for the data submitted to POST handlers.
+     *
+     *  Every route owns its own collection of records, records are kept
+     *  in the order they were appended.
+     */
+
+    fn append(&self, route: &[u8], body: &[u8]) -> Result<(), io::Error>;
+
+    fn read_all(&self, route: &[u8]) -> Result<Vec<Vec<u8>>, io::Error>;
+}
+
+#[derive(Debug)]
+pub struct FileStorage {
+    /*
+     *  Default storage, that keeps an append-only JSON lines file per route.
+     *
+     *  Attributes:
+     *      storage_dir: Directory, where the route files are created.
+     *      write_lock: Serializes the appends, so the lines never interleave.
+     */
+    storage_dir: PathBuf,
+    write_lock: Mutex<()>,
+}
+
+impl FileStorage {
+    pub fn new(storage_dir: &str) -> Result<Self, io::Error> {
+        /*
+         *  Constructor of the file storage.
+         *
+         *  Arguments:
+         *      storage_dir: Directory for the route files, it is created if
+         *      it doesn't exist.
+         *
+         *  Returns:
+         *      The storage or an error if the directory can't be created.
+         */
+        fs::create_dir_all(storage_dir)?;
+        Ok(FileStorage {
+            storage_dir: PathBuf::from(storage_dir),
+            write_lock: Mutex::new(()),
+        })
+    }
+
+    pub fn route_file(&self, route: &[u8]) -> PathBuf {
+        /*
+         *  Map the route onto the file name, e.g. /api/data -> _api_data.jsonl
+         *  The lowercase letters, digits and '-' are kept, '/' becomes '_'
+         *  and every other byte is percent-encoded, so no two routes share
+         *  a file, not even on a case-insensitive file system.
+         *
+         *  Arguments:
+         *      route: Resource path from the request.
+         *
+         *  Returns:
+         *      Path of the file, that holds the records of the route.
+         */
+        let mut file_name: String = String::with_capacity(route.len() + 6);
+        for byte in route {
+            match byte {
+                b'a'..=b'z' | b'0'..=b'9' | b'-' => file_name.push(*byte as char),
+                b'/' => file_name.push('_'),
+                _ => file_name.push_str(&format!("%{byte:02X}")),
+            }
+        }
+        file_name.push_str(".jsonl");
+        self.storage_dir.join(file_name)
+    }
+}
+
+impl Storage for FileStorage {
+    fn append(&self, route: &[u8], body: &[u8]) -> Result<(), io::Error> {
+        /*
+         *  Append the body as a single JSON line to the route's file.
+         *
+         *  Arguments:
+         *      route: Resource path from the request.
+         *      body: The request body, stored as a string, or in base64
+         *      if it isn't UTF-8, e.g. the uploaded image.
+         */
+        let received_at: u64 = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_secs())
+            .unwrap_or(0);
+        let record = match std::str::from_utf8(body) {
+            Ok(body) => json!({ "received_at": received_at, "body": body }),
+            Err(_) => json!({ "received_at": received_at, "body_base64": base64::encode(body) }),
+        };
+        let mut line: Vec<u8> = record.to_string().into_bytes();
+        line.push(b'\n');
+
+        let _guard = self.write_lock.lock().unwrap_or_else(|e| e.into_inner());
+        let mut file: File = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.route_file(route))?;
+        file.write_all(&line)
+    }
+
+    fn read_all(&self, route: &[u8]) -> Result<Vec<Vec<u8>>, io::Error> {
+        /*
+         *  Read every record stored for the route.
+         *
+         *  Returns:
+         *      Bodies in the order they were appended, empty if the route
+         *      hasn't received anything yet.
+         */
+        let file: File = match File::open(self.route_file(route)) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+        let mut records: Vec<Vec<u8>> = Vec::new();
+        for line in BufReader::new(file).lines() {
+            let record: serde_json::Value = serde_json::from_str(&line?)?;
+            if let Some(body) = record["body"].as_str() {
+                records.push(body.as_bytes().to_vec());
+            } else if let Some(body) = record["body_base64"].as_str().and_then(base64::decode) {
+                records.push(body);
+            }
+        }
+        Ok(records)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn file_storage_append_test() {
+        let dir: PathBuf = std::env::temp_dir().join("diana_storage_append_test");
+        let _ = fs::remove_dir_all(&dir);
+        let storage = FileStorage::new(dir.to_str().unwrap()).unwrap();
+
+        assert_eq!(
+            storage.route_file(b"/api/data"),
+            dir.join("_api_data.jsonl")
+        );
+        assert!(storage.read_all(b"/api/data").unwrap().is_empty());
+
+        storage.append(b"/api/data", b"name=diana").unwrap();
+        storage
+            .append(b"/api/data", b"{\"key\":\"value\"}")
+            .unwrap();
+        assert_eq!(
+            storage.read_all(b"/api/data").unwrap(),
+            vec![b"name=diana".to_vec(), b"{\"key\":\"value\"}".to_vec()]
+        );
+
+        /* The bodies, that aren't UTF-8, are kept byte for byte */
+        for body in [
+            &b"\x89PNG\r\n\x1a\n\0"[..],
+            b"\xff",
+            b"\xfe\xff",
+            b"\xc3\xa9\xc3",
+        ] {
+            storage.append(b"/api/upload", body).unwrap();
+            let stored: Vec<Vec<u8>> = storage.read_all(b"/api/upload").unwrap();
+            assert_eq!(stored.last().unwrap(), body);
+        }
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn route_file_collision_test() {
+        let storage = FileStorage::new(std::env::temp_dir().to_str().unwrap()).unwrap();
+        let routes: [&[u8]; 9] = [
+            b"/", b"/index", b"/a-b", b"/a_b", b"/a/b", b"/a.b", b"/A/b", b"//a/b", b"/a%2Fb",
+        ];
+        let files: HashSet<PathBuf> = routes
+            .iter()
+            .map(|route| storage.route_file(route))
+            .collect();
+        assert_eq!(files.len(), routes.len());
+        assert_eq!(
+            storage.route_file(b"/a_b/%c3").file_name().unwrap(),
+            "_a%5Fb_%25c3.jsonl"
+        );
+    }
+}
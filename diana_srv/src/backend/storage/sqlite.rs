@@ -0,0 +1,234 @@
+use crate::backend::storage::Storage;
+use rusqlite::{Connection, OptionalExtension, params};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::task;
+
+/*
+ *  Schema migrations, applied in order on startup. The index of the last
+ *  applied migration is kept in PRAGMA user_version, so new migrations must
+ *  only be appended to the end.
+ */
+const MIGRATIONS: &[&str] = &[
+    "CREATE TABLE kv (
+        key TEXT PRIMARY KEY NOT NULL,
+        value BLOB NOT NULL,
+        updated_at INTEGER NOT NULL
+    );",
+    "CREATE TABLE records (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        route BLOB NOT NULL,
+        body BLOB NOT NULL,
+        received_at INTEGER NOT NULL
+    );
+    CREATE INDEX records_route ON records (route);",
+];
+
+#[derive(Debug, Clone)]
+pub struct SqliteStore {
+    /*
+     *  Key-value store backed by a single SQLite database. All the queries
+     *  run on the blocking thread pool, so the async API never stalls
+     *  the connection handlers.
+     *
+     *  Attributes:
+     *      db_path: Path of the database file.
+     *      conn: The connection, shared between clones of the store.
+     */
+    db_path: PathBuf,
+    conn: Arc<Mutex<Connection>>,
+}
+
+impl SqliteStore {
+    pub async fn open(db_path: &Path) -> Result<Self, io::Error> {
+        /*
+         *  Open (or create) the database and bring its schema up to date.
+         *
+         *  Arguments:
+         *      db_path: Path of the database file, :memory: is allowed.
+         *
+         *  Returns:
+         *      The store or an error if the database can't be opened
+         *      or migrated.
+         */
+        let path: PathBuf = db_path.to_path_buf();
+        let conn: Connection = task::spawn_blocking(move || -> Result<Connection, io::Error> {
+            let mut conn: Connection = Connection::open(&path).map_err(io::Error::other)?;
+            migrate(&mut conn)?;
+            Ok(conn)
+        })
+        .await
+        .map_err(io::Error::other)??;
+
+        Ok(SqliteStore {
+            db_path: db_path.to_path_buf(),
+            conn: Arc::new(Mutex::new(conn)),
+        })
+    }
+
+    pub fn db_path(&self) -> &Path {
+        &self.db_path
+    }
+
+    async fn with_conn<T, F>(&self, query: F) -> Result<T, io::Error>
+    where
+        T: Send + 'static,
+        F: FnOnce(&Connection) -> rusqlite::Result<T> + Send + 'static,
+    {
+        /*
+         *  Run the query on the blocking thread pool.
+         */
+        let conn: Arc<Mutex<Connection>> = Arc::clone(&self.conn);
+        task::spawn_blocking(move || query(&lock(&conn)).map_err(io::Error::other))
+            .await
+            .map_err(io::Error::other)?
+    }
+
+    pub async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, io::Error> {
+        /*
+         *  Returns:
+         *      The value stored under the key, None if there is no such key.
+         */
+        let key: String = String::from(key);
+        self.with_conn(move |conn| {
+            conn.query_row("SELECT value FROM kv WHERE key = ?1", params![key], |row| {
+                row.get(0)
+            })
+            .optional()
+        })
+        .await
+    }
+
+    pub async fn put(&self, key: &str, value: &[u8]) -> Result<(), io::Error> {
+        /*
+         *  Insert the value or replace the one, that is already stored
+         *  under the key.
+         */
+        let key: String = String::from(key);
+        let value: Vec<u8> = value.to_vec();
+        self.with_conn(move |conn| {
+            conn.execute(
+                "INSERT INTO kv (key, value, updated_at) VALUES (?1, ?2, ?3)
+                 ON CONFLICT (key) DO UPDATE SET
+                    value = excluded.value, updated_at = excluded.updated_at",
+                params![key, value, unix_now()],
+            )
+            .map(|_| ())
+        })
+        .await
+    }
+
+    pub async fn delete(&self, key: &str) -> Result<bool, io::Error> {
+        /*
+         *  Returns:
+         *      True if the key existed and was removed.
+         */
+        let key: String = String::from(key);
+        self.with_conn(move |conn| {
+            conn.execute("DELETE FROM kv WHERE key = ?1", params![key])
+                .map(|removed| removed > 0)
+        })
+        .await
+    }
+
+    pub async fn list(&self, prefix: &str) -> Result<Vec<(String, Vec<u8>)>, io::Error> {
+        /*
+         *  List the entries, which keys start with the prefix.
+         *
+         *  Returns:
+         *      Pairs of keys and values, ordered by the key.
+         */
+        let prefix: String = String::from(prefix);
+        self.with_conn(move |conn| {
+            let mut stmt = conn.prepare(
+                "SELECT key, value FROM kv WHERE substr(key, 1, length(?1)) = ?1 ORDER BY key",
+            )?;
+            let rows = stmt.query_map(params![prefix], |row| Ok((row.get(0)?, row.get(1)?)))?;
+            rows.collect()
+        })
+        .await
+    }
+}
+
+impl Storage for SqliteStore {
+    fn append(&self, route: &[u8], body: &[u8]) -> Result<(), io::Error> {
+        lock(&self.conn)
+            .execute(
+                "INSERT INTO records (route, body, received_at) VALUES (?1, ?2, ?3)",
+                params![route, body, unix_now()],
+            )
+            .map(|_| ())
+            .map_err(io::Error::other)
+    }
+
+    fn read_all(&self, route: &[u8]) -> Result<Vec<Vec<u8>>, io::Error> {
+        let conn = lock(&self.conn);
+        let mut stmt = conn
+            .prepare("SELECT body FROM records WHERE route = ?1 ORDER BY id")
+            .map_err(io::Error::other)?;
+        let rows = stmt
+            .query_map(params![route], |row| row.get(0))
+            .map_err(io::Error::other)?;
+        rows.collect::<rusqlite::Result<Vec<Vec<u8>>>>()
+            .map_err(io::Error::other)
+    }
+}
+
+fn migrate(conn: &mut Connection) -> Result<(), io::Error> {
+    /*
+     *  Apply the migrations, that weren't applied to the database yet.
+     *  Each migration runs in its own transaction together with the bump
+     *  of the schema version.
+     */
+    let applied: usize = conn
+        .query_row("PRAGMA user_version", [], |row| row.get(0))
+        .map_err(io::Error::other)?;
+    for (idx, migration) in MIGRATIONS.iter().enumerate().skip(applied) {
+        let tx = conn.transaction().map_err(io::Error::other)?;
+        tx.execute_batch(migration).map_err(io::Error::other)?;
+        tx.pragma_update(None, "user_version", idx + 1)
+            .map_err(io::Error::other)?;
+        tx.commit().map_err(io::Error::other)?;
+        println!("[INFO] Applied storage migration {}", idx + 1);
+    }
+    Ok(())
+}
+
+fn lock(conn: &Mutex<Connection>) -> MutexGuard<'_, Connection> {
+    conn.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+fn unix_now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs() as i64)
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn sqlite_store_crud_test() {
+        let store = SqliteStore::open(Path::new(":memory:")).await.unwrap();
+        assert_eq!(store.get("user:1").await.unwrap(), None);
+
+        store.put("user:1", b"diana").await.unwrap();
+        store.put("user:2", b"artemis").await.unwrap();
+        store.put("user:1", b"selene").await.unwrap();
+        assert_eq!(store.get("user:1").await.unwrap(), Some(b"selene".to_vec()));
+        assert_eq!(store.list("user:").await.unwrap().len(), 2);
+
+        assert!(store.delete("user:2").await.unwrap());
+        assert!(!store.delete("user:2").await.unwrap());
+
+        store.append(b"/api/data", b"name=diana").unwrap();
+        assert_eq!(
+            store.read_all(b"/api/data").unwrap(),
+            vec![b"name=diana".to_vec()]
+        );
+    }
+}
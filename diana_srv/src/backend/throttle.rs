@@ -0,0 +1,269 @@
+use crate::backend::limits::matches_prefix;
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::io::AsyncWrite;
+use tokio::time::{Instant, Sleep, sleep};
+
+/* Writes are split into chunks of at most this size, so the limits interleave */
+const MAX_WRITE_CHUNK: usize = 16384;
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct BandwidthRule {
+    /*
+     *  Entry of the [[bandwidth_limits]] table in the config.
+     *
+     *  Attributes:
+     *      prefix: Route prefix, that the limit applies to, e.g. /downloads
+     *      host: Served host, that the limit applies to, any host if missing.
+     *      bytes_per_sec: Rate shared by all the responses on the route.
+     */
+    #[serde(default = "default_prefix")]
+    pub prefix: String,
+    #[serde(default)]
+    pub host: Option<String>,
+    pub bytes_per_sec: u64,
+}
+
+#[derive(Debug)]
+pub struct BandwidthLimit {
+    /*
+     *  Token bucket, one token is one byte. The bucket holds at most one
+     *  second of the rate, so idle limits don't allow long bursts.
+     *
+     *  Attributes:
+     *      bytes_per_sec: Rate of the refill.
+     *      bucket: Tokens left with the instant of the last refill. Tokens
+     *      might go below zero, when the limit is shared by the writers.
+     */
+    bytes_per_sec: u64,
+    bucket: Mutex<(f64, Instant)>,
+}
+
+impl BandwidthLimit {
+    pub fn new(bytes_per_sec: u64) -> Self {
+        BandwidthLimit {
+            bytes_per_sec,
+            bucket: Mutex::new((bytes_per_sec as f64, Instant::now())),
+        }
+    }
+
+    fn available(&self) -> f64 {
+        let mut bucket = self.bucket.lock().unwrap();
+        let now: Instant = Instant::now();
+        let refill: f64 = now.duration_since(bucket.1).as_secs_f64() * self.bytes_per_sec as f64;
+        bucket.0 = (bucket.0 + refill).min(self.bytes_per_sec as f64);
+        bucket.1 = now;
+        bucket.0
+    }
+
+    fn consume(&self, bytes: usize) {
+        self.bucket.lock().unwrap().0 -= bytes as f64;
+    }
+
+    fn wait_for(&self, tokens: f64) -> Duration {
+        /*
+         *  Returns:
+         *      Time, until the bucket refills the missing tokens.
+         */
+        let missing: f64 = (tokens - self.available()).max(1.0);
+        Duration::from_secs_f64(missing / self.bytes_per_sec.max(1) as f64)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct BandwidthShaper {
+    /*
+     *  Limits of the response writes, shared by all the connections.
+     *
+     *  Attributes:
+     *      global: Limit of all the responses together.
+     *      routes: Limits of the routes, each is shared by its responses.
+     *      per_connection: Rate of the limit created for every connection.
+     */
+    global: Option<Arc<BandwidthLimit>>,
+    routes: Vec<(BandwidthRule, Arc<BandwidthLimit>)>,
+    per_connection: Option<u64>,
+}
+
+impl BandwidthShaper {
+    pub fn new(global: Option<u64>, per_connection: Option<u64>, rules: &[BandwidthRule]) -> Self {
+        BandwidthShaper {
+            global: global.map(|rate| Arc::new(BandwidthLimit::new(rate))),
+            routes: rules
+                .iter()
+                .map(|rule| {
+                    (
+                        rule.clone(),
+                        Arc::new(BandwidthLimit::new(rule.bytes_per_sec)),
+                    )
+                })
+                .collect(),
+            per_connection,
+        }
+    }
+
+    pub fn limits_for(
+        &self,
+        host: Option<&[u8]>,
+        resource_path: &[u8],
+    ) -> Vec<Arc<BandwidthLimit>> {
+        /*
+         *  Collect the limits, that apply to the response.
+         *
+         *  Arguments:
+         *      host: Requested host without the port.
+         *      resource_path: Resource path from the request.
+         *
+         *  Returns:
+         *      The limits, empty if the response isn't limited.
+         */
+        let mut limits: Vec<Arc<BandwidthLimit>> = self.global.iter().cloned().collect();
+        for (rule, limit) in &self.routes {
+            let host_matches: bool = match (&rule.host, host) {
+                (None, _) => true,
+                (Some(rule_host), Some(host)) => rule_host.as_bytes().eq_ignore_ascii_case(host),
+                (Some(_), None) => false,
+            };
+            if host_matches && matches_prefix(resource_path, rule.prefix.as_bytes()) {
+                limits.push(Arc::clone(limit));
+            }
+        }
+        if let Some(rate) = self.per_connection {
+            limits.push(Arc::new(BandwidthLimit::new(rate)));
+        }
+        limits
+    }
+}
+
+pub struct Throttled<W> {
+    /*
+     *  Writer, that holds the writes back to fit in all the limits.
+     *
+     *  Attributes:
+     *      inner: The wrapped writer.
+     *      limits: Limits, that every write takes the tokens from.
+     *      delay: Pending wait for the tokens.
+     */
+    inner: W,
+    limits: Vec<Arc<BandwidthLimit>>,
+    delay: Option<Pin<Box<Sleep>>>,
+}
+
+impl<W: AsyncWrite + Unpin> Throttled<W> {
+    pub fn new(inner: W, limits: Vec<Arc<BandwidthLimit>>) -> Self {
+        Throttled {
+            inner,
+            limits,
+            delay: None,
+        }
+    }
+}
+
+impl<W: AsyncWrite + Unpin> AsyncWrite for Throttled<W> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<Result<usize, io::Error>> {
+        if self.limits.is_empty() {
+            return Pin::new(&mut self.inner).poll_write(cx, buf);
+        }
+        loop {
+            if let Some(delay) = self.delay.as_mut() {
+                if delay.as_mut().poll(cx).is_pending() {
+                    return Poll::Pending;
+                }
+                self.delay = None;
+            }
+            let wanted: usize = buf.len().min(MAX_WRITE_CHUNK);
+            let allowed: f64 = self
+                .limits
+                .iter()
+                .map(|limit| limit.available())
+                .fold(f64::INFINITY, f64::min);
+            let allowed: usize = (allowed.max(0.0) as usize).min(wanted);
+            if allowed == 0 {
+                let wait: Duration = self
+                    .limits
+                    .iter()
+                    .map(|limit| limit.wait_for(wanted.min(1024) as f64))
+                    .max()
+                    .unwrap_or_default();
+                self.delay = Some(Box::pin(sleep(wait)));
+                continue;
+            }
+            let written: Poll<Result<usize, io::Error>> =
+                Pin::new(&mut self.inner).poll_write(cx, &buf[..allowed]);
+            if let Poll::Ready(Ok(sz)) = written {
+                for limit in &self.limits {
+                    limit.consume(sz);
+                }
+            }
+            return written;
+        }
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), io::Error>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<(), io::Error>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
+fn default_prefix() -> String {
+    String::from("/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::AsyncWriteExt;
+
+    #[test]
+    fn limits_for_test() {
+        let rules: Vec<BandwidthRule> = vec![
+            BandwidthRule {
+                prefix: String::from("/downloads"),
+                host: None,
+                bytes_per_sec: 1024,
+            },
+            BandwidthRule {
+                prefix: String::from("/"),
+                host: Some(String::from("files.example.com")),
+                bytes_per_sec: 2048,
+            },
+        ];
+        let shaper = BandwidthShaper::new(Some(4096), Some(512), &rules);
+        assert_eq!(shaper.limits_for(None, b"/index.html").len(), 2);
+        assert_eq!(shaper.limits_for(None, b"/downloads/a.iso").len(), 3);
+        assert_eq!(
+            shaper
+                .limits_for(Some(b"FILES.example.com"), b"/downloads/a.iso")
+                .len(),
+            4
+        );
+    }
+
+    #[tokio::test]
+    async fn throttled_write_test() {
+        let limit = Arc::new(BandwidthLimit::new(100_000));
+        let mut written: Vec<u8> = Vec::new();
+        let started: Instant = Instant::now();
+        let mut out = Throttled::new(&mut written, vec![limit]);
+        /* The first second of the rate is the burst, the rest takes 0.3 seconds */
+        out.write_all(&[7; 130_000]).await.unwrap();
+        let elapsed: Duration = started.elapsed();
+        assert!(elapsed >= Duration::from_millis(250), "{elapsed:?}");
+        assert!(elapsed <= Duration::from_millis(1000), "{elapsed:?}");
+        assert_eq!(written.len(), 130_000);
+    }
+}
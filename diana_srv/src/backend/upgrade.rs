@@ -0,0 +1,182 @@
+use crate::backend::limits::matches_prefix;
+use crate::backend::response_headers::strip_identifying_headers;
+use crate::utils::readers::buffers::constants::HEADER_END;
+use crate::utils::readers::buffers::find_in_buffer;
+use diana_http::validation::header_lines;
+use serde::{Deserialize, Serialize};
+use std::io;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, copy_bidirectional};
+use tokio::net::TcpStream;
+
+/* The maximum size of the upstream's response head */
+const MAX_RESPONSE_HEAD: usize = 16384;
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct UpgradeRoute {
+    /*
+     *  Entry of the [[upgrade_routes]] table in the config.
+     *
+     *  Attributes:
+     *      prefix: Route prefix, which upgrade requests are passed through,
+     *      e.g. /ws
+     *      upstream: Address of the backend, e.g. 127.0.0.1:9000
+     */
+    pub prefix: String,
+    pub upstream: String,
+}
+
+pub fn find_upgrade_route<'a>(
+    routes: &'a [UpgradeRoute],
+    resource_path: &[u8],
+) -> Option<&'a UpgradeRoute> {
+    /*
+     *  Returns:
+     *      Route with the longest prefix covering the path.
+     */
+    routes
+        .iter()
+        .filter(|route| matches_prefix(resource_path, route.prefix.as_bytes()))
+        .max_by_key(|route| route.prefix.len())
+}
+
+pub fn is_upgrade_request(buffer: &[u8]) -> bool {
+    /*
+     *  Check if the client asks to switch the protocol, e.g. to WebSocket.
+     *  It needs both the Upgrade header and the upgrade token in Connection.
+     *
+     *  Arguments:
+     *      buffer: Bytes of the request.
+     */
+    let mut has_upgrade: bool = false;
+    let mut connection_upgrade: bool = false;
+    for line in header_lines(buffer) {
+        let Some(colon_idx) = line.iter().position(|byte| *byte == b':') else {
+            continue;
+        };
+        let name: &[u8] = &line[..colon_idx];
+        let value: &[u8] = line[colon_idx + 1..].trim_ascii();
+        if name.eq_ignore_ascii_case(b"upgrade") {
+            has_upgrade |= !value.is_empty();
+        } else if name.eq_ignore_ascii_case(b"connection") {
+            connection_upgrade |= value
+                .split(|byte| *byte == b',')
+                .any(|token| token.trim_ascii().eq_ignore_ascii_case(b"upgrade"));
+        }
+    }
+    has_upgrade && connection_upgrade
+}
+
+pub async fn pass_through(
+    mut client: impl AsyncRead + AsyncWrite + Unpin,
+    request: &[u8],
+    upstream: &str,
+    hide_identity: bool,
+) -> Result<(u64, u64), io::Error> {
+    /*
+     *  Forward the upgrade request to the upstream and relay its answer.
+     *  If the upstream switches the protocol (101), the bytes are copied in
+     *  both directions until either side closes the connection.
+     *
+     *  Arguments:
+     *      client: Connection of the client.
+     *      request: Bytes of the request, read from the client.
+     *      upstream: Address of the backend.
+     *      hide_identity: Strip the identifying headers from its answer.
+     *
+     *  Returns:
+     *      Bytes sent from the client to the upstream and back, after
+     *      the switch.
+     */
+    let mut upstream: TcpStream = TcpStream::connect(upstream).await?;
+    upstream.write_all(request).await?;
+
+    let mut head: Vec<u8> = Vec::with_capacity(1024);
+    while find_in_buffer(&head, HEADER_END) == usize::MAX {
+        if head.len() >= MAX_RESPONSE_HEAD {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Upstream response head is too long",
+            ));
+        }
+        if upstream.read_buf(&mut head).await? == 0 {
+            return Err(io::Error::from(io::ErrorKind::UnexpectedEof));
+        }
+    }
+    /* Frames sent right after the head are relayed with it */
+    if hide_identity {
+        let head_len: usize = find_in_buffer(&head, HEADER_END) + HEADER_END.len();
+        let mut stripped: Vec<u8> = strip_identifying_headers(&head[..head_len]);
+        stripped.extend_from_slice(&head[head_len..]);
+        head = stripped;
+    }
+    client.write_all(&head).await?;
+
+    if !head.starts_with(b"HTTP/1.1 101") {
+        let _ = client.shutdown().await;
+        return Ok((0, 0));
+    }
+    copy_bidirectional(&mut client, &mut upstream).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+
+    #[test]
+    fn is_upgrade_request_test() {
+        assert!(is_upgrade_request(
+            b"GET /ws HTTP/1.1\r\nConnection: keep-alive, Upgrade\r\nUpgrade: websocket\r\n\r\n"
+        ));
+        assert!(!is_upgrade_request(
+            b"GET /ws HTTP/1.1\r\nUpgrade: websocket\r\n\r\n"
+        ));
+        let routes: Vec<UpgradeRoute> = vec![UpgradeRoute {
+            prefix: String::from("/ws"),
+            upstream: String::from("127.0.0.1:9000"),
+        }];
+        assert!(find_upgrade_route(&routes, b"/ws/chat").is_some());
+        assert!(find_upgrade_route(&routes, b"/wss").is_none());
+    }
+
+    #[tokio::test]
+    async fn pass_through_test() {
+        let upstream: TcpListener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let upstream_addr: String = upstream.local_addr().unwrap().to_string();
+        tokio::spawn(async move {
+            let (mut conn, _) = upstream.accept().await.unwrap();
+            let mut request: Vec<u8> = vec![0; 1024];
+            let _ = conn.read(&mut request).await.unwrap();
+            conn.write_all(
+                b"HTTP/1.1 101 Switching Protocols\r\nServer: nginx\r\nUpgrade: websocket\r\n\r\n",
+            )
+            .await
+            .unwrap();
+            /* Echo the frames back */
+            let mut frame: Vec<u8> = vec![0; 4];
+            conn.read_exact(&mut frame).await.unwrap();
+            conn.write_all(&frame).await.unwrap();
+        });
+
+        let front: TcpListener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let front_addr = front.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (client, _) = front.accept().await.unwrap();
+            let request: &[u8] =
+                b"GET /ws HTTP/1.1\r\nConnection: Upgrade\r\nUpgrade: websocket\r\n\r\n";
+            let _ = pass_through(client, request, &upstream_addr, true).await;
+        });
+
+        let mut client: TcpStream = TcpStream::connect(front_addr).await.unwrap();
+        let mut head: Vec<u8> = vec![0; 56];
+        client.read_exact(&mut head).await.unwrap();
+        assert_eq!(
+            head,
+            b"HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\n\r\n".to_vec()
+        );
+        client.write_all(b"ping").await.unwrap();
+        let mut echoed: Vec<u8> = vec![0; 4];
+        client.read_exact(&mut echoed).await.unwrap();
+        assert_eq!(echoed, b"ping".to_vec());
+    }
+}
@@ -0,0 +1,117 @@
+use crate::backend::listing::resolve_under;
+use crate::backend::overrides::OVERRIDE_FILE;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone)]
+pub struct UserDirs {
+    /*
+     *  Homepages of the users, /~alice/notes.html is served from
+     *  <root>/alice/<public_dir>/notes.html
+     *
+     *  Attributes:
+     *      root: Directory of the homes of the users, e.g. /home
+     *      public_dir: Directory in the home, that is served, e.g. public_html
+     */
+    root: PathBuf,
+    public_dir: String,
+}
+
+impl UserDirs {
+    pub fn new(root: &Path, public_dir: &str) -> Self {
+        UserDirs {
+            root: root.to_path_buf(),
+            public_dir: String::from(public_dir.trim_matches('/')),
+        }
+    }
+
+    pub fn resolve(&self, resource_path: &[u8]) -> Option<PathBuf> {
+        /*
+         *  Map the path of the user's site to the file, it may not leave
+         *  the public directory, the same as the sites may not leave
+         *  the resource directory.
+         *
+         *  Arguments:
+         *      resource_path: Resource path from the request, e.g. /~alice/
+         *
+         *  Returns:
+         *      The file, None if the path isn't the user's one or it is
+         *      refused.
+         */
+        let (user, rest) = split_user_path(resource_path)?;
+        let home: PathBuf = resolve_under(&self.root, user)?;
+        let public_dir: PathBuf = resolve_under(&home, &self.public_dir)?;
+        let rest: &str = std::str::from_utf8(rest).ok()?;
+        let mut file: PathBuf = resolve_under(&public_dir, rest)?;
+        /* The override files configure the directory, they aren't sites */
+        if file.file_name().is_some_and(|name| name == OVERRIDE_FILE) {
+            return None;
+        }
+        if file.is_dir() {
+            file.push("index.html");
+        }
+        Some(file)
+    }
+}
+
+pub fn is_user_path(resource_path: &[u8]) -> bool {
+    resource_path.starts_with(b"/~")
+}
+
+fn split_user_path(resource_path: &[u8]) -> Option<(&str, &[u8])> {
+    /*
+     *  Returns:
+     *      The user name and the path in its public directory, e.g.
+     *      alice and /notes.html of /~alice/notes.html
+     */
+    let path: &[u8] = resource_path.strip_prefix(b"/~")?;
+    let user_end: usize = path
+        .iter()
+        .position(|byte| *byte == b'/')
+        .unwrap_or(path.len());
+    let user: &str = std::str::from_utf8(&path[..user_end]).ok()?;
+    let valid: bool = !user.is_empty()
+        && !user.starts_with('.')
+        && user
+            .bytes()
+            .all(|byte| byte.is_ascii_alphanumeric() || b"._-".contains(&byte));
+    valid.then_some((user, &path[user_end..]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn user_dirs_test() {
+        let root: PathBuf =
+            std::env::temp_dir().join(format!("diana_userdir_{}", std::process::id()));
+        let public_dir: PathBuf = root.join("alice/public_html");
+        fs::create_dir_all(public_dir.join("docs")).unwrap();
+        let user_dirs: UserDirs = UserDirs::new(&root, "public_html/");
+
+        assert!(is_user_path(b"/~alice/"));
+        assert!(!is_user_path(b"/alice/"));
+        assert_eq!(
+            user_dirs.resolve(b"/~alice/notes.html"),
+            Some(public_dir.join("notes.html"))
+        );
+        assert_eq!(
+            user_dirs.resolve(b"/~alice/docs"),
+            Some(public_dir.join("docs/index.html"))
+        );
+        assert_eq!(
+            user_dirs.resolve(b"/~alice"),
+            Some(public_dir.join("index.html"))
+        );
+
+        /* Nothing outside of the public directory is served */
+        assert_eq!(user_dirs.resolve(b"/~alice/../.ssh/id_ed25519"), None);
+        assert_eq!(user_dirs.resolve(b"/~../etc/passwd"), None);
+        assert_eq!(user_dirs.resolve(b"/~.alice/"), None);
+        assert_eq!(user_dirs.resolve(b"/~/index.html"), None);
+        assert_eq!(user_dirs.resolve(b"/~alice/docs/.diana"), None);
+        assert_eq!(user_dirs.resolve(b"/docs/index.html"), None);
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,297 @@
+use crate::backend::client::HttpClient;
+use crate::backend::metrics::Metrics;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::sync::Arc;
+use std::sync::atomic::Ordering;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+use tokio::task::JoinSet;
+
+/* Backoff of the first retry, it doubles up to the cap */
+const FIRST_RETRY_DELAY: Duration = Duration::from_secs(1);
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(60);
+const DELIVERY_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct WebhookConfig {
+    /*
+     *  The [webhooks] table of the config.
+     *
+     *  Attributes:
+     *      urls: Endpoints, that every event is POSTed to as JSON. Only
+     *      http:// is supported.
+     *      max_attempts: Deliveries of the event to the endpoint, before it
+     *      is dropped. The retries back off from 1 to 60 seconds.
+     *      error_rate_threshold: Share of the requests answered with 5xx,
+     *      e.g. 0.05, that triggers the error_rate event. Disabled if missing.
+     *      error_rate_window_secs: Window, that the error rate is measured in.
+     *      error_rate_min_requests: Requests needed in the window, so a few
+     *      failures of the idle server don't trigger the event.
+     */
+    pub urls: Vec<String>,
+    pub max_attempts: u32,
+    pub error_rate_threshold: Option<f64>,
+    pub error_rate_window_secs: u64,
+    pub error_rate_min_requests: u64,
+}
+
+impl Default for WebhookConfig {
+    fn default() -> Self {
+        WebhookConfig {
+            urls: Vec::new(),
+            max_attempts: 5,
+            error_rate_threshold: None,
+            error_rate_window_secs: 60,
+            error_rate_min_requests: 20,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum WebhookEvent {
+    /*
+     *  Events of the server, that the endpoints are notified about.
+     */
+    Started {
+        addrs: Vec<String>,
+    },
+    Shutdown,
+    ErrorRate {
+        rate: f64,
+        requests: u64,
+        window_secs: u64,
+    },
+    CertRenewed {
+        domains: Vec<String>,
+    },
+}
+
+impl WebhookEvent {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Started { .. } => "server_started",
+            Self::Shutdown => "server_shutdown",
+            Self::ErrorRate { .. } => "error_rate",
+            Self::CertRenewed { .. } => "cert_renewed",
+        }
+    }
+
+    pub fn to_json(&self) -> Value {
+        /*
+         *  Returns:
+         *      Body of the POST, e.g.
+         *      {"event": "server_started", "time": 1700000000, "addrs": [...]}
+         */
+        let time: u64 = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_secs())
+            .unwrap_or_default();
+        let mut body: Value = json!({ "event": self.name(), "time": time });
+        match self {
+            Self::Started { addrs } => body["addrs"] = json!(addrs),
+            Self::Shutdown => {}
+            Self::ErrorRate {
+                rate,
+                requests,
+                window_secs,
+            } => {
+                body["rate"] = json!(rate);
+                body["requests"] = json!(requests);
+                body["window_secs"] = json!(window_secs);
+            }
+            Self::CertRenewed { domains } => body["domains"] = json!(domains),
+        }
+        body
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Webhooks {
+    /*
+     *  Sender of the events to the background task, that delivers them.
+     *  Sending never waits for the endpoints.
+     *
+     *  Attributes:
+     *      config: The [webhooks] table.
+     *      client: Client of the endpoints.
+     *      events: Queue of the background task.
+     */
+    config: Arc<WebhookConfig>,
+    client: Arc<HttpClient>,
+    events: UnboundedSender<WebhookEvent>,
+}
+
+impl Webhooks {
+    pub fn spawn(config: &WebhookConfig) -> Self {
+        /*
+         *  Start the delivery task, it must be called within the runtime.
+         */
+        let (events, queue): (
+            UnboundedSender<WebhookEvent>,
+            UnboundedReceiver<WebhookEvent>,
+        ) = mpsc::unbounded_channel();
+        let webhooks: Webhooks = Webhooks {
+            config: Arc::new(config.clone()),
+            client: Arc::new(HttpClient::new(1, DELIVERY_TIMEOUT)),
+            events,
+        };
+        tokio::spawn(webhooks.clone().deliver_queued(queue));
+        webhooks
+    }
+
+    pub fn notify(&self, event: WebhookEvent) {
+        if !self.config.urls.is_empty() {
+            let _ = self.events.send(event);
+        }
+    }
+
+    pub async fn notify_now(&self, event: WebhookEvent, wait: Duration) {
+        /*
+         *  Deliver the event right away, e.g. the shutdown, after which
+         *  the background task doesn't run anymore. Every endpoint gets
+         *  a single attempt.
+         *
+         *  Arguments:
+         *      wait: The longest time to wait for the endpoints.
+         */
+        let body: Vec<u8> = event.to_json().to_string().into_bytes();
+        let mut deliveries: JoinSet<()> = JoinSet::new();
+        for url in &self.config.urls {
+            let webhooks: Webhooks = self.clone();
+            let (url, body): (String, Vec<u8>) = (url.clone(), body.clone());
+            deliveries.spawn(async move {
+                let _ = webhooks.post(&url, &body).await;
+            });
+        }
+        let _ = tokio::time::timeout(wait, deliveries.join_all()).await;
+    }
+
+    pub fn watch_error_rate(&self, metrics: Arc<Metrics>) {
+        /*
+         *  Sample the counters once per window, the event is sent when
+         *  the rate crosses the threshold, not again until it drops below.
+         */
+        let Some(threshold) = self.config.error_rate_threshold else {
+            return;
+        };
+        let webhooks: Webhooks = self.clone();
+        let window_secs: u64 = self.config.error_rate_window_secs.max(1);
+        let min_requests: u64 = self.config.error_rate_min_requests;
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(Duration::from_secs(window_secs));
+            let mut last: (u64, u64) = (0, 0);
+            let mut exceeded: bool = false;
+            loop {
+                ticker.tick().await;
+                let now: (u64, u64) = (
+                    metrics.requests.load(Ordering::Relaxed),
+                    metrics.server_errors.load(Ordering::Relaxed),
+                );
+                let (requests, errors): (u64, u64) = (now.0 - last.0, now.1 - last.1);
+                last = now;
+                let rate: f64 = errors as f64 / requests.max(1) as f64;
+                let above: bool = requests >= min_requests && rate >= threshold;
+                if above && !exceeded {
+                    println!("[WARNING] Error rate {rate:.3} crossed the threshold.");
+                    webhooks.notify(WebhookEvent::ErrorRate {
+                        rate,
+                        requests,
+                        window_secs,
+                    });
+                }
+                exceeded = above;
+            }
+        });
+    }
+
+    async fn deliver_queued(self, mut queue: UnboundedReceiver<WebhookEvent>) {
+        /*
+         *  Deliver the queued events, every endpoint retries on its own,
+         *  so the slow one doesn't hold the others back.
+         */
+        while let Some(event) = queue.recv().await {
+            let body: Vec<u8> = event.to_json().to_string().into_bytes();
+            for url in &self.config.urls {
+                let webhooks: Webhooks = self.clone();
+                let (url, body): (String, Vec<u8>) = (url.clone(), body.clone());
+                let name: &'static str = event.name();
+                tokio::spawn(async move {
+                    let mut delay: Duration = FIRST_RETRY_DELAY;
+                    for attempt in 1..=webhooks.config.max_attempts.max(1) {
+                        match webhooks.post(&url, &body).await {
+                            Ok(()) => return,
+                            Err(e) if attempt < webhooks.config.max_attempts => {
+                                println!(
+                                    "[WARNING] Webhook {url} failed ({e}), retrying in {delay:?}."
+                                );
+                                tokio::time::sleep(delay).await;
+                                delay = (delay * 2).min(MAX_RETRY_DELAY);
+                            }
+                            Err(e) => println!("[ERROR] Webhook {url} dropped {name}: {e}"),
+                        }
+                    }
+                });
+            }
+        }
+    }
+
+    async fn post(&self, url: &str, body: &[u8]) -> Result<(), String> {
+        let headers: [(String, String); 1] = [(
+            String::from("Content-Type"),
+            String::from("application/json"),
+        )];
+        match self.client.request("POST", url, &headers, body).await {
+            Ok(response) if (200..300).contains(&response.status) => Ok(()),
+            Ok(response) => Err(format!("status {}", response.status)),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    #[tokio::test]
+    async fn webhook_retry_test() {
+        /* The endpoint fails once, the retry must deliver the same event */
+        let listener: TcpListener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url: String = format!("http://{}/hook", listener.local_addr().unwrap());
+        let (received, mut bodies) = mpsc::unbounded_channel::<String>();
+        tokio::spawn(async move {
+            for status in ["500 Internal Server Error", "204 No Content"] {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let mut request: Vec<u8> = Vec::new();
+                let mut chunk: [u8; 1024] = [0; 1024];
+                while !String::from_utf8_lossy(&request).contains("}") {
+                    let sz: usize = stream.read(&mut chunk).await.unwrap();
+                    request.extend_from_slice(&chunk[..sz]);
+                }
+                let request: String = String::from_utf8(request).unwrap();
+                received
+                    .send(request.split("\r\n\r\n").nth(1).unwrap().to_string())
+                    .unwrap();
+                let response: String =
+                    format!("HTTP/1.1 {status}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n");
+                stream.write_all(response.as_bytes()).await.unwrap();
+            }
+        });
+
+        let webhooks: Webhooks = Webhooks::spawn(&WebhookConfig {
+            urls: vec![url],
+            ..WebhookConfig::default()
+        });
+        webhooks.notify(WebhookEvent::CertRenewed {
+            domains: vec![String::from("example.com")],
+        });
+        let first: Value = serde_json::from_str(&bodies.recv().await.unwrap()).unwrap();
+        let retried: Value = serde_json::from_str(&bodies.recv().await.unwrap()).unwrap();
+        assert_eq!(first["event"], "cert_renewed");
+        assert_eq!(first, retried);
+        assert_eq!(retried["domains"][0], "example.com");
+    }
+}
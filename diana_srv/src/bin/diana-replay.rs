@@ -0,0 +1,68 @@
+use diana_srv::backend::record::{Direction, Record, parse_records, replay_request, status_line};
+use std::env;
+use std::fs;
+use std::process::ExitCode;
+use std::time::Duration;
+
+const USAGE: &str = "Usage: diana-replay <recording.rec> <host:port>";
+
+fn main() -> ExitCode {
+    /*
+     *  Send the recorded requests back to the server, one connection per
+     *  request in the recorded order. The status of every answer is compared
+     *  with the recorded response, if the responses were recorded.
+     */
+    let args: Vec<String> = env::args().collect();
+    let [_, recording_path, target] = &args[..] else {
+        println!("{USAGE}");
+        return ExitCode::FAILURE;
+    };
+    let records: Vec<Record> = match fs::read(recording_path).and_then(|raw| parse_records(&raw)) {
+        Ok(records) => records,
+        Err(e) => {
+            println!("[ERROR] Failed to read {recording_path}: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let mut replayed: usize = 0;
+    let mut differing: usize = 0;
+    for (idx, record) in records.iter().enumerate() {
+        if record.direction != Direction::Request {
+            continue;
+        }
+        /* The recorded answer follows its request, other clients might be in between */
+        let recorded: Option<&Record> = records[idx + 1..]
+            .iter()
+            .take_while(|next| next.direction == Direction::Response || next.peer != record.peer)
+            .find(|next| next.direction == Direction::Response && next.peer == record.peer);
+        let response: Vec<u8> = match replay_request(target, &record.bytes, Duration::from_secs(5))
+        {
+            Ok(response) => response,
+            Err(e) => {
+                println!("[ERROR] Request #{idx} from {}: {e}", record.peer);
+                differing += 1;
+                continue;
+            }
+        };
+        replayed += 1;
+        let status: String = String::from_utf8_lossy(status_line(&response)).into_owned();
+        match recorded.map(|recorded| status_line(&recorded.bytes)) {
+            Some(expected) if expected != status_line(&response) => {
+                differing += 1;
+                println!(
+                    "[WARNING] Request #{idx} from {}: {status}, recorded {}",
+                    record.peer,
+                    String::from_utf8_lossy(expected)
+                );
+            }
+            _ => println!("[INFO] Request #{idx} from {}: {status}", record.peer),
+        }
+    }
+    println!("[INFO] Replayed {replayed} requests, {differing} differ or failed.");
+    if differing == 0 {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
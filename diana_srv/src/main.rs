@@ -1,15 +1,174 @@
-use diana_srv::backend::server::Server;
+use diana_srv::backend::daemon::{PidFile, check_pid_file, daemonize};
+use diana_srv::backend::precompress::{PrecompressReport, precompress};
+use diana_srv::backend::server::check::{print_config, run_check};
+use diana_srv::backend::server::{Server, ServerConfig};
+use diana_srv::utils::configs::cli::{CliArgs, USAGE, parse_args};
 use diana_srv::utils::configs::server::config_toml;
 use std::env;
 use std::path::Path;
-use std::sync::Arc;
+use std::process::ExitCode;
 
-fn main() {
+fn main() -> ExitCode {
     let args: Vec<String> = env::args().collect();
-    let cfg_path: &String = &args[1];
-    let cfg: &Path = config_toml(cfg_path);
-    let mut srv = Server::new(cfg).unwrap();
+    let cli_args: CliArgs = match parse_args(&args) {
+        Ok(cli_args) => cli_args,
+        Err(e) => {
+            println!("[ERROR] {e}");
+            println!("{USAGE}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    if let Some(workdir) = &cli_args.workdir
+        && let Err(e) = env::set_current_dir(workdir)
+    {
+        println!("[ERROR] {workdir}: {e}");
+        return ExitCode::FAILURE;
+    }
+    if let Some(dir) = &cli_args.precompress {
+        return match precompress(Path::new(dir)) {
+            Ok(PrecompressReport {
+                written,
+                up_to_date,
+                not_smaller,
+                saved_bytes,
+            }) => {
+                println!(
+                    "[INFO] Wrote {written} encoded files saving {saved_bytes} bytes, \
+                     {up_to_date} were up to date and {not_smaller} didn't shrink."
+                );
+                ExitCode::SUCCESS
+            }
+            Err(e) => {
+                println!("[ERROR] {dir}: {e}");
+                ExitCode::FAILURE
+            }
+        };
+    }
+    if cli_args.install_service || cli_args.uninstall_service {
+        return match manage_service(&cli_args) {
+            Ok(()) => ExitCode::SUCCESS,
+            Err(e) => {
+                println!("[ERROR] {e}");
+                ExitCode::FAILURE
+            }
+        };
+    }
+
+    /* Dry run, usable in CI and deployment pipelines */
+    if cli_args.check_config || cli_args.print_config {
+        let path: &Path = Path::new(&cli_args.config_path);
+        let passed: bool = if cli_args.check_config {
+            run_check(path, &cli_args.overrides)
+        } else {
+            print_config(path, &cli_args.overrides)
+        };
+        return if passed {
+            ExitCode::SUCCESS
+        } else {
+            ExitCode::FAILURE
+        };
+    }
+
+    let cfg: &Path = config_toml(&cli_args.config_path);
+    let server_cfg: ServerConfig = match Server::load_layered(cfg, &cli_args.overrides) {
+        Ok((server_cfg, _)) => server_cfg,
+        Err(e) => {
+            println!("[ERROR] {}: {e}", cfg.display());
+            return ExitCode::FAILURE;
+        }
+    };
+
+    /* The running server is refused before anything is detached or bound */
+    if cli_args.daemon {
+        let daemon_cfg: &ServerConfig = &server_cfg;
+        if let Some(pid_path) = daemon_cfg.pid_file()
+            && let Err(e) = check_pid_file(pid_path)
+        {
+            println!("[ERROR] {e}");
+            return ExitCode::FAILURE;
+        }
+        return match daemonize(&args, daemon_cfg.daemon_log()) {
+            Ok(pid) => {
+                println!(
+                    "[INFO] Running in the background as {pid}, logging to {}.",
+                    daemon_cfg.daemon_log().display()
+                );
+                ExitCode::SUCCESS
+            }
+            Err(e) => {
+                println!("[ERROR] Failed to detach: {e}");
+                ExitCode::FAILURE
+            }
+        };
+    }
+
+    /* The service manager runs the server and tells when it stops */
+    if cli_args.service {
+        #[cfg(windows)]
+        return match diana_srv::backend::service::windows::run_service(server_cfg) {
+            Ok(()) => ExitCode::SUCCESS,
+            Err(e) => {
+                println!("[ERROR] Failed to run as the service: {e}");
+                ExitCode::FAILURE
+            }
+        };
+        #[cfg(not(windows))]
+        {
+            println!("[ERROR] --service is only available on Windows.");
+            return ExitCode::FAILURE;
+        }
+    }
+
+    let srv: Server = Server::with_config(server_cfg).unwrap();
+    let _pid_file: Option<PidFile> = match srv.pid_file().map(PidFile::create) {
+        Some(Ok(pid_file)) => Some(pid_file),
+        Some(Err(e)) => {
+            println!("[ERROR] {e}");
+            return ExitCode::FAILURE;
+        }
+        None => None,
+    };
     srv.run();
+    ExitCode::SUCCESS
+}
+
+#[cfg(windows)]
+fn manage_service(cli_args: &CliArgs) -> std::io::Result<()> {
+    /*
+     *  Register or remove the Windows service. The service gets the absolute
+     *  paths and the overrides, it is started from the system directory.
+     */
+    use diana_srv::backend::service::{SERVICE_NAME, windows};
+    use std::ffi::OsString;
+
+    if cli_args.uninstall_service {
+        windows::uninstall()?;
+        println!("[INFO] Removed the service {SERVICE_NAME}.");
+        return Ok(());
+    }
+    let workdir: std::path::PathBuf = env::current_dir()?;
+    let mut launch_arguments: Vec<OsString> = vec![
+        OsString::from("--service"),
+        OsString::from("--workdir"),
+        workdir.clone().into_os_string(),
+    ];
+    for pair in &cli_args.overrides {
+        launch_arguments.push(OsString::from("--set"));
+        launch_arguments.push(OsString::from(pair));
+    }
+    launch_arguments.push(workdir.join(&cli_args.config_path).into_os_string());
+    windows::install(launch_arguments)?;
+    println!("[INFO] Installed the service {SERVICE_NAME}.");
+    Ok(())
+}
+
+#[cfg(not(windows))]
+fn manage_service(_cli_args: &CliArgs) -> std::io::Result<()> {
+    Err(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "Windows services are only available on Windows, use a systemd unit with Type=notify",
+    ))
 }
 /*
 * TODO:
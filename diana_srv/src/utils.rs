@@ -1,3 +1,4 @@
 pub mod configs;
 pub mod formatters;
+pub mod patterns;
 pub mod readers;
@@ -6,3 +6,598 @@ pub mod server {
         Path::new(path)
     }
 }
+
+pub mod cli {
+    pub const USAGE: &str = "Usage: diana_srv [--check-config] [--print-config] [--daemon] [--dev] \
+         [--set key=value]... [--workdir dir] [--service | --install-service] <config.toml>\n       \
+         diana_srv --uninstall-service\n       \
+         diana_srv precompress <dir>";
+
+    #[derive(Debug, PartialEq, Default)]
+    pub struct CliArgs {
+        /*
+         *  Arguments of the server binary.
+         *
+         *  Attributes:
+         *      config_path: Path of the TOML config.
+         *      check_config: Only validate the config and exit.
+         *      print_config: Only print the effective config with the source
+         *      of every value and exit.
+         *      daemon: Detach from the terminal and run in the background.
+         *      dev: Development mode, no caching and the live reload.
+         *      overrides: The key=value pairs of --set, they override
+         *      the file and the environment. --dev sets dev_mode=true.
+         *      workdir: Directory to change to before anything is read,
+         *      the relative paths of the config are resolved against it.
+         *      service: Run under the Windows service manager.
+         *      install_service: Register the Windows service, that runs
+         *      the server with these arguments, and exit.
+         *      uninstall_service: Remove the Windows service and exit,
+         *      the config isn't needed.
+         *      precompress: Directory, which sites are encoded ahead of time
+         *      with the precompress subcommand, the config isn't needed.
+         */
+        pub config_path: String,
+        pub check_config: bool,
+        pub print_config: bool,
+        pub daemon: bool,
+        pub dev: bool,
+        pub overrides: Vec<String>,
+        pub workdir: Option<String>,
+        pub service: bool,
+        pub install_service: bool,
+        pub uninstall_service: bool,
+        pub precompress: Option<String>,
+    }
+
+    pub fn parse_args(args: &[String]) -> Result<CliArgs, String> {
+        /*
+         *  Parse the command line, the first argument is the binary itself.
+         *
+         *  Returns:
+         *      Parsed arguments, or the message explaining what is wrong.
+         */
+        let mut cli_args: CliArgs = CliArgs::default();
+        let mut args = args.iter().skip(1);
+        if args.clone().next().is_some_and(|arg| arg == "precompress") {
+            return match (args.nth(1), args.next()) {
+                (Some(dir), None) if !dir.starts_with("--") => Ok(CliArgs {
+                    precompress: Some(dir.clone()),
+                    ..CliArgs::default()
+                }),
+                _ => Err(String::from("precompress expects a directory")),
+            };
+        }
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--check-config" => cli_args.check_config = true,
+                "--print-config" => cli_args.print_config = true,
+                "--daemon" => cli_args.daemon = true,
+                "--dev" => {
+                    cli_args.dev = true;
+                    cli_args.overrides.push(String::from("dev_mode=true"));
+                }
+                "--set" => match args.next() {
+                    Some(pair) if pair.contains('=') => cli_args.overrides.push(pair.clone()),
+                    _ => return Err(String::from("--set expects key=value")),
+                },
+                "--workdir" => match args.next() {
+                    Some(dir) => cli_args.workdir = Some(dir.clone()),
+                    None => return Err(String::from("--workdir expects a directory")),
+                },
+                "--service" => cli_args.service = true,
+                "--install-service" => cli_args.install_service = true,
+                "--uninstall-service" => cli_args.uninstall_service = true,
+                flag if flag.starts_with("--") => return Err(format!("Unknown flag: {flag}")),
+                path if cli_args.config_path.is_empty() => {
+                    cli_args.config_path = String::from(path)
+                }
+                extra => return Err(format!("Unexpected argument: {extra}")),
+            }
+        }
+        if cli_args.config_path.is_empty() && !cli_args.uninstall_service {
+            return Err(String::from("Missing the config path"));
+        }
+        Ok(cli_args)
+    }
+}
+
+pub mod layers {
+    use crate::utils::readers::files::read_to_str;
+    use std::collections::{BTreeMap, BTreeSet};
+    use std::fmt;
+    use std::io;
+    use std::path::Path;
+    use toml::{Table, Value};
+
+    /* Prefix of the environment variables, that override the config */
+    pub const ENV_PREFIX: &str = "DIANA_";
+    /* Separator of the nested keys in the variable names */
+    const ENV_NESTING: &str = "__";
+    /* Start of the variable in the string values, e.g. ${UPSTREAM_HOST} */
+    const INTERPOLATION_START: &str = "${";
+    /* Printed instead of the values, that the variables were filled into */
+    const REDACTED: &str = "\"<redacted>\"";
+
+    #[derive(Debug, Clone, PartialEq)]
+    pub enum Source {
+        /*
+         *  Layer, that the effective value comes from. The later layers
+         *  win: the defaults, the file, the environment, the command line.
+         */
+        Default,
+        File(String),
+        Env(String),
+        Cli(String),
+    }
+
+    impl fmt::Display for Source {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self {
+                Self::Default => write!(f, "default"),
+                Self::File(path) => write!(f, "file {path}"),
+                Self::Env(name) => write!(f, "env {name}"),
+                Self::Cli(arg) => write!(f, "cli {arg}"),
+            }
+        }
+    }
+
+    #[derive(Debug, Default)]
+    pub struct LayeredConfig {
+        /*
+         *  The config merged from its layers, the built-in defaults are
+         *  filled in when it is deserialized.
+         *
+         *  Attributes:
+         *      table: The merged values of the file, the environment and
+         *      the command line.
+         *      sources: Layer of every value set, under its dotted key,
+         *      e.g. logging.access_log
+         *      filled: Variables filled into the values of the file, under
+         *      the same keys. They often carry the secrets, so the values
+         *      are never described.
+         */
+        table: Table,
+        sources: BTreeMap<String, Source>,
+        filled: BTreeMap<String, BTreeSet<String>>,
+    }
+
+    impl LayeredConfig {
+        pub fn load(
+            path: &Path,
+            env: impl IntoIterator<Item = (String, String)>,
+            overrides: &[String],
+        ) -> Result<Self, io::Error> {
+            /*
+             *  Merge the layers of the config.
+             *
+             *  Arguments:
+             *      path: The TOML config.
+             *      env: Environment of the process. The DIANA_ variables
+             *      override the values, e.g. DIANA_PORT=9090 or
+             *      DIANA_LOGGING__ACCESS_LOG=/var/log/diana/access.log
+             *      Any variable fills the ${NAME} in the strings of the file.
+             *      overrides: The key=value pairs of the command line, e.g.
+             *      port=9090 or logging.keep=14
+             *
+             *  Returns:
+             *      The merged config, or the error if a layer is malformed.
+             */
+            let invalid = |e: String| io::Error::new(io::ErrorKind::InvalidData, e);
+            let env: BTreeMap<String, String> = env.into_iter().collect();
+            let mut table: Table =
+                toml::from_str(&read_to_str(path)?).map_err(|e| invalid(e.to_string()))?;
+            let mut layered: LayeredConfig = LayeredConfig::default();
+            interpolate_table(&mut table, "", None, &env, &mut layered.filled).map_err(invalid)?;
+            let file: Source = Source::File(path.display().to_string());
+            for key in leaf_keys(&table, "") {
+                layered.sources.insert(key, file.clone());
+            }
+            layered.table = table;
+
+            for (name, raw) in env
+                .into_iter()
+                .filter(|(name, _)| name.starts_with(ENV_PREFIX))
+            {
+                let key: String = name[ENV_PREFIX.len()..]
+                    .to_ascii_lowercase()
+                    .replace(ENV_NESTING, ".");
+                layered.set(&key, &raw, Source::Env(name))?;
+            }
+            for arg in overrides {
+                let Some((key, raw)) = arg.split_once('=') else {
+                    return Err(invalid(format!("Expected key=value, got {arg}")));
+                };
+                layered.set(key.trim(), raw, Source::Cli(arg.clone()))?;
+            }
+            Ok(layered)
+        }
+
+        pub fn set(&mut self, key: &str, raw: &str, source: Source) -> Result<(), io::Error> {
+            /*
+             *  Set the value under the dotted key. The value is read as TOML,
+             *  e.g. 9090, true or ["a", "b"], anything else is the string.
+             *  The keys, that are strings already, stay strings.
+             */
+            let parts: Vec<&str> = key.split('.').collect();
+            if parts.iter().any(|part| part.is_empty()) {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("Invalid config key {key}"),
+                ));
+            }
+            let mut table: &mut Table = &mut self.table;
+            for part in &parts[..parts.len() - 1] {
+                let nested: &mut Value = table
+                    .entry(String::from(*part))
+                    .or_insert_with(|| Value::Table(Table::new()));
+                let Value::Table(nested) = nested else {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        format!("Config key {key} is not a table"),
+                    ));
+                };
+                table = nested;
+            }
+            let last: &str = parts[parts.len() - 1];
+            let keeps_string: bool = matches!(table.get(last), Some(Value::String(_)));
+            let value: Value = match parse_value(raw) {
+                Some(value) if !keeps_string => value,
+                _ => Value::String(String::from(raw)),
+            };
+            table.insert(String::from(last), value);
+            let replaced = |set: &String| set == key || set.starts_with(&format!("{key}."));
+            self.sources.retain(|set, _| !replaced(set));
+            self.filled.retain(|set, _| !replaced(set));
+            self.sources.insert(String::from(key), source);
+            Ok(())
+        }
+
+        pub fn table(&self) -> &Table {
+            &self.table
+        }
+
+        pub fn source_of(&self, key: &str) -> &Source {
+            /*
+             *  Returns:
+             *      Layer of the value, the defaults if no layer set it.
+             */
+            self.sources.get(key).unwrap_or(&Source::Default)
+        }
+
+        pub fn describe(&self, effective: &Table) -> Vec<String> {
+            /*
+             *  Describe the effective config, one line per value with
+             *  the layer, that it comes from, e.g.
+             *  port = 9090  # env DIANA_PORT
+             *  The values filled from the variables are redacted, e.g.
+             *  webhook_secret = "<redacted>"  # file cfg.toml, ${HOOK_SECRET}
+             *
+             *  Arguments:
+             *      effective: The deserialized config with the defaults.
+             */
+            let mut lines: Vec<String> = Vec::new();
+            describe_table(effective, "", &mut |key, value| {
+                let source: &Source = self.source_of(key);
+                match self.filled.get(key) {
+                    Some(names) => {
+                        let names: Vec<String> =
+                            names.iter().map(|name| format!("${{{name}}}")).collect();
+                        lines.push(format!(
+                            "{key} = {REDACTED}  # {source}, {}",
+                            names.join(", ")
+                        ));
+                    }
+                    None => lines.push(format!("{key} = {value}  # {source}")),
+                }
+            });
+            lines
+        }
+    }
+
+    fn interpolate_table(
+        table: &mut Table,
+        prefix: &str,
+        leaf: Option<&str>,
+        env: &BTreeMap<String, String>,
+        filled: &mut BTreeMap<String, BTreeSet<String>>,
+    ) -> Result<(), String> {
+        /*
+         *  Fill the variables into every string of the table, the nested
+         *  tables and the arrays included, so the secrets and the addresses
+         *  stay out of the committed file.
+         *
+         *  Arguments:
+         *      leaf: Key of the array, that the table is in. The array is
+         *      described as one value, so the variables are noted under it.
+         *      filled: The variables used, under the keys of the values.
+         *
+         *  Returns:
+         *      The error naming the key and the variable, that isn't set.
+         */
+        for (name, value) in table.iter_mut() {
+            let key: String = if prefix.is_empty() {
+                name.clone()
+            } else {
+                format!("{prefix}.{name}")
+            };
+            interpolate_value(value, &key, leaf.unwrap_or(&key), env, filled)?;
+        }
+        Ok(())
+    }
+
+    fn interpolate_value(
+        value: &mut Value,
+        key: &str,
+        leaf: &str,
+        env: &BTreeMap<String, String>,
+        filled: &mut BTreeMap<String, BTreeSet<String>>,
+    ) -> Result<(), String> {
+        match value {
+            Value::String(text) => {
+                let mut used: BTreeSet<String> = BTreeSet::new();
+                *text = interpolate(text, env, &mut used)
+                    .map_err(|e| format!("Config key {key}: {e}"))?;
+                if !used.is_empty() {
+                    filled.entry(String::from(leaf)).or_default().extend(used);
+                }
+            }
+            Value::Array(values) => {
+                for (idx, value) in values.iter_mut().enumerate() {
+                    interpolate_value(value, &format!("{key}[{idx}]"), leaf, env, filled)?;
+                }
+            }
+            Value::Table(nested) => {
+                let in_array: Option<&str> = (leaf != key).then_some(leaf);
+                interpolate_table(nested, key, in_array, env, filled)?;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn interpolate(
+        text: &str,
+        env: &BTreeMap<String, String>,
+        used: &mut BTreeSet<String>,
+    ) -> Result<String, String> {
+        /*
+         *  Replace the ${NAME} with the value of the variable, or with
+         *  the default after :- if it isn't set, e.g. ${LOG_DIR:-logs}
+         *  The $${ is the literal ${.
+         *
+         *  Arguments:
+         *      used: The variables, that were set and filled in.
+         *
+         *  Returns:
+         *      The filled text, or why it can't be filled.
+         */
+        let mut filled: String = String::with_capacity(text.len());
+        let mut rest: &str = text;
+        while let Some(start) = rest.find(INTERPOLATION_START) {
+            if rest[..start].ends_with('$') {
+                filled.push_str(&rest[..start]);
+                filled.push('{');
+                rest = &rest[start + INTERPOLATION_START.len()..];
+                continue;
+            }
+            filled.push_str(&rest[..start]);
+            let after: &str = &rest[start + INTERPOLATION_START.len()..];
+            let Some(end) = after.find('}') else {
+                return Err(format!("${{ without the closing brace in {text:?}"));
+            };
+            let (name, default): (&str, Option<&str>) = match after[..end].split_once(":-") {
+                Some((name, default)) => (name, Some(default)),
+                None => (&after[..end], None),
+            };
+            if name.is_empty()
+                || !name
+                    .bytes()
+                    .all(|byte| byte.is_ascii_alphanumeric() || byte == b'_')
+            {
+                return Err(format!("Invalid variable name {name:?}"));
+            }
+            match (env.get(name), default) {
+                (Some(value), _) => {
+                    filled.push_str(value);
+                    used.insert(String::from(name));
+                }
+                (None, Some(default)) => filled.push_str(default),
+                (None, None) => return Err(format!("Environment variable {name} is not set")),
+            }
+            rest = &after[end + 1..];
+        }
+        filled.push_str(rest);
+        Ok(filled)
+    }
+
+    fn parse_value(raw: &str) -> Option<Value> {
+        let mut parsed: Table = toml::from_str(&format!("value = {raw}")).ok()?;
+        parsed.remove("value")
+    }
+
+    fn leaf_keys(table: &Table, prefix: &str) -> Vec<String> {
+        let mut keys: Vec<String> = Vec::new();
+        describe_table(table, prefix, &mut |key, _| keys.push(String::from(key)));
+        keys
+    }
+
+    fn describe_table(table: &Table, prefix: &str, visit: &mut dyn FnMut(&str, &Value)) {
+        /*
+         *  Visit the values of the table under their dotted keys, the nested
+         *  tables are descended into, the arrays are the values.
+         */
+        for (name, value) in table {
+            let key: String = if prefix.is_empty() {
+                name.clone()
+            } else {
+                format!("{prefix}.{name}")
+            };
+            match value {
+                Value::Table(nested) => describe_table(nested, &key, visit),
+                value => visit(&key, value),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::cli::{CliArgs, parse_args};
+    use super::layers::{LayeredConfig, Source};
+    use std::path::PathBuf;
+
+    #[test]
+    fn parse_args_test() {
+        let args = |line: &str| -> Vec<String> { line.split(' ').map(String::from).collect() };
+        assert_eq!(
+            parse_args(&args("diana_srv --check-config cfg.toml")),
+            Ok(CliArgs {
+                config_path: String::from("cfg.toml"),
+                check_config: true,
+                ..CliArgs::default()
+            })
+        );
+        assert_eq!(
+            parse_args(&args("diana_srv --dev --set port=9090 cfg.toml"))
+                .unwrap()
+                .overrides,
+            ["dev_mode=true", "port=9090"]
+        );
+        assert!(parse_args(&args("diana_srv --set port cfg.toml")).is_err());
+        assert!(
+            parse_args(&args("diana_srv --daemon cfg.toml"))
+                .unwrap()
+                .daemon
+        );
+        assert!(parse_args(&args("diana_srv")).is_err());
+        assert!(
+            parse_args(&args("diana_srv --uninstall-service"))
+                .unwrap()
+                .uninstall_service
+        );
+        assert_eq!(
+            parse_args(&args("diana_srv --service --workdir C:/srv cfg.toml"))
+                .unwrap()
+                .workdir
+                .as_deref(),
+            Some("C:/srv")
+        );
+        assert!(parse_args(&args("diana_srv cfg.toml --workdir")).is_err());
+        assert!(parse_args(&args("diana_srv --verbose cfg.toml")).is_err());
+        assert!(parse_args(&args("diana_srv a.toml b.toml")).is_err());
+        assert_eq!(
+            parse_args(&args("diana_srv precompress resource/html"))
+                .unwrap()
+                .precompress
+                .as_deref(),
+            Some("resource/html")
+        );
+        assert!(parse_args(&args("diana_srv precompress")).is_err());
+        assert!(parse_args(&args("diana_srv precompress a b")).is_err());
+    }
+
+    #[test]
+    fn layered_config_test() {
+        let path: PathBuf =
+            std::env::temp_dir().join(format!("diana_layers_{}.toml", std::process::id()));
+        std::fs::write(
+            &path,
+            "ip = \"127.0.0.1\"\nport = 8080\nserver_header = \"a\"\n[logging]\nkeep = 3\n",
+        )
+        .unwrap();
+        let env: Vec<(String, String)> = [
+            ("DIANA_PORT", "9090"),
+            ("DIANA_SERVER_HEADER", "1.0"),
+            ("DIANA_LOGGING__GZIP", "true"),
+            ("HOME", "/root"),
+        ]
+        .map(|(name, value)| (String::from(name), String::from(value)))
+        .to_vec();
+        let layers: LayeredConfig =
+            LayeredConfig::load(&path, env, &[String::from("port=7070")]).unwrap();
+        let table: &toml::Table = layers.table();
+        assert_eq!(table["port"].as_integer(), Some(7070));
+        /* The string stays a string, even if it looks like a number */
+        assert_eq!(table["server_header"].as_str(), Some("1.0"));
+        assert_eq!(table["logging"]["gzip"].as_bool(), Some(true));
+        assert_eq!(table["logging"]["keep"].as_integer(), Some(3));
+        assert_eq!(
+            layers.source_of("port"),
+            &Source::Cli(String::from("port=7070"))
+        );
+        assert_eq!(
+            layers.source_of("logging.gzip"),
+            &Source::Env(String::from("DIANA_LOGGING__GZIP"))
+        );
+        assert!(matches!(layers.source_of("logging.keep"), Source::File(_)));
+        assert_eq!(layers.source_of("compression"), &Source::Default);
+        assert_eq!(
+            layers.describe(&toml::from_str("port = 7070\n[logging]\nday = 1").unwrap()),
+            ["port = 7070  # cli port=7070", "logging.day = 1  # default"]
+        );
+        assert!(LayeredConfig::load(&path, Vec::new(), &[String::from("ip.v4=1")]).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn interpolation_test() {
+        let path: PathBuf =
+            std::env::temp_dir().join(format!("diana_interpolation_{}.toml", std::process::id()));
+        let env = |pairs: &[(&str, &str)]| -> Vec<(String, String)> {
+            pairs
+                .iter()
+                .map(|(name, value)| (String::from(*name), String::from(*value)))
+                .collect()
+        };
+        std::fs::write(
+            &path,
+            "storage_dir = \"${DATA_DIR}/posts\"\nserver_header = \"$${literal}\"\n\
+             [logging]\naccess_log = \"${LOG_DIR:-logs}/access.log\"\n\
+             [[upgrade_routes]]\nprefix = \"/ws\"\nupstream = \"${WS_HOST}:9000\"\n",
+        )
+        .unwrap();
+        let layers: LayeredConfig = LayeredConfig::load(
+            &path,
+            env(&[("DATA_DIR", "/srv/data"), ("WS_HOST", "10.0.0.7")]),
+            &[],
+        )
+        .unwrap();
+        let table: &toml::Table = layers.table();
+        assert_eq!(table["storage_dir"].as_str(), Some("/srv/data/posts"));
+        assert_eq!(table["server_header"].as_str(), Some("${literal}"));
+        assert_eq!(
+            table["logging"]["access_log"].as_str(),
+            Some("logs/access.log")
+        );
+        assert_eq!(
+            table["upgrade_routes"][0]["upstream"].as_str(),
+            Some("10.0.0.7:9000")
+        );
+        /* The filled values aren't printed, the defaults and the literals are */
+        let file: String = path.display().to_string();
+        assert_eq!(
+            layers.describe(layers.table()),
+            [
+                format!("storage_dir = \"<redacted>\"  # file {file}, ${{DATA_DIR}}"),
+                format!("server_header = \"${{literal}}\"  # file {file}"),
+                format!("logging.access_log = \"logs/access.log\"  # file {file}"),
+                format!("upgrade_routes = \"<redacted>\"  # file {file}, ${{WS_HOST}}"),
+            ]
+        );
+
+        /* The missing variable names the key, that needs it */
+        let missing: String = LayeredConfig::load(&path, env(&[("DATA_DIR", "/srv")]), &[])
+            .unwrap_err()
+            .to_string();
+        assert_eq!(
+            missing,
+            "Config key upgrade_routes[0].upstream: Environment variable WS_HOST is not set"
+        );
+        std::fs::write(&path, "storage_dir = \"${DATA_DIR\"\n").unwrap();
+        assert!(LayeredConfig::load(&path, env(&[("DATA_DIR", "/srv")]), &[]).is_err());
+        std::fs::write(&path, "storage_dir = \"${DATA DIR}\"\n").unwrap();
+        assert!(LayeredConfig::load(&path, Vec::new(), &[]).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+}
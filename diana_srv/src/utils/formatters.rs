@@ -1,7 +1,48 @@
-use std::fs;
+pub mod base64 {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
 
-pub mod http_fmt {
-    pub fn add_headers(headers: &Vec<(String, String)>) -> String {
-        return String::from("Not implemented");
+    pub fn encode(bytes: &[u8]) -> String {
+        /*
+         *  Encode the bytes with the standard alphabet and the padding.
+         */
+        let mut encoded: String = String::with_capacity(bytes.len().div_ceil(3) * 4);
+        for chunk in bytes.chunks(3) {
+            let group: u32 = chunk.iter().enumerate().fold(0, |group, (idx, byte)| {
+                group | u32::from(*byte) << (16 - 8 * idx)
+            });
+            for idx in 0..4 {
+                if idx <= chunk.len() {
+                    encoded.push(ALPHABET[(group >> (18 - 6 * idx) & 63) as usize] as char);
+                } else {
+                    encoded.push('=');
+                }
+            }
+        }
+        encoded
+    }
+
+    pub fn decode(encoded: &str) -> Option<Vec<u8>> {
+        /*
+         *  Decode the standard alphabet, the padding is optional.
+         *
+         *  Returns:
+         *      The bytes, None if the text isn't base64.
+         */
+        let encoded: &[u8] = encoded.trim_end_matches('=').as_bytes();
+        if encoded.len() % 4 == 1 {
+            return None;
+        }
+        let mut decoded: Vec<u8> = Vec::with_capacity(encoded.len() * 3 / 4);
+        for chunk in encoded.chunks(4) {
+            let mut group: u32 = 0;
+            for (idx, symbol) in chunk.iter().enumerate() {
+                let value: usize = ALPHABET.iter().position(|byte| byte == symbol)?;
+                group |= (value as u32) << (18 - 6 * idx);
+            }
+            for idx in 0..chunk.len() - 1 {
+                decoded.push((group >> (16 - 8 * idx)) as u8);
+            }
+        }
+        Some(decoded)
     }
 }
@@ -0,0 +1,61 @@
+pub fn glob_match(pattern: &[u8], path: &[u8]) -> bool {
+    /*
+     *  Match the path against the glob pattern.
+     *
+     *  Supported wildcards:
+     *      ? - any single byte, except /
+     *      * - any sequence of bytes, except /
+     *      ** - any sequence of bytes, including /
+     *
+     *  Arguments:
+     *      pattern: The glob pattern, e.g. *.html
+     *      path: The path to match.
+     *
+     *  Returns:
+     *      True if the whole path matches the pattern.
+     */
+    match pattern.first() {
+        None => path.is_empty(),
+        Some(b'*') if pattern.get(1) == Some(&b'*') => {
+            /* The double star followed by slash also matches no directory */
+            let rest: &[u8] = &pattern[2..];
+            if let Some(after_slash) = rest.strip_prefix(b"/")
+                && glob_match(after_slash, path)
+            {
+                return true;
+            }
+            (0..=path.len()).any(|idx| glob_match(rest, &path[idx..]))
+        }
+        Some(b'*') => {
+            let rest: &[u8] = &pattern[1..];
+            for idx in 0..=path.len() {
+                if glob_match(rest, &path[idx..]) {
+                    return true;
+                }
+                if idx < path.len() && path[idx] == b'/' {
+                    break;
+                }
+            }
+            false
+        }
+        Some(b'?') => !path.is_empty() && path[0] != b'/' && glob_match(&pattern[1..], &path[1..]),
+        Some(byte) => path.first() == Some(byte) && glob_match(&pattern[1..], &path[1..]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::glob_match;
+
+    #[test]
+    fn glob_match_test() {
+        assert!(glob_match(b"*.html", b"index.html"));
+        assert!(!glob_match(b"*.html", b"docs/index.html"));
+        assert!(glob_match(b"**/*.html", b"docs/api/index.html"));
+        assert!(glob_match(b"**/*.html", b"index.html"));
+        assert!(glob_match(b"docs/**", b"docs/api/index.html"));
+        assert!(glob_match(b"site_?.css", b"site_a.css"));
+        assert!(!glob_match(b"site_?.css", b"site_ab.css"));
+        assert!(!glob_match(b"/staging/*", b"/production/a"));
+    }
+}